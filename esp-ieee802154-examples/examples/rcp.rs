@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+use embedded_hal_nb::serial::Read;
+use esp_backtrace as _;
+use esp_hal::{
+    clock::ClockControl, efuse::Efuse, peripherals::Peripherals, prelude::*,
+    system::SystemControl, time::current_time, uart::Uart,
+};
+use esp_ieee802154::{spinel::Rcp, *};
+
+fn now() -> u64 {
+    current_time().duration_since_epoch().to_micros()
+}
+
+#[entry]
+fn main() -> ! {
+    let mut peripherals = Peripherals::take();
+    let system = SystemControl::new(peripherals.SYSTEM);
+    let clocks = ClockControl::max(system.clock_control).freeze();
+
+    // nothing else may be printed to the UART, the host expects Spinel frames
+    let mut uart0 = Uart::new(peripherals.UART0, &clocks);
+
+    // the EUI-64 is derived from the factory MAC address, like ESP-IDF does
+    let mac = Efuse::get_mac_address();
+    let eui64 = [mac[0], mac[1], mac[2], 0xff, 0xfe, mac[3], mac[4], mac[5]];
+
+    let ieee802154 = Ieee802154::new(peripherals.IEEE802154, &mut peripherals.RADIO_CLK);
+    let mut rcp = Rcp::new(ieee802154, eui64, now);
+
+    rcp.start(|data| {
+        uart0.write_bytes(data).ok();
+    });
+
+    loop {
+        while let nb::Result::Ok(byte) = uart0.read() {
+            rcp.receive(byte, |data| {
+                uart0.write_bytes(data).ok();
+            });
+        }
+
+        rcp.process(|data| {
+            uart0.write_bytes(data).ok();
+        });
+    }
+}
@@ -12,7 +12,7 @@ fn main() -> ! {
     let mut ieee802154 = Ieee802154::new(peripherals.IEEE802154, &mut peripherals.RADIO_CLK);
 
     ieee802154.set_config(Config {
-        channel: 15,
+        channel: Channel::new(15).unwrap(),
         promiscuous: true,
         rx_when_idle: true,
         auto_ack_rx: false,
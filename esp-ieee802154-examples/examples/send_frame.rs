@@ -18,7 +18,7 @@ fn main() -> ! {
     let mut ieee802154 = Ieee802154::new(peripherals.IEEE802154, &mut peripherals.RADIO_CLK);
 
     ieee802154.set_config(Config {
-        channel: 15,
+        channel: Channel::new(15).unwrap(),
         promiscuous: false,
         pan_id: Some(0x4242),
         short_addr: Some(0x2222),
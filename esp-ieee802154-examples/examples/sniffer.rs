@@ -37,6 +37,7 @@ fn main() -> ! {
     let channel: u8 = unsafe { core::str::from_utf8_unchecked(&read) }
         .parse()
         .unwrap();
+    let channel = Channel::new(channel).unwrap();
 
     let mut ieee802154 = Ieee802154::new(peripherals.IEEE802154, &mut peripherals.RADIO_CLK);
 
@@ -46,6 +47,7 @@ fn main() -> ! {
         rx_when_idle: true,
         auto_ack_rx: false,
         auto_ack_tx: false,
+        rx_bad_frames: true,
         ..Config::default()
     });
 
@@ -53,7 +55,13 @@ fn main() -> ! {
 
     loop {
         if let Some(frame) = ieee802154.get_raw_received() {
-            println!("@RAW {:02x?}", &frame.data);
+            // the FCS of a frame rejected by the radio can't be recomputed,
+            // it failed the CRC or may be truncated by the address filter
+            if frame.fault.is_some() {
+                println!("@BAD {:02x?}", &frame.data);
+            } else {
+                println!("@RAW {:02x?}", &frame.data);
+            }
         }
 
         if let nb::Result::Ok(c) = uart0.read() {
@@ -1,11 +1,31 @@
-use std::{env, path::PathBuf};
+use std::{env, fs, path::PathBuf};
+
+const RX_QUEUE_SIZE_ENV: &str = "ESP_IEEE802154_RX_QUEUE_SIZE";
+const RX_QUEUE_SIZE_DEFAULT: usize = 20;
 
 fn main() {
     let out = PathBuf::from(env::var_os("OUT_DIR").unwrap());
     println!("cargo:rustc-link-search={}", out.display());
 
-    #[cfg(not(any(feature = "esp32c6", feature = "esp32h2")))]
+    #[cfg(not(any(feature = "esp32c6", feature = "esp32h2", feature = "mock")))]
     {
-        compile_error!("Either esp32c6 or esp32h2 needs to be selected via a feature");
+        compile_error!("Either esp32c6, esp32h2 or mock needs to be selected via a feature");
     }
+
+    // only rebuild for a new queue size, not for every change of the sources
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-env-changed={RX_QUEUE_SIZE_ENV}");
+    let rx_queue_size = match env::var(RX_QUEUE_SIZE_ENV) {
+        Ok(value) => match value.trim().parse::<usize>() {
+            Ok(size) if (1..=255).contains(&size) => size,
+            _ => panic!("{RX_QUEUE_SIZE_ENV} must be a number between 1 and 255, got `{value}`"),
+        },
+        Err(_) => RX_QUEUE_SIZE_DEFAULT,
+    };
+
+    fs::write(
+        out.join("config.rs"),
+        format!("pub(crate) const RX_QUEUE_SIZE: usize = {rx_queue_size};\n"),
+    )
+    .unwrap();
 }
@@ -0,0 +1,75 @@
+//! ACKs reported to OpenThread, by its radio platform and the Spinel RCP
+//!
+//! The radio sends the ACKs of received frames and receives the ACKs of
+//! transmitted frames by itself, without handing them out. What OpenThread
+//! is told about them is derived here from the frame control field: whether a
+//! received frame was ACKed with the frame pending bit set, and the ACK of a
+//! transmitted frame, rebuilt from its sequence number.
+
+use crate::pending::ack_frame_pending;
+
+/// Length of an immediate ACK, including the FCS
+pub(crate) const ACK_PSDU_LEN: usize = 5;
+
+// bits of the first byte of the frame control field
+const FRAME_TYPE_ACK: u8 = 2;
+const FRAME_PENDING: u8 = 1 << 4;
+const ACK_REQUEST: u8 = 1 << 5;
+
+/// Whether `frame`, starting with its frame control field, requests an ACK
+pub(crate) fn ack_requested(frame: &[u8]) -> bool {
+    frame.first().is_some_and(|fcf| fcf & ACK_REQUEST != 0)
+}
+
+/// Whether the received `frame`, given without the FCS, was ACKed with the
+/// frame pending bit set
+pub(crate) fn acked_with_frame_pending(frame: &[u8]) -> bool {
+    ack_requested(frame) && ack_frame_pending(frame)
+}
+
+/// The ACK received for the transmitted `frame`, with a zero FCS
+pub(crate) fn ack_frame(frame: &[u8], frame_pending: bool) -> [u8; ACK_PSDU_LEN] {
+    let frame_control = FRAME_TYPE_ACK | if frame_pending { FRAME_PENDING } else { 0 };
+    [frame_control, 0, frame[2], 0, 0]
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        hal::mock,
+        pending::{add_short_address, clear_short_addresses},
+        raw::{esp_ieee802154_enable, set_pending_mode},
+        PendingMode,
+    };
+
+    // data request from 0x0002 to 0x0001 in PAN 0x1234
+    const DATA_REQUEST: [u8; 10] = [0x63, 0x88, 5, 0x34, 0x12, 0x01, 0x00, 0x02, 0x00, 0x04];
+
+    #[test]
+    fn ack_carries_sequence_number_and_frame_pending() {
+        assert_eq!(ack_frame(&DATA_REQUEST, false), [0x02, 0x00, 5, 0, 0]);
+        assert_eq!(ack_frame(&DATA_REQUEST, true), [0x12, 0x00, 5, 0, 0]);
+    }
+
+    #[test]
+    fn frame_pending_needs_ack_request() {
+        let _lock = mock::test_lock();
+        esp_ieee802154_enable(&mut mock::RADIO_CLK);
+        set_pending_mode(PendingMode::Enable);
+        clear_short_addresses();
+        add_short_address(0x0002).unwrap();
+
+        assert!(ack_requested(&DATA_REQUEST));
+        assert!(acked_with_frame_pending(&DATA_REQUEST));
+
+        let mut unacked = DATA_REQUEST;
+        unacked[0] &= !ACK_REQUEST;
+        assert!(!ack_requested(&unacked));
+        assert!(!acked_with_frame_pending(&unacked));
+        assert!(!ack_requested(&[]));
+
+        clear_short_addresses();
+        assert!(!acked_with_frame_pending(&DATA_REQUEST));
+    }
+}
@@ -0,0 +1,409 @@
+//! Enhanced beacons and enhanced beacon requests
+//!
+//! Devices discover the networks of TSCH, Wi-SUN and Thread by broadcasting an
+//! [`EnhancedBeaconRequest`], whose [`EbFilter`] tells which coordinators
+//! should answer, and coordinators answer with an [`EnhancedBeacon`]. Both are
+//! IEEE 802.15.4-2015 frames carrying IEs. A [`BeaconResponse`] set with
+//! [`Ieee802154::set_beacon_response`](crate::Ieee802154::set_beacon_response)
+//! makes the driver answer the requests it receives, via
+//! [`Ieee802154::transmit_beacon_response`](crate::Ieee802154::transmit_beacon_response).
+
+use ieee802154::mac::{
+    command::Command, Address, FrameContent, FrameType, FrameVersion, Header, PanId, ShortAddress,
+};
+
+use crate::{
+    ie::{EbFilter, Ies, NestedIe, PayloadIe},
+    rng::Rng,
+    Error, Frame, ReceivedFrame,
+};
+
+/// Enhanced beacon request, a beacon request command of the 2015 version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EnhancedBeaconRequest<'a> {
+    /// Which coordinators answer, all of them without filter
+    pub filter: Option<EbFilter<'a>>,
+}
+
+impl<'a> EnhancedBeaconRequest<'a> {
+    /// Frame of the request, broadcast from `source` if given
+    pub fn frame(&self, seq: u8, source: Option<Address>) -> Result<Frame, Error> {
+        let header = Header {
+            frame_type: FrameType::MacCommand,
+            frame_pending: false,
+            ack_request: false,
+            pan_id_compress: source.is_some(),
+            seq_no_suppress: false,
+            ie_present: false,
+            version: FrameVersion::Ieee802154,
+            seq,
+            destination: Some(Address::Short(PanId::broadcast(), ShortAddress::BROADCAST)),
+            source,
+            auxiliary_security_header: None,
+        };
+        let content = FrameContent::Command(Command::BeaconRequest);
+
+        let Some(filter) = self.filter else {
+            return Ok(Frame {
+                header,
+                content,
+                payload: Default::default(),
+                footer: [0; 2],
+            });
+        };
+
+        let nested = [NestedIe::EbFilter(filter)];
+        let payload = [PayloadIe::Mlme(nested[..].into())];
+        let ies = Ies {
+            header: Default::default(),
+            payload: payload[..].into(),
+        };
+
+        Frame::with_ies(header, content, &ies, &[])
+    }
+
+    /// Decode the request of `frame`, [`Error::BadInput`] if it isn't an
+    /// enhanced beacon request
+    pub fn decode(frame: &'a Frame) -> Result<Self, Error> {
+        if frame.header.version != FrameVersion::Ieee802154
+            || frame.content != FrameContent::Command(Command::BeaconRequest)
+        {
+            return Err(Error::BadInput);
+        }
+
+        let (ies, _) = frame.ies()?;
+        let filter = ies.payload.iter().find_map(|ie| match ie {
+            PayloadIe::Mlme(nested) => nested.iter().find_map(|ie| match ie {
+                NestedIe::EbFilter(filter) => Some(filter),
+                _ => None,
+            }),
+            _ => None,
+        });
+
+        Ok(Self { filter })
+    }
+}
+
+/// Enhanced beacon, a beacon of the 2015 version
+///
+/// Its frame has no superframe specification, GTS or pending addresses, its
+/// content is [`FrameContent::Data`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct EnhancedBeacon<'a> {
+    /// IEs of the beacon, like the TSCH IEs or the vendor IEs of Thread
+    pub ies: Ies<'a>,
+    /// Beacon payload
+    pub payload: &'a [u8],
+}
+
+impl<'a> EnhancedBeacon<'a> {
+    /// Frame of the beacon, broadcast from `source` in its PAN
+    pub fn frame(&self, seq: u8, source: Address) -> Result<Frame, Error> {
+        let header = Header {
+            frame_type: FrameType::Beacon,
+            frame_pending: false,
+            ack_request: false,
+            pan_id_compress: true,
+            seq_no_suppress: false,
+            ie_present: true,
+            version: FrameVersion::Ieee802154,
+            seq,
+            destination: Some(Address::Short(source.pan_id(), ShortAddress::BROADCAST)),
+            source: Some(source),
+            auxiliary_security_header: None,
+        };
+
+        Frame::with_ies(header, FrameContent::Data, &self.ies, self.payload)
+    }
+
+    /// Decode the beacon of `frame`, [`Error::BadInput`] if it isn't an
+    /// enhanced beacon
+    pub fn decode(frame: &'a Frame) -> Result<Self, Error> {
+        if frame.header.frame_type != FrameType::Beacon
+            || frame.header.version != FrameVersion::Ieee802154
+        {
+            return Err(Error::BadInput);
+        }
+
+        let (ies, payload) = frame.ies()?;
+
+        Ok(Self { ies, payload })
+    }
+}
+
+/// Enhanced beacon sent by a coordinator in response to the enhanced beacon
+/// requests passing their filter
+#[derive(Debug, Clone)]
+pub struct BeaconResponse {
+    beacon: Frame,
+    permit_joining: bool,
+    requested: bool,
+    rng: Rng,
+}
+
+impl BeaconResponse {
+    /// Response with `beacon`, the frame of an [`EnhancedBeacon`], whose
+    /// sequence number is incremented for each response
+    ///
+    /// `eui64` is the factory assigned EUI-64 of the device, which seeds the
+    /// random numbers of the percent filter.
+    pub fn new(beacon: Frame, eui64: [u8; 8]) -> Self {
+        Self {
+            beacon,
+            permit_joining: false,
+            requested: false,
+            rng: Rng::new(eui64),
+        }
+    }
+
+    /// Set whether joining is permitted, requests with the permit joining
+    /// filter are only answered when it is
+    pub fn set_permit_joining(&mut self, permit_joining: bool) {
+        self.permit_joining = permit_joining;
+    }
+
+    /// The beacon, to update its IEs
+    pub fn beacon_mut(&mut self) -> &mut Frame {
+        &mut self.beacon
+    }
+
+    /// Whether a received request waits for the beacon to be sent
+    pub fn is_requested(&self) -> bool {
+        self.requested
+    }
+
+    /// Note that the beacon is to be sent if `received` is an enhanced beacon
+    /// request passing its filter
+    pub(crate) fn handle_request(&mut self, received: &ReceivedFrame) {
+        if received.fault.is_some() {
+            return;
+        }
+
+        let Ok(request) = EnhancedBeaconRequest::decode(&received.frame) else {
+            return;
+        };
+        if let Some(filter) = request.filter {
+            if filter.permit_joining_on && !self.permit_joining {
+                return;
+            }
+            if filter
+                .link_quality
+                .is_some_and(|link_quality| received.lqi < link_quality)
+            {
+                return;
+            }
+            if filter
+                .percent
+                .is_some_and(|percent| self.rng.next() % 100 >= percent as u32)
+            {
+                return;
+            }
+        }
+
+        self.requested = true;
+    }
+
+    /// The beacon to send if it was requested, with the next sequence number
+    ///
+    /// Requests received before the beacon is sent are answered by the same
+    /// beacon.
+    pub(crate) fn take(&mut self) -> Option<&Frame> {
+        if !core::mem::take(&mut self.requested) {
+            return None;
+        }
+
+        self.beacon.header.seq = self.beacon.header.seq.wrapping_add(1);
+
+        Some(&self.beacon)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ieee802154::mac::PanId;
+
+    use super::*;
+    use crate::{frame::FRAME_SIZE, Channel};
+
+    const COORDINATOR: Address = Address::Short(PanId(0x1234), ShortAddress(1));
+
+    fn round_trip(frame: &Frame) -> Frame {
+        let mut bytes = [0; FRAME_SIZE];
+        let len = frame.encode(&mut bytes).unwrap();
+        Frame::decode(&bytes[..len]).unwrap()
+    }
+
+    fn beacon() -> Frame {
+        let payload = [PayloadIe::Vendor {
+            oui: 0x123456,
+            content: &[7],
+        }];
+        let ies = Ies {
+            header: Default::default(),
+            payload: payload[..].into(),
+        };
+        EnhancedBeacon {
+            ies,
+            payload: &[1, 2, 3],
+        }
+        .frame(9, COORDINATOR)
+        .unwrap()
+    }
+
+    fn request(filter: Option<EbFilter>) -> ReceivedFrame {
+        let frame = EnhancedBeaconRequest { filter }.frame(5, None).unwrap();
+        ReceivedFrame {
+            frame: round_trip(&frame),
+            channel: Channel::MIN,
+            rssi: -50,
+            lqi: 150,
+            hw_lqi: 150,
+            freq_offset: 0,
+            interfaces: 1,
+            fault: None,
+        }
+    }
+
+    #[test]
+    fn beacon_request_round_trips() {
+        let received = request(None);
+        let header = &received.frame.header;
+        assert_eq!(header.frame_type, FrameType::MacCommand);
+        assert_eq!(header.version, FrameVersion::Ieee802154);
+        assert_eq!(
+            header.destination,
+            Some(Address::Short(PanId::broadcast(), ShortAddress::BROADCAST))
+        );
+        assert_eq!(
+            EnhancedBeaconRequest::decode(&received.frame).unwrap(),
+            EnhancedBeaconRequest { filter: None }
+        );
+
+        let filter = EbFilter {
+            permit_joining_on: true,
+            link_quality: Some(100),
+            percent: Some(50),
+            attributes: &[],
+        };
+        let received = request(Some(filter));
+        assert_eq!(
+            EnhancedBeaconRequest::decode(&received.frame).unwrap(),
+            EnhancedBeaconRequest {
+                filter: Some(filter)
+            }
+        );
+        assert!(matches!(
+            EnhancedBeacon::decode(&received.frame),
+            Err(Error::BadInput)
+        ));
+    }
+
+    #[test]
+    fn beacon_round_trips() {
+        let frame = round_trip(&beacon());
+        assert_eq!(frame.header.seq, 9);
+        assert_eq!(frame.header.source, Some(COORDINATOR));
+
+        let decoded = EnhancedBeacon::decode(&frame).unwrap();
+        assert_eq!(decoded.payload, [1, 2, 3]);
+        assert_eq!(
+            decoded.ies.payload.iter().collect::<std::vec::Vec<_>>(),
+            [PayloadIe::Vendor {
+                oui: 0x123456,
+                content: &[7],
+            }]
+        );
+        assert!(matches!(
+            EnhancedBeaconRequest::decode(&frame),
+            Err(Error::BadInput)
+        ));
+    }
+
+    #[test]
+    fn coordinator_answers_requests_passing_filter() {
+        let mut response = BeaconResponse::new(beacon(), [1; 8]);
+        assert!(response.take().is_none());
+
+        // requests without filter are always answered, once
+        response.handle_request(&request(None));
+        response.handle_request(&request(None));
+        assert!(response.is_requested());
+        assert_eq!(response.take().unwrap().header.seq, 10);
+        assert!(response.take().is_none());
+
+        let permit_joining = EbFilter {
+            permit_joining_on: true,
+            ..Default::default()
+        };
+        response.handle_request(&request(Some(permit_joining)));
+        assert!(!response.is_requested());
+        response.set_permit_joining(true);
+        response.handle_request(&request(Some(permit_joining)));
+        assert_eq!(response.take().unwrap().header.seq, 11);
+
+        let link_quality = |link_quality| EbFilter {
+            link_quality: Some(link_quality),
+            ..Default::default()
+        };
+        response.handle_request(&request(Some(link_quality(151))));
+        assert!(!response.is_requested());
+        response.handle_request(&request(Some(link_quality(150))));
+        assert!(response.take().is_some());
+
+        let percent = |percent| EbFilter {
+            percent: Some(percent),
+            ..Default::default()
+        };
+        for _ in 0..10 {
+            response.handle_request(&request(Some(percent(0))));
+            assert!(!response.is_requested());
+            response.handle_request(&request(Some(percent(100))));
+            assert!(response.take().is_some());
+        }
+
+        // neither beacons, nor requests received with a fault
+        let mut faulty = request(None);
+        faulty.fault = Some(crate::RxFault::CrcError);
+        response.handle_request(&faulty);
+        let mut not_a_request = request(None);
+        not_a_request.frame = beacon();
+        response.handle_request(&not_a_request);
+        assert!(!response.is_requested());
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn beacon_is_sent_once_radio_is_free() {
+        use crate::{hal::mock, Ieee802154};
+
+        let _lock = mock::test_lock();
+        let mut radio = Ieee802154::new(crate::hal::IEEE802154, &mut crate::hal::RADIO_CLK);
+        radio.set_beacon_response(Some(BeaconResponse::new(beacon(), [1; 8])));
+        radio.start_receive();
+
+        let mut bytes = [0; FRAME_SIZE];
+        let len = request(None).frame.encode(&mut bytes).unwrap();
+        assert!(mock::receive(&bytes[..len - 2], -50, 150));
+
+        // the request is returned while a frame is transmitted
+        let data = [
+            0x41, 0x88, 1, 0x34, 0x12, 0xff, 0xff, 0x01, 0x00, 0xaa, 0, 0,
+        ];
+        radio.transmit_raw(&data).unwrap();
+        assert!(radio.get_received().unwrap().is_ok());
+        assert_eq!(mock::transmitted().unwrap(), data[..data.len() - 2]);
+        assert!(matches!(
+            radio.transmit_beacon_response(),
+            Err(Error::Incomplete)
+        ));
+        assert_eq!(mock::transmitted().unwrap(), data[..data.len() - 2]);
+
+        mock::transmit_done();
+        assert!(matches!(radio.transmit_beacon_response(), Ok(true)));
+        let mut expected = beacon();
+        expected.header.seq = 10;
+        let len = expected.encode(&mut bytes).unwrap();
+        assert_eq!(mock::transmitted().unwrap(), bytes[..len - 2]);
+        assert!(matches!(radio.transmit_beacon_response(), Ok(false)));
+    }
+}
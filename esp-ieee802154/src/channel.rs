@@ -0,0 +1,193 @@
+//! Channels of the 2.4 GHz O-QPSK PHY
+
+use crate::Error;
+
+const CHANNEL_MIN: u8 = 11;
+const CHANNEL_MAX: u8 = 26;
+
+/// Channel of channel page 0, the 2.4 GHz O-QPSK PHY, numbered from 11 to 26
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Channel(pub(crate) u8);
+
+impl Channel {
+    /// Lowest channel, 11
+    pub const MIN: Channel = Channel(CHANNEL_MIN);
+    /// Highest channel, 26
+    pub const MAX: Channel = Channel(CHANNEL_MAX);
+    /// Channel page of the channels
+    pub const PAGE: u8 = 0;
+
+    /// The channel numbered `number`, [`Error::BadInput`] unless it is from 11
+    /// to 26
+    pub const fn new(number: u8) -> Result<Self, Error> {
+        if number < CHANNEL_MIN || number > CHANNEL_MAX {
+            return Err(Error::BadInput);
+        }
+
+        Ok(Self(number))
+    }
+
+    /// Number of the channel
+    pub const fn number(self) -> u8 {
+        self.0
+    }
+
+    /// Center frequency of the channel, in MHz
+    pub const fn frequency(self) -> u16 {
+        2405 + 5 * (self.0 - CHANNEL_MIN) as u16
+    }
+
+    /// All channels, in ascending order
+    pub fn all() -> impl Iterator<Item = Channel> {
+        (CHANNEL_MIN..=CHANNEL_MAX).map(Channel)
+    }
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self::MIN
+    }
+}
+
+impl TryFrom<u8> for Channel {
+    type Error = Error;
+
+    fn try_from(number: u8) -> Result<Self, Error> {
+        Self::new(number)
+    }
+}
+
+impl From<Channel> for u8 {
+    fn from(channel: Channel) -> Self {
+        channel.0
+    }
+}
+
+/// Set of channels, in which bit `n` stands for channel `n` like in the
+/// channel masks of Zigbee and Thread
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChannelMask(u32);
+
+impl ChannelMask {
+    /// All channels
+    pub const ALL: ChannelMask = ChannelMask(0x07ff_f800);
+
+    /// No channel
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The mask of `bits`, [`Error::BadInput`] if a bit not standing for a
+    /// channel from 11 to 26 is set
+    pub const fn new(bits: u32) -> Result<Self, Error> {
+        if bits & !Self::ALL.0 != 0 {
+            return Err(Error::BadInput);
+        }
+
+        Ok(Self(bits))
+    }
+
+    /// Bits of the mask
+    pub const fn bits(self) -> u32 {
+        self.0
+    }
+
+    /// Whether the mask has no channel
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether `channel` is in the mask
+    pub const fn contains(self, channel: Channel) -> bool {
+        self.0 & 1 << channel.0 != 0
+    }
+
+    /// Add `channel` to the mask
+    pub fn insert(&mut self, channel: Channel) {
+        self.0 |= 1 << channel.0;
+    }
+
+    /// Remove `channel` from the mask
+    pub fn remove(&mut self, channel: Channel) {
+        self.0 &= !(1 << channel.0);
+    }
+
+    /// The channels of the mask, in ascending order
+    pub fn iter(self) -> impl Iterator<Item = Channel> {
+        Channel::all().filter(move |channel| self.contains(*channel))
+    }
+}
+
+impl From<Channel> for ChannelMask {
+    fn from(channel: Channel) -> Self {
+        Self(1 << channel.0)
+    }
+}
+
+impl FromIterator<Channel> for ChannelMask {
+    fn from_iter<T: IntoIterator<Item = Channel>>(iter: T) -> Self {
+        let mut mask = Self::empty();
+        for channel in iter {
+            mask.insert(channel);
+        }
+
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn channels_outside_page_0_are_rejected() {
+        for number in [0, 10, 27, u8::MAX] {
+            assert!(matches!(Channel::new(number), Err(Error::BadInput)));
+            assert!(matches!(Channel::try_from(number), Err(Error::BadInput)));
+        }
+
+        assert_eq!(Channel::new(11).unwrap(), Channel::MIN);
+        assert_eq!(Channel::new(26).unwrap(), Channel::MAX);
+        assert_eq!(Channel::try_from(15).unwrap().number(), 15);
+        assert_eq!(u8::from(Channel::MAX), 26);
+        assert_eq!(Channel::all().count(), 16);
+    }
+
+    #[test]
+    fn frequencies_are_5_mhz_apart() {
+        assert_eq!(Channel::MIN.frequency(), 2405);
+        assert_eq!(Channel::new(15).unwrap().frequency(), 2425);
+        assert_eq!(Channel::MAX.frequency(), 2480);
+    }
+
+    #[test]
+    fn mask_holds_channels_11_to_26() {
+        assert_eq!(ChannelMask::ALL.bits(), 0x07ff_f800);
+        assert_eq!(ChannelMask::ALL, Channel::all().collect());
+        assert!(ChannelMask::empty().is_empty());
+        assert!(matches!(ChannelMask::new(1 << 10), Err(Error::BadInput)));
+        assert!(matches!(ChannelMask::new(1 << 27), Err(Error::BadInput)));
+        assert_eq!(ChannelMask::new(0x07ff_f800).unwrap(), ChannelMask::ALL);
+
+        let channel_15 = Channel::new(15).unwrap();
+        let mut mask = ChannelMask::from(Channel::MAX);
+        assert_eq!(mask.bits(), 1 << 26);
+        mask.insert(channel_15);
+        mask.insert(Channel::MIN);
+        assert!(mask.contains(channel_15));
+        assert!(!mask.contains(Channel::new(16).unwrap()));
+        assert_eq!(
+            mask.iter().collect::<Vec<_>>(),
+            [Channel::MIN, channel_15, Channel::MAX]
+        );
+
+        mask.remove(channel_15);
+        mask.remove(channel_15);
+        assert_eq!(mask.bits(), 1 << 11 | 1 << 26);
+        mask.remove(Channel::MIN);
+        mask.remove(Channel::MAX);
+        assert!(mask.is_empty());
+    }
+}
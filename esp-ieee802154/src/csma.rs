@@ -0,0 +1,240 @@
+//! CSMA-CA backoffs and frame retries in software
+//!
+//! The radio only does a single CCA before transmitting and waits for the
+//! ACK, the backoffs between CCA attempts and the retransmissions after
+//! missing ACKs are driven by polling a [`Transmission`]. A frame whose ACK
+//! is neither received nor timed out by the radio within macAckWaitDuration
+//! is not ACKed.
+
+use crate::{
+    raw::{ieee802154_tx_outcome, TxOutcome},
+    rng::Rng,
+};
+
+// macMinBe and macMaxBe
+const MIN_BACKOFF_EXPONENT: u8 = 3;
+const MAX_BACKOFF_EXPONENT: u8 = 5;
+// aUnitBackoffPeriod, 20 symbols
+const UNIT_BACKOFF_PERIOD_US: u64 = 320;
+// macAckWaitDuration, 54 symbols
+const ACK_WAIT_DURATION_US: u64 = 864;
+
+/// How a transmission ended
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Outcome {
+    /// Sent, without requesting an ACK
+    Sent,
+    Acked {
+        frame_pending: bool,
+    },
+    NoAck,
+    ChannelAccessFailure,
+    Aborted,
+}
+
+/// What to do next for a transmission
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Step {
+    Wait,
+    /// Send the frame, with or without CCA, `first` for the first attempt
+    Start {
+        cca: bool,
+        first: bool,
+    },
+    Done(Outcome),
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Backoff {
+        until: u64,
+    },
+    Sending,
+    /// Sent at `since` or earlier, waiting for the ACK
+    AwaitingAck {
+        since: u64,
+    },
+}
+
+/// A frame being transmitted
+pub(crate) struct Transmission {
+    state: State,
+    started: bool,
+    csma_ca: bool,
+    ack_requested: bool,
+    backoff_exponent: u8,
+    csma_backoffs: u8,
+    max_csma_backoffs: u8,
+    retries: u8,
+    max_retries: u8,
+}
+
+impl Transmission {
+    pub(crate) fn new(
+        csma_ca: bool,
+        ack_requested: bool,
+        max_csma_backoffs: u8,
+        max_retries: u8,
+        now: u64,
+        rng: &mut Rng,
+    ) -> Self {
+        let mut transmission = Self {
+            state: State::Sending,
+            started: false,
+            csma_ca,
+            ack_requested,
+            backoff_exponent: MIN_BACKOFF_EXPONENT,
+            csma_backoffs: 0,
+            max_csma_backoffs,
+            retries: 0,
+            max_retries,
+        };
+        transmission.backoff(now, rng);
+
+        transmission
+    }
+
+    /// Continue the transmission at `now`, in microseconds
+    pub(crate) fn poll(&mut self, now: u64, rng: &mut Rng) -> Step {
+        match self.state {
+            State::Backoff { until } if now >= until => {
+                self.state = State::Sending;
+                let first = !core::mem::replace(&mut self.started, true);
+
+                Step::Start {
+                    cca: self.csma_ca,
+                    first,
+                }
+            }
+            State::Backoff { .. } => Step::Wait,
+            State::Sending | State::AwaitingAck { .. } => match ieee802154_tx_outcome() {
+                TxOutcome::Pending => Step::Wait,
+                // the ACK is received or timed out later
+                TxOutcome::Sent if self.ack_requested => match self.state {
+                    State::AwaitingAck { since } if now - since >= ACK_WAIT_DURATION_US => {
+                        self.no_ack(now, rng)
+                    }
+                    State::AwaitingAck { .. } => Step::Wait,
+                    _ => {
+                        self.state = State::AwaitingAck { since: now };
+                        Step::Wait
+                    }
+                },
+                TxOutcome::Sent => Step::Done(Outcome::Sent),
+                TxOutcome::Acked { frame_pending } => Step::Done(Outcome::Acked { frame_pending }),
+                TxOutcome::NoAck => self.no_ack(now, rng),
+                TxOutcome::ChannelBusy if self.csma_backoffs < self.max_csma_backoffs => {
+                    self.csma_backoffs += 1;
+                    self.backoff_exponent = (self.backoff_exponent + 1).min(MAX_BACKOFF_EXPONENT);
+                    self.backoff(now, rng);
+                    Step::Wait
+                }
+                TxOutcome::ChannelBusy => Step::Done(Outcome::ChannelAccessFailure),
+                TxOutcome::Aborted => Step::Done(Outcome::Aborted),
+            },
+        }
+    }
+
+    /// Retry the frame which was not ACKed, unless out of retries
+    fn no_ack(&mut self, now: u64, rng: &mut Rng) -> Step {
+        if self.retries >= self.max_retries {
+            return Step::Done(Outcome::NoAck);
+        }
+
+        self.retries += 1;
+        self.csma_backoffs = 0;
+        self.backoff_exponent = MIN_BACKOFF_EXPONENT;
+        self.backoff(now, rng);
+        Step::Wait
+    }
+
+    fn backoff(&mut self, now: u64, rng: &mut Rng) {
+        let delay = if self.csma_ca {
+            let periods = rng.next() % (1 << self.backoff_exponent);
+            periods as u64 * UNIT_BACKOFF_PERIOD_US
+        } else {
+            0
+        };
+
+        self.state = State::Backoff { until: now + delay };
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use super::*;
+    use crate::{
+        frame::FRAME_SIZE,
+        hal::mock,
+        raw::{esp_ieee802154_enable, ieee802154_transmit},
+    };
+
+    // data frame from 0x0001 to 0x0002 in PAN 0x1234, requesting an ACK
+    static FRAME: [u8; FRAME_SIZE] = {
+        let mut frame = [0; FRAME_SIZE];
+        let psdu = [12, 0x61, 0x88, 1, 0x34, 0x12, 0x02, 0x00, 0x01, 0x00, 0xaa];
+        let mut i = 0;
+        while i < psdu.len() {
+            frame[i] = psdu[i];
+            i += 1;
+        }
+        frame
+    };
+
+    fn send() {
+        ieee802154_transmit(FRAME.as_ptr(), false);
+        mock::transmit_done();
+    }
+
+    #[test]
+    fn missing_ack_times_out() {
+        let _lock = mock::test_lock();
+        esp_ieee802154_enable(&mut mock::RADIO_CLK);
+        let mut rng = Rng::new([1; 8]);
+
+        let mut transmission = Transmission::new(false, true, 0, 1, 0, &mut rng);
+        let start = Step::Start {
+            cca: false,
+            first: true,
+        };
+        assert_eq!(transmission.poll(0, &mut rng), start);
+
+        // the radio never reports the ACK as received or timed out
+        send();
+        assert_eq!(transmission.poll(100, &mut rng), Step::Wait);
+        assert_eq!(transmission.poll(963, &mut rng), Step::Wait);
+        assert_eq!(transmission.poll(964, &mut rng), Step::Wait);
+        let retry = Step::Start {
+            cca: false,
+            first: false,
+        };
+        assert_eq!(transmission.poll(964, &mut rng), retry);
+
+        send();
+        assert_eq!(transmission.poll(1000, &mut rng), Step::Wait);
+        assert_eq!(
+            transmission.poll(1864, &mut rng),
+            Step::Done(Outcome::NoAck)
+        );
+    }
+
+    #[test]
+    fn ack_completes_transmission() {
+        let _lock = mock::test_lock();
+        esp_ieee802154_enable(&mut mock::RADIO_CLK);
+        let mut rng = Rng::new([1; 8]);
+
+        let mut transmission = Transmission::new(false, true, 0, 0, 0, &mut rng);
+        transmission.poll(0, &mut rng);
+        send();
+        assert_eq!(transmission.poll(100, &mut rng), Step::Wait);
+
+        assert!(mock::ack_received(&[0x02, 0x00, 1], -40));
+        assert_eq!(
+            transmission.poll(200, &mut rng),
+            Step::Done(Outcome::Acked {
+                frame_pending: false
+            })
+        );
+    }
+}
@@ -1,5 +1,14 @@
+use core::{marker::PhantomData, mem::ManuallyDrop};
+
+use byte::{BytesExt, TryRead};
 use heapless::Vec;
-use ieee802154::mac::{FrameContent, Header};
+use ieee802154::mac::{self, FooterMode, FrameContent, FrameType, FrameVersion, Header};
+
+use crate::{
+    ie::Ies,
+    raw::{ieee802154_release, RxFault, RxSlot},
+    rssi_to_lqi, Channel, Error,
+};
 
 pub(crate) const FRAME_SIZE: usize = 129;
 pub(crate) const FRAME_VERSION_1: u8 = 0x10; // IEEE 802.15.4 - 2006 & 2011
@@ -7,6 +16,7 @@ pub(crate) const FRAME_VERSION_2: u8 = 0x20; // IEEE 802.15.4 - 2015
 
 const FRAME_AR_OFFSET: usize = 1;
 const FRAME_AR_BIT: u8 = 0x20;
+const FRAME_IE_PRESENT_BIT: u8 = 0x02;
 const FRAME_VERSION_OFFSET: usize = 2;
 const FRAME_VERSION_MASK: u8 = 0x30;
 
@@ -23,17 +33,270 @@ pub struct Frame {
     pub footer: [u8; 2],
 }
 
+impl Frame {
+    /// Frame carrying IEs, which are held at the start of its payload
+    ///
+    /// Sets `ie_present` and the 2015 version in `header`. The IEs are sent
+    /// between the header and the content of the frame.
+    pub fn with_ies(
+        mut header: Header,
+        content: FrameContent,
+        ies: &Ies,
+        payload: &[u8],
+    ) -> Result<Self, Error> {
+        header.ie_present = true;
+        header.version = FrameVersion::Ieee802154;
+
+        let followed_by_payload = !payload.is_empty()
+            || matches!(content, FrameContent::Beacon(_) | FrameContent::Command(_));
+        let mut bytes = [0; FRAME_SIZE];
+        let len = ies.encode(followed_by_payload, &mut bytes)?;
+
+        let mut frame_payload = Vec::from_slice(&bytes[..len]).unwrap();
+        frame_payload
+            .extend_from_slice(payload)
+            .map_err(|_| Error::Incomplete)?;
+
+        Ok(Self {
+            header,
+            content,
+            payload: frame_payload,
+            footer: [0; 2],
+        })
+    }
+
+    /// The IEs of the frame, and the rest of its payload
+    ///
+    /// Frames whose header doesn't have `ie_present` set have no IEs.
+    pub fn ies(&self) -> Result<(Ies<'_>, &[u8]), Error> {
+        if !self.header.ie_present {
+            return Ok((Ies::default(), &self.payload));
+        }
+
+        let (ies, len) = Ies::decode(&self.payload)?;
+        Ok((ies, &self.payload[len..]))
+    }
+
+    /// Encode the frame into `bytes`, with its IEs between its header and
+    /// content, returns its length
+    pub(crate) fn encode(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        let frame = mac::Frame {
+            header: self.header,
+            content: self.content,
+            payload: &self.payload,
+            footer: self.footer,
+        };
+
+        let offset = &mut 0;
+        bytes.write_with(
+            offset,
+            frame,
+            &mut mac::FrameSerDesContext::no_security(FooterMode::Explicit),
+        )?;
+
+        if self.header.ie_present {
+            // not written by `mac::Header`, in the second byte of the frame
+            // control field
+            bytes[1] |= FRAME_IE_PRESENT_BIT;
+
+            let (_, ies_len) = Ies::decode(&self.payload)?;
+            let content_len = &mut 0;
+            [0; FRAME_SIZE].write(content_len, self.content)?;
+            let content_len = *content_len;
+            let start = *offset - self.footer.len() - self.payload.len() - content_len;
+            bytes[start..][..content_len + ies_len].rotate_right(ies_len);
+        }
+
+        Ok(*offset)
+    }
+
+    /// Decode a frame whose IEs are between its header and content, see
+    /// [`Frame::encode`]
+    ///
+    /// The content of enhanced beacons is [`FrameContent::Data`], as they
+    /// don't have the fields of the content of beacons. Secured frames
+    /// carrying IEs are rejected, as their payload IEs and content are
+    /// encrypted, so the end of the IEs can't be found.
+    pub(crate) fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let offset = &mut 0;
+        let header: Header = bytes.read(offset)?;
+
+        if header.ie_present && header.has_security() {
+            return Err(Error::BadInput);
+        }
+
+        // enhanced beacons don't have the content of beacons
+        let enhanced_beacon =
+            header.frame_type == FrameType::Beacon && header.version == FrameVersion::Ieee802154;
+
+        if !(header.ie_present || enhanced_beacon) || header.has_security() {
+            let (decoded, _) = mac::Frame::try_read(bytes, FooterMode::Explicit)?;
+            return Ok(Self {
+                header: decoded.header,
+                content: decoded.content,
+                payload: Vec::from_slice(decoded.payload).map_err(|_| Error::Incomplete)?,
+                footer: decoded.footer,
+            });
+        }
+
+        let end = bytes.len().checked_sub(2).ok_or(Error::Incomplete)?;
+        let start = *offset;
+        let ies_len = if header.ie_present {
+            Ies::decode(&bytes[start..end])?.1
+        } else {
+            0
+        };
+        *offset += ies_len;
+        let content = if enhanced_beacon {
+            FrameContent::Data
+        } else {
+            bytes.read_with(offset, &header)?
+        };
+
+        let mut payload = Vec::from_slice(&bytes[start..][..ies_len]).unwrap();
+        payload
+            .extend_from_slice(&bytes[*offset..end])
+            .map_err(|_| Error::Incomplete)?;
+
+        Ok(Self {
+            header,
+            content,
+            payload,
+            footer: [bytes[end], bytes[end + 1]],
+        })
+    }
+}
+
 /// IEEE 802.15.4 MAC frame which has been received
 #[derive(Debug, Clone)]
 pub struct ReceivedFrame {
     /// Frame
     pub frame: Frame,
     /// Receiver channel
-    pub channel: u8,
+    pub channel: Channel,
     /// Received Signal Strength Indicator (RSSI)
     pub rssi: i8,
-    /// Link Quality Indication (LQI)
+    /// Link Quality Indication (LQI), derived from the RSSI
     pub lqi: u8,
+    /// Link Quality Indication (LQI), as reported by the radio
+    pub hw_lqi: u8,
+    /// Carrier frequency offset, as reported by the radio
+    pub freq_offset: i8,
+    /// Bitmask of the multipan interfaces whose address filter accepted the
+    /// frame, bit 0 being interface 0
+    pub interfaces: u8,
+    /// Why the frame was rejected by the radio, if it was
+    pub fault: Option<RxFault>,
+}
+
+/// IEEE 802.15.4 MAC frame which has been received, but is still located in
+/// the receive buffer of the driver
+///
+/// Accessing the frame does not copy it, and the receive buffer is handed back
+/// to the radio once the token is dropped.
+#[derive(Debug)]
+pub struct RxToken<'a> {
+    slot: ManuallyDrop<RxSlot>,
+    _phantom: PhantomData<&'a mut ()>,
+}
+
+impl<'a> RxToken<'a> {
+    pub(crate) fn new(slot: RxSlot) -> Self {
+        Self {
+            slot: ManuallyDrop::new(slot),
+            _phantom: PhantomData,
+        }
+    }
+
+    /// The receive buffer as written by the radio, starting with the length
+    /// byte
+    ///
+    /// The length byte is followed by the PSDU, whose last two bytes hold
+    /// the RSSI and the LQI instead of the FCS, and then by the frequency
+    /// offset, which is not counted by the length byte.
+    pub fn raw(&self) -> &[u8; FRAME_SIZE] {
+        self.slot.data()
+    }
+
+    /// The received PSDU, the last two bytes of which hold the RSSI and LQI
+    /// instead of the CRC
+    pub fn data(&self) -> &[u8] {
+        let raw = self.raw();
+        &raw[1..][..raw[0] as usize]
+    }
+
+    /// Receiver channel
+    pub fn channel(&self) -> Channel {
+        self.slot.channel
+    }
+
+    /// Received Signal Strength Indicator (RSSI)
+    ///
+    /// Frames rejected by the address filter may be truncated to less than
+    /// the two bytes the RSSI and LQI are written in place of, their RSSI is
+    /// reported as `i8::MIN`.
+    pub fn rssi(&self) -> i8 {
+        let raw = self.raw();
+        if raw[0] < 2 {
+            return i8::MIN;
+        }
+
+        raw[raw[0] as usize - 1] as i8 // crc is not written to rx buffer
+    }
+
+    /// Link Quality Indication (LQI), derived from the RSSI
+    pub fn lqi(&self) -> u8 {
+        rssi_to_lqi(self.rssi())
+    }
+
+    /// Link Quality Indication (LQI), as reported by the radio
+    ///
+    /// Reported as 0 for frames too short to hold it, see [`Self::rssi`].
+    pub fn hw_lqi(&self) -> u8 {
+        let raw = self.raw();
+        if raw[0] < 2 || raw[0] as usize >= FRAME_SIZE {
+            return 0;
+        }
+
+        raw[raw[0] as usize]
+    }
+
+    /// Carrier frequency offset, as reported by the radio
+    ///
+    /// Reported as 0 for frames too short to be followed by it, see
+    /// [`Self::rssi`].
+    pub fn freq_offset(&self) -> i8 {
+        let raw = self.raw();
+        if raw[0] < 2 || raw[0] as usize + 1 >= FRAME_SIZE {
+            return 0;
+        }
+
+        raw[raw[0] as usize + 1] as i8
+    }
+
+    /// Bitmask of the multipan interfaces whose address filter accepted the
+    /// frame, bit 0 being interface 0
+    pub fn interfaces(&self) -> u8 {
+        self.slot.interfaces
+    }
+
+    /// Why the frame was rejected by the radio, if it was
+    pub fn fault(&self) -> Option<RxFault> {
+        self.slot.fault
+    }
+
+    /// Decode the frame in place
+    pub fn frame(&self) -> Result<mac::Frame<'_>, Error> {
+        let (decoded, _) = mac::Frame::try_read(self.data(), FooterMode::Explicit)?;
+        Ok(decoded)
+    }
+}
+
+impl<'a> Drop for RxToken<'a> {
+    fn drop(&mut self) {
+        // the slot is not used after this, so the frame can't be borrowed anymore
+        ieee802154_release(unsafe { ManuallyDrop::take(&mut self.slot) });
+    }
 }
 
 pub(crate) fn frame_is_ack_required(frame: &[u8]) -> bool {
@@ -43,3 +306,141 @@ pub(crate) fn frame_is_ack_required(frame: &[u8]) -> bool {
 pub(crate) fn frame_get_version(frame: &[u8]) -> u8 {
     frame[FRAME_VERSION_OFFSET] & FRAME_VERSION_MASK
 }
+
+#[cfg(test)]
+mod tests {
+    use ieee802154::mac::{command::Command, Address, PanId, ShortAddress};
+
+    use super::*;
+    use crate::ie::{HeaderIe, PayloadIe};
+
+    fn header(frame_type: FrameType) -> Header {
+        Header {
+            frame_type,
+            frame_pending: false,
+            ack_request: false,
+            pan_id_compress: true,
+            seq_no_suppress: false,
+            ie_present: false,
+            version: FrameVersion::Ieee802154_2006,
+            seq: 7,
+            destination: Some(Address::Short(PanId(0x1234), ShortAddress(2))),
+            source: Some(Address::Short(PanId(0x1234), ShortAddress(1))),
+            auxiliary_security_header: None,
+        }
+    }
+
+    fn round_trip(frame: &Frame) -> Frame {
+        let mut bytes = [0; FRAME_SIZE];
+        let len = frame.encode(&mut bytes).unwrap();
+        let decoded = Frame::decode(&bytes[..len]).unwrap();
+
+        assert_eq!(decoded.header, frame.header);
+        assert_eq!(decoded.content, frame.content);
+        assert_eq!(decoded.payload, frame.payload);
+
+        decoded
+    }
+
+    #[test]
+    fn ies_are_sent_between_header_and_content() {
+        let header_ies = [HeaderIe::Csl {
+            phase: 1,
+            period: 2,
+            rendezvous_time: None,
+        }];
+        let payload_ies = [PayloadIe::Vendor {
+            oui: 0x123456,
+            content: &[7],
+        }];
+        let ies = Ies {
+            header: (&header_ies[..]).into(),
+            payload: (&payload_ies[..]).into(),
+        };
+        let frame = Frame::with_ies(
+            header(FrameType::MacCommand),
+            FrameContent::Command(Command::DataRequest),
+            &ies,
+            &[],
+        )
+        .unwrap();
+
+        let mut bytes = [0; FRAME_SIZE];
+        let len = frame.encode(&mut bytes).unwrap();
+        assert_eq!(bytes[..3], [0x43, 0xaa, 7]);
+        assert_eq!(
+            bytes[9..len - 2],
+            [
+                // CSL, header termination 1, vendor-specific, payload
+                // termination
+                0x04, 0x0d, 1, 0, 2, 0, 0x00, 0x3f, 0x04, 0x90, 0x56, 0x34, 0x12, 7, 0x00, 0xf8,
+                // data request
+                0x04,
+            ]
+        );
+
+        let decoded = round_trip(&frame);
+        let (decoded_ies, rest) = decoded.ies().unwrap();
+        assert_eq!(decoded_ies, ies);
+        assert!(rest.is_empty());
+
+        // data frames with IEs and a payload
+        let frame =
+            Frame::with_ies(header(FrameType::Data), FrameContent::Data, &ies, &[1, 2]).unwrap();
+        let decoded = round_trip(&frame);
+        assert_eq!(decoded.ies().unwrap(), (ies, &[1, 2][..]));
+    }
+
+    #[test]
+    fn frames_without_ies_are_unchanged() {
+        let mut frame = Frame {
+            header: header(FrameType::Data),
+            content: FrameContent::Data,
+            payload: Vec::from_slice(&[1, 2, 3]).unwrap(),
+            footer: [0; 2],
+        };
+        let decoded = round_trip(&frame);
+        assert_eq!(decoded.ies().unwrap(), (Ies::default(), &[1, 2, 3][..]));
+
+        frame.header.version = FrameVersion::Ieee802154;
+        round_trip(&frame);
+    }
+
+    #[test]
+    fn secured_frames_with_ies_are_rejected() {
+        // the IEs and MIC are not taken for the fields of the beacon
+        let frame = [
+            // beacon with security enabled, IEs present, 2015 version
+            0x08, 0xa2, 7, 0x34, 0x12, 0x01, 0x00,
+            // auxiliary security header, level 5, implicit key
+            0x05, 0x01, 0x00, 0x00, 0x00, // header termination 2, MIC and FCS
+            0x80, 0x3f, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert!(matches!(Frame::decode(&frame), Err(Error::BadInput)));
+
+        // truncated and malformed IEs
+        let mut bytes = [0; FRAME_SIZE];
+        let ies = [HeaderIe::Other {
+            id: 0x10,
+            content: &[1, 2, 3],
+        }];
+        let len = Frame::with_ies(
+            header(FrameType::Data),
+            FrameContent::Data,
+            &Ies {
+                header: (&ies[..]).into(),
+                payload: Default::default(),
+            },
+            &[],
+        )
+        .unwrap()
+        .encode(&mut bytes)
+        .unwrap();
+        assert!(matches!(
+            Frame::decode(&[&bytes[..len - 4], &[0, 0]].concat()),
+            Err(Error::Incomplete)
+        ));
+        bytes[10] |= 0x80;
+        assert!(matches!(Frame::decode(&bytes[..len]), Err(Error::BadInput)));
+    }
+}
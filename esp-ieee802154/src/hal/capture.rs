@@ -0,0 +1,455 @@
+//! Replay of captured frames and recording of transmitted frames, used with
+//! the `capture` feature
+//!
+//! Frames are replayed from a pcap file, e.g. one written by the Wireshark
+//! extcap in `extras/sniffer`, into the receive queue of the driver. They are
+//! address filtered like by the radio, and frames with an invalid FCS are
+//! received with a CRC error. Transmitted frames, including the ACKs sent for
+//! replayed frames, are recorded to another pcap file.
+//!
+//! Transmissions complete right away and are never ACKed, frames requesting
+//! an ACK time out right after. Frames are replayed, and transmissions
+//! completed, whenever the driver polls for received frames or [`pump`] is
+//! called.
+//!
+//! The backend is inactive until [`replay`] or [`record`] is called, and again
+//! after [`stop`], so the driver can be used with the other backends in the
+//! meantime. Together with the `sim` feature, the capture belongs to the
+//! calling thread, like the rest of the driver state.
+
+use std::{
+    boxed::Box,
+    collections::VecDeque,
+    io::{Read, Write},
+    sync::Mutex,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    vec::Vec,
+};
+
+pub use pcap_file::PcapError;
+use pcap_file::{
+    pcap::{PcapHeader, PcapPacket, PcapReader, PcapWriter},
+    DataLink, Endianness,
+};
+
+use super::{
+    medium,
+    mock::{raise_events, with_registers},
+    Command, Event,
+};
+use crate::frame::FRAME_SIZE;
+
+// The capture does not tell the signal strength of a frame
+const REPLAY_RSSI: i8 = -50;
+
+#[cfg_attr(feature = "sim", thread_local)]
+static CAPTURE: Mutex<Option<Capture>> = Mutex::new(None);
+
+/// When replayed frames are received
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum Timing {
+    /// With the time between frames they were captured with, frames replayed
+    /// while the radio is not receiving are lost
+    #[default]
+    Original,
+    /// One frame each time the driver polls while receiving, independent of
+    /// how long the application takes to process it
+    Immediate,
+}
+
+struct Capture {
+    replay: VecDeque<Replayed>,
+    timing: Timing,
+    started: Option<Instant>,
+    record: Option<PcapWriter<Box<dyn Write + Send>>>,
+    tx_done: bool,
+    ack_wait: bool,
+}
+
+impl Capture {
+    const fn new() -> Self {
+        Self {
+            replay: VecDeque::new(),
+            timing: Timing::Original,
+            started: None,
+            record: None,
+            tx_done: false,
+            ack_wait: false,
+        }
+    }
+}
+
+struct Replayed {
+    offset: Duration,
+    frame: Vec<u8>,
+    crc_error: bool,
+}
+
+/// Replay the frames of the pcap file read from `reader`
+///
+/// The capture has to use the `IEEE802_15_4` (with FCS) or
+/// `IEEE802_15_4_NOFCS` data link type. Frames which are still to be replayed
+/// from a previous call are discarded, and the timing restarts with the first
+/// frame of this capture.
+pub fn replay(reader: impl Read, timing: Timing) -> Result<(), PcapError> {
+    let mut reader = PcapReader::new(reader)?;
+    let with_fcs = match reader.header().datalink {
+        DataLink::IEEE802_15_4 => true,
+        DataLink::IEEE802_15_4_NOFCS => false,
+        _ => return Err(PcapError::InvalidField("unsupported data link type")),
+    };
+
+    let mut replay = VecDeque::new();
+    let mut first = None;
+    while let Some(packet) = reader.next_packet() {
+        let packet = packet?;
+        let first = *first.get_or_insert(packet.timestamp);
+
+        let (frame, crc_error) = if with_fcs && packet.data.len() >= 2 {
+            let (frame, received_fcs) = packet.data.split_at(packet.data.len() - 2);
+            (frame.to_vec(), fcs(frame) != received_fcs)
+        } else {
+            (packet.data.to_vec(), false)
+        };
+
+        if frame.len() < 3 || frame.len() > FRAME_SIZE - 4 {
+            continue;
+        }
+
+        replay.push_back(Replayed {
+            offset: packet.timestamp.saturating_sub(first),
+            frame,
+            crc_error,
+        });
+    }
+
+    let mut capture = CAPTURE.lock().unwrap();
+    let capture = capture.get_or_insert_with(Capture::new);
+    capture.replay = replay;
+    capture.timing = timing;
+    capture.started = Some(Instant::now());
+
+    Ok(())
+}
+
+/// Number of frames which are still to be replayed
+pub fn remaining() -> usize {
+    CAPTURE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(0, |capture| capture.replay.len())
+}
+
+/// Record all transmitted frames as a pcap file to `writer`
+///
+/// Frames are written with the `IEEE802_15_4` data link type, like by the
+/// extcap. Replaces the writer of a previous call, which is returned.
+pub fn record(
+    writer: impl Write + Send + 'static,
+) -> Result<Option<Box<dyn Write + Send>>, PcapError> {
+    let header = PcapHeader {
+        datalink: DataLink::IEEE802_15_4,
+        endianness: Endianness::Big,
+        ..Default::default()
+    };
+    let writer = PcapWriter::with_header(Box::new(writer) as Box<dyn Write + Send>, header)?;
+
+    let previous = CAPTURE
+        .lock()
+        .unwrap()
+        .get_or_insert_with(Capture::new)
+        .record
+        .replace(writer);
+    Ok(previous.map(PcapWriter::into_writer))
+}
+
+/// Stop recording, returns the writer passed to [`record`]
+pub fn stop_recording() -> Option<Box<dyn Write + Send>> {
+    let writer = CAPTURE.lock().unwrap().as_mut()?.record.take();
+    writer.map(PcapWriter::into_writer)
+}
+
+/// Stop replaying and recording, after which the backend is inactive until
+/// [`replay`] or [`record`] is called again, returns the writer passed to
+/// [`record`]
+pub fn stop() -> Option<Box<dyn Write + Send>> {
+    let capture = CAPTURE.lock().unwrap().take()?;
+    capture.record.map(PcapWriter::into_writer)
+}
+
+/// Receive the replayed frames which are due, and complete the ongoing
+/// transmission and energy detection
+///
+/// This is called when polling for received frames, but needs to be called
+/// explicitly to e.g. complete a transmission.
+pub fn pump() {
+    let receiving = with_registers(|regs| regs.cmd) == Command::RxStart as u8;
+
+    let (tx_done, ack_wait, due) = {
+        let mut capture = CAPTURE.lock().unwrap();
+        let Some(capture) = capture.as_mut() else {
+            return;
+        };
+        let tx_done = core::mem::take(&mut capture.tx_done);
+        let ack_wait = core::mem::take(&mut capture.ack_wait);
+
+        let mut due = Vec::new();
+        match capture.timing {
+            Timing::Original => {
+                let elapsed = capture
+                    .started
+                    .map(|started| started.elapsed())
+                    .unwrap_or_default();
+                while capture
+                    .replay
+                    .front()
+                    .is_some_and(|replayed| replayed.offset <= elapsed)
+                {
+                    due.extend(capture.replay.pop_front());
+                }
+            }
+            Timing::Immediate if receiving && !tx_done => {
+                due.extend(capture.replay.pop_front());
+            }
+            Timing::Immediate => (),
+        }
+
+        (tx_done, ack_wait, due)
+    };
+
+    if tx_done {
+        raise_events(Event::TxDone as u16);
+    }
+    if ack_wait {
+        super::mock::ack_timeout();
+    }
+
+    for replayed in due {
+        receive(&replayed.frame, replayed.crc_error);
+    }
+
+    super::mock::energy_detect_done(medium::NOISE_FLOOR);
+}
+
+/// Record the frame the radio started to transmit
+pub(super) fn transmit(buffer: &[u8; FRAME_SIZE]) {
+    let psdu_len = buffer[0] as usize;
+    if !(5..=127).contains(&psdu_len) {
+        return;
+    }
+
+    let frame = &buffer[1..][..psdu_len - 2];
+    let rx_auto_ack = with_registers(|regs| regs.rx_auto_ack);
+
+    let mut capture = CAPTURE.lock().unwrap();
+    let Some(capture) = capture.as_mut() else {
+        return;
+    };
+    capture.tx_done = true;
+    // the other media deliver the ACKs, or their absence, themselves
+    capture.ack_wait = cfg!(not(any(feature = "sim", feature = "std")))
+        && rx_auto_ack
+        && medium::ack_requested(frame)
+        && !medium::is_broadcast(frame);
+    record_frame(capture, frame);
+}
+
+fn receive(frame: &[u8], crc_error: bool) {
+    let regs = with_registers(|regs| *regs);
+    let filter_fail = medium::filter(&regs, frame);
+
+    if medium::receive(frame, REPLAY_RSSI, filter_fail, crc_error)
+        && medium::will_ack(&regs, frame, filter_fail)
+    {
+        // the frame pending bit was set while receiving the frame
+        let ack = medium::ack_for(&with_registers(|regs| *regs), frame);
+        if let Some(capture) = CAPTURE.lock().unwrap().as_mut() {
+            record_frame(capture, &ack);
+        }
+        raise_events(Event::AckTxDone as u16);
+    }
+}
+
+fn record_frame(capture: &mut Capture, frame: &[u8]) {
+    let Some(writer) = capture.record.as_mut() else {
+        return;
+    };
+
+    let mut psdu = Vec::with_capacity(frame.len() + 2);
+    psdu.extend_from_slice(frame);
+    psdu.extend_from_slice(&fcs(frame));
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    if let Err(err) = writer.write_packet(&PcapPacket::new(timestamp, psdu.len() as u32, &psdu)) {
+        log::warn!("Recording frame failed: {:?}", err);
+    }
+}
+
+/// The FCS of `frame`, CRC-16/KERMIT
+fn fcs(frame: &[u8]) -> [u8; 2] {
+    let mut crc: u16 = 0;
+    for &byte in frame {
+        for bit in 0..8 {
+            let feedback = ((byte >> bit) & 1 != 0) ^ (crc & 1 != 0);
+            crc >>= 1;
+            if feedback {
+                crc ^= 0x8408;
+            }
+        }
+    }
+
+    crc.to_le_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Cursor,
+        sync::{Arc, Mutex},
+    };
+
+    use super::*;
+    use crate::{hal::mock, Channel, Config, Ieee802154, RxFault};
+
+    /// A writer whose bytes can still be read once it was handed over
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    // data frames in PAN 0x1234 from 0x0002 to 0x0001
+    fn data_frame(sequence: u8, ack_request: bool) -> Vec<u8> {
+        let fcf = if ack_request { 0x61 } else { 0x41 };
+        std::vec![fcf, 0x88, sequence, 0x34, 0x12, 0x01, 0x00, 0x02, 0x00, 0xaa]
+    }
+
+    fn pcap(frames: &[Vec<u8>]) -> Vec<u8> {
+        let header = PcapHeader {
+            datalink: DataLink::IEEE802_15_4,
+            ..Default::default()
+        };
+        let mut writer = PcapWriter::with_header(Vec::new(), header).unwrap();
+        for (i, frame) in frames.iter().enumerate() {
+            let timestamp = Duration::from_millis(i as u64);
+            writer
+                .write_packet(&PcapPacket::new(timestamp, frame.len() as u32, frame))
+                .unwrap();
+        }
+
+        writer.into_writer()
+    }
+
+    fn with_fcs(frame: &[u8]) -> Vec<u8> {
+        let mut psdu = frame.to_vec();
+        psdu.extend_from_slice(&fcs(frame));
+        psdu
+    }
+
+    fn packets(pcap: &[u8]) -> Vec<Vec<u8>> {
+        let mut reader = PcapReader::new(pcap).unwrap();
+        assert_eq!(reader.header().datalink, DataLink::IEEE802_15_4);
+
+        let mut packets = Vec::new();
+        while let Some(packet) = reader.next_packet() {
+            packets.push(packet.unwrap().data.to_vec());
+        }
+
+        packets
+    }
+
+    fn received(radio: &mut Ieee802154<'_>) -> Vec<(Vec<u8>, Option<RxFault>)> {
+        // one frame is replayed per poll
+        let mut received = Vec::new();
+        for _ in 0..8 {
+            if let Some(raw) = radio.get_raw_received() {
+                let len = raw.data[0] as usize - 2;
+                received.push((raw.data[1..][..len].to_vec(), raw.fault));
+            }
+        }
+        assert_eq!(remaining(), 0);
+
+        received
+    }
+
+    #[test]
+    fn replayed_and_recorded_frames_round_trip() {
+        let _lock = mock::test_lock();
+        let mut radio = Ieee802154::new(mock::IEEE802154, &mut mock::RADIO_CLK);
+        radio.set_config(Config {
+            channel: Channel::new(15).unwrap(),
+            pan_id: Some(0x1234),
+            short_addr: Some(0x0001),
+            promiscuous: false,
+            auto_ack_tx: true,
+            rx_when_idle: true,
+            rx_bad_frames: true,
+            ..Default::default()
+        });
+        radio.start_receive();
+
+        // the second frame is replayed with a CRC error
+        let mut corrupted = with_fcs(&data_frame(2, false));
+        corrupted[10] ^= 0xff;
+        let capture = pcap(&[with_fcs(&data_frame(1, true)), corrupted]);
+        let recording = Shared::default();
+        replay(Cursor::new(capture), Timing::Immediate).unwrap();
+        record(recording.clone()).unwrap();
+
+        assert_eq!(
+            received(&mut radio),
+            [
+                (data_frame(1, true), None),
+                (data_frame(2, false), Some(RxFault::CrcError)),
+            ]
+        );
+
+        let transmitted = [0x41, 0x88, 3, 0x34, 0x12, 0x02, 0x00, 0x01, 0x00, 0x55];
+        radio.transmit_raw(&with_fcs(&transmitted)).unwrap();
+        pump();
+        assert_eq!(radio.stats().tx_done, 1);
+        assert!(stop().is_some());
+
+        // the ACK of the first frame and the transmitted frame were recorded
+        let recorded = packets(&recording.0.lock().unwrap());
+        assert_eq!(recorded.len(), 2);
+        assert_eq!(recorded[0][2], 1);
+        assert_eq!(recorded[0], with_fcs(&recorded[0][..3]));
+        assert_eq!(recorded[1], with_fcs(&transmitted));
+
+        // replaying the recording receives the same frames
+        radio.reconfigure(Config {
+            promiscuous: true,
+            ..radio.config()
+        });
+        replay(
+            Cursor::new(recording.0.lock().unwrap().clone()),
+            Timing::Immediate,
+        )
+        .unwrap();
+        let replayed = received(&mut radio);
+        assert!(stop().is_none());
+        assert_eq!(
+            replayed,
+            [
+                (recorded[0][..3].to_vec(), None),
+                (transmitted.to_vec(), None)
+            ]
+        );
+
+        // inactive again, transmissions are left to the other backends
+        radio.transmit_raw(&with_fcs(&transmitted)).unwrap();
+        pump();
+        assert_eq!(radio.stats().tx_done, 1);
+    }
+}
@@ -0,0 +1,483 @@
+pub use esp_hal::peripherals::{IEEE802154, RADIO_CLK};
+use esp_hal::{
+    interrupt::Priority,
+    prelude::handler,
+    system::{RadioClockController, RadioPeripherals},
+};
+use esp_wifi_sys::include::{
+    esp_phy_calibration_data_t, esp_phy_calibration_mode_t_PHY_RF_CAL_FULL,
+    ieee802154_coex_event_t, ieee802154_coex_event_t_IEEE802154_IDLE,
+    ieee802154_coex_event_t_IEEE802154_LOW, ieee802154_coex_event_t_IEEE802154_MIDDLE,
+    register_chipv7_phy,
+};
+
+use super::{
+    CcaMode, CoexEvent, Command, EdSampleMode, ErrorCounter, MultipanIndex, RadioRegisters,
+};
+use crate::raw::ieee802154_isr;
+
+const PHY_ENABLE_VERSION_PRINT: u32 = 1;
+
+extern "C" {
+    fn bt_bb_v2_init_cmplx(print_version: u32); // from libbtbb.a
+
+    fn bt_bb_set_zb_tx_on_delay(time: u16); // from libbtbb.a
+
+    fn esp_coex_ieee802154_ack_pti_set(event: ieee802154_coex_event_t); // from ???
+
+    fn esp_coex_ieee802154_txrx_pti_set(event: ieee802154_coex_event_t); // from ???
+
+    fn phy_version_print(); // from libphy.a
+}
+
+/// The IEEE 802.15.4 radio of the ESP32-C6 and ESP32-H2
+pub(crate) struct EspRadio;
+
+impl RadioRegisters for EspRadio {
+    fn enable_clocks(radio_clock_control: &mut RADIO_CLK) {
+        radio_clock_control.init_clocks();
+        radio_clock_control.enable(RadioPeripherals::Phy);
+        radio_clock_control.enable(RadioPeripherals::Ieee802154);
+    }
+
+    fn phy_enable() {
+        unsafe {
+            let mut calibration_data = esp_phy_calibration_data_t {
+                version: [0u8; 4],
+                mac: [0u8; 6],
+                opaque: [0u8; 1894],
+            };
+
+            register_chipv7_phy(
+                core::ptr::null(),
+                &mut calibration_data as *mut esp_phy_calibration_data_t,
+                esp_phy_calibration_mode_t_PHY_RF_CAL_FULL,
+            );
+        }
+    }
+
+    fn btbb_enable() {
+        unsafe { bt_bb_v2_init_cmplx(PHY_ENABLE_VERSION_PRINT) };
+    }
+
+    fn phy_version_print() {
+        unsafe { phy_version_print() }; // libphy.a
+    }
+
+    fn coex_init() {
+        #[cfg(feature = "esp32c6")]
+        unsafe {
+            extern "C" {
+                static mut coex_pti_tab_ptr: u32;
+                static coex_pti_tab: u8;
+            }
+
+            // Manually set `coex_pti_tab_ptr` pointing to `coex_pti_tab`
+            core::ptr::addr_of_mut!(coex_pti_tab_ptr)
+                .write_volatile(&coex_pti_tab as *const _ as u32);
+        }
+    }
+
+    fn set_coex_ack_pti(event: CoexEvent) {
+        unsafe { esp_coex_ieee802154_ack_pti_set(coex_event(event)) };
+    }
+
+    fn set_coex_txrx_pti(event: CoexEvent) {
+        unsafe { esp_coex_ieee802154_txrx_pti_set(coex_event(event)) };
+    }
+
+    fn set_tx_on_delay(delay: u16) {
+        unsafe {
+            bt_bb_set_zb_tx_on_delay(delay); // set tx on delay for libbtbb.a
+        }
+    }
+
+    fn enable_interrupt() {
+        unsafe {
+            esp_hal::interrupt::bind_interrupt(
+                esp_hal::peripherals::Interrupt::ZB_MAC,
+                ZB_MAC.handler(),
+            );
+        }
+        esp_hal::interrupt::enable(esp_hal::peripherals::Interrupt::ZB_MAC, ZB_MAC.priority())
+            .unwrap();
+    }
+
+    #[inline(always)]
+    fn mac_date() -> u32 {
+        unsafe { &*IEEE802154::PTR }.mac_date().read().bits()
+    }
+
+    #[inline(always)]
+    fn set_rx_on_delay(delay: u16) {
+        unsafe { &*IEEE802154::PTR }
+            .rxon_delay()
+            .modify(|_, w| unsafe { w.rxon_delay().bits(delay) });
+    }
+
+    #[inline(always)]
+    fn enable_events(events: u16) {
+        unsafe { &*IEEE802154::PTR }
+            .event_en()
+            .modify(|r, w| unsafe { w.event_en().bits(r.event_en().bits() | events) });
+    }
+
+    #[inline(always)]
+    fn disable_events(events: u16) {
+        unsafe { &*IEEE802154::PTR }
+            .event_en()
+            .modify(|r, w| unsafe { w.event_en().bits(r.event_en().bits() & !events) });
+    }
+
+    #[inline(always)]
+    fn enable_tx_abort_events(events: u32) {
+        unsafe { &*IEEE802154::PTR }
+            .tx_abort_interrupt_control()
+            .modify(|r, w| unsafe {
+                w.tx_abort_interrupt_control()
+                    .bits(r.tx_abort_interrupt_control().bits() | events)
+            });
+    }
+
+    #[inline(always)]
+    fn enable_rx_abort_events(events: u32) {
+        unsafe { &*IEEE802154::PTR }
+            .rx_abort_intr_ctrl()
+            .modify(|r, w| unsafe {
+                w.rx_abort_intr_ctrl()
+                    .bits(r.rx_abort_intr_ctrl().bits() | events)
+            });
+    }
+
+    #[inline(always)]
+    fn disable_rx_abort_events(events: u32) {
+        unsafe { &*IEEE802154::PTR }
+            .rx_abort_intr_ctrl()
+            .modify(|r, w| unsafe {
+                w.rx_abort_intr_ctrl()
+                    .bits(r.rx_abort_intr_ctrl().bits() & !events)
+            });
+    }
+
+    #[inline(always)]
+    fn set_ed_sample_mode(ed_sample_mode: EdSampleMode) {
+        unsafe { &*IEEE802154::PTR }
+            .ed_scan_cfg()
+            .modify(|_, w| unsafe { w.ed_sample_mode().bits(ed_sample_mode as u8) });
+    }
+
+    #[inline(always)]
+    fn set_ed_duration(duration: u32) {
+        unsafe { &*IEEE802154::PTR }
+            .ed_scan_duration()
+            .modify(|_, w| unsafe { w.ed_scan_duration().bits(duration) });
+    }
+
+    #[inline(always)]
+    fn get_ed_rss() -> i8 {
+        unsafe { &*IEEE802154::PTR }
+            .ed_scan_cfg()
+            .read()
+            .ed_rss()
+            .bits() as i8
+    }
+
+    #[inline(always)]
+    fn set_tx_addr(addr: *const u8) {
+        unsafe { &*IEEE802154::PTR }
+            .txdma_addr()
+            .modify(|_, w| unsafe { w.txdma_addr().bits(addr as u32) });
+    }
+
+    #[inline(always)]
+    fn set_cmd(cmd: Command) {
+        unsafe { &*IEEE802154::PTR }
+            .command()
+            .modify(|_, w| unsafe { w.opcode().bits(cmd as u8) })
+    }
+
+    #[inline(always)]
+    fn set_freq(freq: u8) {
+        unsafe { &*IEEE802154::PTR }
+            .channel()
+            .modify(|_, w| unsafe { w.hop().bits(freq) });
+    }
+
+    #[inline(always)]
+    fn get_freq() -> u8 {
+        unsafe { &*IEEE802154::PTR }.channel().read().hop().bits()
+    }
+
+    #[inline(always)]
+    fn set_power(power: u8) {
+        unsafe { &*IEEE802154::PTR }
+            .tx_power()
+            .modify(|_, w| unsafe { w.tx_power().bits(power) });
+    }
+
+    #[inline(always)]
+    fn set_multipan_enable_mask(mask: u8) {
+        // apparently the REGS are garbage and the struct is right?
+        unsafe { &*IEEE802154::PTR }
+            .ctrl_cfg()
+            .modify(|r, w| unsafe { w.bits(r.bits() & !(0b1111 << 29) | (mask as u32) << 29) })
+    }
+
+    #[inline(always)]
+    fn set_multipan_panid(index: MultipanIndex, panid: u16) {
+        unsafe {
+            let pan_id = (&*IEEE802154::PTR)
+                .inf0_pan_id()
+                .as_ptr()
+                .offset(4 * index as isize);
+            pan_id.write_volatile(panid as u32);
+        }
+    }
+
+    #[inline(always)]
+    fn set_multipan_short_addr(index: MultipanIndex, value: u16) {
+        unsafe {
+            let short_addr = (&*IEEE802154::PTR)
+                .inf0_short_addr()
+                .as_ptr()
+                .offset(4 * index as isize);
+            short_addr.write_volatile(value as u32);
+        }
+    }
+
+    #[inline(always)]
+    fn set_multipan_ext_addr(index: MultipanIndex, ext_addr: *const u8) {
+        unsafe {
+            let mut ext_addr_ptr = (&*IEEE802154::PTR)
+                .inf0_extend_addr0()
+                .as_ptr()
+                .offset(4 * index as isize);
+
+            ext_addr_ptr.write_volatile(
+                (ext_addr.offset(0).read_volatile() as u32)
+                    | ((ext_addr.offset(1).read_volatile() as u32) << 8)
+                    | ((ext_addr.offset(2).read_volatile() as u32) << 16)
+                    | ((ext_addr.offset(3).read_volatile() as u32) << 24),
+            );
+
+            ext_addr_ptr = ext_addr_ptr.offset(1);
+
+            ext_addr_ptr.write_volatile(
+                (ext_addr.offset(4).read_volatile() as u32)
+                    | ((ext_addr.offset(5).read_volatile() as u32) << 8)
+                    | ((ext_addr.offset(6).read_volatile() as u32) << 16)
+                    | ((ext_addr.offset(7).read_volatile() as u32) << 24),
+            );
+        }
+    }
+
+    #[inline(always)]
+    fn set_cca_mode(cca_mode: CcaMode) {
+        unsafe { &*IEEE802154::PTR }
+            .ed_scan_cfg()
+            .modify(|_, w| unsafe { w.cca_mode().bits(cca_mode as u8) });
+    }
+
+    #[inline(always)]
+    fn set_cca_threshold(cca_threshold: i8) {
+        unsafe { &*IEEE802154::PTR }
+            .ed_scan_cfg()
+            .modify(|_, w| unsafe { w.cca_ed_threshold().bits(cca_threshold as u8) })
+    }
+
+    #[inline(always)]
+    fn set_tx_auto_ack(enable: bool) {
+        unsafe { &*IEEE802154::PTR }
+            .ctrl_cfg()
+            .modify(|_, w| w.hw_auto_ack_tx_en().bit(enable));
+    }
+
+    #[inline(always)]
+    fn get_tx_auto_ack() -> bool {
+        unsafe { &*IEEE802154::PTR }
+            .ctrl_cfg()
+            .read()
+            .hw_auto_ack_tx_en()
+            .bit_is_set()
+    }
+
+    #[inline(always)]
+    fn set_rx_auto_ack(enable: bool) {
+        unsafe { &*IEEE802154::PTR }
+            .ctrl_cfg()
+            .modify(|_, w| w.hw_auto_ack_rx_en().bit(enable));
+    }
+
+    #[inline(always)]
+    fn set_tx_enhance_ack(enable: bool) {
+        unsafe { &*IEEE802154::PTR }
+            .ctrl_cfg()
+            .modify(|_, w| w.hw_enhance_ack_tx_en().bit(enable));
+    }
+
+    #[inline(always)]
+    fn get_tx_enhance_ack() -> bool {
+        unsafe { &*IEEE802154::PTR }
+            .ctrl_cfg()
+            .read()
+            .hw_enhance_ack_tx_en()
+            .bit_is_set()
+    }
+
+    #[inline(always)]
+    fn set_coordinator(enable: bool) {
+        unsafe { &*IEEE802154::PTR }
+            .ctrl_cfg()
+            .modify(|_, w| w.pan_coordinator().bit(enable));
+    }
+
+    #[inline(always)]
+    fn set_promiscuous(enable: bool) {
+        unsafe { &*IEEE802154::PTR }
+            .ctrl_cfg()
+            .modify(|_, w| w.promiscuous_mode().bit(enable));
+    }
+
+    #[inline(always)]
+    fn set_pending_mode(enable: bool) {
+        unsafe { &*IEEE802154::PTR }
+            .ctrl_cfg()
+            .modify(|_, w| w.autopend_enhance().bit(enable));
+    }
+
+    #[inline(always)]
+    fn set_pending_bit(enable: bool) {
+        unsafe { &*IEEE802154::PTR }
+            .ack_frame_pending_en()
+            .modify(|_, w| w.ack_frame_pending_en().bit(enable));
+    }
+
+    #[inline(always)]
+    fn get_events() -> u16 {
+        unsafe { &*IEEE802154::PTR }.event_status().read().bits() as u16
+    }
+
+    #[inline(always)]
+    fn clear_events(events: u16) {
+        unsafe { &*IEEE802154::PTR }
+            .event_status()
+            .modify(|r, w| unsafe { w.event_status().bits(r.event_status().bits() & events) });
+    }
+
+    #[inline(always)]
+    fn set_transmit_security(enable: bool) {
+        unsafe { &*IEEE802154::PTR }
+            .sec_ctrl()
+            .modify(|_, w| w.sec_en().bit(enable));
+    }
+
+    #[inline(always)]
+    fn set_rx_addr(addr: *mut u8) {
+        unsafe { &*IEEE802154::PTR }
+            .rxdma_addr()
+            .modify(|_, w| unsafe { w.rxdma_addr().bits(addr as u32) });
+    }
+
+    #[inline(always)]
+    fn get_filter_fail_status() -> u8 {
+        unsafe { &*IEEE802154::PTR }
+            .rx_status()
+            .read()
+            .filter_fail_status()
+            .bits()
+    }
+
+    #[inline(always)]
+    fn get_rx_abort_reason() -> u8 {
+        unsafe { &*IEEE802154::PTR }
+            .rx_status()
+            .read()
+            .rx_abort_status()
+            .bits()
+    }
+
+    #[inline(always)]
+    fn get_tx_abort_reason() -> u8 {
+        unsafe { &*IEEE802154::PTR }
+            .tx_status()
+            .read()
+            .tx_abort_status()
+            .bits()
+    }
+
+    #[inline(always)]
+    fn set_rx_append_lqi(enable: bool) {
+        unsafe { &*IEEE802154::PTR }
+            .rxdma_ctrl_state()
+            .modify(|_, w| w.rxdma_append_lqi_offset().bit(enable));
+    }
+
+    #[inline(always)]
+    fn set_rx_append_freq_offset(enable: bool) {
+        unsafe { &*IEEE802154::PTR }
+            .rxdma_ctrl_state()
+            .modify(|_, w| w.rxdma_append_freq_offset().bit(enable));
+    }
+
+    #[inline(always)]
+    fn abort_tx() {
+        unsafe { &*IEEE802154::PTR }
+            .tx_status()
+            .modify(|_, w| unsafe { w.tx_abort_status().bits(0) });
+    }
+
+    #[inline(always)]
+    fn abort_rx() {
+        unsafe { &*IEEE802154::PTR }
+            .rx_status()
+            .modify(|_, w| unsafe { w.rx_abort_status().bits(0) });
+    }
+
+    #[inline(always)]
+    fn get_error_count(counter: ErrorCounter) -> u16 {
+        let regs = unsafe { &*IEEE802154::PTR };
+
+        let count = match counter {
+            ErrorCounter::CcaBusy => regs.cca_busy_cnt().read().bits(),
+            ErrorCounter::TxSecurityError => regs.tx_security_error_cnt().read().bits(),
+            ErrorCounter::TxBreakCoex => regs.tx_break_coex_cnt().read().bits(),
+            ErrorCounter::RxAckTimeout => regs.rx_ack_timeout_cnt().read().bits(),
+            ErrorCounter::RxAckAbortCoex => regs.rx_ack_abort_coex_cnt().read().bits(),
+            ErrorCounter::EdScanCoex => regs.ed_scan_coex_cnt().read().bits(),
+            ErrorCounter::TxAckAbortCoex => regs.tx_ack_abort_coex_cnt().read().bits(),
+            ErrorCounter::RxRestart => regs.rx_restart_cnt().read().bits(),
+            ErrorCounter::RxAbortCoex => regs.rx_abort_coex_cnt().read().bits(),
+            ErrorCounter::NoRssDetect => regs.no_rss_detect_cnt().read().bits(),
+            ErrorCounter::RxFilterFail => regs.rx_filter_fail_cnt().read().bits(),
+            ErrorCounter::CcaFail => regs.cca_fail_cnt().read().bits(),
+            ErrorCounter::EdAbort => regs.ed_abort_cnt().read().bits(),
+            ErrorCounter::CrcError => regs.crc_error_cnt().read().bits(),
+            ErrorCounter::SfdTimeout => regs.sfd_timeout_cnt().read().bits(),
+        };
+
+        count as u16
+    }
+
+    #[inline(always)]
+    fn clear_error_counts(counters: u32) {
+        // the register has no reset value, the other bits are written as 0
+        unsafe {
+            (*IEEE802154::PTR)
+                .error_cnt_clear()
+                .write_with_zero(|w| w.bits(counters));
+        }
+    }
+}
+
+fn coex_event(event: CoexEvent) -> ieee802154_coex_event_t {
+    match event {
+        CoexEvent::Idle => ieee802154_coex_event_t_IEEE802154_IDLE,
+        CoexEvent::Low => ieee802154_coex_event_t_IEEE802154_LOW,
+        CoexEvent::Middle => ieee802154_coex_event_t_IEEE802154_MIDDLE,
+    }
+}
+
+#[handler(priority = "Priority::Priority1")]
+fn ZB_MAC() {
+    ieee802154_isr();
+}
@@ -0,0 +1,151 @@
+//! Reception of frames by the in-memory radio, shared by the backends which
+//! connect it to the radios of other drivers
+
+use super::{
+    mock::{count_error, raise_events, with_registers, write_rx_buffer, Registers},
+    Command, ErrorCounter, Event, RxAbortReason,
+};
+use crate::rssi_to_lqi;
+
+/// Energy detected while no frame is on the air, in dBm
+pub(super) const NOISE_FLOOR: i8 = -100;
+
+pub(super) const FRAME_TYPE_BEACON: u16 = 0;
+pub(super) const FRAME_TYPE_ACK: u16 = 2;
+const ADDR_MODE_SHORT: u16 = 2;
+const ADDR_MODE_EXT: u16 = 3;
+const BROADCAST: u16 = 0xffff;
+// IEEE 802.15.4-2006
+const FRAME_VERSION_MAX_AUTO_ACK: u16 = 1;
+const FRAME_PENDING: u8 = 1 << 4;
+
+/// Receive `frame`, given without the FCS, if the radio is receiving
+///
+/// `filter_fail` holds the result of [`filter`] for the frame. Frames which
+/// are rejected, or have a CRC error, are counted and reported as a receive
+/// abort. Returns `true` if the frame was received without error.
+pub(super) fn receive(frame: &[u8], rssi: i8, filter_fail: u8, crc_error: bool) -> bool {
+    let regs = with_registers(|regs| *regs);
+    if regs.cmd != Command::RxStart as u8 {
+        return false;
+    }
+
+    let reason = if crc_error {
+        Some((RxAbortReason::CrcError, ErrorCounter::CrcError))
+    } else if !accepted(&regs, filter_fail) {
+        Some((RxAbortReason::FilterFail, ErrorCounter::RxFilterFail))
+    } else {
+        None
+    };
+
+    let Some((reason, counter)) = reason else {
+        with_registers(|regs| regs.filter_fail_status = filter_fail);
+        return super::mock::receive(frame, rssi, rssi_to_lqi(rssi));
+    };
+
+    let enabled = with_registers(|regs| {
+        count_error(regs, counter);
+        regs.rx_abort_en & reason.bit() != 0
+    });
+
+    if enabled && write_rx_buffer(frame, rssi, rssi_to_lqi(rssi)) {
+        with_registers(|regs| {
+            regs.rx_abort_status = reason as u8;
+            regs.filter_fail_status = filter_fail;
+        });
+        raise_events(Event::RxAbort as u16);
+    }
+
+    false
+}
+
+/// Whether a frame which `filter` returned `filter_fail` for is received
+pub(super) fn accepted(regs: &Registers, filter_fail: u8) -> bool {
+    regs.promiscuous || !filter_fail & regs.multipan_mask != 0
+}
+
+/// Whether the radio of `regs` sends an ACK for `frame` after receiving it
+///
+/// The enhanced ACKs of frames of the 2015 version are sent by the driver.
+pub(super) fn will_ack(regs: &Registers, frame: &[u8], filter_fail: u8) -> bool {
+    regs.tx_auto_ack
+        && ack_requested(frame)
+        && frame_version(frame) <= FRAME_VERSION_MAX_AUTO_ACK
+        && !is_broadcast(frame)
+        && accepted(regs, filter_fail)
+}
+
+/// Build the ACK the radio of `regs` sends for `frame`, without the FCS
+pub(super) fn ack_for(regs: &Registers, frame: &[u8]) -> [u8; 3] {
+    let frame_pending = if regs.pending_bit { FRAME_PENDING } else { 0 };
+    [
+        FRAME_TYPE_ACK as u8 | frame_pending,
+        0,
+        sequence_number(frame),
+    ]
+}
+
+pub(super) fn frame_type(frame: &[u8]) -> u16 {
+    frame_control(frame) & 0b111
+}
+
+fn frame_version(frame: &[u8]) -> u16 {
+    (frame_control(frame) >> 12) & 0b11
+}
+
+pub(super) fn sequence_number(frame: &[u8]) -> u8 {
+    frame.get(2).copied().unwrap_or(0)
+}
+
+pub(super) fn ack_requested(frame: &[u8]) -> bool {
+    frame_control(frame) & (1 << 5) != 0
+}
+
+pub(super) fn is_broadcast(frame: &[u8]) -> bool {
+    let fcf = frame_control(frame);
+    (fcf >> 10) & 0b11 == ADDR_MODE_SHORT
+        && frame.len() >= 7
+        && u16::from_le_bytes([frame[5], frame[6]]) == BROADCAST
+}
+
+fn frame_control(frame: &[u8]) -> u16 {
+    match frame {
+        [first, second, ..] => u16::from_le_bytes([*first, *second]),
+        _ => 0,
+    }
+}
+
+/// Run the address filter of every interface of `regs` on `frame`, returns a
+/// bit for each interface rejecting the frame
+pub(super) fn filter(regs: &Registers, frame: &[u8]) -> u8 {
+    let dst_mode = (frame_control(frame) >> 10) & 0b11;
+
+    let mut fail = 0u8;
+    for index in 0..regs.panid.len() {
+        let accepted = match dst_mode {
+            ADDR_MODE_SHORT if frame.len() >= 7 => {
+                let panid = u16::from_le_bytes([frame[3], frame[4]]);
+                let addr = u16::from_le_bytes([frame[5], frame[6]]);
+                (panid == BROADCAST || panid == regs.panid[index])
+                    && (addr == BROADCAST || addr == regs.short_addr[index])
+            }
+            ADDR_MODE_EXT if frame.len() >= 13 => {
+                let panid = u16::from_le_bytes([frame[3], frame[4]]);
+                let mut addr = [0u8; 8];
+                addr.copy_from_slice(&frame[5..13]);
+                (panid == BROADCAST || panid == regs.panid[index])
+                    && u64::from_le_bytes(addr) == u64::from_be_bytes(regs.ext_addr[index])
+            }
+            // without a destination address only beacons, and frames for the
+            // PAN coordinator, are accepted
+            0 => frame_type(frame) == FRAME_TYPE_BEACON || regs.coordinator,
+            _ => false,
+        };
+
+        if !accepted {
+            fail |= 1 << index;
+        }
+    }
+
+    fail
+}
@@ -0,0 +1,579 @@
+//! In-memory radio, used instead of the ESP32-C6/H2 radio with the `mock`
+//! feature
+//!
+//! Registers written by the driver are stored, and transmissions, receptions
+//! and events are driven by the functions in this module, which allows running
+//! the driver on the host.
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use heapless::Vec;
+
+use super::{
+    CcaMode, CoexEvent, Command, EdSampleMode, ErrorCounter, Event, MultipanIndex, RadioRegisters,
+    RxAbortReason, TxAbortReason,
+};
+use crate::{
+    pib::IEEE802154_FRAME_EXT_ADDR_SIZE,
+    raw::{ieee802154_isr, FRAME_SIZE},
+    rssi_to_lqi,
+};
+
+const IEEE802154_MULTIPAN_MAX: usize = 4;
+
+#[cfg_attr(feature = "sim", thread_local)]
+static REGISTERS: Mutex<RefCell<Registers>> = Mutex::new(RefCell::new(Registers::new()));
+#[cfg_attr(feature = "sim", thread_local)]
+static TRANSMITTED: Mutex<RefCell<Transmitted>> = Mutex::new(RefCell::new(Transmitted {
+    frame: [0u8; FRAME_SIZE],
+    valid: false,
+}));
+
+/// Stand-in for the IEEE 802.15.4 radio peripheral
+#[allow(non_camel_case_types)]
+pub struct IEEE802154;
+
+/// Stand-in for the radio clock peripheral
+#[allow(non_camel_case_types)]
+pub struct RADIO_CLK;
+
+/// The register file of the in-memory radio
+///
+/// Fields hold the raw values the driver wrote to the corresponding
+/// registers of the ESP32-C6/H2 radio.
+#[derive(Debug, Clone, Copy)]
+pub struct Registers {
+    pub clocks_enabled: bool,
+    pub interrupt_enabled: bool,
+    pub coex_ack_pti: u8,
+    pub coex_txrx_pti: u8,
+    pub tx_on_delay: u16,
+    pub rx_on_delay: u16,
+    pub event_en: u16,
+    pub event_status: u16,
+    pub tx_abort_en: u32,
+    pub rx_abort_en: u32,
+    pub tx_abort_status: u8,
+    pub rx_abort_status: u8,
+    pub filter_fail_status: u8,
+    pub ed_sample_mode: u8,
+    pub ed_duration: u32,
+    pub ed_rss: i8,
+    pub freq_offset: i8,
+    pub tx_addr: usize,
+    pub rx_addr: usize,
+    pub cmd: u8,
+    pub freq: u8,
+    pub power: u8,
+    pub multipan_mask: u8,
+    pub panid: [u16; IEEE802154_MULTIPAN_MAX],
+    pub short_addr: [u16; IEEE802154_MULTIPAN_MAX],
+    pub ext_addr: [[u8; IEEE802154_FRAME_EXT_ADDR_SIZE]; IEEE802154_MULTIPAN_MAX],
+    pub cca_mode: u8,
+    pub cca_threshold: i8,
+    pub tx_auto_ack: bool,
+    pub rx_auto_ack: bool,
+    pub tx_enhance_ack: bool,
+    pub coordinator: bool,
+    pub promiscuous: bool,
+    pub pending_mode: bool,
+    pub pending_bit: bool,
+    pub transmit_security: bool,
+    pub rx_append_lqi: bool,
+    pub rx_append_freq_offset: bool,
+    pub error_counts: [u16; 15],
+}
+
+impl Registers {
+    const fn new() -> Self {
+        Self {
+            clocks_enabled: false,
+            interrupt_enabled: false,
+            coex_ack_pti: 0,
+            coex_txrx_pti: 0,
+            tx_on_delay: 0,
+            rx_on_delay: 0,
+            event_en: 0,
+            event_status: 0,
+            tx_abort_en: 0,
+            rx_abort_en: 0,
+            tx_abort_status: 0,
+            rx_abort_status: 0,
+            filter_fail_status: 0,
+            ed_sample_mode: 0,
+            ed_duration: 0,
+            ed_rss: 0,
+            freq_offset: 0,
+            tx_addr: 0,
+            rx_addr: 0,
+            cmd: 0,
+            freq: 0,
+            power: 0,
+            multipan_mask: 0,
+            panid: [0u16; IEEE802154_MULTIPAN_MAX],
+            short_addr: [0u16; IEEE802154_MULTIPAN_MAX],
+            ext_addr: [[0u8; IEEE802154_FRAME_EXT_ADDR_SIZE]; IEEE802154_MULTIPAN_MAX],
+            cca_mode: 0,
+            cca_threshold: 0,
+            tx_auto_ack: false,
+            rx_auto_ack: false,
+            tx_enhance_ack: false,
+            coordinator: false,
+            promiscuous: false,
+            pending_mode: false,
+            pending_bit: false,
+            transmit_security: false,
+            rx_append_lqi: false,
+            rx_append_freq_offset: false,
+            error_counts: [0u16; 15],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Transmitted {
+    frame: [u8; FRAME_SIZE],
+    valid: bool,
+}
+
+/// Return a copy of the registers
+pub fn registers() -> Registers {
+    with_registers(|regs| *regs)
+}
+
+/// Return a copy of the frame most recently transmitted, without the FCS
+pub fn transmitted() -> Option<Vec<u8, 127>> {
+    let transmitted = critical_section::with(|cs| *TRANSMITTED.borrow_ref(cs));
+
+    if !transmitted.valid {
+        return None;
+    }
+
+    let len = (transmitted.frame[0] as usize).saturating_sub(2);
+    Vec::from_slice(&transmitted.frame[1..][..len]).ok()
+}
+
+/// Finish the ongoing transmission
+pub fn transmit_done() {
+    raise_events(Event::TxDone as u16);
+}
+
+/// Finish the ongoing energy detection, measuring `rssi`
+///
+/// Returns `false` if the radio is not detecting energy.
+pub fn energy_detect_done(rssi: i8) -> bool {
+    let detecting = with_registers(|regs| {
+        regs.ed_rss = rssi;
+        regs.cmd == Command::EdStart as u8
+    });
+
+    if detecting {
+        raise_events(Event::EdDone as u16);
+    }
+
+    detecting
+}
+
+/// Abort the ongoing energy detection, as the radio does e.g. when the
+/// coexistence arbiter takes it away, returns whether one was ongoing
+pub fn energy_detect_aborted() -> bool {
+    let enabled = with_registers(|regs| {
+        if regs.cmd != Command::EdStart as u8 {
+            return false;
+        }
+        count_error(regs, ErrorCounter::EdAbort);
+        regs.rx_abort_status = RxAbortReason::EdAbort as u8;
+        regs.rx_abort_en & RxAbortReason::EdAbort.bit() != 0
+    });
+
+    if enabled {
+        raise_events(Event::RxAbort as u16);
+    }
+
+    enabled
+}
+
+/// Set the carrier frequency offset measured for the next received frames
+pub fn set_freq_offset(offset: i8) {
+    with_registers(|regs| regs.freq_offset = offset);
+}
+
+/// Receive `frame`, given without the FCS, if the radio is receiving
+///
+/// Returns `false` if the radio is not receiving.
+pub fn receive(frame: &[u8], rssi: i8, lqi: u8) -> bool {
+    if with_registers(|regs| regs.cmd) != Command::RxStart as u8
+        || !write_rx_buffer(frame, rssi, lqi)
+    {
+        return false;
+    }
+
+    raise_events(Event::RxDone as u16);
+
+    true
+}
+
+/// Receive the SFD of a frame, which is then received by [`receive`]
+///
+/// Returns `false` if the radio is not receiving.
+pub fn frame_started() -> bool {
+    if with_registers(|regs| regs.cmd) != Command::RxStart as u8 {
+        return false;
+    }
+
+    raise_events(Event::RxSfdDone as u16);
+
+    true
+}
+
+/// Run `f` with the interrupt disabled, as in a critical section of the
+/// driver, and then run the interrupt handler for the events raised by `f`
+pub fn without_interrupts<R>(f: impl FnOnce() -> R) -> R {
+    let enabled = with_registers(|regs| core::mem::replace(&mut regs.interrupt_enabled, false));
+    let result = f();
+    with_registers(|regs| regs.interrupt_enabled = enabled);
+    raise_events(0);
+
+    result
+}
+
+/// Receive `ack`, given without the FCS, as the ACK of the ongoing
+/// transmission
+///
+/// Returns `false` if the radio has no buffer to receive into.
+pub fn ack_received(ack: &[u8], rssi: i8) -> bool {
+    if !write_rx_buffer(ack, rssi, rssi_to_lqi(rssi)) {
+        return false;
+    }
+
+    raise_events(Event::AckRxDone as u16);
+
+    true
+}
+
+/// Abort the ongoing transmission as no ACK was received
+pub fn ack_timeout() {
+    transmit_abort(TxAbortReason::RxAckTimeout, ErrorCounter::RxAckTimeout);
+}
+
+/// Abort the ongoing transmission as the CCA found the channel busy
+pub fn channel_busy() {
+    transmit_abort(TxAbortReason::CcaBusy, ErrorCounter::CcaBusy);
+}
+
+fn transmit_abort(reason: TxAbortReason, counter: ErrorCounter) {
+    let enabled = with_registers(|regs| {
+        count_error(regs, counter);
+        regs.tx_abort_status = reason as u8;
+        regs.tx_abort_en & reason.bit() != 0
+    });
+
+    if enabled {
+        raise_events(Event::TxAbort as u16);
+    }
+}
+
+pub(super) fn count_error(regs: &mut Registers, counter: ErrorCounter) {
+    let count = &mut regs.error_counts[counter as usize];
+    *count = count.wrapping_add(1);
+}
+
+/// Write `frame` to the receive buffer the radio currently points to
+pub(super) fn write_rx_buffer(frame: &[u8], rssi: i8, lqi: u8) -> bool {
+    let rx_addr = with_registers(|regs| regs.rx_addr);
+
+    if rx_addr == 0 || frame.len() > 125 {
+        return false;
+    }
+
+    // the radio replaces the FCS with the RSSI and LQI, and appends the
+    // frequency offset without counting it in the length
+    let (append_lqi, append_freq_offset, freq_offset) = with_registers(|regs| {
+        (
+            regs.rx_append_lqi,
+            regs.rx_append_freq_offset,
+            regs.freq_offset,
+        )
+    });
+    let buffer = unsafe { &mut *(rx_addr as *mut [u8; FRAME_SIZE]) };
+    buffer[0] = frame.len() as u8 + 2;
+    buffer[1..][..frame.len()].copy_from_slice(frame);
+    buffer[frame.len() + 1] = rssi as u8;
+    buffer[frame.len() + 2] = if append_lqi { lqi } else { 0 };
+    buffer[frame.len() + 3] = if append_freq_offset {
+        freq_offset as u8
+    } else {
+        0
+    };
+
+    true
+}
+
+/// Set the status bits for `events`, and run the interrupt handler if any of
+/// them is enabled
+pub(crate) fn raise_events(events: u16) {
+    let pending = with_registers(|regs| {
+        regs.event_status |= events;
+        regs.interrupt_enabled && regs.event_status & regs.event_en != 0
+    });
+
+    if pending {
+        ieee802154_isr();
+    }
+}
+
+/// Serialize the tests using the radio, whose state is shared by the test
+/// threads unless each one has its own with the `sim` feature
+#[cfg(test)]
+pub(crate) fn test_lock() -> std::sync::MutexGuard<'static, ()> {
+    static LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+pub(super) fn with_registers<R>(f: impl FnOnce(&mut Registers) -> R) -> R {
+    critical_section::with(|cs| {
+        let mut regs = REGISTERS.borrow_ref_mut(cs);
+        let result = f(&mut regs);
+        #[cfg(feature = "sim")]
+        super::sim::update(&regs);
+        result
+    })
+}
+
+/// The in-memory radio
+pub(crate) struct MockRadio;
+
+impl RadioRegisters for MockRadio {
+    fn enable_clocks(_radio_clock_control: &mut RADIO_CLK) {
+        with_registers(|regs| {
+            *regs = Registers::new();
+            regs.clocks_enabled = true;
+        });
+    }
+
+    fn phy_enable() {}
+
+    fn btbb_enable() {}
+
+    fn phy_version_print() {}
+
+    fn coex_init() {}
+
+    fn set_coex_ack_pti(event: CoexEvent) {
+        with_registers(|regs| regs.coex_ack_pti = event as u8);
+    }
+
+    fn set_coex_txrx_pti(event: CoexEvent) {
+        with_registers(|regs| regs.coex_txrx_pti = event as u8);
+    }
+
+    fn set_tx_on_delay(delay: u16) {
+        with_registers(|regs| regs.tx_on_delay = delay);
+    }
+
+    fn enable_interrupt() {
+        with_registers(|regs| regs.interrupt_enabled = true);
+    }
+
+    fn mac_date() -> u32 {
+        0
+    }
+
+    fn set_rx_on_delay(delay: u16) {
+        with_registers(|regs| regs.rx_on_delay = delay);
+    }
+
+    fn enable_events(events: u16) {
+        with_registers(|regs| regs.event_en |= events);
+    }
+
+    fn disable_events(events: u16) {
+        with_registers(|regs| regs.event_en &= !events);
+    }
+
+    fn enable_tx_abort_events(events: u32) {
+        with_registers(|regs| regs.tx_abort_en |= events);
+    }
+
+    fn enable_rx_abort_events(events: u32) {
+        with_registers(|regs| regs.rx_abort_en |= events);
+    }
+
+    fn disable_rx_abort_events(events: u32) {
+        with_registers(|regs| regs.rx_abort_en &= !events);
+    }
+
+    fn set_ed_sample_mode(ed_sample_mode: EdSampleMode) {
+        with_registers(|regs| regs.ed_sample_mode = ed_sample_mode as u8);
+    }
+
+    fn set_ed_duration(duration: u32) {
+        with_registers(|regs| regs.ed_duration = duration);
+    }
+
+    fn get_ed_rss() -> i8 {
+        with_registers(|regs| regs.ed_rss)
+    }
+
+    fn set_tx_addr(addr: *const u8) {
+        with_registers(|regs| regs.tx_addr = addr as usize);
+    }
+
+    fn set_cmd(cmd: Command) {
+        let tx_addr = with_registers(|regs| {
+            regs.cmd = cmd as u8;
+            regs.tx_addr
+        });
+
+        if matches!(cmd, Command::TxStart | Command::CcaTxStart) && tx_addr != 0 {
+            let frame = unsafe { *(tx_addr as *const [u8; FRAME_SIZE]) };
+            critical_section::with(|cs| {
+                *TRANSMITTED.borrow_ref_mut(cs) = Transmitted { frame, valid: true };
+            });
+
+            #[cfg(feature = "sim")]
+            super::sim::transmit(&frame);
+            #[cfg(feature = "std")]
+            super::socket::transmit(&frame);
+            #[cfg(feature = "capture")]
+            super::capture::transmit(&frame);
+        }
+    }
+
+    fn set_freq(freq: u8) {
+        with_registers(|regs| regs.freq = freq);
+    }
+
+    fn get_freq() -> u8 {
+        with_registers(|regs| regs.freq)
+    }
+
+    fn set_power(power: u8) {
+        with_registers(|regs| regs.power = power);
+    }
+
+    fn set_multipan_enable_mask(mask: u8) {
+        with_registers(|regs| regs.multipan_mask = mask);
+    }
+
+    fn set_multipan_panid(index: MultipanIndex, panid: u16) {
+        with_registers(|regs| regs.panid[index as usize] = panid);
+    }
+
+    fn set_multipan_short_addr(index: MultipanIndex, value: u16) {
+        with_registers(|regs| regs.short_addr[index as usize] = value);
+    }
+
+    fn set_multipan_ext_addr(index: MultipanIndex, ext_addr: *const u8) {
+        let mut address = [0u8; IEEE802154_FRAME_EXT_ADDR_SIZE];
+        address.copy_from_slice(unsafe {
+            core::slice::from_raw_parts(ext_addr, IEEE802154_FRAME_EXT_ADDR_SIZE)
+        });
+
+        with_registers(|regs| regs.ext_addr[index as usize] = address);
+    }
+
+    fn set_cca_mode(cca_mode: CcaMode) {
+        with_registers(|regs| regs.cca_mode = cca_mode as u8);
+    }
+
+    fn set_cca_threshold(cca_threshold: i8) {
+        with_registers(|regs| regs.cca_threshold = cca_threshold);
+    }
+
+    fn set_tx_auto_ack(enable: bool) {
+        with_registers(|regs| regs.tx_auto_ack = enable);
+    }
+
+    fn get_tx_auto_ack() -> bool {
+        with_registers(|regs| regs.tx_auto_ack)
+    }
+
+    fn set_rx_auto_ack(enable: bool) {
+        with_registers(|regs| regs.rx_auto_ack = enable);
+    }
+
+    fn set_tx_enhance_ack(enable: bool) {
+        with_registers(|regs| regs.tx_enhance_ack = enable);
+    }
+
+    fn get_tx_enhance_ack() -> bool {
+        with_registers(|regs| regs.tx_enhance_ack)
+    }
+
+    fn set_coordinator(enable: bool) {
+        with_registers(|regs| regs.coordinator = enable);
+    }
+
+    fn set_promiscuous(enable: bool) {
+        with_registers(|regs| regs.promiscuous = enable);
+    }
+
+    fn set_pending_mode(enable: bool) {
+        with_registers(|regs| regs.pending_mode = enable);
+    }
+
+    fn set_pending_bit(enable: bool) {
+        with_registers(|regs| regs.pending_bit = enable);
+    }
+
+    fn get_events() -> u16 {
+        with_registers(|regs| regs.event_status)
+    }
+
+    fn clear_events(events: u16) {
+        with_registers(|regs| regs.event_status &= !events);
+    }
+
+    fn set_transmit_security(enable: bool) {
+        with_registers(|regs| regs.transmit_security = enable);
+    }
+
+    fn set_rx_addr(addr: *mut u8) {
+        with_registers(|regs| regs.rx_addr = addr as usize);
+    }
+
+    fn get_filter_fail_status() -> u8 {
+        with_registers(|regs| regs.filter_fail_status)
+    }
+
+    fn get_rx_abort_reason() -> u8 {
+        with_registers(|regs| regs.rx_abort_status)
+    }
+
+    fn get_tx_abort_reason() -> u8 {
+        with_registers(|regs| regs.tx_abort_status)
+    }
+
+    fn set_rx_append_lqi(enable: bool) {
+        with_registers(|regs| regs.rx_append_lqi = enable);
+    }
+
+    fn set_rx_append_freq_offset(enable: bool) {
+        with_registers(|regs| regs.rx_append_freq_offset = enable);
+    }
+
+    fn abort_tx() {
+        with_registers(|regs| regs.tx_abort_status = 0);
+    }
+
+    fn abort_rx() {
+        with_registers(|regs| regs.rx_abort_status = 0);
+    }
+
+    fn get_error_count(counter: ErrorCounter) -> u16 {
+        with_registers(|regs| regs.error_counts[counter as usize])
+    }
+
+    fn clear_error_counts(counters: u32) {
+        with_registers(|regs| {
+            for (index, count) in regs.error_counts.iter_mut().enumerate() {
+                if counters & (1 << index) != 0 {
+                    *count = 0;
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,548 @@
+use core::ops::{BitAnd, BitOr};
+
+#[cfg(not(feature = "mock"))]
+pub(crate) use self::esp::{EspRadio as Radio, IEEE802154, RADIO_CLK};
+#[cfg(feature = "mock")]
+pub(crate) use self::mock::{MockRadio as Radio, IEEE802154, RADIO_CLK};
+use crate::pib::CcaMode;
+
+#[cfg(feature = "capture")]
+pub mod capture;
+#[cfg(not(feature = "mock"))]
+mod esp;
+#[cfg(any(feature = "sim", feature = "std", feature = "capture"))]
+mod medium;
+#[cfg(feature = "mock")]
+pub mod mock;
+#[cfg(feature = "sim")]
+pub mod sim;
+#[cfg(feature = "std")]
+pub mod socket;
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Event {
+    TxDone = 1 << 0,
+    RxDone = 1 << 1,
+    AckTxDone = 1 << 2,
+    AckRxDone = 1 << 3,
+    RxAbort = 1 << 4,
+    TxAbort = 1 << 5,
+    EdDone = 1 << 6,
+    Timer0Overflow = 1 << 8,
+    Timer1Overflow = 1 << 9,
+    ClockCountMatch = 1 << 10,
+    TxSfdDone = 1 << 11,
+    RxSfdDone = 1 << 12,
+}
+
+impl Event {
+    pub(crate) fn mask() -> u16 {
+        0x0000_1FFF
+    }
+}
+
+impl BitAnd<Event> for u16 {
+    type Output = u16;
+
+    fn bitand(self, rhs: Event) -> Self::Output {
+        self & rhs as u16
+    }
+}
+
+impl BitOr for Event {
+    type Output = u16;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self as u16 | rhs as u16
+    }
+}
+
+impl BitOr<Event> for u16 {
+    type Output = u16;
+
+    fn bitor(self, rhs: Event) -> Self::Output {
+        self | rhs as u16
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum TxAbortReason {
+    RxAckStop = 1,
+    RxAckSfdTimeout = 2,
+    RxAckCrcError = 3,
+    RxAckInvalidLen = 4,
+    RxAckFilterFail = 5,
+    RxAckNoRss = 6,
+    RxAckCoexBreak = 7,
+    RxAckTypeNotAck = 8,
+    RxAckRestart = 9,
+    RxAckTimeout = 16,
+    TxStop = 17,
+    TxCoexBreak = 18,
+    TxSecurityError = 19,
+    CcaFailed = 24,
+    CcaBusy = 25,
+}
+
+impl TxAbortReason {
+    pub fn bit(&self) -> u32 {
+        1 << (*self as u32 - 1)
+    }
+}
+
+impl BitOr for TxAbortReason {
+    type Output = u32;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.bit() | rhs.bit()
+    }
+}
+
+impl BitOr<TxAbortReason> for u32 {
+    type Output = u32;
+
+    fn bitor(self, rhs: TxAbortReason) -> Self::Output {
+        self | rhs.bit()
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RxAbortReason {
+    RxStop = 1,
+    SfdTimeout = 2,
+    CrcError = 3,
+    InvalidLen = 4,
+    FilterFail = 5,
+    NoRss = 6,
+    CoexBreak = 7,
+    UnexpectedAck = 8,
+    RxRestart = 9,
+    TxAckTimeout = 16,
+    TxAckStop = 17,
+    TxAckCoexBreak = 18,
+    EnhackSecurityError = 19,
+    EdAbort = 24,
+    EdStop = 25,
+    EdCoexReject = 26,
+}
+
+impl RxAbortReason {
+    pub fn bit(&self) -> u32 {
+        1 << (*self as u32 - 1)
+    }
+}
+
+impl BitOr for RxAbortReason {
+    type Output = u32;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        self.bit() | rhs.bit()
+    }
+}
+
+impl BitOr<RxAbortReason> for u32 {
+    type Output = u32;
+
+    fn bitor(self, rhs: RxAbortReason) -> Self::Output {
+        self | rhs.bit()
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum EdSampleMode {
+    Max = 0,
+    Avg = 1,
+}
+
+#[allow(unused)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Command {
+    TxStart = 0x41,
+    RxStart = 0x42,
+    CcaTxStart = 0x43,
+    EdStart = 0x44,
+    Stop = 0x45,
+    DtmTxStart = 0x46,
+    DtmRxStart = 0x47,
+    DtmStop = 0x48,
+    Timer0Start = 0x4C,
+    Timer0Stop = 0x4D,
+    Timer1Start = 0x4E,
+    Timer1Stop = 0x4F,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum ErrorCounter {
+    CcaBusy = 0,
+    TxSecurityError = 1,
+    TxBreakCoex = 2,
+    RxAckTimeout = 3,
+    RxAckAbortCoex = 4,
+    EdScanCoex = 5,
+    TxAckAbortCoex = 6,
+    RxRestart = 7,
+    RxAbortCoex = 8,
+    NoRssDetect = 9,
+    RxFilterFail = 10,
+    CcaFail = 11,
+    EdAbort = 12,
+    CrcError = 13,
+    SfdTimeout = 14,
+}
+
+impl ErrorCounter {
+    pub(crate) fn mask() -> u32 {
+        0x0000_7FFF
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum CoexEvent {
+    Idle,
+    Low,
+    Middle,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum MultipanIndex {
+    Multipan0 = 0,
+    Multipan1 = 1,
+    Multipan2 = 2,
+    Multipan3 = 3,
+}
+
+impl From<usize> for MultipanIndex {
+    fn from(value: usize) -> Self {
+        match value {
+            0 => MultipanIndex::Multipan0,
+            1 => MultipanIndex::Multipan1,
+            2 => MultipanIndex::Multipan2,
+            3 => MultipanIndex::Multipan3,
+            _ => panic!(),
+        }
+    }
+}
+
+/// The operations the driver needs from the radio
+///
+/// Implemented by the ESP32-C6/H2 radio, and by an in-memory radio with the
+/// `mock` feature. The driver only accesses the radio via the functions below,
+/// which forward to the selected implementation.
+pub(crate) trait RadioRegisters {
+    fn enable_clocks(radio_clock_control: &mut RADIO_CLK);
+    fn phy_enable();
+    fn btbb_enable();
+    fn phy_version_print();
+    fn coex_init();
+    fn set_coex_ack_pti(event: CoexEvent);
+    fn set_coex_txrx_pti(event: CoexEvent);
+    fn set_tx_on_delay(delay: u16);
+    fn enable_interrupt();
+    fn mac_date() -> u32;
+    fn set_rx_on_delay(delay: u16);
+    fn enable_events(events: u16);
+    fn disable_events(events: u16);
+    fn enable_tx_abort_events(events: u32);
+    fn enable_rx_abort_events(events: u32);
+    fn disable_rx_abort_events(events: u32);
+    fn set_ed_sample_mode(ed_sample_mode: EdSampleMode);
+    fn set_ed_duration(duration: u32);
+    fn get_ed_rss() -> i8;
+    fn set_tx_addr(addr: *const u8);
+    fn set_cmd(cmd: Command);
+    fn set_freq(freq: u8);
+    fn get_freq() -> u8;
+    fn set_power(power: u8);
+    fn set_multipan_enable_mask(mask: u8);
+    fn set_multipan_panid(index: MultipanIndex, panid: u16);
+    fn set_multipan_short_addr(index: MultipanIndex, value: u16);
+    fn set_multipan_ext_addr(index: MultipanIndex, ext_addr: *const u8);
+    fn set_cca_mode(cca_mode: CcaMode);
+    fn set_cca_threshold(cca_threshold: i8);
+    fn set_tx_auto_ack(enable: bool);
+    fn get_tx_auto_ack() -> bool;
+    fn set_rx_auto_ack(enable: bool);
+    fn set_tx_enhance_ack(enable: bool);
+    fn get_tx_enhance_ack() -> bool;
+    fn set_coordinator(enable: bool);
+    fn set_promiscuous(enable: bool);
+    fn set_pending_mode(enable: bool);
+    fn set_pending_bit(enable: bool);
+    fn get_events() -> u16;
+    fn clear_events(events: u16);
+    fn set_transmit_security(enable: bool);
+    fn set_rx_addr(addr: *mut u8);
+    fn get_filter_fail_status() -> u8;
+    fn get_rx_abort_reason() -> u8;
+    fn get_tx_abort_reason() -> u8;
+    fn set_rx_append_lqi(enable: bool);
+    fn set_rx_append_freq_offset(enable: bool);
+    fn abort_tx();
+    fn abort_rx();
+    fn get_error_count(counter: ErrorCounter) -> u16;
+    fn clear_error_counts(counters: u32);
+}
+
+#[inline(always)]
+pub(crate) fn enable_clocks(radio_clock_control: &mut RADIO_CLK) {
+    Radio::enable_clocks(radio_clock_control)
+}
+
+#[inline(always)]
+pub(crate) fn phy_enable() {
+    Radio::phy_enable()
+}
+
+#[inline(always)]
+pub(crate) fn btbb_enable() {
+    Radio::btbb_enable()
+}
+
+#[inline(always)]
+pub(crate) fn phy_version_print() {
+    Radio::phy_version_print()
+}
+
+#[inline(always)]
+pub(crate) fn coex_init() {
+    Radio::coex_init()
+}
+
+#[inline(always)]
+pub(crate) fn set_coex_ack_pti(event: CoexEvent) {
+    Radio::set_coex_ack_pti(event)
+}
+
+#[inline(always)]
+pub(crate) fn set_coex_txrx_pti(event: CoexEvent) {
+    Radio::set_coex_txrx_pti(event)
+}
+
+#[inline(always)]
+pub(crate) fn set_tx_on_delay(delay: u16) {
+    Radio::set_tx_on_delay(delay)
+}
+
+#[inline(always)]
+pub(crate) fn enable_interrupt() {
+    Radio::enable_interrupt()
+}
+
+#[inline(always)]
+pub(crate) fn mac_date() -> u32 {
+    Radio::mac_date()
+}
+
+#[inline(always)]
+pub(crate) fn set_rx_on_delay(delay: u16) {
+    Radio::set_rx_on_delay(delay)
+}
+
+#[inline(always)]
+pub(crate) fn enable_events(events: u16) {
+    Radio::enable_events(events)
+}
+
+#[inline(always)]
+pub(crate) fn disable_events(events: u16) {
+    Radio::disable_events(events)
+}
+
+#[inline(always)]
+pub(crate) fn enable_tx_abort_events(events: u32) {
+    Radio::enable_tx_abort_events(events)
+}
+
+#[inline(always)]
+pub(crate) fn enable_rx_abort_events(events: u32) {
+    Radio::enable_rx_abort_events(events)
+}
+
+#[inline(always)]
+pub(crate) fn disable_rx_abort_events(events: u32) {
+    Radio::disable_rx_abort_events(events)
+}
+
+#[inline(always)]
+pub(crate) fn set_ed_sample_mode(ed_sample_mode: EdSampleMode) {
+    Radio::set_ed_sample_mode(ed_sample_mode)
+}
+
+#[inline(always)]
+pub(crate) fn set_ed_duration(duration: u32) {
+    Radio::set_ed_duration(duration)
+}
+
+#[inline(always)]
+pub(crate) fn get_ed_rss() -> i8 {
+    Radio::get_ed_rss()
+}
+
+#[inline(always)]
+pub(crate) fn set_tx_addr(addr: *const u8) {
+    Radio::set_tx_addr(addr)
+}
+
+#[inline(always)]
+pub(crate) fn set_cmd(cmd: Command) {
+    Radio::set_cmd(cmd)
+}
+
+#[inline(always)]
+pub(crate) fn set_freq(freq: u8) {
+    Radio::set_freq(freq)
+}
+
+#[inline(always)]
+pub(crate) fn get_freq() -> u8 {
+    Radio::get_freq()
+}
+
+#[inline(always)]
+pub(crate) fn set_power(power: u8) {
+    Radio::set_power(power)
+}
+
+#[inline(always)]
+pub(crate) fn set_multipan_enable_mask(mask: u8) {
+    Radio::set_multipan_enable_mask(mask)
+}
+
+#[inline(always)]
+pub(crate) fn set_multipan_panid(index: MultipanIndex, panid: u16) {
+    Radio::set_multipan_panid(index, panid)
+}
+
+#[inline(always)]
+pub(crate) fn set_multipan_short_addr(index: MultipanIndex, value: u16) {
+    Radio::set_multipan_short_addr(index, value)
+}
+
+#[inline(always)]
+pub(crate) fn set_multipan_ext_addr(index: MultipanIndex, ext_addr: *const u8) {
+    Radio::set_multipan_ext_addr(index, ext_addr)
+}
+
+#[inline(always)]
+pub(crate) fn set_cca_mode(cca_mode: CcaMode) {
+    Radio::set_cca_mode(cca_mode)
+}
+
+#[inline(always)]
+pub(crate) fn set_cca_threshold(cca_threshold: i8) {
+    Radio::set_cca_threshold(cca_threshold)
+}
+
+#[inline(always)]
+pub(crate) fn set_tx_auto_ack(enable: bool) {
+    Radio::set_tx_auto_ack(enable)
+}
+
+#[inline(always)]
+pub(crate) fn get_tx_auto_ack() -> bool {
+    Radio::get_tx_auto_ack()
+}
+
+#[inline(always)]
+pub(crate) fn set_rx_auto_ack(enable: bool) {
+    Radio::set_rx_auto_ack(enable)
+}
+
+#[inline(always)]
+pub(crate) fn set_tx_enhance_ack(enable: bool) {
+    Radio::set_tx_enhance_ack(enable)
+}
+
+#[inline(always)]
+pub(crate) fn get_tx_enhance_ack() -> bool {
+    Radio::get_tx_enhance_ack()
+}
+
+#[inline(always)]
+pub(crate) fn set_coordinator(enable: bool) {
+    Radio::set_coordinator(enable)
+}
+
+#[inline(always)]
+pub(crate) fn set_promiscuous(enable: bool) {
+    Radio::set_promiscuous(enable)
+}
+
+#[inline(always)]
+pub(crate) fn set_pending_mode(enable: bool) {
+    Radio::set_pending_mode(enable)
+}
+
+#[inline(always)]
+pub(crate) fn set_pending_bit(enable: bool) {
+    Radio::set_pending_bit(enable)
+}
+
+#[inline(always)]
+pub(crate) fn get_events() -> u16 {
+    Radio::get_events()
+}
+
+#[inline(always)]
+pub(crate) fn clear_events(events: u16) {
+    Radio::clear_events(events)
+}
+
+#[inline(always)]
+pub(crate) fn set_transmit_security(enable: bool) {
+    Radio::set_transmit_security(enable)
+}
+
+#[inline(always)]
+pub(crate) fn set_rx_addr(addr: *mut u8) {
+    Radio::set_rx_addr(addr)
+}
+
+#[inline(always)]
+pub(crate) fn get_filter_fail_status() -> u8 {
+    Radio::get_filter_fail_status()
+}
+
+#[inline(always)]
+pub(crate) fn get_rx_abort_reason() -> u8 {
+    Radio::get_rx_abort_reason()
+}
+
+#[inline(always)]
+pub(crate) fn get_tx_abort_reason() -> u8 {
+    Radio::get_tx_abort_reason()
+}
+
+#[inline(always)]
+pub(crate) fn set_rx_append_lqi(enable: bool) {
+    Radio::set_rx_append_lqi(enable)
+}
+
+#[inline(always)]
+pub(crate) fn set_rx_append_freq_offset(enable: bool) {
+    Radio::set_rx_append_freq_offset(enable)
+}
+
+#[inline(always)]
+pub(crate) fn abort_tx() {
+    Radio::abort_tx()
+}
+
+#[inline(always)]
+pub(crate) fn abort_rx() {
+    Radio::abort_rx()
+}
+
+#[inline(always)]
+pub(crate) fn get_error_count(counter: ErrorCounter) -> u16 {
+    Radio::get_error_count(counter)
+}
+
+#[inline(always)]
+pub(crate) fn clear_error_counts(counters: u32) {
+    Radio::clear_error_counts(counters)
+}
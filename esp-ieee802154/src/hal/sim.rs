@@ -0,0 +1,524 @@
+//! Virtual radio medium shared by several nodes, used with the `sim` feature
+//!
+//! Every node is an [`Ieee802154`](crate::Ieee802154) driver running on its
+//! own thread, as the driver state is thread local with this feature. A node is
+//! attached to a [`Medium`] from its thread via [`Medium::attach`], after which
+//! the frames it transmits are delivered to the other nodes which are
+//! receiving on the same channel, subject to the [`Link`] between the two
+//! nodes.
+//!
+//! ACKs are sent by receivers which have auto ACK enabled, a missing ACK is
+//! reported to the transmitter as a transmit abort after its transmit done,
+//! and counted in [`RadioStats::rx_ack_timeout`](crate::RadioStats). As the
+//! ACK is decided when the frame is transmitted, its frame pending bit is the
+//! one the receiver set for the frame it received before. Frames which end
+//! while the receiver sends an ACK are missed by it. Enhanced ACKs are
+//! sent by the driver of the receiver once it handles the frame, they are
+//! only received if that happens before the ACK timeout. Energy detection
+//! measures the strongest transmission on the channel.
+//!
+//! The timing of every event is decided when a frame is transmitted, based on
+//! its air time and the latency of the link. Events are handled once they are
+//! due, whenever the thread of the node polls for received frames or calls
+//! [`pump`] or [`run_for`].
+
+use core::cell::RefCell;
+use std::{
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+    vec::Vec,
+};
+
+use super::{
+    medium::{self, ack_for, ack_requested, is_broadcast, sequence_number, FRAME_TYPE_ACK},
+    mock::{self, raise_events, Registers},
+    Command, Event,
+};
+use crate::frame::FRAME_SIZE;
+
+// Duration of one octet at 250 kbit/s
+const OCTET_DURATION: Duration = Duration::from_micros(32);
+// Preamble, SFD and PHR
+const PHY_HEADER_OCTETS: u32 = 6;
+// aTurnaroundTime, 12 symbols
+const TURNAROUND_TIME: Duration = Duration::from_micros(192);
+// Frame control, sequence number and FCS
+const ACK_OCTETS: u32 = 5;
+
+std::thread_local! {
+    static NODE: RefCell<Option<(Medium, NodeId)>> = const { RefCell::new(None) };
+}
+
+/// Identifies a node attached to a [`Medium`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(usize);
+
+/// Propagation of frames from one node to another
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Link {
+    /// Probability of a frame being lost, from `0.0` to `1.0`
+    pub loss: f32,
+    /// RSSI of received frames, in dBm
+    pub rssi: i8,
+    /// Delay added to the air time of a frame
+    pub latency: Duration,
+}
+
+impl Link {
+    /// A link over which no frames are received, e.g. for nodes out of range
+    pub const fn disconnected() -> Self {
+        Self {
+            loss: 1.0,
+            rssi: -128,
+            latency: Duration::ZERO,
+        }
+    }
+}
+
+impl Default for Link {
+    fn default() -> Self {
+        Self {
+            loss: 0.0,
+            rssi: -50,
+            latency: Duration::ZERO,
+        }
+    }
+}
+
+/// Configuration of a [`Medium`]
+#[derive(Debug, Clone, Copy)]
+pub struct MediumConfig {
+    /// Link between nodes for which no link was set via [`Medium::set_link`]
+    pub link: Link,
+    /// Corrupt frames whose transmissions overlap on the same channel, they
+    /// are received with a CRC error
+    pub collisions: bool,
+    /// How long a transmitter waits for an ACK after the end of a frame
+    pub ack_timeout: Duration,
+    /// Seed for deciding which frames are lost
+    pub seed: u64,
+}
+
+impl Default for MediumConfig {
+    fn default() -> Self {
+        Self {
+            link: Link::default(),
+            collisions: true,
+            // macAckWaitDuration, 54 symbols
+            ack_timeout: Duration::from_micros(864),
+            seed: 0x2545_f491_4f6c_dd1d,
+        }
+    }
+}
+
+/// Virtual radio medium, cloning it returns a handle to the same medium
+#[derive(Debug, Clone)]
+pub struct Medium {
+    inner: Arc<Mutex<MediumState>>,
+}
+
+#[derive(Debug)]
+struct MediumState {
+    config: MediumConfig,
+    rng: u64,
+    nodes: Vec<NodeState>,
+    links: BTreeMap<(NodeId, NodeId), Link>,
+    transmissions: Vec<Transmission>,
+}
+
+#[derive(Debug)]
+struct NodeState {
+    registers: Registers,
+    pending: Vec<(Instant, SimEvent)>,
+}
+
+#[derive(Debug)]
+struct Transmission {
+    sender: NodeId,
+    freq: u8,
+    end: Instant,
+    collided: Arc<AtomicBool>,
+}
+
+#[derive(Debug)]
+enum SimEvent {
+    TxDone,
+    Receive {
+        frame: heapless::Vec<u8, 127>,
+        rssi: i8,
+        filter_fail: u8,
+        collided: Arc<AtomicBool>,
+    },
+    AckSent {
+        collided: Arc<AtomicBool>,
+    },
+    AckReceived {
+        frame: heapless::Vec<u8, 127>,
+        rssi: i8,
+        collided: Arc<AtomicBool>,
+    },
+    AckTimeout {
+        sequence: u8,
+    },
+}
+
+impl Medium {
+    /// Create a medium without any nodes
+    pub fn new(config: MediumConfig) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(MediumState {
+                config,
+                rng: config.seed.max(1),
+                nodes: Vec::new(),
+                links: BTreeMap::new(),
+                transmissions: Vec::new(),
+            })),
+        }
+    }
+
+    /// Attach the radio of the calling thread to the medium
+    ///
+    /// A thread can only be attached to one medium at a time, attaching it
+    /// again detaches it from the previous one.
+    pub fn attach(&self) -> NodeId {
+        let registers = mock::registers();
+
+        let id = {
+            let mut state = self.inner.lock().unwrap();
+            state.nodes.push(NodeState {
+                registers,
+                pending: Vec::new(),
+            });
+            NodeId(state.nodes.len() - 1)
+        };
+
+        NODE.with(|node| node.borrow_mut().replace((self.clone(), id)));
+
+        id
+    }
+
+    /// Set the link used for frames sent by `from` and received by `to`
+    pub fn set_link(&self, from: NodeId, to: NodeId, link: Link) {
+        self.inner.lock().unwrap().links.insert((from, to), link);
+    }
+
+    /// Set the link between `a` and `b` in both directions
+    pub fn set_links(&self, a: NodeId, b: NodeId, link: Link) {
+        let mut state = self.inner.lock().unwrap();
+        state.links.insert((a, b), link);
+        state.links.insert((b, a), link);
+    }
+}
+
+/// Handle the events of the calling thread's node which are due, and
+/// complete its energy detection
+///
+/// This is called when polling for received frames, but needs to be called
+/// explicitly to e.g. complete a transmission.
+pub fn pump() {
+    let Some((medium, id)) = current() else {
+        return;
+    };
+
+    let now = Instant::now();
+    let due = {
+        let mut state = medium.inner.lock().unwrap();
+        let pending = &mut state.nodes[id.0].pending;
+        pending.sort_by_key(|(at, _)| *at);
+        let count = pending.iter().take_while(|(at, _)| *at <= now).count();
+        pending.drain(..count).collect::<Vec<_>>()
+    };
+
+    for (_, event) in due {
+        handle(event);
+    }
+
+    mock::energy_detect_done(energy(&medium, id, now));
+}
+
+/// Handle the events of the calling thread's node for `duration`
+pub fn run_for(duration: Duration) {
+    let until = Instant::now() + duration;
+
+    loop {
+        pump();
+
+        let now = Instant::now();
+        if now >= until {
+            break;
+        }
+
+        let next = current().and_then(|(medium, id)| {
+            let state = medium.inner.lock().unwrap();
+            state.nodes[id.0].pending.iter().map(|(at, _)| *at).min()
+        });
+
+        thread::sleep(next.unwrap_or(until).clamp(now, until) - now);
+    }
+}
+
+/// Strongest signal on the channel of `id` at `now`
+fn energy(medium: &Medium, id: NodeId, now: Instant) -> i8 {
+    let state = medium.inner.lock().unwrap();
+    let freq = state.nodes[id.0].registers.freq;
+
+    state
+        .transmissions
+        .iter()
+        .filter(|transmission| {
+            transmission.freq == freq && transmission.end > now && transmission.sender != id
+        })
+        .map(|transmission| state.link(transmission.sender, id))
+        .filter(|link| link.loss < 1.0)
+        .map(|link| link.rssi)
+        .max()
+        .unwrap_or(medium::NOISE_FLOOR)
+}
+
+fn current() -> Option<(Medium, NodeId)> {
+    NODE.with(|node| node.borrow().clone())
+}
+
+/// Mirror the registers of the calling thread's node into the medium
+pub(super) fn update(registers: &Registers) {
+    if let Some((medium, id)) = current() {
+        medium.inner.lock().unwrap().nodes[id.0].registers = *registers;
+    }
+}
+
+/// Put the frame the calling thread's node started to transmit on the medium
+pub(super) fn transmit(buffer: &[u8; FRAME_SIZE]) {
+    let Some((medium, sender)) = current() else {
+        return;
+    };
+
+    let psdu_len = buffer[0] as usize;
+    if !(5..=127).contains(&psdu_len) {
+        return;
+    }
+    let frame = &buffer[1..][..psdu_len - 2];
+
+    let mut state = medium.inner.lock().unwrap();
+    let config = state.config;
+    let sender_regs = state.nodes[sender.0].registers;
+    let freq = sender_regs.freq;
+
+    let start = Instant::now();
+    let end = start + air_time(psdu_len as u32);
+    let collided = Arc::new(AtomicBool::new(false));
+
+    state
+        .transmissions
+        .retain(|transmission| transmission.end > start);
+    if config.collisions {
+        for transmission in state.transmissions.iter() {
+            if transmission.freq == freq {
+                transmission.collided.store(true, Ordering::Relaxed);
+                collided.store(true, Ordering::Relaxed);
+            }
+        }
+    }
+    state.transmissions.push(Transmission {
+        sender,
+        freq,
+        end,
+        collided: collided.clone(),
+    });
+
+    state.nodes[sender.0].pending.push((end, SimEvent::TxDone));
+
+    if medium::frame_type(frame) == FRAME_TYPE_ACK {
+        state.enhanced_ack(sender, frame, end, &collided);
+    }
+
+    let wants_ack = ack_requested(frame) && sender_regs.rx_auto_ack;
+    let mut acked = false;
+
+    for index in 0..state.nodes.len() {
+        let receiver = NodeId(index);
+        let receiver_regs = state.nodes[index].registers;
+        if receiver == sender
+            || receiver_regs.cmd != Command::RxStart as u8
+            || receiver_regs.freq != freq
+        {
+            continue;
+        }
+
+        let link = state.link(sender, receiver);
+        if state.lost(link.loss) {
+            continue;
+        }
+
+        let received = end + link.latency;
+        if state.sending_ack(receiver, received) {
+            continue;
+        }
+
+        let filter_fail = medium::filter(&receiver_regs, frame);
+        state.nodes[index].pending.push((
+            received,
+            SimEvent::Receive {
+                frame: heapless::Vec::from_slice(frame).unwrap(),
+                rssi: link.rssi,
+                filter_fail,
+                collided: collided.clone(),
+            },
+        ));
+
+        if !wants_ack || acked || !medium::will_ack(&receiver_regs, frame, filter_fail) {
+            continue;
+        }
+
+        // the ACK is sent by the receiver, and travels back over the reverse link
+        let ack_end = received + TURNAROUND_TIME + air_time(ACK_OCTETS);
+        state.nodes[index].pending.push((
+            ack_end,
+            SimEvent::AckSent {
+                collided: collided.clone(),
+            },
+        ));
+
+        let reverse = state.link(receiver, sender);
+        if state.lost(reverse.loss) {
+            continue;
+        }
+
+        let ack_received = ack_end + reverse.latency;
+        if ack_received > end + config.ack_timeout {
+            continue;
+        }
+
+        state.nodes[sender.0].pending.push((
+            ack_received,
+            SimEvent::AckReceived {
+                frame: heapless::Vec::from_slice(&ack_for(&receiver_regs, frame)).unwrap(),
+                rssi: reverse.rssi,
+                collided: collided.clone(),
+            },
+        ));
+        acked = true;
+    }
+
+    if wants_ack && !acked && !is_broadcast(frame) {
+        state.nodes[sender.0].pending.push((
+            end + config.ack_timeout,
+            SimEvent::AckTimeout {
+                sequence: sequence_number(frame),
+            },
+        ));
+    }
+}
+
+impl MediumState {
+    /// Deliver the enhanced ACK `frame`, sent by `sender` and ending at
+    /// `end`, to the node waiting for it
+    fn enhanced_ack(
+        &mut self,
+        sender: NodeId,
+        frame: &[u8],
+        end: Instant,
+        collided: &Arc<AtomicBool>,
+    ) {
+        let freq = self.nodes[sender.0].registers.freq;
+        let sequence = sequence_number(frame);
+
+        for index in 0..self.nodes.len() {
+            let receiver = NodeId(index);
+            if receiver == sender || self.nodes[index].registers.freq != freq {
+                continue;
+            }
+
+            let link = self.link(sender, receiver);
+            let received = end + link.latency;
+            let Some(position) = self.nodes[index].pending.iter().position(|(at, event)| {
+                matches!(event, SimEvent::AckTimeout { sequence: waiting } if *waiting == sequence)
+                    && received <= *at
+            }) else {
+                continue;
+            };
+
+            if self.lost(link.loss) {
+                continue;
+            }
+
+            self.nodes[index].pending[position] = (
+                received,
+                SimEvent::AckReceived {
+                    frame: heapless::Vec::from_slice(frame).unwrap(),
+                    rssi: link.rssi,
+                    collided: collided.clone(),
+                },
+            );
+        }
+    }
+
+    /// Whether `node` is sending an ACK when a frame ends at `at`, in which
+    /// case it did not receive that frame, ACKs of collided frames are not sent
+    fn sending_ack(&self, node: NodeId, at: Instant) -> bool {
+        self.nodes[node.0].pending.iter().any(|(ack_end, event)| {
+            matches!(event, SimEvent::AckSent { collided } if !collided.load(Ordering::Relaxed))
+                && at <= *ack_end
+                && at + TURNAROUND_TIME + air_time(ACK_OCTETS) > *ack_end
+        })
+    }
+
+    fn link(&self, from: NodeId, to: NodeId) -> Link {
+        self.links
+            .get(&(from, to))
+            .copied()
+            .unwrap_or(self.config.link)
+    }
+
+    fn lost(&mut self, loss: f32) -> bool {
+        if loss <= 0.0 {
+            return false;
+        }
+
+        // xorshift64
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+
+        ((self.rng >> 40) as f32) < loss * (1u32 << 24) as f32
+    }
+}
+
+fn handle(event: SimEvent) {
+    match event {
+        SimEvent::TxDone => raise_events(Event::TxDone as u16),
+        SimEvent::Receive {
+            frame,
+            rssi,
+            filter_fail,
+            collided,
+        } => {
+            medium::receive(&frame, rssi, filter_fail, collided.load(Ordering::Relaxed));
+        }
+        SimEvent::AckSent { collided } => {
+            if !collided.load(Ordering::Relaxed) {
+                raise_events(Event::AckTxDone as u16);
+            }
+        }
+        SimEvent::AckReceived {
+            frame,
+            rssi,
+            collided,
+        } => {
+            if collided.load(Ordering::Relaxed) {
+                mock::ack_timeout();
+            } else {
+                mock::ack_received(&frame, rssi);
+            }
+        }
+        SimEvent::AckTimeout { .. } => mock::ack_timeout(),
+    }
+}
+
+fn air_time(psdu_octets: u32) -> Duration {
+    OCTET_DURATION * (PHY_HEADER_OCTETS + psdu_octets)
+}
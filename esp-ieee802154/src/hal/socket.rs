@@ -0,0 +1,472 @@
+//! Virtual radio on top of UDP multicast or Unix datagram sockets, used with
+//! the `std` feature
+//!
+//! Connects the driver of this process to the drivers of other processes,
+//! which allows running firmware logic written against
+//! [`Ieee802154`](crate::Ieee802154) as native processes. Every channel uses
+//! its own multicast group or socket directory, so only radios on the same
+//! channel receive each other's frames.
+//!
+//! Received frames are filtered by address and ACKed like by the radio, a
+//! missing ACK is reported to the transmitter as a transmit abort after its
+//! transmit done. Frames are received, and transmissions completed, whenever
+//! the driver polls for received frames or [`pump`] is called. Together with
+//! the `sim` feature, the connection belongs to the calling thread, like the
+//! rest of the driver state.
+
+use std::{
+    format, fs, io,
+    net::{Ipv4Addr, SocketAddrV4, UdpSocket},
+    os::unix::net::UnixDatagram,
+    path::PathBuf,
+    string::ToString,
+    sync::Mutex,
+    time::{Duration, Instant},
+    vec::Vec,
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use super::{
+    medium::{self, ack_for, ack_requested, is_broadcast, sequence_number, FRAME_TYPE_ACK},
+    mock::{self, raise_events, with_registers},
+    Event,
+};
+use crate::frame::FRAME_SIZE;
+
+// Node id and channel, followed by the frame without the FCS
+const HEADER_SIZE: usize = 5;
+
+// Every node has its own connection with the `sim` feature, as it has its own
+// registers
+#[cfg_attr(feature = "sim", thread_local)]
+static CONNECTION: Mutex<Option<Connection>> = Mutex::new(None);
+
+/// How frames are exchanged with other processes
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transport {
+    /// UDP multicast to the group `239.255.154.<channel>` on `port`, using the
+    /// network interface with the address `interface`
+    Udp { port: u16, interface: Ipv4Addr },
+    /// Unix datagram sockets, one per radio in the directory
+    /// `<path>/<channel>`
+    Unix { path: PathBuf },
+}
+
+/// Configuration of the socket backend
+#[derive(Debug, Clone)]
+pub struct SocketConfig {
+    /// How frames are exchanged
+    pub transport: Transport,
+    /// RSSI of received frames, in dBm
+    pub rssi: i8,
+    /// How long a transmitter waits for an ACK after sending a frame
+    pub ack_timeout: Duration,
+}
+
+impl Default for SocketConfig {
+    fn default() -> Self {
+        Self {
+            transport: Transport::Udp {
+                port: 9154,
+                interface: Ipv4Addr::LOCALHOST,
+            },
+            rssi: -50,
+            ack_timeout: Duration::from_millis(20),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Connection {
+    config: SocketConfig,
+    id: u32,
+    channel: Option<u8>,
+    endpoint: Option<Endpoint>,
+    tx_done: bool,
+    ack_wait: Option<(u8, Instant)>,
+}
+
+#[derive(Debug)]
+enum Endpoint {
+    Udp(UdpSocket, SocketAddrV4),
+    Unix(UnixDatagram, PathBuf),
+}
+
+enum SocketEvent {
+    TxDone,
+    Receive(Vec<u8>),
+    AckReceived(Vec<u8>),
+    AckTimeout,
+}
+
+/// Connect the radio of this process to other processes
+///
+/// The sockets are opened once the driver selects a channel, and reopened
+/// whenever it changes the channel.
+pub fn connect(config: SocketConfig) {
+    let id = std::process::id()
+        ^ std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.subsec_nanos() << 8)
+            .unwrap_or(0);
+
+    let previous = CONNECTION.lock().unwrap().replace(Connection {
+        config,
+        id,
+        channel: None,
+        endpoint: None,
+        tx_done: false,
+        ack_wait: None,
+    });
+
+    if let Some(mut previous) = previous {
+        previous.close();
+    }
+}
+
+/// Disconnect the radio of this process, removing its Unix socket
+pub fn disconnect() {
+    if let Some(mut connection) = CONNECTION.lock().unwrap().take() {
+        connection.close();
+    }
+}
+
+/// Receive the frames sent by other processes, and complete the ongoing
+/// transmission and energy detection
+///
+/// This is called when polling for received frames, but needs to be called
+/// explicitly to e.g. complete a transmission.
+pub fn pump() {
+    let channel = current_channel();
+
+    let events = {
+        let mut connection = CONNECTION.lock().unwrap();
+        let Some(connection) = connection.as_mut() else {
+            return;
+        };
+
+        if let Err(err) = connection.open(channel) {
+            log::warn!("Opening socket for channel {} failed: {:?}", channel, err);
+            return;
+        }
+
+        connection.poll()
+    };
+
+    for event in events {
+        match event {
+            SocketEvent::TxDone => raise_events(Event::TxDone as u16),
+            SocketEvent::Receive(frame) => receive(&frame),
+            SocketEvent::AckReceived(frame) => {
+                mock::ack_received(&frame, rssi());
+            }
+            SocketEvent::AckTimeout => mock::ack_timeout(),
+        }
+    }
+
+    super::mock::energy_detect_done(medium::NOISE_FLOOR);
+}
+
+/// Send the frame the radio started to transmit to the other processes
+pub(super) fn transmit(buffer: &[u8; FRAME_SIZE]) {
+    let psdu_len = buffer[0] as usize;
+    if !(5..=127).contains(&psdu_len) {
+        return;
+    }
+    let frame = &buffer[1..][..psdu_len - 2];
+
+    let channel = current_channel();
+    let rx_auto_ack = with_registers(|regs| regs.rx_auto_ack);
+
+    let mut connection = CONNECTION.lock().unwrap();
+    let Some(connection) = connection.as_mut() else {
+        return;
+    };
+
+    if let Err(err) = connection
+        .open(channel)
+        .and_then(|_| connection.send(frame))
+    {
+        log::warn!("Sending frame failed: {:?}", err);
+    }
+
+    connection.tx_done = true;
+    connection.ack_wait =
+        (rx_auto_ack && ack_requested(frame) && !is_broadcast(frame)).then(|| {
+            (
+                sequence_number(frame),
+                Instant::now() + connection.config.ack_timeout,
+            )
+        });
+}
+
+fn receive(frame: &[u8]) {
+    let regs = with_registers(|regs| *regs);
+    if medium::frame_type(frame) == FRAME_TYPE_ACK && !regs.promiscuous {
+        return;
+    }
+
+    let filter_fail = medium::filter(&regs, frame);
+
+    if medium::receive(frame, rssi(), filter_fail, false)
+        && medium::will_ack(&regs, frame, filter_fail)
+    {
+        // the frame pending bit was set while receiving the frame
+        let ack = ack_for(&with_registers(|regs| *regs), frame);
+        if let Some(connection) = CONNECTION.lock().unwrap().as_mut() {
+            if let Err(err) = connection.send(&ack) {
+                log::warn!("Sending ACK failed: {:?}", err);
+            }
+        }
+
+        raise_events(Event::AckTxDone as u16);
+    }
+}
+
+fn current_channel() -> u8 {
+    let freq = with_registers(|regs| regs.freq);
+    (freq.saturating_sub(3)) / 5 + 11
+}
+
+fn rssi() -> i8 {
+    CONNECTION
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|connection| connection.config.rssi)
+        .unwrap_or(0)
+}
+
+impl Connection {
+    /// Open the sockets for `channel`, if not already open
+    fn open(&mut self, channel: u8) -> io::Result<()> {
+        if self.channel == Some(channel) && self.endpoint.is_some() {
+            return Ok(());
+        }
+
+        self.close();
+
+        let endpoint = match &self.config.transport {
+            Transport::Udp { port, interface } => {
+                let group = Ipv4Addr::new(239, 255, 154, channel);
+
+                let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP))?;
+                socket.set_reuse_address(true)?;
+                socket.bind(&SocketAddrV4::new(Ipv4Addr::UNSPECIFIED, *port).into())?;
+                socket.join_multicast_v4(&group, interface)?;
+                socket.set_multicast_if_v4(interface)?;
+                socket.set_multicast_loop_v4(true)?;
+                socket.set_nonblocking(true)?;
+
+                Endpoint::Udp(socket.into(), SocketAddrV4::new(group, *port))
+            }
+            Transport::Unix { path } => {
+                let dir = path.join(channel.to_string());
+                fs::create_dir_all(&dir)?;
+
+                let path = dir.join(format!("{:08x}", self.id));
+                fs::remove_file(&path).ok();
+                let socket = UnixDatagram::bind(&path)?;
+                socket.set_nonblocking(true)?;
+
+                Endpoint::Unix(socket, path)
+            }
+        };
+
+        self.endpoint = Some(endpoint);
+        self.channel = Some(channel);
+
+        Ok(())
+    }
+
+    fn close(&mut self) {
+        if let Some(Endpoint::Unix(_, path)) = self.endpoint.take() {
+            fs::remove_file(path).ok();
+        }
+    }
+
+    fn send(&self, frame: &[u8]) -> io::Result<()> {
+        let (Some(channel), Some(endpoint)) = (self.channel, self.endpoint.as_ref()) else {
+            return Ok(());
+        };
+
+        let mut datagram = Vec::with_capacity(HEADER_SIZE + frame.len());
+        datagram.extend_from_slice(&self.id.to_le_bytes());
+        datagram.push(channel);
+        datagram.extend_from_slice(frame);
+
+        match endpoint {
+            Endpoint::Udp(socket, group) => {
+                socket.send_to(&datagram, group)?;
+            }
+            Endpoint::Unix(socket, own) => {
+                let Some(dir) = own.parent() else {
+                    return Ok(());
+                };
+
+                for entry in fs::read_dir(dir)? {
+                    let peer = entry?.path();
+                    if peer == *own {
+                        continue;
+                    }
+
+                    match socket.send_to(&datagram, &peer) {
+                        // the radio of a process which exited without disconnecting
+                        Err(err) if err.kind() == io::ErrorKind::ConnectionRefused => {
+                            fs::remove_file(&peer).ok();
+                        }
+                        Err(err) => log::debug!("Sending to {:?} failed: {:?}", peer, err),
+                        Ok(_) => (),
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Collect the events which happened since the last poll
+    fn poll(&mut self) -> Vec<SocketEvent> {
+        let mut events = Vec::new();
+
+        if core::mem::take(&mut self.tx_done) {
+            events.push(SocketEvent::TxDone);
+        }
+
+        let mut buffer = [0u8; HEADER_SIZE + FRAME_SIZE];
+        while let Some(len) = self.recv(&mut buffer) {
+            if len < HEADER_SIZE + 3 {
+                continue;
+            }
+
+            let id = u32::from_le_bytes([buffer[0], buffer[1], buffer[2], buffer[3]]);
+            if id == self.id || Some(buffer[4]) != self.channel {
+                continue;
+            }
+
+            let frame = buffer[HEADER_SIZE..len].to_vec();
+            match self.ack_wait {
+                Some((sequence, _))
+                    if medium::frame_type(&frame) == FRAME_TYPE_ACK
+                        && sequence_number(&frame) == sequence =>
+                {
+                    self.ack_wait = None;
+                    events.push(SocketEvent::AckReceived(frame));
+                }
+                _ => events.push(SocketEvent::Receive(frame)),
+            }
+        }
+
+        if let Some((_, deadline)) = self.ack_wait {
+            if Instant::now() >= deadline {
+                self.ack_wait = None;
+                events.push(SocketEvent::AckTimeout);
+            }
+        }
+
+        events
+    }
+
+    fn recv(&self, buffer: &mut [u8]) -> Option<usize> {
+        let result = match self.endpoint.as_ref()? {
+            Endpoint::Udp(socket, _) => socket.recv(buffer),
+            Endpoint::Unix(socket, _) => socket.recv(buffer),
+        };
+
+        match result {
+            Ok(len) => Some(len),
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => None,
+            Err(err) => {
+                log::debug!("Receiving failed: {:?}", err);
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Channel, Config, Ieee802154};
+
+    // data frames in PAN 0x1234 from `source` to `destination`, with room for
+    // the FCS
+    fn data_frame(sequence: u8, ack_request: bool, destination: u16, source: u16) -> Vec<u8> {
+        let mut frame = std::vec![
+            if ack_request { 0x61 } else { 0x41 },
+            0x88,
+            sequence,
+            0x34,
+            0x12
+        ];
+        frame.extend_from_slice(&destination.to_le_bytes());
+        frame.extend_from_slice(&source.to_le_bytes());
+        frame.extend_from_slice(&[0xaa, 0x00, 0x00]);
+        frame
+    }
+
+    fn received(peer: &mut Connection) -> Vec<Vec<u8>> {
+        peer.poll()
+            .into_iter()
+            .filter_map(|event| match event {
+                SocketEvent::Receive(frame) => Some(frame),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nodes_exchange_frames_over_unix_sockets() {
+        let _lock = mock::test_lock();
+        let path = std::env::temp_dir().join(format!("esp-ieee802154-{}", std::process::id()));
+        let config = SocketConfig {
+            transport: Transport::Unix { path: path.clone() },
+            ..Default::default()
+        };
+
+        // the driver is one node, the other speaks the protocol of the sockets
+        connect(config.clone());
+        let mut radio = Ieee802154::new(mock::IEEE802154, &mut mock::RADIO_CLK);
+        radio.set_config(Config {
+            channel: Channel::new(15).unwrap(),
+            pan_id: Some(0x1234),
+            short_addr: Some(0x0001),
+            promiscuous: false,
+            auto_ack_rx: true,
+            auto_ack_tx: true,
+            rx_when_idle: true,
+            ..Default::default()
+        });
+        radio.start_receive();
+        pump();
+
+        let mut peer = Connection {
+            config,
+            id: 0x0002,
+            channel: None,
+            endpoint: None,
+            tx_done: false,
+            ack_wait: None,
+        };
+        peer.open(15).unwrap();
+
+        let frame = data_frame(1, false, 0x0002, 0x0001);
+        radio.transmit_raw(&frame).unwrap();
+        assert_eq!(received(&mut peer), [&frame[..frame.len() - 2]]);
+
+        let frame = data_frame(2, true, 0x0001, 0x0002);
+        peer.send(&frame[..frame.len() - 2]).unwrap();
+        let raw = radio.get_raw_received().unwrap();
+        assert_eq!(&raw.data[1..][..frame.len() - 2], &frame[..frame.len() - 2]);
+        // the frame is ACKed like by the radio
+        let acks = received(&mut peer);
+        assert_eq!(acks.len(), 1);
+        assert_eq!(medium::frame_type(&acks[0]), FRAME_TYPE_ACK);
+        assert_eq!(sequence_number(&acks[0]), 2);
+
+        peer.close();
+        disconnect();
+        fs::remove_dir_all(path).ok();
+    }
+}
@@ -0,0 +1,304 @@
+//! Header IEs
+
+use byte::{BytesExt, LE};
+
+use super::{end_ie, start_ie, Element};
+
+pub(super) const LEN_MASK: u16 = 0x7f;
+pub(super) const ID_SHIFT: u16 = 7;
+
+const VENDOR_SPECIFIC: u8 = 0x00;
+const CSL: u8 = 0x1a;
+const RIT: u8 = 0x1b;
+const TIME_CORRECTION: u8 = 0x1e;
+
+/// OUI of the Thread Group, identifying the vendor-specific IEs of Thread
+pub const THREAD_OUI: u32 = 0xeab89b;
+/// Thread vendor-specific IE carrying the link metrics of an enhanced ACK
+const THREAD_LINK_METRICS: u8 = 0x00;
+
+const TIME_CORRECTION_NACK: u16 = 0x8000;
+const TIME_CORRECTION_MASK: u16 = 0x0fff;
+
+/// Header IE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HeaderIe<'a> {
+    /// Coordinated Sampled Listening, the phase and period of the sampling of
+    /// the sender, in units of 10 symbols
+    Csl {
+        phase: u16,
+        period: u16,
+        /// Time until the payload of a wake-up frame is sent
+        rendezvous_time: Option<u16>,
+    },
+    /// Receiver Initiated Transmission
+    Rit {
+        /// In units of 10 symbols
+        time_to_first_listen: u8,
+        number_of_repeat_listen: u8,
+        /// In units of 10 symbols
+        repeat_listen_interval: u16,
+    },
+    /// Time correction of an enhanced ACK
+    TimeCorrection {
+        /// Difference between the expected and actual arrival time of the
+        /// acknowledged frame, in microseconds, from -2048 to 2047
+        correction: i16,
+        /// The frame was not accepted
+        nack: bool,
+    },
+    /// Thread link metrics of an enhanced ACK, one value for each metric
+    /// configured by the probing initiator
+    LinkMetrics(&'a [u8]),
+    /// Other vendor-specific IE of Thread
+    Thread { subtype: u8, content: &'a [u8] },
+    /// Vendor-specific IE, whose vendor isn't Thread
+    Vendor { oui: u32, content: &'a [u8] },
+    /// IE of another type
+    Other { id: u8, content: &'a [u8] },
+}
+
+impl<'a> HeaderIe<'a> {
+    /// The element ID of the IE
+    pub fn id(&self) -> u8 {
+        match self {
+            HeaderIe::Csl { .. } => CSL,
+            HeaderIe::Rit { .. } => RIT,
+            HeaderIe::TimeCorrection { .. } => TIME_CORRECTION,
+            HeaderIe::LinkMetrics(_) | HeaderIe::Thread { .. } | HeaderIe::Vendor { .. } => {
+                VENDOR_SPECIFIC
+            }
+            HeaderIe::Other { id, .. } => *id,
+        }
+    }
+
+    fn decode_content(id: u8, content: &'a [u8]) -> byte::Result<Self> {
+        let offset = &mut 0;
+
+        let ie = match id {
+            CSL => HeaderIe::Csl {
+                phase: content.read_with(offset, LE)?,
+                period: content.read_with(offset, LE)?,
+                rendezvous_time: if content.len() > *offset {
+                    Some(content.read_with(offset, LE)?)
+                } else {
+                    None
+                },
+            },
+            RIT => HeaderIe::Rit {
+                time_to_first_listen: content.read(offset)?,
+                number_of_repeat_listen: content.read(offset)?,
+                repeat_listen_interval: content.read_with(offset, LE)?,
+            },
+            TIME_CORRECTION => {
+                let value: u16 = content.read_with(offset, LE)?;
+                // sign-extend the 12-bit correction
+                let correction = ((value & TIME_CORRECTION_MASK) << 4) as i16 >> 4;
+
+                HeaderIe::TimeCorrection {
+                    correction,
+                    nack: value & TIME_CORRECTION_NACK != 0,
+                }
+            }
+            VENDOR_SPECIFIC => {
+                let oui = content.read_with::<u16>(offset, LE)? as u32
+                    | (content.read::<u8>(offset)? as u32) << 16;
+                if oui != THREAD_OUI {
+                    return Ok(HeaderIe::Vendor {
+                        oui,
+                        content: &content[*offset..],
+                    });
+                }
+
+                match content.read::<u8>(offset)? {
+                    THREAD_LINK_METRICS => HeaderIe::LinkMetrics(&content[*offset..]),
+                    subtype => HeaderIe::Thread {
+                        subtype,
+                        content: &content[*offset..],
+                    },
+                }
+            }
+            id => HeaderIe::Other { id, content },
+        };
+
+        Ok(ie)
+    }
+}
+
+fn write_oui(bytes: &mut [u8], offset: &mut usize, oui: u32) -> byte::Result<()> {
+    bytes.write(offset, &oui.to_le_bytes()[..3])
+}
+
+impl<'a> Element<'a> for HeaderIe<'a> {
+    fn read(bytes: &'a [u8], offset: &mut usize) -> byte::Result<Self> {
+        let descriptor: u16 = bytes.read_with(offset, LE)?;
+        let len = (descriptor & LEN_MASK) as usize;
+        let content = bytes.read_with(offset, byte::ctx::Bytes::Len(len))?;
+
+        Self::decode_content((descriptor >> ID_SHIFT) as u8, content)
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        let start = start_ie(bytes, offset, (self.id() as u16) << ID_SHIFT)?;
+
+        match *self {
+            HeaderIe::Csl {
+                phase,
+                period,
+                rendezvous_time,
+            } => {
+                bytes.write_with(offset, phase, LE)?;
+                bytes.write_with(offset, period, LE)?;
+                if let Some(rendezvous_time) = rendezvous_time {
+                    bytes.write_with(offset, rendezvous_time, LE)?;
+                }
+            }
+            HeaderIe::Rit {
+                time_to_first_listen,
+                number_of_repeat_listen,
+                repeat_listen_interval,
+            } => {
+                bytes.write(offset, time_to_first_listen)?;
+                bytes.write(offset, number_of_repeat_listen)?;
+                bytes.write_with(offset, repeat_listen_interval, LE)?;
+            }
+            HeaderIe::TimeCorrection { correction, nack } => {
+                if !(-2048..2048).contains(&correction) {
+                    return Err(byte::Error::BadInput {
+                        err: "Time correction out of range",
+                    });
+                }
+                let mut value = correction as u16 & TIME_CORRECTION_MASK;
+                if nack {
+                    value |= TIME_CORRECTION_NACK;
+                }
+                bytes.write_with(offset, value, LE)?;
+            }
+            HeaderIe::LinkMetrics(values) => {
+                write_oui(bytes, offset, THREAD_OUI)?;
+                bytes.write(offset, THREAD_LINK_METRICS)?;
+                bytes.write(offset, values)?;
+            }
+            HeaderIe::Thread { subtype, content } => {
+                write_oui(bytes, offset, THREAD_OUI)?;
+                bytes.write(offset, subtype)?;
+                bytes.write(offset, content)?;
+            }
+            HeaderIe::Vendor { oui, content } => {
+                write_oui(bytes, offset, oui)?;
+                bytes.write(offset, content)?;
+            }
+            HeaderIe::Other { content, .. } => bytes.write(offset, content)?,
+        }
+
+        end_ie(bytes, offset, start, LEN_MASK as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ie::tests::round_trip;
+
+    #[test]
+    fn header_ies_round_trip() {
+        let mut bytes = [0; 16];
+
+        for (ie, expected) in [
+            (
+                HeaderIe::Csl {
+                    phase: 0x0102,
+                    period: 0x0304,
+                    rendezvous_time: Some(0x0506),
+                },
+                &[0x06, 0x0d, 2, 1, 4, 3, 6, 5][..],
+            ),
+            (
+                HeaderIe::Rit {
+                    time_to_first_listen: 1,
+                    number_of_repeat_listen: 2,
+                    repeat_listen_interval: 3,
+                },
+                &[0x84, 0x0d, 1, 2, 3, 0],
+            ),
+            (
+                HeaderIe::TimeCorrection {
+                    correction: 100,
+                    nack: false,
+                },
+                &[0x02, 0x0f, 0x64, 0x00],
+            ),
+            (
+                HeaderIe::TimeCorrection {
+                    correction: 2047,
+                    nack: false,
+                },
+                &[0x02, 0x0f, 0xff, 0x07],
+            ),
+            (
+                HeaderIe::TimeCorrection {
+                    correction: -2048,
+                    nack: true,
+                },
+                &[0x02, 0x0f, 0x00, 0x88],
+            ),
+            (
+                HeaderIe::LinkMetrics(&[0xaa, 0xbb]),
+                &[0x06, 0x00, 0x9b, 0xb8, 0xea, 0x00, 0xaa, 0xbb],
+            ),
+            (
+                HeaderIe::Thread {
+                    subtype: 1,
+                    content: &[5],
+                },
+                &[0x05, 0x00, 0x9b, 0xb8, 0xea, 0x01, 5],
+            ),
+            (
+                HeaderIe::Vendor {
+                    oui: 0x123456,
+                    content: &[1],
+                },
+                &[0x04, 0x00, 0x56, 0x34, 0x12, 1],
+            ),
+            (
+                HeaderIe::Other {
+                    id: 0x10,
+                    content: &[1, 2, 3],
+                },
+                &[0x03, 0x08, 1, 2, 3],
+            ),
+        ] {
+            round_trip(ie, expected, &mut bytes);
+        }
+    }
+
+    #[test]
+    fn malformed_header_ies_are_rejected() {
+        // contents too short for their type
+        for bytes in [
+            &[0x03, 0x0d, 2, 1, 4][..],
+            &[0x03, 0x0d, 1, 2, 3],
+            &[0x01, 0x0f, 0x64],
+            &[0x02, 0x00, 0x9b, 0xb8],
+            &[0x03, 0x00, 0x9b, 0xb8, 0xea],
+            // content beyond the IEs
+            &[0x04, 0x08, 1, 2, 3],
+        ] {
+            assert!(HeaderIe::read(bytes, &mut 0).is_err(), "{bytes:02x?}");
+        }
+
+        for correction in [-2049, 2048] {
+            let ie = HeaderIe::TimeCorrection {
+                correction,
+                nack: false,
+            };
+            assert!(ie.write(&mut [0; 8], &mut 0).is_err());
+        }
+        let content = [0; 128];
+        let ie = HeaderIe::Other {
+            id: 0x10,
+            content: &content,
+        };
+        assert!(ie.write(&mut [0; 256], &mut 0).is_err());
+    }
+}
@@ -0,0 +1,430 @@
+//! Information Elements (IEs) of IEEE 802.15.4-2015 frames
+//!
+//! Header IEs follow the MAC header, and payload IEs start the MAC payload,
+//! before the content of beacon and command frames. A [`Frame`](crate::Frame)
+//! whose header has `ie_present` set holds its IEs at the start of its
+//! payload, see [`Frame::with_ies`](crate::Frame::with_ies) and
+//! [`Frame::ies`](crate::Frame::ies), and the driver sends and receives them
+//! at their place in the frame.
+
+mod header;
+mod payload;
+
+use core::fmt;
+
+use byte::{BytesExt, LE};
+
+pub use self::{
+    header::{HeaderIe, THREAD_OUI},
+    payload::{link_options, EbFilter, Link, NestedIe, PayloadIe, Slotframe},
+};
+use crate::Error;
+
+/// Element of a list of IEs, or of the lists they contain
+pub trait Element<'a>: Sized + Copy {
+    fn read(bytes: &'a [u8], offset: &mut usize) -> byte::Result<Self>;
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()>;
+}
+
+#[derive(Clone, Copy)]
+enum Inner<'a, T> {
+    /// Elements decoded while iterating, which were checked when decoding the
+    /// frame
+    Encoded(&'a [u8]),
+    Slice(&'a [T]),
+}
+
+/// List of IEs, either received, or to send
+///
+/// Received IEs are decoded while iterating over them, so that they don't
+/// need to be stored. IEs to send are created from a slice.
+#[derive(Clone, Copy)]
+pub struct List<'a, T>(Inner<'a, T>);
+
+impl<'a, T: Element<'a>> List<'a, T> {
+    pub fn iter(&self) -> impl Iterator<Item = T> + 'a {
+        let mut encoded = match self.0 {
+            Inner::Encoded(bytes) => bytes,
+            Inner::Slice(_) => &[],
+        };
+        let mut slice = match self.0 {
+            Inner::Encoded(_) => <&[T]>::default().iter(),
+            Inner::Slice(slice) => slice.iter(),
+        };
+
+        core::iter::from_fn(move || {
+            if let Some(element) = slice.next() {
+                return Some(*element);
+            }
+            if encoded.is_empty() {
+                return None;
+            }
+
+            let offset = &mut 0;
+            let element = T::read(encoded, offset).ok()?;
+            encoded = &encoded[*offset..];
+
+            Some(element)
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self.0 {
+            Inner::Encoded(bytes) => bytes.is_empty(),
+            Inner::Slice(slice) => slice.is_empty(),
+        }
+    }
+
+    /// Check the elements encoded in `bytes`
+    fn decode(bytes: &'a [u8]) -> byte::Result<Self> {
+        let offset = &mut 0;
+        while *offset < bytes.len() {
+            T::read(bytes, offset)?;
+        }
+
+        Ok(Self(Inner::Encoded(bytes)))
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        match self.0 {
+            Inner::Encoded(encoded) => bytes.write(offset, encoded),
+            Inner::Slice(slice) => slice
+                .iter()
+                .try_for_each(|element| element.write(bytes, offset)),
+        }
+    }
+}
+
+impl<T> Default for List<'_, T> {
+    fn default() -> Self {
+        Self(Inner::Slice(&[]))
+    }
+}
+
+impl<'a, T> From<&'a [T]> for List<'a, T> {
+    fn from(slice: &'a [T]) -> Self {
+        Self(Inner::Slice(slice))
+    }
+}
+
+impl<'a, T: Element<'a>> PartialEq for List<'a, T>
+where
+    T: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<'a, T: Element<'a>> fmt::Debug for List<'a, T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Header termination IE followed by payload IEs
+const HEADER_TERMINATION_1: u8 = 0x7e;
+/// Header termination IE followed by the payload
+const HEADER_TERMINATION_2: u8 = 0x7f;
+/// Group of the payload termination IE
+const PAYLOAD_TERMINATION: u8 = 0x0f;
+
+const TYPE_PAYLOAD: u16 = 0x8000;
+
+/// Write a descriptor whose length is patched by [`end_ie`], returns the
+/// offset of the content
+fn start_ie(bytes: &mut [u8], offset: &mut usize, descriptor: u16) -> byte::Result<usize> {
+    bytes.write_with(offset, descriptor, LE)?;
+    Ok(*offset)
+}
+
+/// Set the length of the IE whose content started at `start`
+fn end_ie(bytes: &mut [u8], offset: &usize, start: usize, max_len: usize) -> byte::Result<()> {
+    let len = *offset - start;
+    if len > max_len {
+        return Err(byte::Error::BadInput { err: "IE too long" });
+    }
+
+    let descriptor = &mut bytes[start - 2..start];
+    let value = u16::from_le_bytes([descriptor[0], descriptor[1]]) | len as u16;
+    descriptor.copy_from_slice(&value.to_le_bytes());
+
+    Ok(())
+}
+
+/// Header and payload IEs of a frame
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Ies<'a> {
+    pub header: List<'a, HeaderIe<'a>>,
+    pub payload: List<'a, PayloadIe<'a>>,
+}
+
+impl<'a> Ies<'a> {
+    /// Decode the IEs at the start of `bytes`, the bytes following the MAC
+    /// header, returns them and their length including the termination IEs
+    pub fn decode(bytes: &'a [u8]) -> Result<(Self, usize), Error> {
+        let offset = &mut 0;
+        let mut payload_ies = false;
+
+        while *offset < bytes.len() {
+            let start = *offset;
+            let descriptor: u16 = bytes.read_with(offset, LE)?;
+            if descriptor & TYPE_PAYLOAD != 0 {
+                return Err(Error::BadInput);
+            }
+            let len = (descriptor & header::LEN_MASK) as usize;
+            let id = (descriptor >> header::ID_SHIFT) as u8;
+
+            if id == HEADER_TERMINATION_1 || id == HEADER_TERMINATION_2 {
+                payload_ies = id == HEADER_TERMINATION_1;
+                *offset = start;
+                break;
+            }
+            byte::check_len(&bytes[*offset..], len)?;
+            *offset += len;
+        }
+
+        let header = List::decode(&bytes[..*offset])?;
+        if *offset == bytes.len() {
+            return Ok((
+                Self {
+                    header,
+                    payload: List::default(),
+                },
+                *offset,
+            ));
+        }
+
+        // the termination IE
+        *offset += 2;
+        if !payload_ies {
+            return Ok((
+                Self {
+                    header,
+                    payload: List::default(),
+                },
+                *offset,
+            ));
+        }
+
+        let payload_start = *offset;
+        let mut payload_end = bytes.len();
+        while *offset < bytes.len() {
+            let start = *offset;
+            let descriptor: u16 = bytes.read_with(offset, LE)?;
+            if descriptor & TYPE_PAYLOAD == 0 {
+                return Err(Error::BadInput);
+            }
+            let len = (descriptor & payload::LEN_MASK) as usize;
+
+            if (descriptor >> payload::GROUP_SHIFT) as u8 & payload::GROUP_MASK
+                == PAYLOAD_TERMINATION
+            {
+                payload_end = start;
+                break;
+            }
+            byte::check_len(&bytes[*offset..], len)?;
+            *offset += len;
+        }
+
+        let payload = List::decode(&bytes[payload_start..payload_end])?;
+
+        Ok((Self { header, payload }, *offset))
+    }
+
+    /// Encode the IEs into `bytes`, with the termination IEs needed when
+    /// `followed_by_payload`, returns their length
+    pub fn encode(&self, followed_by_payload: bool, bytes: &mut [u8]) -> Result<usize, Error> {
+        let offset = &mut 0;
+
+        self.header.write(bytes, offset)?;
+
+        if !self.payload.is_empty() {
+            bytes.write_with(
+                offset,
+                (HEADER_TERMINATION_1 as u16) << header::ID_SHIFT,
+                LE,
+            )?;
+            self.payload.write(bytes, offset)?;
+
+            if followed_by_payload {
+                bytes.write_with(
+                    offset,
+                    TYPE_PAYLOAD | (PAYLOAD_TERMINATION as u16) << payload::GROUP_SHIFT,
+                    LE,
+                )?;
+            }
+        } else if followed_by_payload {
+            bytes.write_with(
+                offset,
+                (HEADER_TERMINATION_2 as u16) << header::ID_SHIFT,
+                LE,
+            )?;
+        }
+
+        Ok(*offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `element`, checking its encoding and that it reads back the same
+    pub(super) fn round_trip<'a, T>(element: T, expected: &[u8], bytes: &'a mut [u8])
+    where
+        T: Element<'a> + PartialEq + fmt::Debug,
+    {
+        let offset = &mut 0;
+        element.write(bytes, offset).unwrap();
+        assert_eq!(bytes[..*offset], *expected, "{element:?}");
+
+        let len = *offset;
+        let bytes = &bytes[..len];
+        let offset = &mut 0;
+        assert_eq!(T::read(bytes, offset).unwrap(), element);
+        assert_eq!(*offset, len);
+    }
+
+    fn header_ies() -> [HeaderIe<'static>; 2] {
+        [
+            HeaderIe::Csl {
+                phase: 0x10,
+                period: 0x64,
+                rendezvous_time: None,
+            },
+            HeaderIe::TimeCorrection {
+                correction: -5,
+                nack: true,
+            },
+        ]
+    }
+
+    const HEADER_IES: [u8; 10] = [0x04, 0x0d, 0x10, 0, 0x64, 0, 0x02, 0x0f, 0xfb, 0x8f];
+
+    #[test]
+    fn header_ies_round_trip() {
+        let header_ies = header_ies();
+        let ies = Ies {
+            header: (&header_ies[..]).into(),
+            payload: List::default(),
+        };
+        let mut bytes = [0; 32];
+
+        // the IEs end the frame
+        let len = ies.encode(false, &mut bytes).unwrap();
+        assert_eq!(bytes[..len], HEADER_IES);
+        assert_eq!(Ies::decode(&bytes[..len]).unwrap(), (ies, len));
+
+        // the IEs are followed by the payload
+        let len = ies.encode(true, &mut bytes).unwrap();
+        assert_eq!(bytes[len - 2..len], [0x80, 0x3f]);
+        bytes[len] = 0x55;
+        assert_eq!(Ies::decode(&bytes[..len + 1]).unwrap(), (ies, len));
+
+        // only a payload
+        let ies = Ies::default();
+        assert_eq!(ies.encode(false, &mut bytes).unwrap(), 0);
+        assert_eq!(ies.encode(true, &mut bytes).unwrap(), 2);
+        assert_eq!(Ies::decode(&bytes[..3]).unwrap(), (ies, 2));
+        assert_eq!(Ies::decode(&[]).unwrap(), (ies, 0));
+    }
+
+    #[test]
+    fn payload_ies_round_trip() {
+        let header_ies = header_ies();
+        let payload_ies = [
+            PayloadIe::Vendor {
+                oui: 0x123456,
+                content: &[7],
+            },
+            PayloadIe::Other {
+                group: 0x5,
+                content: &[1, 2],
+            },
+        ];
+        let ies = Ies {
+            header: (&header_ies[..]).into(),
+            payload: (&payload_ies[..]).into(),
+        };
+        let mut bytes = [0; 32];
+
+        let len = ies.encode(false, &mut bytes).unwrap();
+        assert_eq!(bytes[..HEADER_IES.len()], HEADER_IES);
+        assert_eq!(
+            bytes[HEADER_IES.len()..len],
+            [0x00, 0x3f, 0x04, 0x90, 0x56, 0x34, 0x12, 7, 0x02, 0xa8, 1, 2]
+        );
+        assert_eq!(Ies::decode(&bytes[..len]).unwrap(), (ies, len));
+
+        // the payload termination IE is only needed before the payload
+        let len = ies.encode(true, &mut bytes).unwrap();
+        assert_eq!(bytes[len - 2..len], [0x00, 0xf8]);
+        bytes[len] = 0x55;
+        assert_eq!(Ies::decode(&bytes[..len + 1]).unwrap(), (ies, len));
+
+        // without header IEs
+        let ies = Ies {
+            header: List::default(),
+            payload: (&payload_ies[..]).into(),
+        };
+        let len = ies.encode(false, &mut bytes).unwrap();
+        assert_eq!(bytes[..2], [0x00, 0x3f]);
+        assert_eq!(Ies::decode(&bytes[..len]).unwrap(), (ies, len));
+    }
+
+    #[test]
+    fn malformed_ies_are_rejected() {
+        // truncated descriptors and contents
+        for bytes in [
+            &[0x04][..],
+            &[0x04, 0x0d, 0x10, 0, 0x64],
+            &[0x00, 0x3f, 0x04],
+            &[0x00, 0x3f, 0x04, 0x90, 0x56],
+        ] {
+            assert!(
+                matches!(Ies::decode(bytes), Err(Error::Incomplete)),
+                "{bytes:02x?}"
+            );
+        }
+
+        // header IEs which are too short for their type
+        assert!(matches!(
+            Ies::decode(&[0x02, 0x0d, 0x10, 0]),
+            Err(Error::Incomplete)
+        ));
+
+        // payload IEs among the header IEs, and the other way around
+        assert!(matches!(
+            Ies::decode(&[0x01, 0x90, 0x56]),
+            Err(Error::BadInput)
+        ));
+        assert!(matches!(
+            Ies::decode(&[0x00, 0x3f, 0x04, 0x0d, 0x10, 0, 0x64, 0]),
+            Err(Error::BadInput)
+        ));
+
+        // IEs too long for their descriptor
+        let content = [0; 128];
+        let header_ies = [HeaderIe::Other {
+            id: 0x10,
+            content: &content,
+        }];
+        let ies = Ies {
+            header: (&header_ies[..]).into(),
+            payload: List::default(),
+        };
+        assert!(matches!(
+            ies.encode(false, &mut [0; 256]),
+            Err(Error::BadInput)
+        ));
+        assert!(matches!(
+            ies.encode(false, &mut [0; 64]),
+            Err(Error::Incomplete)
+        ));
+    }
+}
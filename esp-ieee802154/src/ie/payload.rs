@@ -0,0 +1,552 @@
+//! Payload IEs, and the IEs nested in MLME IEs
+
+use byte::{BytesExt, LE};
+
+use super::{end_ie, start_ie, Element, Inner, List, TYPE_PAYLOAD};
+
+pub(super) const LEN_MASK: u16 = 0x07ff;
+pub(super) const GROUP_SHIFT: u16 = 11;
+pub(super) const GROUP_MASK: u8 = 0x0f;
+
+const GROUP_MLME: u8 = 0x1;
+const GROUP_VENDOR_SPECIFIC: u8 = 0x2;
+
+const NESTED_LONG: u16 = 0x8000;
+const NESTED_SHORT_LEN_MASK: u16 = 0x00ff;
+const NESTED_SHORT_ID_SHIFT: u16 = 8;
+const NESTED_SHORT_ID_MASK: u8 = 0x7f;
+const NESTED_LONG_LEN_MASK: u16 = 0x07ff;
+const NESTED_LONG_ID_SHIFT: u16 = 11;
+const NESTED_LONG_ID_MASK: u8 = 0x0f;
+
+const TSCH_SYNCHRONIZATION: u8 = 0x1a;
+const TSCH_SLOTFRAME_AND_LINK: u8 = 0x1b;
+const TSCH_TIMESLOT: u8 = 0x1c;
+const EB_FILTER: u8 = 0x1e;
+const CHANNEL_HOPPING: u8 = 0x09;
+
+/// Payload IE
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PayloadIe<'a> {
+    /// IEs of the MAC sublayer management entity
+    Mlme(List<'a, NestedIe<'a>>),
+    Vendor {
+        oui: u32,
+        content: &'a [u8],
+    },
+    /// IE of another group
+    Other {
+        group: u8,
+        content: &'a [u8],
+    },
+}
+
+impl PayloadIe<'_> {
+    /// The group ID of the IE
+    pub fn group(&self) -> u8 {
+        match self {
+            PayloadIe::Mlme(_) => GROUP_MLME,
+            PayloadIe::Vendor { .. } => GROUP_VENDOR_SPECIFIC,
+            PayloadIe::Other { group, .. } => *group,
+        }
+    }
+}
+
+impl<'a> Element<'a> for PayloadIe<'a> {
+    fn read(bytes: &'a [u8], offset: &mut usize) -> byte::Result<Self> {
+        let descriptor: u16 = bytes.read_with(offset, LE)?;
+        let len = (descriptor & LEN_MASK) as usize;
+        let content: &[u8] = bytes.read_with(offset, byte::ctx::Bytes::Len(len))?;
+
+        let ie = match (descriptor >> GROUP_SHIFT) as u8 & GROUP_MASK {
+            GROUP_MLME => PayloadIe::Mlme(List::decode(content)?),
+            GROUP_VENDOR_SPECIFIC => {
+                let offset = &mut 0;
+                let oui = content.read_with::<u16>(offset, LE)? as u32
+                    | (content.read::<u8>(offset)? as u32) << 16;
+
+                PayloadIe::Vendor {
+                    oui,
+                    content: &content[*offset..],
+                }
+            }
+            group => PayloadIe::Other { group, content },
+        };
+
+        Ok(ie)
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        let descriptor = TYPE_PAYLOAD | ((self.group() & GROUP_MASK) as u16) << GROUP_SHIFT;
+        let start = start_ie(bytes, offset, descriptor)?;
+
+        match *self {
+            PayloadIe::Mlme(ies) => ies.write(bytes, offset)?,
+            PayloadIe::Vendor { oui, content } => {
+                bytes.write(offset, &oui.to_le_bytes()[..3])?;
+                bytes.write(offset, content)?;
+            }
+            PayloadIe::Other { content, .. } => bytes.write(offset, content)?,
+        }
+
+        end_ie(bytes, offset, start, LEN_MASK as usize)
+    }
+}
+
+/// IE nested in an MLME IE
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NestedIe<'a> {
+    /// TSCH synchronization, sent in enhanced beacons
+    TschSynchronization {
+        /// Absolute slot number, on 40 bits
+        asn: u64,
+        join_metric: u8,
+    },
+    /// TSCH slotframes and the links of the sender in them
+    TschSlotframeAndLink(List<'a, Slotframe<'a>>),
+    /// TSCH timeslot template
+    TschTimeslot {
+        id: u8,
+        /// The timings of the template, empty for the default template
+        template: &'a [u8],
+    },
+    /// Filter of an enhanced beacon request
+    EbFilter(EbFilter<'a>),
+    /// Channel hopping sequence
+    ChannelHopping {
+        sequence_id: u8,
+        /// The rest of the IE, empty for the default sequence
+        content: &'a [u8],
+    },
+    /// IE of another type, `long` telling whether it has a long descriptor
+    Other {
+        long: bool,
+        id: u8,
+        content: &'a [u8],
+    },
+}
+
+impl NestedIe<'_> {
+    /// Whether the IE has a long descriptor, and its sub-ID
+    pub fn id(&self) -> (bool, u8) {
+        match self {
+            NestedIe::TschSynchronization { .. } => (false, TSCH_SYNCHRONIZATION),
+            NestedIe::TschSlotframeAndLink(_) => (false, TSCH_SLOTFRAME_AND_LINK),
+            NestedIe::TschTimeslot { .. } => (false, TSCH_TIMESLOT),
+            NestedIe::EbFilter(_) => (false, EB_FILTER),
+            NestedIe::ChannelHopping { .. } => (true, CHANNEL_HOPPING),
+            NestedIe::Other { long, id, .. } => (*long, *id),
+        }
+    }
+}
+
+impl<'a> Element<'a> for NestedIe<'a> {
+    fn read(bytes: &'a [u8], offset: &mut usize) -> byte::Result<Self> {
+        let descriptor: u16 = bytes.read_with(offset, LE)?;
+        let long = descriptor & NESTED_LONG != 0;
+        let (id, len) = if long {
+            (
+                (descriptor >> NESTED_LONG_ID_SHIFT) as u8 & NESTED_LONG_ID_MASK,
+                descriptor & NESTED_LONG_LEN_MASK,
+            )
+        } else {
+            (
+                (descriptor >> NESTED_SHORT_ID_SHIFT) as u8 & NESTED_SHORT_ID_MASK,
+                descriptor & NESTED_SHORT_LEN_MASK,
+            )
+        };
+        let content: &[u8] = bytes.read_with(offset, byte::ctx::Bytes::Len(len as usize))?;
+        let offset = &mut 0;
+
+        let ie = match (long, id) {
+            (false, TSCH_SYNCHRONIZATION) => {
+                let low: u32 = content.read_with(offset, LE)?;
+                let high: u8 = content.read(offset)?;
+
+                NestedIe::TschSynchronization {
+                    asn: low as u64 | (high as u64) << 32,
+                    join_metric: content.read(offset)?,
+                }
+            }
+            (false, TSCH_SLOTFRAME_AND_LINK) => {
+                let count: u8 = content.read(offset)?;
+                let start = *offset;
+                for _ in 0..count {
+                    Slotframe::read(content, offset)?;
+                }
+
+                NestedIe::TschSlotframeAndLink(List(Inner::Encoded(&content[start..*offset])))
+            }
+            (false, TSCH_TIMESLOT) => NestedIe::TschTimeslot {
+                id: content.read(offset)?,
+                template: &content[*offset..],
+            },
+            (false, EB_FILTER) => NestedIe::EbFilter(EbFilter::read(content, offset)?),
+            (true, CHANNEL_HOPPING) => NestedIe::ChannelHopping {
+                sequence_id: content.read(offset)?,
+                content: &content[*offset..],
+            },
+            (long, id) => NestedIe::Other { long, id, content },
+        };
+
+        Ok(ie)
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        let (long, id) = self.id();
+        let (descriptor, max_len) = if long {
+            (
+                NESTED_LONG | ((id & NESTED_LONG_ID_MASK) as u16) << NESTED_LONG_ID_SHIFT,
+                NESTED_LONG_LEN_MASK,
+            )
+        } else {
+            (
+                ((id & NESTED_SHORT_ID_MASK) as u16) << NESTED_SHORT_ID_SHIFT,
+                NESTED_SHORT_LEN_MASK,
+            )
+        };
+        let start = start_ie(bytes, offset, descriptor)?;
+
+        match *self {
+            NestedIe::TschSynchronization { asn, join_metric } => {
+                bytes.write(offset, &asn.to_le_bytes()[..5])?;
+                bytes.write(offset, join_metric)?;
+            }
+            NestedIe::TschSlotframeAndLink(slotframes) => {
+                let count =
+                    u8::try_from(slotframes.iter().count()).map_err(|_| byte::Error::BadInput {
+                        err: "Too many slotframes",
+                    })?;
+                bytes.write(offset, count)?;
+                slotframes.write(bytes, offset)?;
+            }
+            NestedIe::TschTimeslot { id, template } => {
+                bytes.write(offset, id)?;
+                bytes.write(offset, template)?;
+            }
+            NestedIe::EbFilter(filter) => filter.write(bytes, offset)?,
+            NestedIe::ChannelHopping {
+                sequence_id,
+                content,
+            } => {
+                bytes.write(offset, sequence_id)?;
+                bytes.write(offset, content)?;
+            }
+            NestedIe::Other { content, .. } => bytes.write(offset, content)?,
+        }
+
+        end_ie(bytes, offset, start, max_len as usize)
+    }
+}
+
+const FILTER_PERMIT_JOINING_ON: u8 = 0x01;
+const FILTER_LINK_QUALITY: u8 = 0x02;
+const FILTER_PERCENT: u8 = 0x04;
+const FILTER_ATTRIBUTES_SHIFT: u8 = 3;
+const FILTER_ATTRIBUTES_MASK: u8 = 0x03;
+
+/// Conditions under which coordinators answer an enhanced beacon request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct EbFilter<'a> {
+    /// Only coordinators permitting joining answer
+    pub permit_joining_on: bool,
+    /// Only coordinators receiving the request with at least this LQI answer
+    pub link_quality: Option<u8>,
+    /// Coordinators answer with this probability, in percent
+    pub percent: Option<u8>,
+    /// Identifiers of up to 3 PIB attributes requested in the beacon
+    pub attributes: &'a [u8],
+}
+
+impl<'a> EbFilter<'a> {
+    fn read(bytes: &'a [u8], offset: &mut usize) -> byte::Result<Self> {
+        let control: u8 = bytes.read(offset)?;
+        let link_quality = if control & FILTER_LINK_QUALITY != 0 {
+            Some(bytes.read(offset)?)
+        } else {
+            None
+        };
+        let percent = if control & FILTER_PERCENT != 0 {
+            Some(bytes.read(offset)?)
+        } else {
+            None
+        };
+        let count = (control >> FILTER_ATTRIBUTES_SHIFT) & FILTER_ATTRIBUTES_MASK;
+
+        Ok(Self {
+            permit_joining_on: control & FILTER_PERMIT_JOINING_ON != 0,
+            link_quality,
+            percent,
+            attributes: bytes.read_with(offset, byte::ctx::Bytes::Len(count as usize))?,
+        })
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        if self.attributes.len() > FILTER_ATTRIBUTES_MASK as usize {
+            return Err(byte::Error::BadInput {
+                err: "Too many PIB attributes",
+            });
+        }
+
+        let mut control = (self.attributes.len() as u8) << FILTER_ATTRIBUTES_SHIFT;
+        if self.permit_joining_on {
+            control |= FILTER_PERMIT_JOINING_ON;
+        }
+        if self.link_quality.is_some() {
+            control |= FILTER_LINK_QUALITY;
+        }
+        if self.percent.is_some() {
+            control |= FILTER_PERCENT;
+        }
+
+        bytes.write(offset, control)?;
+        if let Some(link_quality) = self.link_quality {
+            bytes.write(offset, link_quality)?;
+        }
+        if let Some(percent) = self.percent {
+            bytes.write(offset, percent)?;
+        }
+        bytes.write(offset, self.attributes)
+    }
+}
+
+/// Slotframe of a TSCH slotframe and link IE
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Slotframe<'a> {
+    pub handle: u8,
+    /// Number of timeslots
+    pub size: u16,
+    pub links: List<'a, Link>,
+}
+
+impl<'a> Element<'a> for Slotframe<'a> {
+    fn read(bytes: &'a [u8], offset: &mut usize) -> byte::Result<Self> {
+        let handle = bytes.read(offset)?;
+        let size = bytes.read_with(offset, LE)?;
+        let count: u8 = bytes.read(offset)?;
+        let links: &[u8] = bytes.read_with(offset, byte::ctx::Bytes::Len(count as usize * 5))?;
+
+        Ok(Self {
+            handle,
+            size,
+            links: List(Inner::Encoded(links)),
+        })
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        let count = u8::try_from(self.links.iter().count()).map_err(|_| byte::Error::BadInput {
+            err: "Too many links",
+        })?;
+
+        bytes.write(offset, self.handle)?;
+        bytes.write_with(offset, self.size, LE)?;
+        bytes.write(offset, count)?;
+        self.links.write(bytes, offset)
+    }
+}
+
+/// Link of a slotframe
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    pub timeslot: u16,
+    pub channel_offset: u16,
+    /// See [`link_options`]
+    pub options: u8,
+}
+
+/// Options of a [`Link`]
+pub mod link_options {
+    pub const TX: u8 = 0x01;
+    pub const RX: u8 = 0x02;
+    pub const SHARED: u8 = 0x04;
+    pub const TIMEKEEPING: u8 = 0x08;
+    pub const PRIORITY: u8 = 0x10;
+}
+
+impl Element<'_> for Link {
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        Ok(Self {
+            timeslot: bytes.read_with(offset, LE)?,
+            channel_offset: bytes.read_with(offset, LE)?,
+            options: bytes.read(offset)?,
+        })
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        bytes.write_with(offset, self.timeslot, LE)?;
+        bytes.write_with(offset, self.channel_offset, LE)?;
+        bytes.write(offset, self.options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ie::tests::round_trip;
+
+    const LINKS: [Link; 1] = [Link {
+        timeslot: 0,
+        channel_offset: 0,
+        options: link_options::TX
+            | link_options::RX
+            | link_options::SHARED
+            | link_options::TIMEKEEPING,
+    }];
+
+    #[test]
+    fn minimal_enhanced_beacon_round_trips() {
+        // the IEs of the enhanced beacons of the minimal configuration of RFC
+        // 8180
+        let slotframes = [Slotframe {
+            handle: 0,
+            size: 101,
+            links: (&LINKS[..]).into(),
+        }];
+        let nested = [
+            NestedIe::TschSynchronization {
+                asn: 0x01_0203_0405,
+                join_metric: 1,
+            },
+            NestedIe::TschTimeslot {
+                id: 0,
+                template: &[],
+            },
+            NestedIe::TschSlotframeAndLink((&slotframes[..]).into()),
+            NestedIe::ChannelHopping {
+                sequence_id: 0,
+                content: &[],
+            },
+        ];
+
+        let mut bytes = [0; 32];
+        round_trip(
+            PayloadIe::Mlme((&nested[..]).into()),
+            &[
+                0x1a, 0x88, 0x06, 0x1a, 0x05, 0x04, 0x03, 0x02, 0x01, 0x01, 0x01, 0x1c, 0x00, 0x0a,
+                0x1b, 0x01, 0x00, 0x65, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x0f, 0x01, 0xc8, 0x00,
+            ],
+            &mut bytes,
+        );
+
+        let Ok(PayloadIe::Mlme(nested)) = PayloadIe::read(&bytes, &mut 0) else {
+            panic!("no MLME IE");
+        };
+        let Some(NestedIe::TschSlotframeAndLink(slotframes)) = nested.iter().nth(2) else {
+            panic!("no slotframe and link IE");
+        };
+        let slotframe = slotframes.iter().next().unwrap();
+        assert_eq!(slotframe.size, 101);
+        assert!(slotframe.links.iter().eq(LINKS));
+    }
+
+    #[test]
+    fn nested_ies_round_trip() {
+        let mut bytes = [0; 16];
+
+        for (ie, expected) in [
+            (
+                NestedIe::EbFilter(EbFilter {
+                    permit_joining_on: true,
+                    link_quality: Some(100),
+                    percent: None,
+                    attributes: &[1],
+                }),
+                &[0x03, 0x1e, 0x0b, 100, 1][..],
+            ),
+            (
+                NestedIe::EbFilter(EbFilter {
+                    percent: Some(50),
+                    ..Default::default()
+                }),
+                &[0x02, 0x1e, 0x04, 50],
+            ),
+            (
+                NestedIe::TschTimeslot {
+                    id: 1,
+                    template: &[1, 2],
+                },
+                &[0x03, 0x1c, 1, 1, 2],
+            ),
+            (
+                NestedIe::ChannelHopping {
+                    sequence_id: 1,
+                    content: &[2],
+                },
+                &[0x02, 0xc8, 1, 2],
+            ),
+            (
+                NestedIe::Other {
+                    long: true,
+                    id: 0x0a,
+                    content: &[1],
+                },
+                &[0x01, 0xd0, 1],
+            ),
+            (
+                NestedIe::Other {
+                    long: false,
+                    id: 0x30,
+                    content: &[1, 2],
+                },
+                &[0x02, 0x30, 1, 2],
+            ),
+        ] {
+            round_trip(ie, expected, &mut bytes);
+        }
+
+        // slotframes without links
+        let slotframes = [
+            Slotframe {
+                handle: 0,
+                size: 101,
+                links: List::default(),
+            },
+            Slotframe {
+                handle: 1,
+                size: 7,
+                links: (&LINKS[..]).into(),
+            },
+        ];
+        round_trip(
+            NestedIe::TschSlotframeAndLink((&slotframes[..]).into()),
+            &[
+                0x0e, 0x1b, 2, 0, 0x65, 0x00, 0, 1, 0x07, 0x00, 1, 0x00, 0x00, 0x00, 0x00, 0x0f,
+            ],
+            &mut bytes,
+        );
+    }
+
+    #[test]
+    fn malformed_payload_ies_are_rejected() {
+        for bytes in [
+            // truncated TSCH synchronization
+            &[0x05, 0x1a, 0x05, 0x04, 0x03, 0x02, 0x01][..],
+            // more slotframes and links than present
+            &[0x05, 0x1b, 2, 0, 0x65, 0x00, 0],
+            &[0x05, 0x1b, 1, 0, 0x65, 0x00, 1],
+            // EB filter attributes beyond the IE
+            &[0x02, 0x1e, 0x10, 1],
+            // content beyond the nested IEs
+            &[0x03, 0x1c, 1],
+        ] {
+            assert!(NestedIe::read(bytes, &mut 0).is_err(), "{bytes:02x?}");
+        }
+
+        // nested IEs beyond the MLME IE, and truncated vendor OUIs
+        assert!(PayloadIe::read(&[0x03, 0x88, 0x03, 0x1c, 1], &mut 0).is_err());
+        assert!(PayloadIe::read(&[0x02, 0x90, 0x56, 0x34], &mut 0).is_err());
+
+        let filter = NestedIe::EbFilter(EbFilter {
+            attributes: &[1, 2, 3, 4],
+            ..Default::default()
+        });
+        assert!(filter.write(&mut [0; 16], &mut 0).is_err());
+        let content = [0; 256];
+        let ie = NestedIe::Other {
+            long: false,
+            id: 0x30,
+            content: &content,
+        };
+        assert!(ie.write(&mut [0; 512], &mut 0).is_err());
+    }
+}
@@ -6,33 +6,80 @@
 //! [IEEE 802.15.4]: https://en.wikipedia.org/wiki/IEEE_802.15.4
 
 #![no_std]
-#![feature(c_variadic)]
+#![cfg_attr(not(feature = "mock"), feature(c_variadic))]
+#![cfg_attr(feature = "sim", feature(thread_local))]
+
+#[cfg(any(test, feature = "sim", feature = "std", feature = "capture"))]
+extern crate std;
 
 use core::{cell::RefCell, marker::PhantomData};
 
-use byte::{BytesExt, TryRead};
 use critical_section::Mutex;
-use esp_hal::peripherals::{IEEE802154, RADIO_CLK};
-use heapless::Vec;
-use ieee802154::mac::{self, FooterMode, FrameSerDesContext};
 
+#[cfg(feature = "capture")]
+pub use self::hal::capture;
+#[cfg(feature = "mock")]
+pub use self::hal::mock;
+#[cfg(feature = "sim")]
+pub use self::hal::sim;
+#[cfg(feature = "std")]
+pub use self::hal::socket;
 use self::{
+    beacon::BeaconResponse,
     frame::FRAME_SIZE,
-    pib::{CONFIG_IEEE802154_CCA_THRESHOLD, IEEE802154_FRAME_EXT_ADDR_SIZE},
+    hal::{IEEE802154, RADIO_CLK},
+    link_metrics::{FrameTypes, Metrics, Neighbor, Values},
+    pib::{
+        check_cca_threshold, check_tx_power, CONFIG_IEEE802154_CCA_THRESHOLD,
+        IEEE802154_FRAME_EXT_ADDR_SIZE,
+    },
     raw::*,
+    stats::{stats_get, stats_reset},
 };
 pub use self::{
-    frame::{Frame, ReceivedFrame},
-    pib::{CcaMode, PendingMode},
-    raw::RawReceived,
+    channel::{Channel, ChannelMask},
+    frame::{Frame, ReceivedFrame, RxToken},
+    pib::{CcaMode, PendingMode, TxPower},
+    raw::{OverflowPolicy, RawReceived, Reconfiguration, RxFault},
+    stats::RadioStats,
 };
 
+#[cfg(any(feature = "openthread", feature = "spinel"))]
+mod ack;
+pub mod beacon;
+mod channel;
+#[cfg(not(feature = "mock"))]
 mod compat;
+#[cfg(any(feature = "openthread", feature = "spinel"))]
+mod csma;
 mod frame;
 mod hal;
+pub mod ie;
+pub mod link_metrics;
+#[cfg(feature = "mle")]
+pub mod mle;
+#[cfg(feature = "embassy-net-driver")]
+pub mod net_driver;
+#[cfg(any(feature = "smoltcp", feature = "embassy-net-driver"))]
+mod netdev;
+#[cfg(feature = "openthread")]
+pub mod ot_radio;
+mod pending;
+#[cfg(feature = "smoltcp")]
+pub mod phy;
 mod pib;
+pub mod radio;
 mod raw;
-
+mod rng;
+#[cfg(feature = "sixlowpan")]
+pub mod sixlowpan;
+#[cfg(feature = "spinel")]
+pub mod spinel;
+mod stats;
+#[cfg(feature = "zigbee")]
+pub mod zigbee;
+
+#[cfg(not(feature = "mock"))]
 #[no_mangle]
 extern "C" fn rtc_clk_xtal_freq_get() -> i32 {
     0
@@ -46,6 +93,8 @@ pub enum Error {
     Incomplete,
     /// The requested data content is invalid
     BadInput,
+    /// The table the data is added to is full
+    Full,
 }
 
 impl From<byte::Error> for Error {
@@ -67,12 +116,19 @@ pub struct Config {
     pub coordinator: bool,
     pub rx_when_idle: bool,
     pub txpower: i8,
-    pub channel: u8,
+    pub channel: Channel,
     pub cca_threshold: i8,
     pub cca_mode: CcaMode,
     pub pan_id: Option<u16>,
     pub short_addr: Option<u16>,
     pub ext_addr: Option<u64>,
+    pub rx_queue_policy: OverflowPolicy,
+    /// How the frame pending bit of sent ACKs is decided, see
+    /// [`Ieee802154::add_pending_short_address`]
+    pub pending_mode: PendingMode,
+    /// Also deliver frames which were rejected due to a CRC error or by the
+    /// address filter, see [`RxFault`]
+    pub rx_bad_frames: bool,
 }
 
 impl Default for Config {
@@ -85,21 +141,139 @@ impl Default for Config {
             coordinator: Default::default(),
             rx_when_idle: Default::default(),
             txpower: 10,
-            channel: 15,
+            channel: Channel(15),
             cca_threshold: CONFIG_IEEE802154_CCA_THRESHOLD,
             cca_mode: CcaMode::Ed,
             pan_id: None,
             short_addr: None,
             ext_addr: None,
+            rx_queue_policy: OverflowPolicy::DropNewest,
+            pending_mode: PendingMode::Disable,
+            rx_bad_frames: false,
+        }
+    }
+}
+
+impl Config {
+    /// Builder starting from the default configuration
+    pub fn builder() -> ConfigBuilder {
+        ConfigBuilder {
+            config: Self::default(),
         }
     }
 }
 
+/// Builder of a [`Config`] which is validated by [`ConfigBuilder::build`]
+#[derive(Debug, Clone, Copy)]
+pub struct ConfigBuilder {
+    config: Config,
+}
+
+impl ConfigBuilder {
+    pub fn auto_ack_tx(mut self, enable: bool) -> Self {
+        self.config.auto_ack_tx = enable;
+        self
+    }
+
+    pub fn auto_ack_rx(mut self, enable: bool) -> Self {
+        self.config.auto_ack_rx = enable;
+        self
+    }
+
+    pub fn enhance_ack_tx(mut self, enable: bool) -> Self {
+        self.config.enhance_ack_tx = enable;
+        self
+    }
+
+    pub fn promiscuous(mut self, enable: bool) -> Self {
+        self.config.promiscuous = enable;
+        self
+    }
+
+    pub fn coordinator(mut self, enable: bool) -> Self {
+        self.config.coordinator = enable;
+        self
+    }
+
+    pub fn rx_when_idle(mut self, enable: bool) -> Self {
+        self.config.rx_when_idle = enable;
+        self
+    }
+
+    /// Transmit power in dBm, from -32 to 13
+    ///
+    /// The radio steps the power by 3 dB, see [`TxPower::from_dbm`].
+    pub fn txpower(mut self, power: i8) -> Self {
+        self.config.txpower = power;
+        self
+    }
+
+    pub fn channel(mut self, channel: Channel) -> Self {
+        self.config.channel = channel;
+        self
+    }
+
+    /// Energy detect threshold of the CCA in dBm, from -120 to 0
+    pub fn cca_threshold(mut self, cca_threshold: i8) -> Self {
+        self.config.cca_threshold = cca_threshold;
+        self
+    }
+
+    pub fn cca_mode(mut self, mode: CcaMode) -> Self {
+        self.config.cca_mode = mode;
+        self
+    }
+
+    pub fn pan_id(mut self, pan_id: u16) -> Self {
+        self.config.pan_id = Some(pan_id);
+        self
+    }
+
+    /// Short address, which must not be the broadcast address 0xffff
+    pub fn short_addr(mut self, address: u16) -> Self {
+        self.config.short_addr = Some(address);
+        self
+    }
+
+    pub fn ext_addr(mut self, address: u64) -> Self {
+        self.config.ext_addr = Some(address);
+        self
+    }
+
+    pub fn rx_queue_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.config.rx_queue_policy = policy;
+        self
+    }
+
+    pub fn pending_mode(mut self, mode: PendingMode) -> Self {
+        self.config.pending_mode = mode;
+        self
+    }
+
+    pub fn rx_bad_frames(mut self, enable: bool) -> Self {
+        self.config.rx_bad_frames = enable;
+        self
+    }
+
+    /// The configuration, [`Error::BadInput`] if the transmit power, the CCA
+    /// threshold or the short address is out of range
+    pub fn build(self) -> Result<Config, Error> {
+        check_tx_power(self.config.txpower)?;
+        check_cca_threshold(self.config.cca_threshold)?;
+        if self.config.short_addr == Some(0xffff) {
+            return Err(Error::BadInput);
+        }
+
+        Ok(self.config)
+    }
+}
+
 /// IEEE 802.15.4 driver
 #[derive(Debug)]
 pub struct Ieee802154<'a> {
     _align: u32,
     transmit_buffer: [u8; FRAME_SIZE],
+    beacon_response: Option<BeaconResponse>,
     _phantom1: PhantomData<&'a ()>,
     //_phantom2:PhantomData< &'b ()>,
 }
@@ -112,12 +286,16 @@ impl<'a> Ieee802154<'a> {
         Self {
             _align: 0,
             transmit_buffer: [0u8; FRAME_SIZE],
-            _phantom1: PhantomData::default(),
+            beacon_response: None,
+            _phantom1: PhantomData,
             //_phantom2: PhantomData::default(),
         }
     }
 
     /// Set the configuration for the driver
+    ///
+    /// The configuration is not validated, out of range transmit powers are
+    /// clamped. Use [`Config::builder`] to catch invalid settings.
     pub fn set_config(&mut self, cfg: Config) {
         set_auto_ack_tx(cfg.auto_ack_tx);
         set_auto_ack_rx(cfg.auto_ack_rx);
@@ -129,6 +307,9 @@ impl<'a> Ieee802154<'a> {
         set_channel(cfg.channel);
         set_cca_theshold(cfg.cca_threshold);
         set_cca_mode(cfg.cca_mode);
+        set_rx_queue_policy(cfg.rx_queue_policy);
+        set_pending_mode(cfg.pending_mode);
+        set_rx_bad_frames(cfg.rx_bad_frames);
 
         if let Some(pan_id) = cfg.pan_id {
             set_panid(0, pan_id);
@@ -144,71 +325,103 @@ impl<'a> Ieee802154<'a> {
 
             set_extended_address(0, address);
         }
+
+        #[cfg(feature = "embassy-net-driver")]
+        net_driver::wake();
+    }
+
+    /// Set the configuration for the driver and apply it to the radio
+    ///
+    /// [`Ieee802154::set_config`] only takes effect with the next transmission
+    /// or reception, which stops the ongoing one. Here the configuration is
+    /// applied right away if the radio is idle or receiving, restarting the
+    /// reception, and otherwise once the ongoing transmission, ACK, energy
+    /// detection or reception of a frame is done. Received frames stay queued
+    /// in either case.
+    ///
+    /// A [`Reconfiguration::Deferred`] configuration took effect once
+    /// [`Ieee802154::reconfigure_pending`] returns `false`.
+    pub fn reconfigure(&mut self, cfg: Config) -> Reconfiguration {
+        self.set_config(cfg);
+        ieee802154_reconfigure()
+    }
+
+    /// Whether a configuration deferred by [`Ieee802154::reconfigure`] is yet
+    /// to be applied
+    pub fn reconfigure_pending(&self) -> bool {
+        ieee802154_reconfigure_pending()
+    }
+
+    /// The configuration of the driver
+    ///
+    /// Unlike the [`Config`] given to [`Ieee802154::set_config`], the
+    /// addresses and PAN ID are always set, to the values used by the address
+    /// filter. The settings are applied to the radio with the next
+    /// transmission or reception.
+    pub fn config(&self) -> Config {
+        get_config()
+    }
+
+    /// The transmit power used by the radio, which is the configured power
+    /// clamped to the range of the radio and rounded down to its 3 dB steps
+    pub fn effective_tx_power(&self) -> TxPower {
+        TxPower::from_dbm(get_tx_power())
     }
 
     /// Start receiving frames
     pub fn start_receive(&mut self) {
         ieee802154_receive();
+
+        #[cfg(feature = "embassy-net-driver")]
+        net_driver::wake();
     }
 
     /// Return the raw data of a received frame
     pub fn get_raw_received(&mut self) -> Option<RawReceived> {
-        ieee802154_poll()
+        self.get_received_token().map(|token| RawReceived {
+            data: *token.raw(),
+            channel: token.channel(),
+            fault: token.fault(),
+        })
+    }
+
+    /// Get a received frame without copying it out of the receive buffer, if
+    /// available
+    pub fn get_received_token(&mut self) -> Option<RxToken<'_>> {
+        ieee802154_poll().map(RxToken::new)
     }
 
     /// Get a received frame, if available
     pub fn get_received(&mut self) -> Option<Result<ReceivedFrame, Error>> {
-        if let Some(raw) = ieee802154_poll() {
-            let maybe_decoded =
-                mac::Frame::try_read(&raw.data[1..][..raw.data[0] as usize], FooterMode::Explicit);
-
-            let result = match maybe_decoded {
-                Ok((decoded, _)) => {
-                    let rssi = raw.data[raw.data[0] as usize - 1] as i8; // crc is not written to rx buffer
-
-                    Ok(ReceivedFrame {
-                        frame: Frame {
-                            header: decoded.header,
-                            content: decoded.content,
-                            payload: Vec::from_slice(decoded.payload).unwrap(),
-                            footer: decoded.footer,
-                        },
-                        channel: raw.channel,
-                        rssi,
-                        lqi: rssi_to_lqi(rssi),
-                    })
-                }
-                Err(err) => Err(err.into()),
-            };
-
-            Some(result)
-        } else {
-            None
+        let token = self.get_received_token()?;
+        let result = Frame::decode(token.data()).map(|frame| ReceivedFrame {
+            frame,
+            channel: token.channel(),
+            rssi: token.rssi(),
+            lqi: token.lqi(),
+            hw_lqi: token.hw_lqi(),
+            freq_offset: token.freq_offset(),
+            interfaces: token.interfaces(),
+            fault: token.fault(),
+        });
+        drop(token);
+
+        if let Ok(received) = &result {
+            link_metrics::record(received);
+        }
+
+        // the beacon is sent by `transmit_beacon_response`, the transmit
+        // buffer may still be in use
+        if let (Ok(received), Some(response)) = (&result, &mut self.beacon_response) {
+            response.handle_request(received);
         }
+
+        Some(result)
     }
 
     /// Transmit a frame
     pub fn transmit(&mut self, frame: &Frame) -> Result<(), Error> {
-        let frm = mac::Frame {
-            header: frame.header,
-            content: frame.content,
-            payload: &frame.payload,
-            footer: frame.footer,
-        };
-
-        let mut offset = 1usize;
-        self.transmit_buffer
-            .write_with(
-                &mut offset,
-                frm,
-                &mut FrameSerDesContext::no_security(FooterMode::Explicit),
-            )
-            .unwrap();
-        self.transmit_buffer[0] = (offset - 1) as u8;
-
-        ieee802154_transmit(self.transmit_buffer.as_ptr() as *const u8, false); // what about CCA?
-
-        Ok(())
+        transmit_frame(&mut self.transmit_buffer, frame)
     }
 
     /// Transmit a raw frame
@@ -216,15 +429,158 @@ impl<'a> Ieee802154<'a> {
         self.transmit_buffer[1..][..frame.len()].copy_from_slice(frame);
         self.transmit_buffer[0] = frame.len() as u8;
 
-        ieee802154_transmit(self.transmit_buffer.as_ptr() as *const u8, false); // what about CCA?
+        ieee802154_transmit(self.transmit_buffer.as_ptr(), false); // what about CCA?
 
         Ok(())
     }
 
+    /// Answer the enhanced beacon requests returned by
+    /// [`Ieee802154::get_received`] with the beacon of `response`, as a
+    /// coordinator does, or stop answering them with `None`
+    ///
+    /// The beacon is sent by [`Ieee802154::transmit_beacon_response`].
+    pub fn set_beacon_response(&mut self, response: Option<BeaconResponse>) {
+        self.beacon_response = response;
+    }
+
+    /// Transmit the beacon answering the enhanced beacon requests returned by
+    /// [`Ieee802154::get_received`], if one was requested
+    ///
+    /// Returns whether the beacon was transmitted, or [`Error::Incomplete`]
+    /// while the radio is transmitting, in which case the beacon stays
+    /// requested.
+    pub fn transmit_beacon_response(&mut self) -> Result<bool, Error> {
+        let Some(response) = &mut self.beacon_response else {
+            return Ok(false);
+        };
+        if !response.is_requested() {
+            return Ok(false);
+        }
+        if ieee802154_tx_busy() {
+            return Err(Error::Incomplete);
+        }
+
+        let Some(beacon) = response.take() else {
+            return Ok(false);
+        };
+        transmit_frame(&mut self.transmit_buffer, beacon)?;
+
+        Ok(true)
+    }
+
+    /// The response to enhanced beacon requests, to update it
+    pub fn beacon_response_mut(&mut self) -> Option<&mut BeaconResponse> {
+        self.beacon_response.as_mut()
+    }
+
+    /// Add a short address to the table of addresses frames are pending for
+    ///
+    /// Unless [`Config::pending_mode`] is [`PendingMode::Disable`], the frame
+    /// pending bit of the ACK sent for a frame is set if its source address
+    /// is in the table. Returns [`Error::Full`] if the table is full.
+    pub fn add_pending_short_address(&mut self, address: u16) -> Result<(), Error> {
+        pending::add_short_address(address)
+    }
+
+    /// Add an extended address to the table of addresses frames are pending
+    /// for, see [`Ieee802154::add_pending_short_address`]
+    pub fn add_pending_ext_address(&mut self, address: u64) -> Result<(), Error> {
+        pending::add_ext_address(address)
+    }
+
+    /// Remove a short address from the table of addresses frames are pending
+    /// for, returns `false` if it was not in the table
+    pub fn remove_pending_short_address(&mut self, address: u16) -> bool {
+        pending::remove_short_address(address)
+    }
+
+    /// Remove an extended address from the table of addresses frames are
+    /// pending for, returns `false` if it was not in the table
+    pub fn remove_pending_ext_address(&mut self, address: u64) -> bool {
+        pending::remove_ext_address(address)
+    }
+
+    /// Remove all short addresses from the table of addresses frames are
+    /// pending for
+    pub fn clear_pending_short_addresses(&mut self) {
+        pending::clear_short_addresses();
+    }
+
+    /// Remove all extended addresses from the table of addresses frames are
+    /// pending for
+    pub fn clear_pending_ext_addresses(&mut self) {
+        pending::clear_ext_addresses();
+    }
+
+    /// Configure a forward tracking series of link metrics for `neighbor`,
+    /// aggregating `metrics` over the frames of `frame_types` received from
+    /// it, or remove the series if `frame_types` is empty
+    ///
+    /// Returns [`Error::BadInput`] if the series is already configured, or
+    /// if it is removed but wasn't configured, and [`Error::Full`] if the
+    /// table of series is full.
+    pub fn configure_link_metrics_series(
+        &mut self,
+        neighbor: Neighbor,
+        series_id: u8,
+        frame_types: FrameTypes,
+        metrics: Metrics,
+    ) -> Result<(), Error> {
+        link_metrics::configure_series(neighbor, series_id, frame_types, metrics)
+    }
+
+    /// The metrics configured for a forward tracking series of `neighbor`,
+    /// and the values they have aggregated, if the series is configured
+    pub fn link_metrics_series(
+        &self,
+        neighbor: Neighbor,
+        series_id: u8,
+    ) -> Option<(Metrics, Values)> {
+        link_metrics::series_values(neighbor, series_id)
+    }
+
+    /// Report `metrics` in the enhanced ACKs sent to `neighbor`, or stop
+    /// reporting them if `metrics` is empty
+    ///
+    /// Only the LQI, the link margin and the RSSI can be probed, and at most
+    /// two of them, [`Error::BadInput`] is returned otherwise. Returns
+    /// [`Error::Full`] if the table of probed neighbors is full.
+    pub fn configure_link_metrics_probing(
+        &mut self,
+        neighbor: Neighbor,
+        metrics: Metrics,
+    ) -> Result<(), Error> {
+        link_metrics::configure_probing(neighbor, metrics)
+    }
+
+    /// Remove the series and the probing configured for `neighbor`, e.g.
+    /// when it is no longer a neighbor
+    pub fn remove_link_metrics_neighbor(&mut self, neighbor: Neighbor) {
+        link_metrics::remove_neighbor(neighbor);
+    }
+
+    /// Aggregate the metrics of `received`, which carries an MLE Link Probe
+    /// message, into the series tracking link probes
+    pub fn record_link_probe(&mut self, received: &ReceivedFrame) {
+        link_metrics::record_link_probe(received);
+    }
+
+    /// Return a snapshot of the radio statistics
+    pub fn stats(&self) -> RadioStats {
+        stats_get()
+    }
+
+    /// Reset all radio statistics, including the hardware error counters
+    pub fn reset_stats(&mut self) {
+        stats_reset();
+    }
+
     pub fn set_tx_done_callback(&mut self, callback: &'a mut (dyn FnMut() + Send)) {
         critical_section::with(|cs| {
             let mut tx_done_callback = TX_DONE_CALLBACK.borrow_ref_mut(cs);
-            tx_done_callback.replace(unsafe { core::mem::transmute(callback) });
+            tx_done_callback.replace(unsafe {
+                core::mem::transmute::<&'a mut (dyn FnMut() + Send), Callback>(callback)
+            });
         });
     }
 
@@ -238,7 +594,9 @@ impl<'a> Ieee802154<'a> {
     pub fn set_rx_available_callback(&mut self, callback: &'a mut (dyn FnMut() + Send)) {
         critical_section::with(|cs| {
             let mut rx_available_callback = RX_AVAILABLE_CALLBACK.borrow_ref_mut(cs);
-            rx_available_callback.replace(unsafe { core::mem::transmute(callback) });
+            rx_available_callback.replace(unsafe {
+                core::mem::transmute::<&'a mut (dyn FnMut() + Send), Callback>(callback)
+            });
         });
     }
 
@@ -266,7 +624,7 @@ impl<'a> Ieee802154<'a> {
     pub fn set_rx_available_callback_fn(&mut self, callback: fn()) {
         critical_section::with(|cs| {
             let mut rx_available_callback_fn = RX_AVAILABLE_CALLBACK_FN.borrow_ref_mut(cs);
-            rx_available_callback_fn.replace(unsafe { core::mem::transmute(callback) });
+            rx_available_callback_fn.replace(callback);
         });
     }
 
@@ -287,6 +645,15 @@ impl<'a> Drop for Ieee802154<'a> {
     }
 }
 
+fn transmit_frame(transmit_buffer: &mut [u8; FRAME_SIZE], frame: &Frame) -> Result<(), Error> {
+    let len = frame.encode(&mut transmit_buffer[1..])?;
+    transmit_buffer[0] = len as u8;
+
+    ieee802154_transmit(transmit_buffer.as_ptr(), false); // what about CCA?
+
+    Ok(())
+}
+
 pub fn rssi_to_lqi(rssi: i8) -> u8 {
     if rssi < -80 {
         0
@@ -298,15 +665,21 @@ pub fn rssi_to_lqi(rssi: i8) -> u8 {
     }
 }
 
-static TX_DONE_CALLBACK: Mutex<RefCell<Option<&'static mut (dyn FnMut() + Send)>>> =
-    Mutex::new(RefCell::new(None));
+// Callback registered by the driver, which unregisters it when dropped
+type Callback = &'static mut (dyn FnMut() + Send);
+type CallbackSlot<T> = Mutex<RefCell<Option<T>>>;
 
-static RX_AVAILABLE_CALLBACK: Mutex<RefCell<Option<&'static mut (dyn FnMut() + Send)>>> =
-    Mutex::new(RefCell::new(None));
+#[cfg_attr(feature = "sim", thread_local)]
+static TX_DONE_CALLBACK: CallbackSlot<Callback> = Mutex::new(RefCell::new(None));
 
-static TX_DONE_CALLBACK_FN: Mutex<RefCell<Option<fn()>>> = Mutex::new(RefCell::new(None));
+#[cfg_attr(feature = "sim", thread_local)]
+static RX_AVAILABLE_CALLBACK: CallbackSlot<Callback> = Mutex::new(RefCell::new(None));
 
-static RX_AVAILABLE_CALLBACK_FN: Mutex<RefCell<Option<fn()>>> = Mutex::new(RefCell::new(None));
+#[cfg_attr(feature = "sim", thread_local)]
+static TX_DONE_CALLBACK_FN: CallbackSlot<fn()> = Mutex::new(RefCell::new(None));
+
+#[cfg_attr(feature = "sim", thread_local)]
+static RX_AVAILABLE_CALLBACK_FN: CallbackSlot<fn()> = Mutex::new(RefCell::new(None));
 
 fn tx_done() {
     log::trace!("tx_done callback");
@@ -326,6 +699,9 @@ fn tx_done() {
             tx_done_callback_fn();
         }
     });
+
+    #[cfg(feature = "embassy-net-driver")]
+    net_driver::wake();
 }
 
 fn rx_available() {
@@ -346,4 +722,51 @@ fn rx_available() {
             rx_available_callback_fn();
         }
     });
+
+    #[cfg(feature = "embassy-net-driver")]
+    net_driver::wake();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builder_rejects_out_of_range_settings() {
+        let config = Config::builder()
+            .txpower(13)
+            .cca_threshold(-120)
+            .short_addr(0xfffe)
+            .build()
+            .unwrap();
+        assert_eq!(
+            (config.txpower, config.cca_threshold, config.short_addr),
+            (13, -120, Some(0xfffe))
+        );
+
+        for builder in [
+            Config::builder().txpower(14),
+            Config::builder().txpower(-33),
+            Config::builder().cca_threshold(1),
+            Config::builder().cca_threshold(-121),
+            Config::builder().short_addr(0xffff),
+        ] {
+            assert!(matches!(builder.build(), Err(Error::BadInput)));
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    #[test]
+    fn effective_tx_power_is_rounded_down() {
+        let _lock = hal::mock::test_lock();
+        let mut radio = Ieee802154::new(hal::IEEE802154, &mut hal::RADIO_CLK);
+
+        for (txpower, dbm, step) in [(10, 10, 14), (12, 10, 14), (0, -2, 10), (20, 13, 15)] {
+            radio.set_config(Config {
+                txpower,
+                ..Default::default()
+            });
+            assert_eq!(radio.effective_tx_power(), TxPower { dbm, step });
+        }
+    }
 }
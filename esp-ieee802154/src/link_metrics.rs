@@ -0,0 +1,648 @@
+//! Link metrics of Thread 1.2
+//!
+//! A Thread device is the subject of the link metrics its neighbors, the
+//! initiators, ask for in MLE Link Metrics Management Requests. An initiator
+//! either configures a forward tracking series, whose metrics are aggregated
+//! over the frames received from it until it queries them, or enhanced-ACK
+//! based probing, whose metrics are reported in the [`HeaderIe::LinkMetrics`]
+//! of every enhanced ACK sent to it.
+//!
+//! The MLE messages are handled by the Thread stack, which hands the
+//! configuration to the `configure_link_metrics_*` methods of
+//! [`Ieee802154`](crate::Ieee802154).
+//!
+//! [`HeaderIe::LinkMetrics`]: crate::ie::HeaderIe::LinkMetrics
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use heapless::Vec;
+use ieee802154::mac::{command::Command, Address, FrameContent, FrameType};
+
+use crate::{Error, ReceivedFrame};
+
+const SERIES_TABLE_SIZE: usize = 16;
+const PROBING_TABLE_SIZE: usize = 8;
+
+/// Most metrics reported in an enhanced ACK
+pub(crate) const MAX_PROBING_METRICS: usize = 2;
+
+// Noise floor the link margin is relative to, the typical receive sensitivity
+// of the ESP32-C6
+const NOISE_FLOOR: i8 = -104;
+
+// Link margins from 0 to 130 dB and RSSIs from -130 to 0 dBm are reported
+// scaled to 0-255
+const SCALED_RANGE: i32 = 130;
+
+// Averages are exponential moving averages over about this many frames
+const AVERAGE_WINDOW: u16 = 8;
+// Fractional precision of averages
+const AVERAGE_SCALE: i32 = 16;
+
+// Type ID flags, the length flag is set for 4-byte values
+const TYPE_ID_LENGTH: u8 = 0x40;
+const TYPE_ID_TYPE_SHIFT: u8 = 3;
+const TYPE_ID_TYPE_COUNT: u8 = 0;
+const TYPE_ID_TYPE_AVERAGE: u8 = 1;
+
+#[cfg_attr(feature = "sim", thread_local)]
+static SERIES: Mutex<RefCell<Vec<Series, SERIES_TABLE_SIZE>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+#[cfg_attr(feature = "sim", thread_local)]
+static PROBING: Mutex<RefCell<Vec<(Neighbor, Metrics), PROBING_TABLE_SIZE>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+/// Link metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    /// Number of frames received
+    PduCount,
+    /// Link Quality Indication, as in [`ReceivedFrame::lqi`]
+    Lqi,
+    /// RSSI above the noise floor, in dB
+    LinkMargin,
+    /// Received Signal Strength Indicator, in dBm
+    Rssi,
+}
+
+impl Metric {
+    /// The type ID of the metric in MLE messages, the PDU count is counted
+    /// and the others are averaged
+    pub fn type_id(self) -> u8 {
+        match self {
+            Metric::PduCount => TYPE_ID_LENGTH | TYPE_ID_TYPE_COUNT << TYPE_ID_TYPE_SHIFT,
+            Metric::Lqi => TYPE_ID_TYPE_AVERAGE << TYPE_ID_TYPE_SHIFT | 1,
+            Metric::LinkMargin => TYPE_ID_TYPE_AVERAGE << TYPE_ID_TYPE_SHIFT | 2,
+            Metric::Rssi => TYPE_ID_TYPE_AVERAGE << TYPE_ID_TYPE_SHIFT | 3,
+        }
+    }
+
+    /// The metric of a type ID, if it is one of [`Metric::type_id`]
+    pub fn from_type_id(type_id: u8) -> Option<Self> {
+        [
+            Metric::PduCount,
+            Metric::Lqi,
+            Metric::LinkMargin,
+            Metric::Rssi,
+        ]
+        .into_iter()
+        .find(|metric| metric.type_id() == type_id)
+    }
+}
+
+/// Set of link metrics
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Metrics {
+    pub pdu_count: bool,
+    pub lqi: bool,
+    pub link_margin: bool,
+    pub rssi: bool,
+}
+
+impl Metrics {
+    /// The metrics of a list of type IDs, [`Error::BadInput`] if one of them
+    /// isn't known
+    pub fn from_type_ids(type_ids: &[u8]) -> Result<Self, Error> {
+        let mut metrics = Self::default();
+        for type_id in type_ids {
+            match Metric::from_type_id(*type_id).ok_or(Error::BadInput)? {
+                Metric::PduCount => metrics.pdu_count = true,
+                Metric::Lqi => metrics.lqi = true,
+                Metric::LinkMargin => metrics.link_margin = true,
+                Metric::Rssi => metrics.rssi = true,
+            }
+        }
+
+        Ok(metrics)
+    }
+
+    /// The metrics of the set, in the order they are reported in
+    pub fn iter(&self) -> impl Iterator<Item = Metric> {
+        [
+            (self.pdu_count, Metric::PduCount),
+            (self.lqi, Metric::Lqi),
+            (self.link_margin, Metric::LinkMargin),
+            (self.rssi, Metric::Rssi),
+        ]
+        .into_iter()
+        .filter_map(|(set, metric)| set.then_some(metric))
+    }
+
+    /// Whether the set is empty
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+}
+
+/// Types of frames a forward tracking series aggregates the metrics of
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FrameTypes {
+    /// MLE Link Probe messages, which are reported by the Thread stack via
+    /// [`Ieee802154::record_link_probe`](crate::Ieee802154::record_link_probe)
+    pub link_probe: bool,
+    /// MAC data frames
+    pub mac_data: bool,
+    /// MAC data request commands
+    pub mac_data_request: bool,
+    /// ACKs received for frames sent to the neighbor
+    pub mac_ack: bool,
+}
+
+impl FrameTypes {
+    /// The frame types of the Forward Series Flags of MLE messages
+    pub fn from_bits(bits: u8) -> Self {
+        Self {
+            link_probe: bits & 0x01 != 0,
+            mac_data: bits & 0x02 != 0,
+            mac_data_request: bits & 0x04 != 0,
+            mac_ack: bits & 0x08 != 0,
+        }
+    }
+
+    /// The Forward Series Flags of MLE messages
+    pub fn bits(&self) -> u8 {
+        self.link_probe as u8
+            | (self.mac_data as u8) << 1
+            | (self.mac_data_request as u8) << 2
+            | (self.mac_ack as u8) << 3
+    }
+
+    /// Whether no frame type is set
+    pub fn is_empty(&self) -> bool {
+        self.bits() == 0
+    }
+}
+
+/// Neighbor the link metrics are kept for, frames from it are recognized by
+/// either address
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Neighbor {
+    pub short_address: u16,
+    /// In the same form as the extended addresses of the pending table
+    pub ext_address: u64,
+}
+
+impl Neighbor {
+    fn is(&self, address: Option<Address>) -> bool {
+        match address {
+            Some(Address::Short(_, address)) => address.0 == self.short_address,
+            Some(Address::Extended(_, address)) => address.0 == self.ext_address,
+            None => false,
+        }
+    }
+}
+
+/// Link metrics aggregated by a forward tracking series, or measured for a
+/// single frame
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Values {
+    pub pdu_count: u32,
+    pub lqi: u8,
+    /// In dB
+    pub link_margin: u8,
+    /// In dBm
+    pub rssi: i8,
+}
+
+impl Values {
+    fn new(pdu_count: u32, lqi: u8, rssi: i8) -> Self {
+        Self {
+            pdu_count,
+            lqi,
+            link_margin: (rssi as i32 - NOISE_FLOOR as i32).clamp(0, u8::MAX as i32) as u8,
+            rssi,
+        }
+    }
+
+    /// The value of `metric` as reported in MLE messages and enhanced ACKs,
+    /// with the link margin and the RSSI scaled to 0-255
+    pub fn reported(&self, metric: Metric) -> u32 {
+        match metric {
+            Metric::PduCount => self.pdu_count,
+            Metric::Lqi => self.lqi as u32,
+            Metric::LinkMargin => scale(self.link_margin as i32),
+            Metric::Rssi => scale(self.rssi as i32 + SCALED_RANGE),
+        }
+    }
+}
+
+fn scale(value: i32) -> u32 {
+    (value.clamp(0, SCALED_RANGE) * u8::MAX as i32 / SCALED_RANGE) as u32
+}
+
+/// Exponential moving average, which is the plain average of the first
+/// [`AVERAGE_WINDOW`] samples
+#[derive(Debug, Default, Clone, Copy)]
+struct Average {
+    value: i32,
+    count: u16,
+}
+
+impl Average {
+    fn add(&mut self, sample: i32) {
+        self.count = self.count.saturating_add(1);
+        let weight = self.count.min(AVERAGE_WINDOW) as i32;
+        self.value += (sample * AVERAGE_SCALE - self.value) / weight;
+    }
+
+    fn get(&self) -> i32 {
+        (self.value + AVERAGE_SCALE / 2).div_euclid(AVERAGE_SCALE)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Series {
+    neighbor: Neighbor,
+    id: u8,
+    frame_types: FrameTypes,
+    metrics: Metrics,
+    pdu_count: u32,
+    lqi: Average,
+    rssi: Average,
+}
+
+impl Series {
+    fn aggregate(&mut self, lqi: u8, rssi: i8) {
+        self.pdu_count = self.pdu_count.wrapping_add(1);
+        self.lqi.add(lqi as i32);
+        self.rssi.add(rssi as i32);
+    }
+
+    fn values(&self) -> Values {
+        Values::new(self.pdu_count, self.lqi.get() as u8, self.rssi.get() as i8)
+    }
+}
+
+pub(crate) fn configure_series(
+    neighbor: Neighbor,
+    id: u8,
+    frame_types: FrameTypes,
+    metrics: Metrics,
+) -> Result<(), Error> {
+    critical_section::with(|cs| {
+        let mut table = SERIES.borrow_ref_mut(cs);
+        let index = table
+            .iter()
+            .position(|series| series.neighbor == neighbor && series.id == id);
+
+        if frame_types.is_empty() {
+            // removing a series which isn't registered is an error
+            table.swap_remove(index.ok_or(Error::BadInput)?);
+            return Ok(());
+        }
+
+        // series IDs 0 and 255 are reserved for single probes and all series
+        if index.is_some() || id == 0 || id == u8::MAX || metrics.is_empty() {
+            return Err(Error::BadInput);
+        }
+
+        table
+            .push(Series {
+                neighbor,
+                id,
+                frame_types,
+                metrics,
+                pdu_count: 0,
+                lqi: Average::default(),
+                rssi: Average::default(),
+            })
+            .map_err(|_| Error::Full)
+    })
+}
+
+pub(crate) fn series_values(neighbor: Neighbor, id: u8) -> Option<(Metrics, Values)> {
+    critical_section::with(|cs| {
+        SERIES
+            .borrow_ref(cs)
+            .iter()
+            .find(|series| series.neighbor == neighbor && series.id == id)
+            .map(|series| (series.metrics, series.values()))
+    })
+}
+
+pub(crate) fn configure_probing(neighbor: Neighbor, metrics: Metrics) -> Result<(), Error> {
+    if metrics.pdu_count || metrics.iter().count() > MAX_PROBING_METRICS {
+        return Err(Error::BadInput);
+    }
+
+    critical_section::with(|cs| {
+        let mut table = PROBING.borrow_ref_mut(cs);
+        let index = table.iter().position(|(entry, _)| *entry == neighbor);
+
+        match (index, metrics.is_empty()) {
+            (Some(index), true) => {
+                table.swap_remove(index);
+                Ok(())
+            }
+            (Some(index), false) => {
+                table[index].1 = metrics;
+                Ok(())
+            }
+            (None, true) => Ok(()),
+            (None, false) => table.push((neighbor, metrics)).map_err(|_| Error::Full),
+        }
+    })
+}
+
+pub(crate) fn remove_neighbor(neighbor: Neighbor) {
+    critical_section::with(|cs| {
+        SERIES
+            .borrow_ref_mut(cs)
+            .retain(|series| series.neighbor != neighbor);
+        PROBING
+            .borrow_ref_mut(cs)
+            .retain(|(entry, _)| *entry != neighbor);
+    });
+}
+
+/// Aggregate the metrics of a received frame into the series of its sender
+/// tracking its frame type
+pub(crate) fn record(received: &ReceivedFrame) {
+    if received.fault.is_some() {
+        return;
+    }
+
+    let frame = &received.frame;
+    let tracked = |frame_types: FrameTypes| match (frame.header.frame_type, frame.content) {
+        (FrameType::Data, _) => frame_types.mac_data,
+        (_, FrameContent::Command(Command::DataRequest)) => frame_types.mac_data_request,
+        _ => false,
+    };
+
+    aggregate(frame.header.source, tracked, received.lqi, received.rssi);
+}
+
+/// Aggregate the metrics of a received MLE Link Probe message
+pub(crate) fn record_link_probe(received: &ReceivedFrame) {
+    let tracked = |frame_types: FrameTypes| frame_types.link_probe;
+    aggregate(
+        received.frame.header.source,
+        tracked,
+        received.lqi,
+        received.rssi,
+    );
+}
+
+/// Aggregate the metrics of the ACK received for a frame sent to
+/// `destination`
+pub(crate) fn record_ack(destination: Option<Address>, lqi: u8, rssi: i8) {
+    let tracked = |frame_types: FrameTypes| frame_types.mac_ack;
+    aggregate(destination, tracked, lqi, rssi);
+}
+
+fn aggregate(address: Option<Address>, tracked: impl Fn(FrameTypes) -> bool, lqi: u8, rssi: i8) {
+    critical_section::with(|cs| {
+        for series in SERIES.borrow_ref_mut(cs).iter_mut() {
+            if series.neighbor.is(address) && tracked(series.frame_types) {
+                series.aggregate(lqi, rssi);
+            }
+        }
+    });
+}
+
+/// Write the values of the Link Metrics IE of the enhanced ACK sent for a
+/// frame from `source`, returns their length, zero if no probing is
+/// configured for it
+pub(crate) fn probe(
+    source: Option<Address>,
+    lqi: u8,
+    rssi: i8,
+    values: &mut [u8; MAX_PROBING_METRICS],
+) -> usize {
+    let Some(metrics) = critical_section::with(|cs| {
+        PROBING
+            .borrow_ref(cs)
+            .iter()
+            .find(|(neighbor, _)| neighbor.is(source))
+            .map(|(_, metrics)| *metrics)
+    }) else {
+        return 0;
+    };
+
+    let measured = Values::new(1, lqi, rssi);
+    let mut len = 0;
+    for metric in metrics.iter() {
+        values[len] = measured.reported(metric) as u8;
+        len += 1;
+    }
+
+    len
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use ieee802154::mac::{PanId, ShortAddress};
+
+    use super::*;
+    use crate::{frame::FRAME_SIZE, hal::mock, ie::HeaderIe, Config, Frame, Ieee802154};
+
+    const NEIGHBOR: Neighbor = Neighbor {
+        short_address: 0x0002,
+        ext_address: 0x1122_3344_5566_7788,
+    };
+
+    // data frame from `source` to 0x0001 in PAN 0x1234, with the 2015 version
+    // and an ACK request if `enhanced`
+    fn data_frame(seq: u8, source: u8, enhanced: bool) -> [u8; 10] {
+        let (fcf, version) = if enhanced { (0x61, 0xa8) } else { (0x41, 0x88) };
+        [
+            fcf, version, seq, 0x34, 0x12, 0x01, 0x00, source, 0x00, 0xaa,
+        ]
+    }
+
+    fn radio(config: Config) -> Ieee802154<'static> {
+        critical_section::with(|cs| {
+            SERIES.borrow_ref_mut(cs).clear();
+            PROBING.borrow_ref_mut(cs).clear();
+        });
+
+        let mut radio = Ieee802154::new(crate::hal::IEEE802154, &mut crate::hal::RADIO_CLK);
+        radio.start_receive();
+        // a previous test may have left the radio receiving, with its own
+        // configuration
+        radio.reconfigure(config);
+        radio
+    }
+
+    fn receive(radio: &mut Ieee802154, frame: &[u8], rssi: i8) {
+        assert!(mock::receive(frame, rssi, 0));
+        assert!(radio.get_received().unwrap().is_ok());
+    }
+
+    fn all_metrics() -> Metrics {
+        Metrics {
+            pdu_count: true,
+            lqi: true,
+            link_margin: true,
+            rssi: true,
+        }
+    }
+
+    #[test]
+    fn series_aggregates_tracked_frames() {
+        let _lock = mock::test_lock();
+        let mut radio = radio(Config {
+            auto_ack_rx: true,
+            rx_when_idle: true,
+            ..Default::default()
+        });
+        let frame_types = FrameTypes {
+            mac_data: true,
+            mac_ack: true,
+            ..Default::default()
+        };
+        radio
+            .configure_link_metrics_series(NEIGHBOR, 1, frame_types, all_metrics())
+            .unwrap();
+
+        receive(&mut radio, &data_frame(1, 0x02, false), -50);
+        receive(&mut radio, &data_frame(2, 0x02, false), -54);
+        // frames from other neighbors and data requests aren't tracked
+        receive(&mut radio, &data_frame(3, 0x03, false), -20);
+        let data_request = [0x43, 0x88, 4, 0x34, 0x12, 0x01, 0x00, 0x02, 0x00, 0x04];
+        receive(&mut radio, &data_request, -20);
+
+        let (metrics, values) = radio.link_metrics_series(NEIGHBOR, 1).unwrap();
+        assert_eq!(metrics, all_metrics());
+        assert_eq!(
+            values,
+            Values {
+                pdu_count: 2,
+                lqi: 143,
+                link_margin: 52,
+                rssi: -52,
+            }
+        );
+        assert_eq!(values.reported(Metric::LinkMargin), 102);
+        assert_eq!(values.reported(Metric::Rssi), 153);
+
+        // the ACK of a frame sent to the neighbor
+        radio
+            .transmit_raw(&[
+                0x61, 0x88, 5, 0x34, 0x12, 0x02, 0x00, 0x01, 0x00, 0xaa, 0, 0,
+            ])
+            .unwrap();
+        mock::transmit_done();
+        assert!(mock::ack_received(&[0x02, 0x00, 5], -52));
+        let (_, values) = radio.link_metrics_series(NEIGHBOR, 1).unwrap();
+        assert_eq!((values.pdu_count, values.rssi), (3, -52));
+
+        // series IDs are unique per neighbor, and 0 and 255 are reserved
+        for id in [0, 1, u8::MAX] {
+            assert!(matches!(
+                radio.configure_link_metrics_series(NEIGHBOR, id, frame_types, all_metrics()),
+                Err(Error::BadInput)
+            ));
+        }
+
+        let none = FrameTypes::default();
+        radio
+            .configure_link_metrics_series(NEIGHBOR, 1, none, Metrics::default())
+            .unwrap();
+        assert!(radio.link_metrics_series(NEIGHBOR, 1).is_none());
+        assert!(matches!(
+            radio.configure_link_metrics_series(NEIGHBOR, 1, none, Metrics::default()),
+            Err(Error::BadInput)
+        ));
+    }
+
+    #[test]
+    fn probing_configuration() {
+        let _lock = mock::test_lock();
+        let mut radio = radio(Config::default());
+        let source = Some(Address::Short(PanId(0x1234), ShortAddress(0x0002)));
+        let mut values = [0; MAX_PROBING_METRICS];
+
+        // the PDU count can't be probed, nor more than two metrics
+        for metrics in [
+            Metrics {
+                pdu_count: true,
+                ..Default::default()
+            },
+            Metrics {
+                pdu_count: false,
+                ..all_metrics()
+            },
+        ] {
+            assert!(matches!(
+                radio.configure_link_metrics_probing(NEIGHBOR, metrics),
+                Err(Error::BadInput)
+            ));
+        }
+        assert_eq!(probe(source, 153, -50, &mut values), 0);
+
+        let lqi_and_rssi = Metrics {
+            lqi: true,
+            rssi: true,
+            ..Default::default()
+        };
+        radio
+            .configure_link_metrics_probing(NEIGHBOR, lqi_and_rssi)
+            .unwrap();
+        assert_eq!(probe(source, 153, -50, &mut values), 2);
+        assert_eq!(values, [153, 156]);
+        assert_eq!(probe(None, 153, -50, &mut values), 0);
+
+        // reconfiguring replaces the metrics, none removes the neighbor
+        let link_margin = Metrics {
+            link_margin: true,
+            ..Default::default()
+        };
+        radio
+            .configure_link_metrics_probing(NEIGHBOR, link_margin)
+            .unwrap();
+        assert_eq!(probe(source, 153, -50, &mut values), 1);
+        assert_eq!(values[0], 105);
+
+        radio
+            .configure_link_metrics_probing(NEIGHBOR, Metrics::default())
+            .unwrap();
+        assert_eq!(probe(source, 153, -50, &mut values), 0);
+    }
+
+    #[test]
+    fn enhanced_ack_carries_probed_metrics() {
+        let _lock = mock::test_lock();
+        let mut radio = radio(Config {
+            enhance_ack_tx: true,
+            rx_when_idle: true,
+            ..Default::default()
+        });
+        radio
+            .configure_link_metrics_probing(
+                NEIGHBOR,
+                Metrics {
+                    lqi: true,
+                    link_margin: true,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let enhanced_ack = |seq| {
+            let mut bytes = [0; FRAME_SIZE];
+            let transmitted = mock::transmitted().unwrap();
+            bytes[..transmitted.len()].copy_from_slice(&transmitted);
+            let ack = Frame::decode(&bytes[..transmitted.len() + 2]).unwrap();
+            assert_eq!(ack.header.frame_type, FrameType::Acknowledgement);
+            assert_eq!(ack.header.seq, seq);
+            mock::transmit_done();
+            ack
+        };
+
+        assert!(mock::receive(&data_frame(7, 0x02, true), -50, 0));
+        let ack = enhanced_ack(7);
+        assert_eq!(
+            ack.header.destination,
+            Some(Address::Short(PanId(0x1234), ShortAddress(0x0002)))
+        );
+        let (ies, _) = ack.ies().unwrap();
+        let ies: std::vec::Vec<_> = ies.header.iter().collect();
+        assert_eq!(ies, [HeaderIe::LinkMetrics(&[153, 105])]);
+
+        // neighbors without probing get an enhanced ACK without IEs
+        assert!(mock::receive(&data_frame(8, 0x03, true), -50, 0));
+        assert!(!enhanced_ack(8).header.ie_present);
+    }
+}
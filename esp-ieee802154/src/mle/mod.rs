@@ -0,0 +1,547 @@
+//! Thread Mesh Link Establishment (MLE), used with the `mle` feature
+//!
+//! MLE messages are carried in UDP datagrams sent from and to port
+//! [`PORT`], in IPv6 packets compressed and fragmented by
+//! [`sixlowpan`](crate::sixlowpan). [`udp_payload`] gets the MLE [`Frame`] out
+//! of a received packet, and [`encode_packet`] builds the packet of a frame to
+//! send. A [`Frame`] holds a [`Message`], a command followed by its [`Tlv`]s,
+//! which is encrypted with AES-CCM when the frame is secured. The encryption
+//! is left to the user, this module encodes and decodes the plaintext.
+
+mod tlv;
+
+use byte::{check_len, BytesExt, LE};
+
+pub use self::tlv::{
+    mode, scan_mask, AddressRegistration, AddressRegistrationEntry, Connectivity, LeaderData, Tlv,
+    TlvType, Tlvs,
+};
+use crate::{
+    sixlowpan::{IPV6_HEADER_LEN, UDP, UDP_HEADER_LEN},
+    Error,
+};
+
+/// UDP port of MLE
+pub const PORT: u16 = 19788;
+/// Hop limit of the IPv6 packets carrying MLE messages
+pub const HOP_LIMIT: u8 = 255;
+
+const SECURITY_SUITE_SECURED: u8 = 0;
+const SECURITY_SUITE_NONE: u8 = 255;
+
+const LEVEL_MASK: u8 = 0x07;
+const KEY_ID_MODE_SHIFT: u8 = 3;
+const KEY_ID_MODE_MASK: u8 = 0x03;
+
+/// MLE command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Command {
+    LinkRequest = 0,
+    LinkAccept = 1,
+    LinkAcceptAndRequest = 2,
+    LinkReject = 3,
+    Advertisement = 4,
+    Update = 5,
+    UpdateRequest = 6,
+    DataRequest = 7,
+    DataResponse = 8,
+    ParentRequest = 9,
+    ParentResponse = 10,
+    ChildIdRequest = 11,
+    ChildIdResponse = 12,
+    ChildUpdateRequest = 13,
+    ChildUpdateResponse = 14,
+    Announce = 15,
+    DiscoveryRequest = 16,
+    DiscoveryResponse = 17,
+    LinkMetricsManagementRequest = 18,
+    LinkMetricsManagementResponse = 19,
+    LinkProbe = 20,
+    TimeSync = 99,
+}
+
+impl TryFrom<u8> for Command {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use Command::*;
+
+        const COMMANDS: [Command; 22] = [
+            LinkRequest,
+            LinkAccept,
+            LinkAcceptAndRequest,
+            LinkReject,
+            Advertisement,
+            Update,
+            UpdateRequest,
+            DataRequest,
+            DataResponse,
+            ParentRequest,
+            ParentResponse,
+            ChildIdRequest,
+            ChildIdResponse,
+            ChildUpdateRequest,
+            ChildUpdateResponse,
+            Announce,
+            DiscoveryRequest,
+            DiscoveryResponse,
+            LinkMetricsManagementRequest,
+            LinkMetricsManagementResponse,
+            LinkProbe,
+            TimeSync,
+        ];
+
+        COMMANDS
+            .into_iter()
+            .find(|&command| command as u8 == value)
+            .ok_or(Error::BadInput)
+    }
+}
+
+/// Key securing a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyIdentifier {
+    /// Key determined by the sender and the receiver
+    Implicit,
+    Index(u8),
+    /// 4-byte key source and key index, used by Thread with the key sequence
+    /// as the source
+    Source4(u32, u8),
+    Source8(u64, u8),
+}
+
+/// Auxiliary security header of secured frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuxiliaryHeader {
+    /// Security level, 5 (ENC-MIC-32) in Thread
+    pub level: u8,
+    pub frame_counter: u32,
+    pub key_identifier: KeyIdentifier,
+}
+
+impl AuxiliaryHeader {
+    /// Length of the MIC of the security level
+    pub fn mic_len(&self) -> usize {
+        match self.level & 0x03 {
+            0 => 0,
+            1 => 4,
+            2 => 8,
+            _ => 16,
+        }
+    }
+
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        let control: u8 = bytes.read(offset)?;
+        let frame_counter = bytes.read_with(offset, LE)?;
+
+        let key_identifier = match (control >> KEY_ID_MODE_SHIFT) & KEY_ID_MODE_MASK {
+            0 => KeyIdentifier::Implicit,
+            1 => KeyIdentifier::Index(bytes.read(offset)?),
+            // the key source is big-endian
+            2 => KeyIdentifier::Source4(bytes.read_with(offset, byte::BE)?, bytes.read(offset)?),
+            _ => KeyIdentifier::Source8(bytes.read_with(offset, byte::BE)?, bytes.read(offset)?),
+        };
+
+        Ok(Self {
+            level: control & LEVEL_MASK,
+            frame_counter,
+            key_identifier,
+        })
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        let mode = match self.key_identifier {
+            KeyIdentifier::Implicit => 0,
+            KeyIdentifier::Index(_) => 1,
+            KeyIdentifier::Source4(..) => 2,
+            KeyIdentifier::Source8(..) => 3,
+        };
+        bytes.write(
+            offset,
+            (self.level & LEVEL_MASK) | (mode << KEY_ID_MODE_SHIFT),
+        )?;
+        bytes.write_with(offset, self.frame_counter, LE)?;
+
+        match self.key_identifier {
+            KeyIdentifier::Implicit => {}
+            KeyIdentifier::Index(index) => bytes.write(offset, index)?,
+            KeyIdentifier::Source4(source, index) => {
+                bytes.write_with(offset, source, byte::BE)?;
+                bytes.write(offset, index)?;
+            }
+            KeyIdentifier::Source8(source, index) => {
+                bytes.write_with(offset, source, byte::BE)?;
+                bytes.write(offset, index)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// MLE frame, the payload of the UDP datagram
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    /// `None` for frames which aren't secured, which are only discovery
+    /// requests and responses
+    pub security: Option<AuxiliaryHeader>,
+    /// The [`Message`], encrypted if the frame is secured
+    pub payload: &'a [u8],
+    /// The MIC of secured frames, empty otherwise
+    pub mic: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    /// Decode the payload of a UDP datagram sent to the MLE port
+    pub fn decode(bytes: &'a [u8]) -> Result<Self, Error> {
+        let offset = &mut 0;
+
+        match bytes.read::<u8>(offset)? {
+            SECURITY_SUITE_SECURED => {
+                let security = AuxiliaryHeader::read(bytes, offset)?;
+                let end = bytes
+                    .len()
+                    .checked_sub(security.mic_len())
+                    .filter(|&end| end >= *offset)
+                    .ok_or(Error::Incomplete)?;
+
+                Ok(Self {
+                    security: Some(security),
+                    payload: &bytes[*offset..end],
+                    mic: &bytes[end..],
+                })
+            }
+            SECURITY_SUITE_NONE => Ok(Self {
+                security: None,
+                payload: &bytes[*offset..],
+                mic: &[],
+            }),
+            _ => Err(Error::BadInput),
+        }
+    }
+
+    /// Encode the frame into `bytes`, returns the length of the frame
+    pub fn encode(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        let offset = &mut 0;
+
+        match self.security {
+            Some(security) => {
+                if self.mic.len() != security.mic_len() {
+                    return Err(Error::BadInput);
+                }
+                bytes.write(offset, SECURITY_SUITE_SECURED)?;
+                security.write(bytes, offset)?;
+            }
+            None => bytes.write(offset, SECURITY_SUITE_NONE)?,
+        }
+        bytes.write(offset, self.payload)?;
+        bytes.write(offset, self.mic)?;
+
+        Ok(*offset)
+    }
+}
+
+/// MLE message, the plaintext payload of a [`Frame`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Message<'a> {
+    pub command: Command,
+    pub tlvs: Tlvs<'a>,
+}
+
+impl<'a> Message<'a> {
+    /// Decode a message, checking its TLVs
+    pub fn decode(bytes: &'a [u8]) -> Result<Self, Error> {
+        let offset = &mut 0;
+        let command = Command::try_from(bytes.read::<u8>(offset)?)?;
+
+        Ok(Self {
+            command,
+            tlvs: Tlvs::decode(&bytes[*offset..])?,
+        })
+    }
+
+    /// Encode the message into `bytes`, returns its length
+    pub fn encode(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        let offset = &mut 0;
+        bytes.write(offset, self.command as u8)?;
+        self.tlvs.write(bytes, offset)?;
+
+        Ok(*offset)
+    }
+}
+
+/// Checksum of a UDP datagram in an IPv6 packet, 0 if it is valid
+fn udp_checksum(packet: &[u8]) -> u16 {
+    let udp = &packet[IPV6_HEADER_LEN..];
+    let mut sum = 0u32;
+    let mut add = |bytes: &[u8]| {
+        for chunk in bytes.chunks(2) {
+            sum += u16::from_be_bytes([chunk[0], *chunk.get(1).unwrap_or(&0)]) as u32;
+        }
+    };
+
+    // the pseudo-header of the addresses, the length and the next header
+    add(&packet[8..40]);
+    add(&(udp.len() as u32).to_be_bytes());
+    add(&[0, 0, 0, UDP]);
+    add(udp);
+
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// The payload of the UDP datagram of an IPv6 packet, if it was sent to the
+/// MLE port with a valid checksum
+pub fn udp_payload(packet: &[u8]) -> Option<&[u8]> {
+    if packet.len() < IPV6_HEADER_LEN + UDP_HEADER_LEN || packet[0] >> 4 != 6 {
+        return None;
+    }
+
+    let udp = &packet[IPV6_HEADER_LEN..];
+    let destination_port = u16::from_be_bytes([udp[2], udp[3]]);
+    let length = u16::from_be_bytes([udp[4], udp[5]]) as usize;
+
+    if packet[6] != UDP
+        || destination_port != PORT
+        || length != udp.len()
+        || udp_checksum(packet) != 0
+    {
+        return None;
+    }
+
+    Some(&udp[UDP_HEADER_LEN..])
+}
+
+/// Encode the IPv6 packet carrying `payload`, an encoded [`Frame`], from and
+/// to the MLE port into `bytes`, returns the length of the packet
+///
+/// The source is usually the link-local address of the sender, and the
+/// destination a link-local or the link-local all-nodes or all-routers
+/// multicast address.
+pub fn encode_packet(
+    source: &[u8; 16],
+    destination: &[u8; 16],
+    payload: &[u8],
+    bytes: &mut [u8],
+) -> Result<usize, Error> {
+    let len = IPV6_HEADER_LEN + UDP_HEADER_LEN + payload.len();
+    check_len(bytes, len)?;
+    let udp_len = u16::try_from(UDP_HEADER_LEN + payload.len()).map_err(|_| Error::BadInput)?;
+
+    let offset = &mut 0;
+    bytes.write_with(offset, 6u32 << 28, byte::BE)?;
+    bytes.write_with(offset, udp_len, byte::BE)?;
+    bytes.write(offset, UDP)?;
+    bytes.write(offset, HOP_LIMIT)?;
+    bytes.write(offset, &source[..])?;
+    bytes.write(offset, &destination[..])?;
+
+    bytes.write_with(offset, PORT, byte::BE)?;
+    bytes.write_with(offset, PORT, byte::BE)?;
+    bytes.write_with(offset, udp_len, byte::BE)?;
+    bytes.write_with(offset, 0u16, byte::BE)?;
+    bytes.write(offset, payload)?;
+
+    // a computed checksum of 0 is sent as 0xffff
+    let checksum = match udp_checksum(&bytes[..len]) {
+        0 => 0xffff,
+        checksum => checksum,
+    };
+    bytes[IPV6_HEADER_LEN + 6..][..2].copy_from_slice(&checksum.to_be_bytes());
+
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINK_LOCAL: [u8; 16] = [0xfe, 0x80, 0, 0, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8];
+    const ALL_NODES: [u8; 16] = [0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+
+    #[test]
+    fn frames_round_trip() {
+        let mut bytes = [0; 64];
+
+        for (key_identifier, control, header_len) in [
+            (KeyIdentifier::Implicit, 0x05, 5),
+            (KeyIdentifier::Index(1), 0x0d, 6),
+            (KeyIdentifier::Source4(3, 4), 0x15, 10),
+            (KeyIdentifier::Source8(0x0102_0304_0506_0708, 4), 0x1d, 14),
+        ] {
+            let frame = Frame {
+                security: Some(AuxiliaryHeader {
+                    level: 5,
+                    frame_counter: 7,
+                    key_identifier,
+                }),
+                payload: &[9; 20],
+                mic: &[1, 2, 3, 4],
+            };
+            let len = frame.encode(&mut bytes).unwrap();
+            assert_eq!(len, 1 + header_len + 20 + 4);
+            assert_eq!(bytes[..6], [0, control, 7, 0, 0, 0]);
+            assert_eq!(Frame::decode(&bytes[..len]).unwrap(), frame);
+        }
+
+        // with the key source of Thread, the key sequence
+        let frame = Frame {
+            security: Some(AuxiliaryHeader {
+                level: 5,
+                frame_counter: 7,
+                key_identifier: KeyIdentifier::Source4(3, 4),
+            }),
+            payload: &[],
+            mic: &[1, 2, 3, 4],
+        };
+        let len = frame.encode(&mut bytes).unwrap();
+        assert_eq!(
+            bytes[..len],
+            [0, 0x15, 7, 0, 0, 0, 0, 0, 0, 3, 4, 1, 2, 3, 4]
+        );
+
+        let frame = Frame {
+            security: None,
+            payload: &[Command::DiscoveryRequest as u8],
+            mic: &[],
+        };
+        let len = frame.encode(&mut bytes).unwrap();
+        assert_eq!(bytes[..len], [255, 16]);
+        assert_eq!(Frame::decode(&bytes[..len]).unwrap(), frame);
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        // truncated auxiliary headers and MICs
+        for bytes in [
+            &[][..],
+            &[0],
+            &[0, 0x05, 7, 0, 0],
+            &[0, 0x15, 7, 0, 0, 0, 0, 0, 0, 3],
+            &[0, 0x05, 7, 0, 0, 0, 1, 2, 3],
+            &[
+                0, 0x07, 7, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+            ],
+        ] {
+            assert!(
+                matches!(Frame::decode(bytes), Err(Error::Incomplete)),
+                "{bytes:02x?}"
+            );
+        }
+
+        // unknown security suite
+        assert!(matches!(Frame::decode(&[1, 16]), Err(Error::BadInput)));
+
+        // MIC not matching the security level
+        let frame = Frame {
+            security: Some(AuxiliaryHeader {
+                level: 6,
+                frame_counter: 0,
+                key_identifier: KeyIdentifier::Implicit,
+            }),
+            payload: &[],
+            mic: &[0; 4],
+        };
+        assert!(matches!(frame.encode(&mut [0; 64]), Err(Error::BadInput)));
+        assert!(matches!(frame.encode(&mut [0; 4]), Err(Error::BadInput)));
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let tlvs = [
+            Tlv::Mode(mode::RX_ON_WHEN_IDLE),
+            Tlv::Challenge(&[1, 2, 3, 4, 5, 6, 7, 8]),
+            Tlv::ScanMask(scan_mask::ROUTERS),
+            Tlv::Version(4),
+        ];
+        let message = Message {
+            command: Command::ParentRequest,
+            tlvs: Tlvs::from(&tlvs[..]),
+        };
+
+        let mut bytes = [0; 64];
+        let len = message.encode(&mut bytes).unwrap();
+        assert_eq!(bytes[..4], [9, 1, 1, 0x08]);
+        assert_eq!(len, 1 + 3 + 10 + 3 + 4);
+
+        let decoded = Message::decode(&bytes[..len]).unwrap();
+        assert_eq!(decoded, message);
+        assert_eq!(
+            decoded.tlvs.get(TlvType::ScanMask),
+            Some(Tlv::ScanMask(0x80))
+        );
+        assert_eq!(decoded.tlvs.get(TlvType::Timeout), None);
+
+        // re-encoding the received TLVs
+        let mut reencoded = [0; 64];
+        assert_eq!(decoded.encode(&mut reencoded).unwrap(), len);
+        assert_eq!(reencoded[..len], bytes[..len]);
+
+        // no room for the message
+        assert!(matches!(
+            message.encode(&mut bytes[..len - 1]),
+            Err(Error::Incomplete)
+        ));
+    }
+
+    #[test]
+    fn malformed_messages_are_rejected() {
+        let message = [Command::ChildIdRequest as u8, 1, 1, 0x0b, 18, 2, 0, 4];
+        assert!(Message::decode(&message).is_ok());
+
+        // every truncated TLV
+        for len in 2..message.len() {
+            if len != 4 {
+                assert!(Message::decode(&message[..len]).is_err(), "{len}");
+            }
+        }
+
+        assert!(matches!(Message::decode(&[]), Err(Error::Incomplete)));
+        // unknown command
+        assert!(matches!(
+            Message::decode(&[21, 1, 1, 0]),
+            Err(Error::BadInput)
+        ));
+        assert_eq!(Command::try_from(99).unwrap(), Command::TimeSync);
+    }
+
+    #[test]
+    fn packets_round_trip() {
+        let payload = [0, 0x15, 7, 0, 0, 0, 0, 0, 0, 3, 4, 9, 9, 9, 1, 2, 3, 4];
+        let mut packet = [0; 128];
+        let len = encode_packet(&LINK_LOCAL, &ALL_NODES, &payload, &mut packet).unwrap();
+        assert_eq!(len, IPV6_HEADER_LEN + UDP_HEADER_LEN + payload.len());
+        assert_eq!(packet[..8], [0x60, 0, 0, 0, 0, 26, UDP, HOP_LIMIT]);
+        assert_eq!(packet[40..46], [0x4d, 0x4c, 0x4d, 0x4c, 0, 26]);
+
+        assert_eq!(udp_payload(&packet[..len]), Some(&payload[..]));
+
+        // odd length
+        let len = encode_packet(&LINK_LOCAL, &ALL_NODES, &payload[..17], &mut packet).unwrap();
+        assert_eq!(udp_payload(&packet[..len]), Some(&payload[..17]));
+
+        assert!(matches!(
+            encode_packet(&LINK_LOCAL, &ALL_NODES, &payload, &mut [0; 60]),
+            Err(Error::Incomplete)
+        ));
+    }
+
+    #[test]
+    fn other_packets_are_ignored() {
+        let payload = [255, 16];
+        let mut packet = [0; 64];
+        let len = encode_packet(&LINK_LOCAL, &ALL_NODES, &payload, &mut packet).unwrap();
+
+        // corrupted, to another port, not UDP, not IPv6, or truncated
+        for (index, value) in [(len - 1, 17), (43, 0x4d), (6, 58), (0, 0x40), (45, 11)] {
+            let mut other = packet;
+            other[index] = value;
+            assert_eq!(udp_payload(&other[..len]), None, "{index}");
+        }
+        assert_eq!(udp_payload(&packet[..len - 1]), None);
+        assert_eq!(udp_payload(&packet[..IPV6_HEADER_LEN + 4]), None);
+    }
+}
@@ -0,0 +1,686 @@
+//! MLE TLVs
+
+use core::fmt;
+
+use byte::{BytesExt, BE};
+
+use crate::Error;
+
+/// Bits of the mode TLV
+pub mod mode {
+    /// The receiver is on when the device is idle
+    pub const RX_ON_WHEN_IDLE: u8 = 0x08;
+    /// The device is a full Thread device, which can become a router
+    pub const FULL_THREAD_DEVICE: u8 = 0x02;
+    /// The device wants the full network data, rather than the stable one
+    pub const FULL_NETWORK_DATA: u8 = 0x01;
+}
+
+/// Bits of the scan mask TLV
+pub mod scan_mask {
+    /// Routers answer the parent request
+    pub const ROUTERS: u8 = 0x80;
+    /// Router-eligible end devices answer the parent request
+    pub const END_DEVICES: u8 = 0x40;
+}
+
+/// Type of a TLV
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum TlvType {
+    SourceAddress = 0,
+    Mode = 1,
+    Timeout = 2,
+    Challenge = 3,
+    Response = 4,
+    LinkLayerFrameCounter = 5,
+    MleFrameCounter = 8,
+    Route64 = 9,
+    Address16 = 10,
+    LeaderData = 11,
+    NetworkData = 12,
+    TlvRequest = 13,
+    ScanMask = 14,
+    Connectivity = 15,
+    LinkMargin = 16,
+    Status = 17,
+    Version = 18,
+    AddressRegistration = 19,
+    Channel = 20,
+    PanId = 21,
+    ActiveTimestamp = 22,
+    PendingTimestamp = 23,
+    ActiveDataset = 24,
+    PendingDataset = 25,
+    Discovery = 26,
+    SupervisionInterval = 27,
+    CslSynchronizedTimeout = 85,
+}
+
+/// Leader data
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LeaderData {
+    pub partition_id: u32,
+    pub weighting: u8,
+    pub data_version: u8,
+    pub stable_data_version: u8,
+    pub leader_router_id: u8,
+}
+
+/// Connectivity of a router, sent in parent responses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Connectivity {
+    /// Parent priority in bits 6 and 7, as a signed value
+    pub flags: u8,
+    /// Number of neighbors with a link quality of 3
+    pub link_quality_3: u8,
+    pub link_quality_2: u8,
+    pub link_quality_1: u8,
+    pub leader_cost: u8,
+    pub id_sequence: u8,
+    pub active_routers: u8,
+    /// Buffer size and datagram count for sleepy end devices, absent if they
+    /// have their default values
+    pub sed: Option<(u16, u8)>,
+}
+
+/// Entry of an address registration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressRegistrationEntry {
+    /// Address whose prefix is the one of a 6LoWPAN context
+    Compressed {
+        context: u8,
+        iid: [u8; 8],
+    },
+    Full([u8; 16]),
+}
+
+const ADDRESS_COMPRESSED: u8 = 0x80;
+const ADDRESS_CONTEXT_MASK: u8 = 0x0f;
+
+impl AddressRegistrationEntry {
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        let control: u8 = bytes.read(offset)?;
+
+        if control & ADDRESS_COMPRESSED != 0 {
+            let mut iid = [0; 8];
+            iid.copy_from_slice(bytes.read_with(offset, byte::ctx::Bytes::Len(8))?);
+            Ok(Self::Compressed {
+                context: control & ADDRESS_CONTEXT_MASK,
+                iid,
+            })
+        } else {
+            let mut address = [0; 16];
+            address.copy_from_slice(bytes.read_with(offset, byte::ctx::Bytes::Len(16))?);
+            Ok(Self::Full(address))
+        }
+    }
+
+    /// Write the entry into `bytes`, returns its length
+    pub fn encode(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        let offset = &mut 0;
+
+        match self {
+            Self::Compressed { context, iid } => {
+                bytes.write(
+                    offset,
+                    ADDRESS_COMPRESSED | (context & ADDRESS_CONTEXT_MASK),
+                )?;
+                bytes.write(offset, &iid[..])?;
+            }
+            Self::Full(address) => {
+                bytes.write(offset, 0u8)?;
+                bytes.write(offset, &address[..])?;
+            }
+        }
+
+        Ok(*offset)
+    }
+}
+
+/// Addresses registered by a child
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct AddressRegistration<'a>(&'a [u8]);
+
+impl<'a> AddressRegistration<'a> {
+    /// Entries encoded in `bytes`, see [`AddressRegistrationEntry::encode`]
+    pub fn new(bytes: &'a [u8]) -> Result<Self, Error> {
+        let offset = &mut 0;
+        while *offset < bytes.len() {
+            AddressRegistrationEntry::read(bytes, offset)?;
+        }
+
+        Ok(Self(bytes))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = AddressRegistrationEntry> + 'a {
+        let bytes = self.0;
+        let mut offset = 0;
+
+        core::iter::from_fn(move || {
+            if offset == bytes.len() {
+                return None;
+            }
+            AddressRegistrationEntry::read(bytes, &mut offset).ok()
+        })
+    }
+}
+
+impl fmt::Debug for AddressRegistration<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// MLE TLV
+///
+/// Multi-byte values are big-endian.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tlv<'a> {
+    /// RLOC16 of the sender
+    SourceAddress(u16),
+    /// See [`mode`]
+    Mode(u8),
+    /// Child timeout, in seconds
+    Timeout(u32),
+    /// 4 to 8 random bytes, which the receiver returns in a response TLV
+    Challenge(&'a [u8]),
+    Response(&'a [u8]),
+    LinkLayerFrameCounter(u32),
+    MleFrameCounter(u32),
+    Route64 {
+        id_sequence: u8,
+        /// Bit `7 - i % 8` of byte `i / 8` is set if router `i` is allocated
+        router_mask: [u8; 8],
+        /// One byte of link quality and route cost for each allocated router
+        route_data: &'a [u8],
+    },
+    /// RLOC16 assigned to a child
+    Address16(u16),
+    LeaderData(LeaderData),
+    NetworkData(&'a [u8]),
+    /// Types of the TLVs requested in the response
+    TlvRequest(&'a [u8]),
+    /// See [`scan_mask`]
+    ScanMask(u8),
+    Connectivity(Connectivity),
+    LinkMargin(u8),
+    Status(u8),
+    Version(u16),
+    AddressRegistration(AddressRegistration<'a>),
+    Channel {
+        page: u8,
+        channel: u16,
+    },
+    PanId(u16),
+    /// Seconds in bits 16 to 63, ticks of 1/32768 s in bits 1 to 15 and the
+    /// authoritative flag in bit 0
+    ActiveTimestamp(u64),
+    PendingTimestamp(u64),
+    ActiveDataset(&'a [u8]),
+    PendingDataset(&'a [u8]),
+    /// Nested discovery TLVs of discovery requests and responses
+    Discovery(&'a [u8]),
+    /// In seconds
+    SupervisionInterval(u16),
+    /// In seconds
+    CslSynchronizedTimeout(u32),
+    /// TLV of another type
+    Other {
+        kind: u8,
+        value: &'a [u8],
+    },
+}
+
+impl<'a> Tlv<'a> {
+    /// The type of the TLV
+    pub fn kind(&self) -> u8 {
+        let kind = match self {
+            Tlv::SourceAddress(_) => TlvType::SourceAddress,
+            Tlv::Mode(_) => TlvType::Mode,
+            Tlv::Timeout(_) => TlvType::Timeout,
+            Tlv::Challenge(_) => TlvType::Challenge,
+            Tlv::Response(_) => TlvType::Response,
+            Tlv::LinkLayerFrameCounter(_) => TlvType::LinkLayerFrameCounter,
+            Tlv::MleFrameCounter(_) => TlvType::MleFrameCounter,
+            Tlv::Route64 { .. } => TlvType::Route64,
+            Tlv::Address16(_) => TlvType::Address16,
+            Tlv::LeaderData(_) => TlvType::LeaderData,
+            Tlv::NetworkData(_) => TlvType::NetworkData,
+            Tlv::TlvRequest(_) => TlvType::TlvRequest,
+            Tlv::ScanMask(_) => TlvType::ScanMask,
+            Tlv::Connectivity(_) => TlvType::Connectivity,
+            Tlv::LinkMargin(_) => TlvType::LinkMargin,
+            Tlv::Status(_) => TlvType::Status,
+            Tlv::Version(_) => TlvType::Version,
+            Tlv::AddressRegistration(_) => TlvType::AddressRegistration,
+            Tlv::Channel { .. } => TlvType::Channel,
+            Tlv::PanId(_) => TlvType::PanId,
+            Tlv::ActiveTimestamp(_) => TlvType::ActiveTimestamp,
+            Tlv::PendingTimestamp(_) => TlvType::PendingTimestamp,
+            Tlv::ActiveDataset(_) => TlvType::ActiveDataset,
+            Tlv::PendingDataset(_) => TlvType::PendingDataset,
+            Tlv::Discovery(_) => TlvType::Discovery,
+            Tlv::SupervisionInterval(_) => TlvType::SupervisionInterval,
+            Tlv::CslSynchronizedTimeout(_) => TlvType::CslSynchronizedTimeout,
+            Tlv::Other { kind, .. } => return *kind,
+        };
+
+        kind as u8
+    }
+
+    fn read(bytes: &'a [u8], offset: &mut usize) -> byte::Result<Self> {
+        let kind: u8 = bytes.read(offset)?;
+        let len: u8 = bytes.read(offset)?;
+        let value: &[u8] = bytes.read_with(offset, byte::ctx::Bytes::Len(len as usize))?;
+
+        let tlv = Self::decode_value(kind, value)?;
+
+        Ok(tlv)
+    }
+
+    fn decode_value(kind: u8, value: &'a [u8]) -> byte::Result<Self> {
+        let offset = &mut 0;
+
+        let tlv = match kind {
+            0 => Tlv::SourceAddress(value.read_with(offset, BE)?),
+            1 => Tlv::Mode(value.read(offset)?),
+            2 => Tlv::Timeout(value.read_with(offset, BE)?),
+            3 => Tlv::Challenge(value),
+            4 => Tlv::Response(value),
+            5 => Tlv::LinkLayerFrameCounter(value.read_with(offset, BE)?),
+            8 => Tlv::MleFrameCounter(value.read_with(offset, BE)?),
+            9 => {
+                let id_sequence = value.read(offset)?;
+                let mut router_mask = [0; 8];
+                router_mask.copy_from_slice(value.read_with(offset, byte::ctx::Bytes::Len(8))?);
+                let routers = router_mask.iter().map(|b| b.count_ones()).sum::<u32>();
+                let route_data =
+                    value.read_with(offset, byte::ctx::Bytes::Len(routers as usize))?;
+
+                Tlv::Route64 {
+                    id_sequence,
+                    router_mask,
+                    route_data,
+                }
+            }
+            10 => Tlv::Address16(value.read_with(offset, BE)?),
+            11 => Tlv::LeaderData(LeaderData {
+                partition_id: value.read_with(offset, BE)?,
+                weighting: value.read(offset)?,
+                data_version: value.read(offset)?,
+                stable_data_version: value.read(offset)?,
+                leader_router_id: value.read(offset)?,
+            }),
+            12 => Tlv::NetworkData(value),
+            13 => Tlv::TlvRequest(value),
+            14 => Tlv::ScanMask(value.read(offset)?),
+            15 => {
+                let mut connectivity = Connectivity {
+                    flags: value.read(offset)?,
+                    link_quality_3: value.read(offset)?,
+                    link_quality_2: value.read(offset)?,
+                    link_quality_1: value.read(offset)?,
+                    leader_cost: value.read(offset)?,
+                    id_sequence: value.read(offset)?,
+                    active_routers: value.read(offset)?,
+                    sed: None,
+                };
+                if *offset < value.len() {
+                    connectivity.sed = Some((value.read_with(offset, BE)?, value.read(offset)?));
+                }
+
+                Tlv::Connectivity(connectivity)
+            }
+            16 => Tlv::LinkMargin(value.read(offset)?),
+            17 => Tlv::Status(value.read(offset)?),
+            18 => Tlv::Version(value.read_with(offset, BE)?),
+            19 => Tlv::AddressRegistration(AddressRegistration::new(value).map_err(|_| {
+                byte::Error::BadInput {
+                    err: "Invalid address registration",
+                }
+            })?),
+            20 => Tlv::Channel {
+                page: value.read(offset)?,
+                channel: value.read_with(offset, BE)?,
+            },
+            21 => Tlv::PanId(value.read_with(offset, BE)?),
+            22 => Tlv::ActiveTimestamp(value.read_with(offset, BE)?),
+            23 => Tlv::PendingTimestamp(value.read_with(offset, BE)?),
+            24 => Tlv::ActiveDataset(value),
+            25 => Tlv::PendingDataset(value),
+            26 => Tlv::Discovery(value),
+            27 => Tlv::SupervisionInterval(value.read_with(offset, BE)?),
+            85 => Tlv::CslSynchronizedTimeout(value.read_with(offset, BE)?),
+            kind => Tlv::Other { kind, value },
+        };
+
+        Ok(tlv)
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        bytes.write(offset, self.kind())?;
+        // the length is written once the value is
+        let len_offset = *offset;
+        bytes.write(offset, 0u8)?;
+        let start = *offset;
+
+        match *self {
+            Tlv::SourceAddress(value)
+            | Tlv::Address16(value)
+            | Tlv::Version(value)
+            | Tlv::PanId(value)
+            | Tlv::SupervisionInterval(value) => bytes.write_with(offset, value, BE)?,
+            Tlv::Mode(value)
+            | Tlv::ScanMask(value)
+            | Tlv::LinkMargin(value)
+            | Tlv::Status(value) => bytes.write(offset, value)?,
+            Tlv::Timeout(value)
+            | Tlv::LinkLayerFrameCounter(value)
+            | Tlv::MleFrameCounter(value)
+            | Tlv::CslSynchronizedTimeout(value) => bytes.write_with(offset, value, BE)?,
+            Tlv::Challenge(value)
+            | Tlv::Response(value)
+            | Tlv::NetworkData(value)
+            | Tlv::TlvRequest(value)
+            | Tlv::ActiveDataset(value)
+            | Tlv::PendingDataset(value)
+            | Tlv::Discovery(value)
+            | Tlv::Other { value, .. } => bytes.write(offset, value)?,
+            Tlv::AddressRegistration(entries) => bytes.write(offset, entries.0)?,
+            Tlv::Route64 {
+                id_sequence,
+                router_mask,
+                route_data,
+            } => {
+                let routers = router_mask.iter().map(|b| b.count_ones()).sum::<u32>();
+                if route_data.len() != routers as usize {
+                    return Err(byte::Error::BadInput {
+                        err: "Mismatched route data",
+                    });
+                }
+                bytes.write(offset, id_sequence)?;
+                bytes.write(offset, &router_mask[..])?;
+                bytes.write(offset, route_data)?;
+            }
+            Tlv::LeaderData(data) => {
+                bytes.write_with(offset, data.partition_id, BE)?;
+                bytes.write(offset, data.weighting)?;
+                bytes.write(offset, data.data_version)?;
+                bytes.write(offset, data.stable_data_version)?;
+                bytes.write(offset, data.leader_router_id)?;
+            }
+            Tlv::Connectivity(connectivity) => {
+                bytes.write(offset, connectivity.flags)?;
+                bytes.write(offset, connectivity.link_quality_3)?;
+                bytes.write(offset, connectivity.link_quality_2)?;
+                bytes.write(offset, connectivity.link_quality_1)?;
+                bytes.write(offset, connectivity.leader_cost)?;
+                bytes.write(offset, connectivity.id_sequence)?;
+                bytes.write(offset, connectivity.active_routers)?;
+                if let Some((buffer_size, datagram_count)) = connectivity.sed {
+                    bytes.write_with(offset, buffer_size, BE)?;
+                    bytes.write(offset, datagram_count)?;
+                }
+            }
+            Tlv::Channel { page, channel } => {
+                bytes.write(offset, page)?;
+                bytes.write_with(offset, channel, BE)?;
+            }
+            Tlv::ActiveTimestamp(value) | Tlv::PendingTimestamp(value) => {
+                bytes.write_with(offset, value, BE)?
+            }
+        }
+
+        bytes[len_offset] = u8::try_from(*offset - start).map_err(|_| byte::Error::BadInput {
+            err: "TLV too long",
+        })?;
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Inner<'a> {
+    /// TLVs decoded while iterating, which were checked when decoding the
+    /// message
+    Encoded(&'a [u8]),
+    Slice(&'a [Tlv<'a>]),
+}
+
+/// TLVs of a message, either received, or to send
+///
+/// Received TLVs are decoded while iterating over them, so that they don't
+/// need to be stored. TLVs to send are created from a slice.
+#[derive(Clone, Copy)]
+pub struct Tlvs<'a>(Inner<'a>);
+
+impl<'a> Tlvs<'a> {
+    pub fn iter(&self) -> impl Iterator<Item = Tlv<'a>> + 'a {
+        let mut encoded = match self.0 {
+            Inner::Encoded(bytes) => bytes,
+            Inner::Slice(_) => &[],
+        };
+        let mut slice = match self.0 {
+            Inner::Encoded(_) => [].iter(),
+            Inner::Slice(slice) => slice.iter(),
+        };
+
+        core::iter::from_fn(move || {
+            if let Some(tlv) = slice.next() {
+                return Some(*tlv);
+            }
+            if encoded.is_empty() {
+                return None;
+            }
+
+            let offset = &mut 0;
+            let tlv = Tlv::read(encoded, offset).ok()?;
+            encoded = &encoded[*offset..];
+
+            Some(tlv)
+        })
+    }
+
+    /// The first TLV of type `kind`
+    pub fn get(&self, kind: TlvType) -> Option<Tlv<'a>> {
+        self.iter().find(|tlv| tlv.kind() == kind as u8)
+    }
+
+    /// Check the TLVs encoded in `bytes`
+    pub(super) fn decode(bytes: &'a [u8]) -> byte::Result<Self> {
+        let offset = &mut 0;
+        while *offset < bytes.len() {
+            Tlv::read(bytes, offset)?;
+        }
+
+        Ok(Self(Inner::Encoded(bytes)))
+    }
+
+    pub(super) fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        match self.0 {
+            Inner::Encoded(encoded) => bytes.write(offset, encoded),
+            Inner::Slice(slice) => slice.iter().try_for_each(|tlv| tlv.write(bytes, offset)),
+        }
+    }
+}
+
+impl<'a> From<&'a [Tlv<'a>]> for Tlvs<'a> {
+    fn from(slice: &'a [Tlv<'a>]) -> Self {
+        Self(Inner::Slice(slice))
+    }
+}
+
+impl PartialEq for Tlvs<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl fmt::Debug for Tlvs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(tlvs: &[Tlv<'_>], bytes: &mut [u8]) -> byte::Result<usize> {
+        let offset = &mut 0;
+        Tlvs::from(tlvs).write(bytes, offset)?;
+        Ok(*offset)
+    }
+
+    #[test]
+    fn tlvs_round_trip() {
+        let mut registration = [0; 64];
+        let len = AddressRegistrationEntry::Compressed {
+            context: 1,
+            iid: [1; 8],
+        }
+        .encode(&mut registration)
+        .unwrap();
+        let len = len
+            + AddressRegistrationEntry::Full([2; 16])
+                .encode(&mut registration[len..])
+                .unwrap();
+        assert_eq!(len, 9 + 17);
+
+        let tlvs = [
+            Tlv::SourceAddress(0x0400),
+            Tlv::Mode(mode::RX_ON_WHEN_IDLE | mode::FULL_THREAD_DEVICE),
+            Tlv::Timeout(240),
+            Tlv::Challenge(&[1, 2, 3, 4, 5, 6, 7, 8]),
+            Tlv::Response(&[8, 7, 6, 5, 4, 3, 2, 1]),
+            Tlv::LinkLayerFrameCounter(0x0102_0304),
+            Tlv::MleFrameCounter(5),
+            Tlv::Route64 {
+                id_sequence: 5,
+                router_mask: [0xc0, 0, 0, 0, 0, 0, 0, 0x01],
+                route_data: &[0x11, 0x22, 0x33],
+            },
+            Tlv::Address16(0x0401),
+            Tlv::LeaderData(LeaderData {
+                partition_id: 0xdead_beef,
+                weighting: 64,
+                data_version: 1,
+                stable_data_version: 2,
+                leader_router_id: 3,
+            }),
+            Tlv::NetworkData(&[0x08, 0x00]),
+            Tlv::TlvRequest(&[10, 12]),
+            Tlv::ScanMask(scan_mask::ROUTERS | scan_mask::END_DEVICES),
+            Tlv::Connectivity(Connectivity {
+                flags: 0x40,
+                link_quality_3: 1,
+                link_quality_2: 0,
+                link_quality_1: 0,
+                leader_cost: 1,
+                id_sequence: 5,
+                active_routers: 2,
+                sed: None,
+            }),
+            Tlv::Connectivity(Connectivity {
+                flags: 0xc0,
+                link_quality_3: 0,
+                link_quality_2: 1,
+                link_quality_1: 2,
+                leader_cost: 0,
+                id_sequence: 6,
+                active_routers: 1,
+                sed: Some((1280, 1)),
+            }),
+            Tlv::LinkMargin(30),
+            Tlv::Status(1),
+            Tlv::Version(4),
+            Tlv::AddressRegistration(AddressRegistration::new(&registration[..len]).unwrap()),
+            Tlv::Channel {
+                page: 0,
+                channel: 15,
+            },
+            Tlv::PanId(0x1234),
+            Tlv::ActiveTimestamp(0x0001_0000_0000_0001),
+            Tlv::PendingTimestamp(2),
+            Tlv::ActiveDataset(&[0, 3, 0, 0, 15]),
+            Tlv::PendingDataset(&[]),
+            Tlv::Discovery(&[0x80, 0x02, 0x00, 0x00]),
+            Tlv::SupervisionInterval(129),
+            Tlv::CslSynchronizedTimeout(30),
+            Tlv::Other {
+                kind: 200,
+                value: &[9],
+            },
+        ];
+
+        let mut bytes = [0; 256];
+        let len = encode(&tlvs, &mut bytes).unwrap();
+        assert_eq!(bytes[..4], [0, 2, 0x04, 0x00]);
+
+        let decoded = Tlvs::decode(&bytes[..len]).unwrap();
+        assert_eq!(decoded, Tlvs::from(&tlvs[..]));
+
+        assert_eq!(decoded.get(TlvType::Version), Some(Tlv::Version(4)));
+        assert_eq!(
+            decoded.get(TlvType::Address16),
+            Some(Tlv::Address16(0x0401))
+        );
+        assert_eq!(decoded.get(TlvType::Status), Some(Tlv::Status(1)));
+        assert_eq!(decoded.get(TlvType::LinkMargin), Some(Tlv::LinkMargin(30)));
+        // the first one
+        assert!(matches!(
+            decoded.get(TlvType::Connectivity),
+            Some(Tlv::Connectivity(Connectivity { sed: None, .. }))
+        ));
+        let Some(Tlv::AddressRegistration(registration)) =
+            decoded.get(TlvType::AddressRegistration)
+        else {
+            panic!("no address registration");
+        };
+        assert!(registration.iter().eq([
+            AddressRegistrationEntry::Compressed {
+                context: 1,
+                iid: [1; 8],
+            },
+            AddressRegistrationEntry::Full([2; 16]),
+        ]));
+    }
+
+    #[test]
+    fn malformed_tlvs_are_rejected() {
+        // truncated values, of fixed and of declared lengths
+        for bytes in [
+            &[0, 1, 0x04][..],
+            &[2, 3, 0, 0, 0],
+            &[3, 8, 1, 2, 3],
+            &[11, 7, 0, 0, 0, 0, 0, 0, 0],
+            &[15, 6, 0, 0, 0, 0, 0, 0],
+            &[15, 8, 0, 0, 0, 0, 0, 0, 0, 0],
+            &[20, 2, 0, 0],
+            &[22, 7, 0, 0, 0, 0, 0, 0, 0],
+            &[1],
+        ] {
+            assert!(Tlvs::decode(bytes).is_err(), "{bytes:02x?}");
+        }
+
+        // fewer route data bytes than allocated routers
+        assert!(Tlvs::decode(&[9, 10, 5, 0xe0, 0, 0, 0, 0, 0, 0, 0, 1]).is_err());
+        let route64 = [Tlv::Route64 {
+            id_sequence: 0,
+            router_mask: [0x80, 0, 0, 0, 0, 0, 0, 0],
+            route_data: &[],
+        }];
+        assert!(encode(&route64, &mut [0; 16]).is_err());
+
+        // truncated address registration entries
+        assert!(AddressRegistration::new(&[0x80, 1, 2, 3]).is_err());
+        assert!(AddressRegistration::new(&[0x00; 16]).is_err());
+        assert!(Tlvs::decode(&[19, 4, 0x80, 1, 2, 3]).is_err());
+
+        // values too long for their length
+        let challenge = [0; 256];
+        assert!(encode(&[Tlv::Challenge(&challenge)], &mut [0; 512]).is_err());
+        assert!(encode(&[Tlv::Timeout(1)], &mut [0; 5]).is_err());
+    }
+}
@@ -0,0 +1,237 @@
+//! [`embassy_net_driver`] implementation, used with the `embassy-net-driver`
+//! feature
+//!
+//! [`Ieee802154`] implements [`Driver`], so it can be used by an embassy-net
+//! `Stack` to run IPv6 over 6LoWPAN. The task running the stack is woken from
+//! the interrupt handler when a frame was received or a transmission is done.
+//!
+//! The hardware address is the extended address set via
+//! [`Config::ext_addr`](crate::Config::ext_addr), which is read when the
+//! stack is created. The link is reported as up once the radio was started,
+//! e.g. by [`Ieee802154::start_receive`], and while it receives whenever it
+//! is not transmitting, as set via
+//! [`Config::rx_when_idle`](crate::Config::rx_when_idle), since frames sent
+//! to the stack are lost otherwise. Frames are passed without the FCS, which
+//! is added and checked by the radio, and frames the radio reports with a
+//! [`RxFault`](crate::RxFault) are dropped.
+
+use core::{
+    cell::RefCell,
+    task::{Context, Waker},
+};
+
+use critical_section::Mutex;
+use embassy_net_driver::{Capabilities, Driver, HardwareAddress, LinkState};
+
+use crate::{
+    netdev::{self, MAX_PSDU_LEN},
+    raw::{get_extended_address, ieee802154_listening},
+    Ieee802154, RawReceived,
+};
+
+#[cfg_attr(feature = "sim", thread_local)]
+static WAKER: Mutex<RefCell<Option<Waker>>> = Mutex::new(RefCell::new(None));
+
+/// A frame received by [`Ieee802154`]
+pub struct RxToken {
+    received: RawReceived,
+}
+
+/// A transmission by [`Ieee802154`]
+pub struct TxToken<'d, 'a> {
+    driver: &'d mut Ieee802154<'a>,
+}
+
+impl<'a> Driver for Ieee802154<'a> {
+    type RxToken<'d>
+        = RxToken
+    where
+        Self: 'd;
+    type TxToken<'d>
+        = TxToken<'d, 'a>
+    where
+        Self: 'd;
+
+    fn receive(&mut self, cx: &mut Context) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        register(cx);
+
+        let received = netdev::receive(self)?;
+        Some((RxToken { received }, TxToken { driver: self }))
+    }
+
+    fn transmit(&mut self, cx: &mut Context) -> Option<Self::TxToken<'_>> {
+        register(cx);
+
+        netdev::can_transmit().then_some(TxToken { driver: self })
+    }
+
+    fn link_state(&mut self, cx: &mut Context) -> LinkState {
+        register(cx);
+
+        if ieee802154_listening() {
+            LinkState::Up
+        } else {
+            LinkState::Down
+        }
+    }
+
+    /// The MTU is the size of the PSDU including the FCS, for the IEEE
+    /// 802.15.4 hardware address the stack builds frames which leave room for
+    /// it
+    fn capabilities(&self) -> Capabilities {
+        let mut caps = Capabilities::default();
+        caps.max_transmission_unit = MAX_PSDU_LEN;
+        caps
+    }
+
+    fn hardware_address(&self) -> HardwareAddress {
+        HardwareAddress::Ieee802154(get_extended_address(0))
+    }
+}
+
+impl embassy_net_driver::RxToken for RxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        netdev::consume_received(self.received, f)
+    }
+}
+
+impl<'d, 'a> embassy_net_driver::TxToken for TxToken<'d, 'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        netdev::transmit(self.driver, len, f)
+    }
+}
+
+fn register(cx: &mut Context) {
+    critical_section::with(|cs| {
+        let mut waker = WAKER.borrow_ref_mut(cs);
+        match waker.as_ref() {
+            Some(waker) if waker.will_wake(cx.waker()) => (),
+            _ => *waker = Some(cx.waker().clone()),
+        }
+    });
+}
+
+/// Wake the task using the driver, after a frame was received, a
+/// transmission is done or the configuration changed
+pub(crate) fn wake() {
+    if let Some(waker) = critical_section::with(|cs| WAKER.borrow_ref_mut(cs).take()) {
+        waker.wake();
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Barrier,
+        },
+        task::Wake,
+        time::Duration,
+    };
+
+    use embassy_net_driver::{RxToken as _, TxToken as _};
+
+    use super::*;
+    use crate::{
+        hal::{mock, sim},
+        netdev::tests::{data_frame, node, poll_until, receiver, sender},
+        Config,
+    };
+
+    /// Counts how often the task is woken
+    #[derive(Default)]
+    struct Wakes(AtomicUsize);
+
+    impl Wake for Wakes {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    impl Wakes {
+        fn count(&self) -> usize {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    #[test]
+    fn link_is_up_while_receiving_when_idle() {
+        let medium = sim::Medium::new(Default::default());
+        let wakes = Arc::new(Wakes::default());
+        let waker = Waker::from(wakes.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut radio = Ieee802154::new(mock::IEEE802154, &mut mock::RADIO_CLK);
+        medium.attach();
+        assert!(matches!(radio.link_state(&mut cx), LinkState::Down));
+
+        // receiving while idle, but not started yet
+        radio.set_config(Config {
+            ext_addr: Some(0x0011_2233_4455_6677),
+            rx_when_idle: true,
+            ..Default::default()
+        });
+        assert_eq!(wakes.count(), 1);
+        assert!(matches!(radio.link_state(&mut cx), LinkState::Down));
+        assert!(matches!(
+            radio.hardware_address(),
+            HardwareAddress::Ieee802154([0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77])
+        ));
+
+        radio.start_receive();
+        assert_eq!(wakes.count(), 2);
+        assert!(matches!(radio.link_state(&mut cx), LinkState::Up));
+
+        // a single reception is not enough for the stack
+        radio.set_config(Config {
+            rx_when_idle: false,
+            ..radio.config()
+        });
+        assert_eq!(wakes.count(), 3);
+        assert!(matches!(radio.link_state(&mut cx), LinkState::Down));
+    }
+
+    #[test]
+    fn tokens_receive_and_transmit() {
+        let medium = sim::Medium::new(Default::default());
+        let wakes = Arc::new(Wakes::default());
+        let waker = Waker::from(wakes.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let barrier = Arc::new(Barrier::new(3));
+        let sender = sender(&medium, &barrier, data_frame(1, 0xffff, 40));
+        let receiver = receiver(&medium, &barrier, Duration::from_millis(30));
+
+        let mut radio = node(&medium, 1);
+        assert!(Driver::receive(&mut radio, &mut cx).is_none());
+        barrier.wait();
+
+        // answer the received frame with the returned transmit token
+        let frame = data_frame(2, 2, 20);
+        let received = poll_until(|| {
+            let (rx, tx) = Driver::receive(&mut radio, &mut cx)?;
+            let received = rx.consume(|frame| frame.to_vec());
+            tx.consume(frame.len(), |buffer| buffer.copy_from_slice(&frame));
+            Some(received)
+        });
+        assert_eq!(received.unwrap(), data_frame(1, 0xffff, 40));
+        // the reception woke the task
+        assert!(wakes.count() >= 1);
+
+        sim::run_for(Duration::from_millis(5));
+        assert!(Driver::transmit(&mut radio, &mut cx).is_some());
+        assert_eq!(radio.stats().tx_done, 1);
+        sender.join().unwrap();
+
+        // the answer reached the other node
+        let frames = receiver.join().unwrap();
+        assert!(frames.contains(&frame));
+    }
+}
@@ -0,0 +1,208 @@
+//! Frame handling shared by the smoltcp and embassy-net adapters
+
+use crate::{raw::ieee802154_tx_pending, stats::stats_tx_oversize, Ieee802154, RawReceived};
+
+/// aMaxPhyPacketSize, the largest PSDU including the FCS
+pub(crate) const MAX_PSDU_LEN: usize = 127;
+const FCS_LEN: usize = 2;
+
+/// The next received frame to hand to the stack, skipping frames the radio
+/// reports with a fault
+///
+/// Returns `None` while transmitting, as the stack can only answer a frame
+/// once the transmit buffer is free.
+pub(crate) fn receive(driver: &mut Ieee802154<'_>) -> Option<RawReceived> {
+    if !can_transmit() {
+        return None;
+    }
+
+    loop {
+        let received = driver.get_raw_received()?;
+        if received.fault.is_none() && received.data[0] as usize > FCS_LEN {
+            return Some(received);
+        }
+    }
+}
+
+/// Whether a frame can be transmitted, the ongoing transmission uses the
+/// transmit buffer until it is done
+pub(crate) fn can_transmit() -> bool {
+    !ieee802154_tx_pending()
+}
+
+/// Pass the frame of `received` to `f`
+pub(crate) fn consume_received<R>(mut received: RawReceived, f: impl FnOnce(&mut [u8]) -> R) -> R {
+    // the last two bytes hold the RSSI and LQI instead of the FCS
+    let len = received.data[0] as usize - FCS_LEN;
+    f(&mut received.data[1..][..len])
+}
+
+/// Let `f` write a frame of `len` bytes and transmit it, frames exceeding the
+/// MTU are dropped and counted in
+/// [`RadioStats::tx_oversize`](crate::RadioStats::tx_oversize)
+pub(crate) fn transmit<R>(
+    driver: &mut Ieee802154<'_>,
+    len: usize,
+    f: impl FnOnce(&mut [u8]) -> R,
+) -> R {
+    let mut frame = [0u8; MAX_PSDU_LEN];
+    let result = f(&mut frame[..len.min(MAX_PSDU_LEN - FCS_LEN)]);
+
+    if len > MAX_PSDU_LEN - FCS_LEN {
+        log::warn!("Dropping frame of {} bytes, exceeding the MTU", len);
+        stats_tx_oversize();
+        return result;
+    }
+
+    // room for the FCS, which is filled in by the radio
+    if let Err(err) = driver.transmit_raw(&frame[..len + FCS_LEN]) {
+        log::warn!("Transmitting frame failed: {:?}", err);
+    }
+
+    result
+}
+
+#[cfg(all(test, feature = "sim"))]
+pub(crate) mod tests {
+    use std::{
+        sync::{Arc, Barrier},
+        thread::{self, JoinHandle},
+        time::{Duration, Instant},
+        vec::Vec,
+    };
+
+    use super::*;
+    use crate::{
+        hal::{mock, sim},
+        Config,
+    };
+
+    /// A node receiving while idle on channel 15 of PAN 0x1234
+    pub(crate) fn node(medium: &sim::Medium, short_addr: u16) -> Ieee802154<'static> {
+        let mut radio = Ieee802154::new(mock::IEEE802154, &mut mock::RADIO_CLK);
+        medium.attach();
+        radio.set_config(Config {
+            channel: crate::Channel::new(15).unwrap(),
+            pan_id: Some(0x1234),
+            short_addr: Some(short_addr),
+            rx_when_idle: true,
+            ..Default::default()
+        });
+        radio.start_receive();
+        radio
+    }
+
+    /// A data frame between short addresses without ACK request, without the
+    /// FCS
+    pub(crate) fn data_frame(sequence: u8, destination: u16, len: usize) -> Vec<u8> {
+        let mut frame = std::vec![0x41, 0x88, sequence, 0x34, 0x12];
+        frame.extend_from_slice(&destination.to_le_bytes());
+        frame.extend_from_slice(&[0x01, 0x00]);
+        frame.resize(len, 0x55);
+        frame
+    }
+
+    /// A node which transmits `frame` once the barrier is passed
+    pub(crate) fn sender(
+        medium: &sim::Medium,
+        barrier: &Arc<Barrier>,
+        frame: Vec<u8>,
+    ) -> JoinHandle<()> {
+        let (medium, barrier) = (medium.clone(), barrier.clone());
+        thread::spawn(move || {
+            let mut radio = node(&medium, 2);
+            barrier.wait();
+            let mut psdu = frame;
+            psdu.extend_from_slice(&[0; FCS_LEN]);
+            radio.transmit_raw(&psdu).unwrap();
+            sim::run_for(Duration::from_millis(10));
+        })
+    }
+
+    /// A node which returns the frames it received, without the FCS, for
+    /// `duration` after the barrier is passed
+    pub(crate) fn receiver(
+        medium: &sim::Medium,
+        barrier: &Arc<Barrier>,
+        duration: Duration,
+    ) -> JoinHandle<Vec<Vec<u8>>> {
+        let (medium, barrier) = (medium.clone(), barrier.clone());
+        thread::spawn(move || {
+            let mut radio = node(&medium, 2);
+            barrier.wait();
+
+            let until = Instant::now() + duration;
+            let mut frames = Vec::new();
+            while Instant::now() < until {
+                if let Some(received) = radio.get_raw_received() {
+                    let len = received.data[0] as usize - FCS_LEN;
+                    frames.push(received.data[1..][..len].to_vec());
+                }
+            }
+            frames
+        })
+    }
+
+    /// The first value returned by `f` within 50 ms
+    pub(crate) fn poll_until<T>(mut f: impl FnMut() -> Option<T>) -> Option<T> {
+        let until = Instant::now() + Duration::from_millis(50);
+        while Instant::now() < until {
+            if let Some(value) = f() {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn oversize_frame_is_dropped_and_counted() {
+        let medium = sim::Medium::new(Default::default());
+        let barrier = Arc::new(Barrier::new(2));
+        let receiver = receiver(&medium, &barrier, Duration::from_millis(20));
+
+        let mut radio = node(&medium, 1);
+        barrier.wait();
+
+        // the frame is written to a buffer truncated to the MTU, but not sent
+        let written = transmit(&mut radio, MAX_PSDU_LEN - 1, |frame| frame.len());
+        assert_eq!(written, MAX_PSDU_LEN - FCS_LEN);
+        assert_eq!(radio.stats().tx_oversize, 1);
+        assert!(can_transmit());
+
+        let frame = data_frame(1, 2, MAX_PSDU_LEN - FCS_LEN);
+        transmit(&mut radio, frame.len(), |buffer| {
+            buffer.copy_from_slice(&frame)
+        });
+        sim::run_for(Duration::from_millis(10));
+        assert_eq!(radio.stats().tx_oversize, 1);
+        assert_eq!(radio.stats().tx_done, 1);
+        assert_eq!(receiver.join().unwrap(), [frame]);
+    }
+
+    #[test]
+    fn receive_waits_for_transmission() {
+        let medium = sim::Medium::new(Default::default());
+        let barrier = Arc::new(Barrier::new(2));
+        let sender = sender(&medium, &barrier, data_frame(1, 0xffff, 40));
+
+        let mut radio = node(&medium, 1);
+        barrier.wait();
+        sim::run_for(Duration::from_millis(5));
+
+        // the frame of the sender is received while transmitting, but only
+        // handed out once the transmission is done
+        // the longest frame, which takes 4 ms to transmit
+        let len = MAX_PSDU_LEN - FCS_LEN;
+        transmit(&mut radio, len, |frame| {
+            frame.copy_from_slice(&data_frame(2, 0xffff, len))
+        });
+        assert!(!can_transmit());
+        assert!(receive(&mut radio).is_none());
+
+        let received = poll_until(|| receive(&mut radio)).unwrap();
+        let frame = consume_received(received, |frame| frame.to_vec());
+        assert_eq!(frame, data_frame(1, 0xffff, 40));
+        assert!(can_transmit());
+        sender.join().unwrap();
+    }
+}
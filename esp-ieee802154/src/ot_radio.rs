@@ -0,0 +1,1007 @@
+//! OpenThread radio platform, used with the `openthread` feature
+//!
+//! Implements the `otPlatRadio*` functions of the OpenThread radio platform
+//! API on top of [`Ieee802154`], so the OpenThread library can be linked into
+//! the firmware. The driver is handed over via [`init`], and [`process`] has
+//! to be called from the OpenThread task loop, where it passes received
+//! frames, completed transmissions and energy scans to OpenThread.
+//!
+//! The radio handles ACKs and ACK timeouts, CSMA-CA backoffs and frame retries
+//! are done in software. The frame pending bit of ACKs is decided by the
+//! source match tables. The types follow `openthread/platform/radio.h` for a
+//! build with a single radio link.
+
+// the `unsafe` functions are only called by OpenThread
+#![allow(non_camel_case_types, non_snake_case, clippy::missing_safety_doc)]
+
+use core::{cell::RefCell, ffi::c_void, ptr::addr_of_mut};
+
+use critical_section::Mutex;
+
+use crate::{
+    ack::{ack_frame, ack_requested, acked_with_frame_pending, ACK_PSDU_LEN},
+    csma::{Outcome, Step, Transmission},
+    link_metrics::{Metrics, Neighbor},
+    raw::{
+        ieee802154_energy_detect, ieee802154_energy_detect_result, ieee802154_last_rssi,
+        ieee802154_sleep, ieee802154_transmit, set_cca_theshold, set_channel, set_extended_address,
+        set_panid, set_pending_mode, set_promiscuous, set_rx_when_idle, set_short_address,
+        set_tx_power,
+    },
+    rng::Rng,
+    Channel, Config, Error, Ieee802154, PendingMode, TxPower,
+};
+
+/// OpenThread instance, opaque to the radio
+#[repr(C)]
+pub struct otInstance {
+    _private: [u8; 0],
+}
+
+/// Extended address, in little-endian byte order
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct otExtAddress {
+    pub m8: [u8; 8],
+}
+
+pub type otError = u32;
+pub type otRadioCaps = u16;
+pub type otRadioState = u32;
+
+pub const OT_ERROR_NONE: otError = 0;
+pub const OT_ERROR_NO_BUFS: otError = 3;
+pub const OT_ERROR_BUSY: otError = 5;
+pub const OT_ERROR_INVALID_ARGS: otError = 7;
+pub const OT_ERROR_NO_ADDRESS: otError = 10;
+pub const OT_ERROR_ABORT: otError = 11;
+pub const OT_ERROR_INVALID_STATE: otError = 13;
+pub const OT_ERROR_NO_ACK: otError = 14;
+pub const OT_ERROR_CHANNEL_ACCESS_FAILURE: otError = 15;
+
+pub const OT_RADIO_CAPS_ACK_TIMEOUT: otRadioCaps = 1 << 0;
+pub const OT_RADIO_CAPS_ENERGY_SCAN: otRadioCaps = 1 << 1;
+pub const OT_RADIO_CAPS_TRANSMIT_RETRIES: otRadioCaps = 1 << 2;
+pub const OT_RADIO_CAPS_CSMA_BACKOFF: otRadioCaps = 1 << 3;
+
+pub const OT_RADIO_STATE_DISABLED: otRadioState = 0;
+pub const OT_RADIO_STATE_SLEEP: otRadioState = 1;
+pub const OT_RADIO_STATE_RECEIVE: otRadioState = 2;
+pub const OT_RADIO_STATE_TRANSMIT: otRadioState = 3;
+
+/// Transmit power which selects the configured one
+pub const OT_RADIO_POWER_INVALID: i8 = 127;
+
+/// Bits of [`otRadioFrameTxInfo::_bitfield_1`]
+pub const OT_TX_INFO_IS_HEADER_UPDATED: u8 = 1 << 0;
+pub const OT_TX_INFO_IS_A_RETX: u8 = 1 << 1;
+pub const OT_TX_INFO_CSMA_CA_ENABLED: u8 = 1 << 2;
+pub const OT_TX_INFO_CSL_PRESENT: u8 = 1 << 3;
+pub const OT_TX_INFO_IS_SECURITY_PROCESSED: u8 = 1 << 4;
+
+/// Bits of [`otRadioFrameRxInfo::_bitfield_1`]
+pub const OT_RX_INFO_ACKED_WITH_FRAME_PENDING: u8 = 1 << 0;
+pub const OT_RX_INFO_ACKED_WITH_SEC_ENH_ACK: u8 = 1 << 1;
+
+/// Bits of [`otLinkMetrics::_bitfield_1`]
+pub const OT_LINK_METRICS_PDU_COUNT: u8 = 1 << 0;
+pub const OT_LINK_METRICS_LQI: u8 = 1 << 1;
+pub const OT_LINK_METRICS_LINK_MARGIN: u8 = 1 << 2;
+pub const OT_LINK_METRICS_RSSI: u8 = 1 << 3;
+
+/// Link metrics to probe
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct otLinkMetrics {
+    pub _bitfield_1: u8,
+}
+
+/// Transmission parameters of a frame
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct otRadioFrameTxInfo {
+    pub mAesKey: *const c_void,
+    pub mIeInfo: *mut c_void,
+    pub mTxDelay: u32,
+    pub mTxDelayBaseTime: u32,
+    pub mMaxCsmaBackoffs: u8,
+    pub mMaxFrameRetries: u8,
+    pub mRxChannelAfterTxDone: u8,
+    pub mTxPower: i8,
+    pub _bitfield_1: u8,
+}
+
+/// Reception information of a frame
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct otRadioFrameRxInfo {
+    pub mTimestamp: u64,
+    pub mAckFrameCounter: u32,
+    pub mAckKeyId: u8,
+    pub mRssi: i8,
+    pub mLqi: u8,
+    pub _bitfield_1: u8,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub union otRadioFrameInfo {
+    pub mTxInfo: otRadioFrameTxInfo,
+    pub mRxInfo: otRadioFrameRxInfo,
+}
+
+/// A frame, `mLength` includes the FCS
+#[repr(C)]
+pub struct otRadioFrame {
+    pub mPsdu: *mut u8,
+    pub mLength: u16,
+    pub mChannel: u8,
+    pub mInfo: otRadioFrameInfo,
+}
+
+extern "C" {
+    fn otPlatRadioReceiveDone(instance: *mut otInstance, frame: *mut otRadioFrame, error: otError);
+    fn otPlatRadioTxStarted(instance: *mut otInstance, frame: *mut otRadioFrame);
+    fn otPlatRadioTxDone(
+        instance: *mut otInstance,
+        frame: *mut otRadioFrame,
+        ack_frame: *mut otRadioFrame,
+        error: otError,
+    );
+    fn otPlatRadioEnergyScanDone(instance: *mut otInstance, energy_scan_max_rssi: i8);
+}
+
+const MAX_PSDU_LEN: usize = 127;
+
+const SYMBOL_US: u32 = 16;
+
+// Typical receive sensitivity of the ESP32-C6, the ESP32-H2 is slightly better
+const RECEIVE_SENSITIVITY: i8 = -104;
+
+#[cfg_attr(feature = "sim", thread_local)]
+static RADIO: Mutex<RefCell<Option<OtRadio>>> = Mutex::new(RefCell::new(None));
+
+#[cfg_attr(feature = "sim", thread_local)]
+static mut TX_PSDU: [u8; MAX_PSDU_LEN] = [0u8; MAX_PSDU_LEN];
+#[cfg_attr(feature = "sim", thread_local)]
+static mut RX_PSDU: [u8; MAX_PSDU_LEN] = [0u8; MAX_PSDU_LEN];
+#[cfg_attr(feature = "sim", thread_local)]
+static mut ACK_PSDU: [u8; ACK_PSDU_LEN] = [0u8; ACK_PSDU_LEN];
+
+#[cfg_attr(feature = "sim", thread_local)]
+static mut TX_FRAME: otRadioFrame = otRadioFrame::new();
+#[cfg_attr(feature = "sim", thread_local)]
+static mut RX_FRAME: otRadioFrame = otRadioFrame::new();
+#[cfg_attr(feature = "sim", thread_local)]
+static mut ACK_FRAME: otRadioFrame = otRadioFrame::new();
+
+impl otRadioFrame {
+    const fn new() -> Self {
+        Self {
+            mPsdu: core::ptr::null_mut(),
+            mLength: 0,
+            mChannel: 0,
+            mInfo: otRadioFrameInfo {
+                mRxInfo: otRadioFrameRxInfo {
+                    mTimestamp: 0,
+                    mAckFrameCounter: 0,
+                    mAckKeyId: 0,
+                    mRssi: 0,
+                    mLqi: 0,
+                    _bitfield_1: 0,
+                },
+            },
+        }
+    }
+}
+
+struct OtRadio {
+    driver: Ieee802154<'static>,
+    eui64: [u8; 8],
+    now: fn() -> u64,
+    enabled: bool,
+    receiving: bool,
+    channel: Channel,
+    promiscuous: bool,
+    tx_power: i8,
+    cca_threshold: i8,
+    transmit: Option<Transmission>,
+    energy_scan: bool,
+    rng: Rng,
+}
+
+/// What [`process`] reports to OpenThread
+enum Report {
+    TxStarted,
+    TxDone { error: otError, acked: bool },
+    Received,
+    EnergyScanDone(i8),
+}
+
+/// Hand `driver` to the radio platform
+///
+/// `eui64` is the factory assigned EUI-64 of the device, and `now` returns
+/// the time in microseconds, used for the CSMA-CA backoffs and to timestamp
+/// received frames.
+pub fn init(mut driver: Ieee802154<'static>, eui64: [u8; 8], now: fn() -> u64) {
+    let config = Config {
+        auto_ack_tx: true,
+        auto_ack_rx: true,
+        enhance_ack_tx: true,
+        rx_when_idle: true,
+        channel: Channel::MIN,
+        ..Default::default()
+    };
+    driver.set_config(config);
+
+    unsafe {
+        (*addr_of_mut!(TX_FRAME)).mPsdu = addr_of_mut!(TX_PSDU) as *mut u8;
+        (*addr_of_mut!(RX_FRAME)).mPsdu = addr_of_mut!(RX_PSDU) as *mut u8;
+        (*addr_of_mut!(ACK_FRAME)).mPsdu = addr_of_mut!(ACK_PSDU) as *mut u8;
+    }
+
+    let radio = OtRadio {
+        driver,
+        eui64,
+        now,
+        enabled: false,
+        receiving: false,
+        channel: config.channel,
+        promiscuous: config.promiscuous,
+        tx_power: config.txpower,
+        cca_threshold: config.cca_threshold,
+        transmit: None,
+        energy_scan: false,
+        rng: Rng::new(eui64),
+    };
+
+    critical_section::with(|cs| RADIO.borrow_ref_mut(cs).replace(radio));
+}
+
+/// Pass received frames, completed transmissions and energy scans to
+/// OpenThread, and continue the ongoing transmission
+///
+/// # Safety
+///
+/// `instance` has to be the initialized OpenThread instance.
+pub unsafe fn process(instance: *mut otInstance) {
+    while let Some(report) = with_radio(OtRadio::poll).flatten() {
+        unsafe {
+            match report {
+                Report::TxStarted => otPlatRadioTxStarted(instance, addr_of_mut!(TX_FRAME)),
+                Report::TxDone { error, acked } => otPlatRadioTxDone(
+                    instance,
+                    addr_of_mut!(TX_FRAME),
+                    if acked {
+                        addr_of_mut!(ACK_FRAME)
+                    } else {
+                        core::ptr::null_mut()
+                    },
+                    error,
+                ),
+                Report::Received => {
+                    otPlatRadioReceiveDone(instance, addr_of_mut!(RX_FRAME), OT_ERROR_NONE)
+                }
+                Report::EnergyScanDone(rssi) => otPlatRadioEnergyScanDone(instance, rssi),
+            }
+        }
+    }
+}
+
+fn with_radio<R>(f: impl FnOnce(&mut OtRadio) -> R) -> Option<R> {
+    critical_section::with(|cs| RADIO.borrow_ref_mut(cs).as_mut().map(f))
+}
+
+impl OtRadio {
+    /// The next event to report, if any
+    fn poll(&mut self) -> Option<Report> {
+        if self.energy_scan {
+            if let Some(result) = ieee802154_energy_detect_result() {
+                self.energy_scan = false;
+                // an aborted scan measured nothing
+                let rssi = result.unwrap_or(OT_RADIO_POWER_INVALID);
+                return Some(Report::EnergyScanDone(rssi));
+            }
+        }
+
+        if let Some(report) = self.poll_transmit() {
+            return Some(report);
+        }
+
+        self.poll_receive()
+    }
+
+    fn poll_transmit(&mut self) -> Option<Report> {
+        let now = (self.now)();
+
+        match self.transmit.as_mut()?.poll(now, &mut self.rng) {
+            Step::Wait => None,
+            Step::Start { cca, first } => {
+                self.start_transmit(cca);
+                first.then_some(Report::TxStarted)
+            }
+            Step::Done(Outcome::Sent) => Some(self.tx_done(OT_ERROR_NONE, None)),
+            Step::Done(Outcome::Acked { frame_pending }) => {
+                Some(self.tx_done(OT_ERROR_NONE, Some(frame_pending)))
+            }
+            Step::Done(Outcome::NoAck) => Some(self.tx_done(OT_ERROR_NO_ACK, None)),
+            Step::Done(Outcome::ChannelAccessFailure) => {
+                Some(self.tx_done(OT_ERROR_CHANNEL_ACCESS_FAILURE, None))
+            }
+            Step::Done(Outcome::Aborted) => Some(self.tx_done(OT_ERROR_ABORT, None)),
+        }
+    }
+
+    fn poll_receive(&mut self) -> Option<Report> {
+        loop {
+            let token = self.driver.get_received_token()?;
+            if token.fault().is_some() {
+                continue;
+            }
+
+            // the last two bytes hold the RSSI and LQI, in place of the FCS
+            let psdu = token.data();
+            let frame = &psdu[..psdu.len().saturating_sub(2)];
+
+            unsafe {
+                let rx_frame = &mut *addr_of_mut!(RX_FRAME);
+                (&mut *addr_of_mut!(RX_PSDU))[..psdu.len()].copy_from_slice(psdu);
+                rx_frame.mLength = psdu.len() as u16;
+                rx_frame.mChannel = token.channel().into();
+                rx_frame.mInfo.mRxInfo = otRadioFrameRxInfo {
+                    mTimestamp: (self.now)(),
+                    mAckFrameCounter: 0,
+                    mAckKeyId: 0,
+                    mRssi: token.rssi(),
+                    mLqi: token.lqi(),
+                    _bitfield_1: if acked_with_frame_pending(frame) {
+                        OT_RX_INFO_ACKED_WITH_FRAME_PENDING
+                    } else {
+                        0
+                    },
+                };
+            }
+
+            return Some(Report::Received);
+        }
+    }
+
+    fn start_transmit(&mut self, csma_ca: bool) {
+        let (len, channel, power) = unsafe {
+            let frame = &*addr_of_mut!(TX_FRAME);
+            (
+                frame.mLength as usize,
+                frame.mChannel,
+                frame.mInfo.mTxInfo.mTxPower,
+            )
+        };
+        // checked by `otPlatRadioTransmit`
+        let channel = Channel::new(channel).unwrap_or(self.channel);
+
+        set_channel(channel);
+        set_tx_power(if power == OT_RADIO_POWER_INVALID {
+            self.tx_power
+        } else {
+            power
+        });
+        self.channel = channel;
+
+        let len = len.min(MAX_PSDU_LEN);
+        let psdu = unsafe { &(&*addr_of_mut!(TX_PSDU))[..len] };
+        let buffer = &mut self.driver.transmit_buffer;
+        buffer[1..][..len].copy_from_slice(psdu);
+        buffer[0] = len as u8;
+
+        ieee802154_transmit(buffer.as_ptr(), csma_ca);
+    }
+
+    fn tx_done(&mut self, error: otError, ack_frame_pending: Option<bool>) -> Report {
+        self.transmit = None;
+        // the radio receives after transmitting
+        self.receiving = true;
+
+        // on the channel OpenThread asked for, rather than the one of the frame
+        let tx_info = unsafe { (*addr_of_mut!(TX_FRAME)).mInfo.mTxInfo };
+        if let Ok(channel) = Channel::new(tx_info.mRxChannelAfterTxDone) {
+            if channel != self.channel {
+                set_channel(channel);
+                self.channel = channel;
+                ieee802154_sleep();
+                self.driver.start_receive();
+            }
+        }
+
+        if let Some(frame_pending) = ack_frame_pending {
+            unsafe {
+                *addr_of_mut!(ACK_PSDU) = ack_frame(&*addr_of_mut!(TX_PSDU), frame_pending);
+
+                let ack_frame = &mut *addr_of_mut!(ACK_FRAME);
+                ack_frame.mLength = ACK_PSDU_LEN as u16;
+                ack_frame.mChannel = self.channel.into();
+                ack_frame.mInfo.mRxInfo = otRadioFrameRxInfo {
+                    mTimestamp: (self.now)(),
+                    mAckFrameCounter: 0,
+                    mAckKeyId: 0,
+                    mRssi: ieee802154_last_rssi(),
+                    mLqi: 0,
+                    _bitfield_1: 0,
+                };
+            }
+        }
+
+        Report::TxDone {
+            error,
+            acked: ack_frame_pending.is_some(),
+        }
+    }
+
+    /// Restart receiving, to apply a changed address or filter setting
+    fn restart_receive(&mut self) {
+        if self.receiving && self.transmit.is_none() && !self.energy_scan {
+            ieee802154_sleep();
+            self.driver.start_receive();
+        }
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioGetCaps(_instance: *mut otInstance) -> otRadioCaps {
+    OT_RADIO_CAPS_ACK_TIMEOUT
+        | OT_RADIO_CAPS_ENERGY_SCAN
+        | OT_RADIO_CAPS_TRANSMIT_RETRIES
+        | OT_RADIO_CAPS_CSMA_BACKOFF
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn otPlatRadioGetIeeeEui64(_instance: *mut otInstance, eui64: *mut u8) {
+    if let Some(address) = with_radio(|radio| radio.eui64) {
+        core::ptr::copy_nonoverlapping(address.as_ptr(), eui64, address.len());
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioSetPanId(_instance: *mut otInstance, pan_id: u16) {
+    with_radio(|radio| {
+        set_panid(0, pan_id);
+        radio.restart_receive();
+    });
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn otPlatRadioSetExtendedAddress(
+    _instance: *mut otInstance,
+    address: *const otExtAddress,
+) {
+    // the radio takes the address most significant byte first
+    let mut address = (*address).m8;
+    address.reverse();
+
+    with_radio(|radio| {
+        set_extended_address(0, address);
+        radio.restart_receive();
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioSetShortAddress(_instance: *mut otInstance, address: u16) {
+    with_radio(|radio| {
+        set_short_address(0, address);
+        radio.restart_receive();
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioGetPromiscuous(_instance: *mut otInstance) -> bool {
+    with_radio(|radio| radio.promiscuous).unwrap_or(false)
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioSetPromiscuous(_instance: *mut otInstance, enable: bool) {
+    with_radio(|radio| {
+        radio.promiscuous = enable;
+        set_promiscuous(enable);
+        radio.restart_receive();
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioGetState(_instance: *mut otInstance) -> otRadioState {
+    with_radio(|radio| {
+        if !radio.enabled {
+            OT_RADIO_STATE_DISABLED
+        } else if radio.transmit.is_some() {
+            OT_RADIO_STATE_TRANSMIT
+        } else if radio.receiving {
+            OT_RADIO_STATE_RECEIVE
+        } else {
+            OT_RADIO_STATE_SLEEP
+        }
+    })
+    .unwrap_or(OT_RADIO_STATE_DISABLED)
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioEnable(_instance: *mut otInstance) -> otError {
+    with_radio(|radio| {
+        radio.enabled = true;
+        OT_ERROR_NONE
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioDisable(_instance: *mut otInstance) -> otError {
+    with_radio(|radio| {
+        set_rx_when_idle(false);
+        ieee802154_sleep();
+        radio.enabled = false;
+        radio.receiving = false;
+        radio.transmit = None;
+        radio.energy_scan = false;
+        OT_ERROR_NONE
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioIsEnabled(_instance: *mut otInstance) -> bool {
+    with_radio(|radio| radio.enabled).unwrap_or(false)
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioSleep(_instance: *mut otInstance) -> otError {
+    with_radio(|radio| {
+        if !radio.enabled {
+            return OT_ERROR_INVALID_STATE;
+        }
+        if radio.transmit.is_some() {
+            return OT_ERROR_BUSY;
+        }
+
+        // the radio would otherwise receive again after the next operation
+        set_rx_when_idle(false);
+        ieee802154_sleep();
+        radio.receiving = false;
+        radio.energy_scan = false;
+        OT_ERROR_NONE
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioReceive(_instance: *mut otInstance, channel: u8) -> otError {
+    let Ok(channel) = Channel::new(channel) else {
+        return OT_ERROR_INVALID_ARGS;
+    };
+
+    with_radio(|radio| {
+        if !radio.enabled {
+            return OT_ERROR_INVALID_STATE;
+        }
+
+        if radio.receiving && radio.channel != channel {
+            ieee802154_sleep();
+        }
+
+        set_channel(channel);
+        set_rx_when_idle(true);
+        radio.channel = channel;
+        radio.receiving = true;
+        radio.driver.start_receive();
+        OT_ERROR_NONE
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioGetTransmitBuffer(_instance: *mut otInstance) -> *mut otRadioFrame {
+    addr_of_mut!(TX_FRAME)
+}
+
+/// Transmit the frame in the transmit buffer, the transmission is continued
+/// by [`process`]
+#[no_mangle]
+pub unsafe extern "C" fn otPlatRadioTransmit(
+    _instance: *mut otInstance,
+    frame: *mut otRadioFrame,
+) -> otError {
+    // OpenThread always transmits from the transmit buffer
+    if frame != addr_of_mut!(TX_FRAME) {
+        return OT_ERROR_INVALID_STATE;
+    }
+
+    let tx_info = (*frame).mInfo.mTxInfo;
+    if Channel::new((*frame).mChannel).is_err() {
+        return OT_ERROR_INVALID_ARGS;
+    }
+
+    with_radio(|radio| {
+        if !radio.enabled || radio.transmit.is_some() {
+            return OT_ERROR_INVALID_STATE;
+        }
+
+        // the radio receives once the frame is transmitted
+        set_rx_when_idle(true);
+        let now = (radio.now)();
+        radio.transmit = Some(Transmission::new(
+            tx_info._bitfield_1 & OT_TX_INFO_CSMA_CA_ENABLED != 0,
+            ack_requested(&*addr_of_mut!(TX_PSDU)),
+            tx_info.mMaxCsmaBackoffs,
+            tx_info.mMaxFrameRetries,
+            now,
+            &mut radio.rng,
+        ));
+        OT_ERROR_NONE
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+/// Returns the RSSI of the most recently received frame
+#[no_mangle]
+pub extern "C" fn otPlatRadioGetRssi(_instance: *mut otInstance) -> i8 {
+    ieee802154_last_rssi()
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioGetReceiveSensitivity(_instance: *mut otInstance) -> i8 {
+    RECEIVE_SENSITIVITY
+}
+
+/// Measure the energy on `channel` for `duration` milliseconds, the result is
+/// reported by [`process`]
+#[no_mangle]
+pub extern "C" fn otPlatRadioEnergyScan(
+    _instance: *mut otInstance,
+    channel: u8,
+    duration: u16,
+) -> otError {
+    let Ok(channel) = Channel::new(channel) else {
+        return OT_ERROR_INVALID_ARGS;
+    };
+
+    with_radio(|radio| {
+        if !radio.enabled || radio.transmit.is_some() || radio.energy_scan {
+            return OT_ERROR_BUSY;
+        }
+
+        set_channel(channel);
+        radio.channel = channel;
+        radio.energy_scan = true;
+        ieee802154_energy_detect(duration as u32 * 1000 / SYMBOL_US);
+        OT_ERROR_NONE
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn otPlatRadioGetTransmitPower(
+    _instance: *mut otInstance,
+    power: *mut i8,
+) -> otError {
+    with_radio(|radio| {
+        *power = TxPower::from_dbm(radio.tx_power).dbm;
+        OT_ERROR_NONE
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioSetTransmitPower(_instance: *mut otInstance, power: i8) -> otError {
+    with_radio(|radio| {
+        radio.tx_power = power;
+        set_tx_power(power);
+        OT_ERROR_NONE
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn otPlatRadioGetCcaEnergyDetectThreshold(
+    _instance: *mut otInstance,
+    threshold: *mut i8,
+) -> otError {
+    with_radio(|radio| {
+        *threshold = radio.cca_threshold;
+        OT_ERROR_NONE
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioSetCcaEnergyDetectThreshold(
+    _instance: *mut otInstance,
+    threshold: i8,
+) -> otError {
+    with_radio(|radio| {
+        radio.cca_threshold = threshold;
+        set_cca_theshold(threshold);
+        OT_ERROR_NONE
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+/// With source match disabled the frame pending bit is set in all ACKs to
+/// data requests, otherwise only if the source address is in a table
+#[no_mangle]
+pub extern "C" fn otPlatRadioEnableSrcMatch(_instance: *mut otInstance, enable: bool) {
+    with_radio(|radio| {
+        set_pending_mode(if enable {
+            PendingMode::Enable
+        } else {
+            PendingMode::Disable
+        });
+        radio.restart_receive();
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioAddSrcMatchShortEntry(
+    _instance: *mut otInstance,
+    address: u16,
+) -> otError {
+    with_radio(|radio| to_ot_error(radio.driver.add_pending_short_address(address)))
+        .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn otPlatRadioAddSrcMatchExtEntry(
+    _instance: *mut otInstance,
+    address: *const otExtAddress,
+) -> otError {
+    let address = u64::from_le_bytes((*address).m8);
+    with_radio(|radio| to_ot_error(radio.driver.add_pending_ext_address(address)))
+        .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioClearSrcMatchShortEntry(
+    _instance: *mut otInstance,
+    address: u16,
+) -> otError {
+    with_radio(|radio| {
+        if radio.driver.remove_pending_short_address(address) {
+            OT_ERROR_NONE
+        } else {
+            OT_ERROR_NO_ADDRESS
+        }
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn otPlatRadioClearSrcMatchExtEntry(
+    _instance: *mut otInstance,
+    address: *const otExtAddress,
+) -> otError {
+    let address = u64::from_le_bytes((*address).m8);
+    with_radio(|radio| {
+        if radio.driver.remove_pending_ext_address(address) {
+            OT_ERROR_NONE
+        } else {
+            OT_ERROR_NO_ADDRESS
+        }
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioClearSrcMatchShortEntries(_instance: *mut otInstance) {
+    with_radio(|radio| radio.driver.clear_pending_short_addresses());
+}
+
+#[no_mangle]
+pub extern "C" fn otPlatRadioClearSrcMatchExtEntries(_instance: *mut otInstance) {
+    with_radio(|radio| radio.driver.clear_pending_ext_addresses());
+}
+
+/// Report `link_metrics` in the enhanced ACKs sent to the probing initiator,
+/// whose extended address is in big-endian byte order, or stop reporting
+/// them if none are set
+#[no_mangle]
+pub unsafe extern "C" fn otPlatRadioConfigureEnhAckProbing(
+    _instance: *mut otInstance,
+    link_metrics: otLinkMetrics,
+    short_address: u16,
+    ext_address: *const otExtAddress,
+) -> otError {
+    let bits = link_metrics._bitfield_1;
+    let metrics = Metrics {
+        pdu_count: bits & OT_LINK_METRICS_PDU_COUNT != 0,
+        lqi: bits & OT_LINK_METRICS_LQI != 0,
+        link_margin: bits & OT_LINK_METRICS_LINK_MARGIN != 0,
+        rssi: bits & OT_LINK_METRICS_RSSI != 0,
+    };
+    let neighbor = Neighbor {
+        short_address,
+        ext_address: u64::from_be_bytes((*ext_address).m8),
+    };
+
+    with_radio(|radio| {
+        match radio
+            .driver
+            .configure_link_metrics_probing(neighbor, metrics)
+        {
+            Ok(()) => OT_ERROR_NONE,
+            Err(Error::BadInput) => OT_ERROR_INVALID_ARGS,
+            Err(_) => OT_ERROR_NO_BUFS,
+        }
+    })
+    .unwrap_or(OT_ERROR_INVALID_STATE)
+}
+
+fn to_ot_error(result: Result<(), Error>) -> otError {
+    match result {
+        Ok(()) => OT_ERROR_NONE,
+        Err(_) => OT_ERROR_NO_BUFS,
+    }
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use core::ptr::{null, null_mut};
+    use std::{cell::RefCell, sync::MutexGuard, vec::Vec};
+
+    use super::*;
+    use crate::{hal::mock, raw::ieee802154_receiving};
+
+    const EUI64: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    // broadcast data frame from 0x0001 in PAN 0x1234, with room for the FCS
+    const PSDU: [u8; 12] = [
+        0x41, 0x88, 1, 0x34, 0x12, 0xff, 0xff, 0x01, 0x00, 0xaa, 0, 0,
+    ];
+
+    /// What was reported to OpenThread
+    #[derive(Debug, PartialEq)]
+    enum Reported {
+        TxStarted,
+        TxDone(otError),
+        Received { channel: u8 },
+        EnergyScanDone(i8),
+    }
+
+    std::thread_local! {
+        static REPORTED: RefCell<Vec<Reported>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn report(reported: Reported) {
+        REPORTED.with(|reports| reports.borrow_mut().push(reported));
+    }
+
+    #[no_mangle]
+    unsafe extern "C" fn otPlatRadioReceiveDone(
+        _instance: *mut otInstance,
+        frame: *mut otRadioFrame,
+        _error: otError,
+    ) {
+        report(Reported::Received {
+            channel: (*frame).mChannel,
+        });
+    }
+
+    #[no_mangle]
+    extern "C" fn otPlatRadioTxStarted(_instance: *mut otInstance, _frame: *mut otRadioFrame) {
+        report(Reported::TxStarted);
+    }
+
+    #[no_mangle]
+    extern "C" fn otPlatRadioTxDone(
+        _instance: *mut otInstance,
+        _frame: *mut otRadioFrame,
+        _ack_frame: *mut otRadioFrame,
+        error: otError,
+    ) {
+        report(Reported::TxDone(error));
+    }
+
+    #[no_mangle]
+    extern "C" fn otPlatRadioEnergyScanDone(_instance: *mut otInstance, rssi: i8) {
+        report(Reported::EnergyScanDone(rssi));
+    }
+
+    fn now() -> u64 {
+        0
+    }
+
+    /// Hand the driver to the platform, enabled and receiving on channel 15
+    fn enabled() -> MutexGuard<'static, ()> {
+        let lock = mock::test_lock();
+        let driver = Ieee802154::new(crate::hal::IEEE802154, &mut crate::hal::RADIO_CLK);
+        init(driver, EUI64, now);
+
+        assert_eq!(otPlatRadioEnable(null_mut()), OT_ERROR_NONE);
+        assert_eq!(otPlatRadioReceive(null_mut(), 15), OT_ERROR_NONE);
+        REPORTED.with(|reports| reports.borrow_mut().clear());
+
+        lock
+    }
+
+    /// What is reported to OpenThread by [`process`]
+    fn process() -> Vec<Reported> {
+        unsafe { super::process(null_mut()) };
+        REPORTED.with(|reports| reports.take())
+    }
+
+    /// Transmit [`PSDU`] on channel 15 without CSMA-CA, then receive on
+    /// `rx_channel`
+    fn transmit(rx_channel: u8) -> otError {
+        unsafe {
+            let frame = &mut *otPlatRadioGetTransmitBuffer(null_mut());
+            core::slice::from_raw_parts_mut(frame.mPsdu, PSDU.len()).copy_from_slice(&PSDU);
+            frame.mLength = PSDU.len() as u16;
+            frame.mChannel = 15;
+            frame.mInfo.mTxInfo = otRadioFrameTxInfo {
+                mAesKey: null(),
+                mIeInfo: null_mut(),
+                mTxDelay: 0,
+                mTxDelayBaseTime: 0,
+                mMaxCsmaBackoffs: 0,
+                mMaxFrameRetries: 0,
+                mRxChannelAfterTxDone: rx_channel,
+                mTxPower: OT_RADIO_POWER_INVALID,
+                _bitfield_1: 0,
+            };
+
+            otPlatRadioTransmit(null_mut(), frame)
+        }
+    }
+
+    #[test]
+    fn energy_scan_is_refused_while_busy() {
+        let _lock = enabled();
+
+        assert_eq!(otPlatRadioEnergyScan(null_mut(), 20, 1), OT_ERROR_NONE);
+        assert_eq!(otPlatRadioEnergyScan(null_mut(), 21, 1), OT_ERROR_BUSY);
+        assert!(mock::energy_detect_done(-70));
+        assert_eq!(process(), [Reported::EnergyScanDone(-70)]);
+        // the radio receives again after the scan
+        assert!(ieee802154_receiving());
+
+        assert_eq!(otPlatRadioEnergyScan(null_mut(), 20, 1), OT_ERROR_NONE);
+        assert!(mock::energy_detect_aborted());
+        assert_eq!(
+            process(),
+            [Reported::EnergyScanDone(OT_RADIO_POWER_INVALID)]
+        );
+
+        assert_eq!(transmit(15), OT_ERROR_NONE);
+        assert_eq!(otPlatRadioEnergyScan(null_mut(), 20, 1), OT_ERROR_BUSY);
+        assert_eq!(process(), [Reported::TxStarted]);
+        assert_eq!(otPlatRadioEnergyScan(null_mut(), 20, 1), OT_ERROR_BUSY);
+    }
+
+    #[test]
+    fn receives_on_rx_channel_after_tx_done() {
+        let _lock = enabled();
+
+        assert_eq!(transmit(20), OT_ERROR_NONE);
+        assert_eq!(process(), [Reported::TxStarted]);
+        assert_eq!(mock::transmitted().unwrap(), PSDU[..PSDU.len() - 2]);
+        mock::transmit_done();
+        assert_eq!(process(), [Reported::TxDone(OT_ERROR_NONE)]);
+
+        assert!(ieee802154_receiving());
+        assert_eq!(otPlatRadioGetState(null_mut()), OT_RADIO_STATE_RECEIVE);
+        assert!(mock::receive(&PSDU[..PSDU.len() - 2], -50, 200));
+        assert_eq!(process(), [Reported::Received { channel: 20 }]);
+    }
+
+    #[test]
+    fn sleep_stops_receiving() {
+        let _lock = enabled();
+
+        assert_eq!(otPlatRadioSleep(null_mut()), OT_ERROR_NONE);
+        assert!(!ieee802154_receiving());
+        assert_eq!(otPlatRadioGetState(null_mut()), OT_RADIO_STATE_SLEEP);
+
+        // not even once an operation is done
+        assert_eq!(otPlatRadioEnergyScan(null_mut(), 15, 1), OT_ERROR_NONE);
+        assert!(mock::energy_detect_done(-80));
+        assert_eq!(process(), [Reported::EnergyScanDone(-80)]);
+        assert!(!ieee802154_receiving());
+        assert!(!mock::receive(&PSDU[..PSDU.len() - 2], -50, 200));
+
+        assert_eq!(otPlatRadioReceive(null_mut(), 15), OT_ERROR_NONE);
+        assert!(ieee802154_receiving());
+    }
+}
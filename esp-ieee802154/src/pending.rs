@@ -0,0 +1,146 @@
+//! Tables of the addresses frames are pending for, which decide the frame
+//! pending bit of the ACKs sent by the radio
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use heapless::Vec;
+
+use crate::{
+    pib::{ieee802154_pib_get_pending_mode, PendingMode},
+    Error,
+};
+
+const PENDING_TABLE_SIZE: usize = 20;
+
+// IEEE 802.15.4-2006
+const FRAME_VERSION_MAX: u16 = 1;
+
+const ADDR_MODE_NONE: u8 = 0;
+const ADDR_MODE_SHORT: u8 = 2;
+const ADDR_MODE_EXT: u8 = 3;
+
+#[cfg_attr(feature = "sim", thread_local)]
+static PENDING_SHORT: Mutex<RefCell<Vec<u16, PENDING_TABLE_SIZE>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+#[cfg_attr(feature = "sim", thread_local)]
+static PENDING_EXT: Mutex<RefCell<Vec<u64, PENDING_TABLE_SIZE>>> =
+    Mutex::new(RefCell::new(Vec::new()));
+
+pub(crate) fn add_short_address(address: u16) -> Result<(), Error> {
+    critical_section::with(|cs| add(&mut PENDING_SHORT.borrow_ref_mut(cs), address))
+}
+
+pub(crate) fn add_ext_address(address: u64) -> Result<(), Error> {
+    critical_section::with(|cs| add(&mut PENDING_EXT.borrow_ref_mut(cs), address))
+}
+
+pub(crate) fn remove_short_address(address: u16) -> bool {
+    critical_section::with(|cs| remove(&mut PENDING_SHORT.borrow_ref_mut(cs), address))
+}
+
+pub(crate) fn remove_ext_address(address: u64) -> bool {
+    critical_section::with(|cs| remove(&mut PENDING_EXT.borrow_ref_mut(cs), address))
+}
+
+pub(crate) fn clear_short_addresses() {
+    critical_section::with(|cs| PENDING_SHORT.borrow_ref_mut(cs).clear());
+}
+
+pub(crate) fn clear_ext_addresses() {
+    critical_section::with(|cs| PENDING_EXT.borrow_ref_mut(cs).clear());
+}
+
+fn add<T: PartialEq>(table: &mut Vec<T, PENDING_TABLE_SIZE>, address: T) -> Result<(), Error> {
+    if table.contains(&address) {
+        return Ok(());
+    }
+
+    table.push(address).map_err(|_| Error::Full)
+}
+
+fn remove<T: PartialEq>(table: &mut Vec<T, PENDING_TABLE_SIZE>, address: T) -> bool {
+    match table.iter().position(|entry| *entry == address) {
+        Some(index) => {
+            table.swap_remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// The frame pending bit of the ACK for `frame`, given without the length
+/// byte, according to the pending mode
+pub(crate) fn ack_frame_pending(frame: &[u8]) -> bool {
+    let mode = ieee802154_pib_get_pending_mode();
+    if mode == PendingMode::Disable {
+        return true;
+    }
+
+    let in_table = match src_address(frame) {
+        Some(SrcAddress::Short(address)) => {
+            critical_section::with(|cs| PENDING_SHORT.borrow_ref(cs).contains(&address))
+        }
+        Some(SrcAddress::Ext(address)) => {
+            // the ZigBee mode only looks up short addresses
+            if mode == PendingMode::Zigbee {
+                return true;
+            }
+
+            critical_section::with(|cs| PENDING_EXT.borrow_ref(cs).contains(&address))
+        }
+        None => return false,
+    };
+
+    if mode == PendingMode::Zigbee {
+        !in_table
+    } else {
+        in_table
+    }
+}
+
+enum SrcAddress {
+    Short(u16),
+    Ext(u64),
+}
+
+/// The source address of `frame`, for frames of version 2006 or older
+fn src_address(frame: &[u8]) -> Option<SrcAddress> {
+    if frame.len() < 3 {
+        return None;
+    }
+
+    let fcf = u16::from_le_bytes([frame[0], frame[1]]);
+    if (fcf >> 12) & 0b11 > FRAME_VERSION_MAX {
+        return None;
+    }
+
+    let pan_id_compression = fcf & (1 << 6) != 0;
+    let dst_mode = ((fcf >> 10) & 0b11) as u8;
+    let src_mode = ((fcf >> 14) & 0b11) as u8;
+
+    let mut offset = 3;
+    offset += match dst_mode {
+        ADDR_MODE_SHORT => 4,
+        ADDR_MODE_EXT => 10,
+        _ => 0,
+    };
+    if src_mode != ADDR_MODE_NONE && !pan_id_compression {
+        offset += 2;
+    }
+
+    match src_mode {
+        ADDR_MODE_SHORT => {
+            let address = frame.get(offset..offset + 2)?;
+            Some(SrcAddress::Short(u16::from_le_bytes([
+                address[0], address[1],
+            ])))
+        }
+        ADDR_MODE_EXT => {
+            let mut address = [0u8; 8];
+            address.copy_from_slice(frame.get(offset..offset + 8)?);
+            Some(SrcAddress::Ext(u64::from_le_bytes(address)))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,128 @@
+//! [`smoltcp`] device, used with the `smoltcp` feature
+//!
+//! [`Ieee802154`] implements [`Device`] with the IEEE 802.15.4 medium, so it
+//! can be used by a smoltcp `Interface` to run IPv6 over 6LoWPAN. smoltcp
+//! passes frames without the FCS, which is added and checked by the radio.
+//!
+//! Frames are transmitted without CSMA-CA, and frames the radio reports
+//! with a [`RxFault`](crate::RxFault) are dropped.
+
+use smoltcp::{
+    phy::{self, Device, DeviceCapabilities, Medium},
+    time::Instant,
+};
+
+use crate::{
+    netdev::{self, MAX_PSDU_LEN},
+    Ieee802154, RawReceived,
+};
+
+/// A frame received by [`Ieee802154`]
+pub struct RxToken {
+    received: RawReceived,
+}
+
+/// A transmission by [`Ieee802154`]
+pub struct TxToken<'d, 'a> {
+    driver: &'d mut Ieee802154<'a>,
+}
+
+impl<'a> Device for Ieee802154<'a> {
+    type RxToken<'d>
+        = RxToken
+    where
+        Self: 'd;
+    type TxToken<'d>
+        = TxToken<'d, 'a>
+    where
+        Self: 'd;
+
+    fn receive(&mut self, _timestamp: Instant) -> Option<(Self::RxToken<'_>, Self::TxToken<'_>)> {
+        let received = netdev::receive(self)?;
+        Some((RxToken { received }, TxToken { driver: self }))
+    }
+
+    fn transmit(&mut self, _timestamp: Instant) -> Option<Self::TxToken<'_>> {
+        netdev::can_transmit().then_some(TxToken { driver: self })
+    }
+
+    /// The MTU is the size of the PSDU including the FCS, smoltcp limits the
+    /// frames it builds to leave room for the FCS
+    fn capabilities(&self) -> DeviceCapabilities {
+        let mut caps = DeviceCapabilities::default();
+        caps.medium = Medium::Ieee802154;
+        caps.max_transmission_unit = MAX_PSDU_LEN;
+        caps
+    }
+}
+
+impl phy::RxToken for RxToken {
+    fn consume<R, F>(self, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        netdev::consume_received(self.received, f)
+    }
+}
+
+impl<'d, 'a> phy::TxToken for TxToken<'d, 'a> {
+    fn consume<R, F>(self, len: usize, f: F) -> R
+    where
+        F: FnOnce(&mut [u8]) -> R,
+    {
+        netdev::transmit(self.driver, len, f)
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use std::{
+        sync::{Arc, Barrier},
+        time::Duration,
+    };
+
+    use smoltcp::phy::{RxToken as _, TxToken as _};
+
+    use super::*;
+    use crate::{
+        hal::sim,
+        netdev::tests::{data_frame, node, poll_until, receiver, sender},
+    };
+
+    #[test]
+    fn rx_token_passes_frame_without_fcs() {
+        let medium = sim::Medium::new(Default::default());
+        let barrier = Arc::new(Barrier::new(2));
+        let sender = sender(&medium, &barrier, data_frame(1, 0xffff, 60));
+
+        let mut radio = node(&medium, 1);
+        assert_eq!(radio.capabilities().max_transmission_unit, MAX_PSDU_LEN);
+        barrier.wait();
+
+        let frame = poll_until(|| {
+            let (rx, _) = Device::receive(&mut radio, Instant::ZERO)?;
+            Some(rx.consume(|frame| frame.to_vec()))
+        });
+        assert_eq!(frame.unwrap(), data_frame(1, 0xffff, 60));
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn tx_token_transmits_frame() {
+        let medium = sim::Medium::new(Default::default());
+        let barrier = Arc::new(Barrier::new(2));
+        let receiver = receiver(&medium, &barrier, Duration::from_millis(20));
+
+        let mut radio = node(&medium, 1);
+        barrier.wait();
+
+        let frame = data_frame(3, 2, 30);
+        let tx = Device::transmit(&mut radio, Instant::ZERO).unwrap();
+        tx.consume(frame.len(), |buffer| buffer.copy_from_slice(&frame));
+
+        sim::run_for(Duration::from_millis(5));
+        assert!(Device::transmit(&mut radio, Instant::ZERO).is_some());
+        assert_eq!(radio.stats().tx_done, 1);
+        assert_eq!(receiver.join().unwrap(), [frame]);
+    }
+}
@@ -2,18 +2,27 @@ use core::cell::RefCell;
 
 use critical_section::Mutex;
 
-use crate::hal::{
-    set_cca_mode, set_cca_threshold, set_coordinator, set_freq, set_multipan_enable_mask,
-    set_multipan_ext_addr, set_multipan_panid, set_multipan_short_addr, set_pending_mode,
-    set_power, set_promiscuous, set_rx_auto_ack, set_tx_auto_ack, set_tx_enhance_ack,
+use crate::{
+    hal::{
+        set_cca_mode, set_cca_threshold, set_coordinator, set_freq, set_multipan_enable_mask,
+        set_multipan_ext_addr, set_multipan_panid, set_multipan_short_addr, set_pending_mode,
+        set_power, set_promiscuous, set_rx_auto_ack, set_tx_auto_ack, set_tx_enhance_ack,
+    },
+    Channel, Config, Error,
 };
 
-pub(crate) const CONFIG_IEEE802154_CCA_THRESHOLD: i8 = 1;
+pub(crate) const CONFIG_IEEE802154_CCA_THRESHOLD: i8 = -60;
+pub(crate) const CCA_THRESHOLD_MIN: i8 = -120;
+pub(crate) const CCA_THRESHOLD_MAX: i8 = 0;
+pub(crate) const TX_POWER_MIN: i8 = -32;
+pub(crate) const TX_POWER_MAX: i8 = 13;
+const TX_POWER_STEP: i8 = 3;
 pub(crate) const IEEE802154_FRAME_EXT_ADDR_SIZE: usize = 8;
 
 const IEEE802154_MULTIPAN_0: u8 = 0;
 const IEEE802154_MULTIPAN_MAX: usize = 4;
 
+#[cfg_attr(feature = "sim", thread_local)]
 static PIB: Mutex<RefCell<Option<Pib>>> = Mutex::new(RefCell::new(None));
 
 /// Frame pending mode
@@ -46,6 +55,28 @@ pub enum CcaMode {
     CarrierAndEd = 0x03,
 }
 
+/// Transmit power used by the radio
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TxPower {
+    /// Power in dBm, rounded down to the 3 dB steps of the radio
+    pub dbm: i8,
+    /// Step written to the radio, from 0 for -32 dBm to 15 for 13 dBm
+    pub step: u8,
+}
+
+impl TxPower {
+    /// The power used for a requested power of `dbm`, which is clamped to the
+    /// range of the radio
+    pub const fn from_dbm(dbm: i8) -> Self {
+        let step = ieee802154_txpower_convert(dbm);
+
+        Self {
+            dbm: TX_POWER_MIN + step as i8 * TX_POWER_STEP,
+            step,
+        }
+    }
+}
+
 #[derive(Debug, Default, Clone, Copy)]
 struct Pib {
     auto_ack_tx: bool,
@@ -55,7 +86,7 @@ struct Pib {
     coordinator: bool,
     rx_when_idle: bool,
     txpower: i8,
-    channel: u8,
+    channel: Channel,
     pending_mode: PendingMode,
     multipan_mask: u8,
     panid: [u16; IEEE802154_MULTIPAN_MAX],
@@ -75,7 +106,7 @@ pub(crate) fn ieee802154_pib_init() {
             promiscuous: true,
             rx_when_idle: false,
             txpower: 10,
-            channel: 11,
+            channel: Channel::MIN,
             pending_mode: PendingMode::Disable,
             multipan_mask: 1 << IEEE802154_MULTIPAN_0,
             panid: [0u16; 4],
@@ -111,6 +142,10 @@ pub(crate) fn ieee802154_pib_set_auto_ack_rx(enable: bool) {
     });
 }
 
+pub(crate) fn ieee802154_pib_get_auto_ack_rx() -> bool {
+    critical_section::with(|cs| PIB.borrow_ref_mut(cs).as_mut().unwrap().auto_ack_rx)
+}
+
 pub(crate) fn ieee802154_pib_set_enhance_ack_tx(enable: bool) {
     critical_section::with(|cs| {
         PIB.borrow_ref_mut(cs).as_mut().unwrap().enhance_ack_tx = enable;
@@ -133,24 +168,36 @@ pub(crate) fn ieee802154_pib_get_rx_when_idle() -> bool {
     critical_section::with(|cs| PIB.borrow_ref_mut(cs).as_mut().unwrap().rx_when_idle)
 }
 
+pub(crate) fn ieee802154_pib_get_multipan_mask() -> u8 {
+    critical_section::with(|cs| PIB.borrow_ref_mut(cs).as_mut().unwrap().multipan_mask)
+}
+
 pub(crate) fn ieee802154_pib_set_tx_power(power: i8) {
     critical_section::with(|cs| {
         PIB.borrow_ref_mut(cs).as_mut().unwrap().txpower = power;
     });
 }
 
-pub(crate) fn ieee802154_pib_set_channel(channel: u8) {
+pub(crate) fn ieee802154_pib_set_channel(channel: Channel) {
     critical_section::with(|cs| {
         PIB.borrow_ref_mut(cs).as_mut().unwrap().channel = channel;
     });
 }
 
+pub(crate) fn ieee802154_pib_get_tx_power() -> i8 {
+    critical_section::with(|cs| PIB.borrow_ref_mut(cs).as_mut().unwrap().txpower)
+}
+
 pub(crate) fn ieee802154_pib_set_pending_mode(mode: PendingMode) {
     critical_section::with(|cs| {
         PIB.borrow_ref_mut(cs).as_mut().unwrap().pending_mode = mode;
     });
 }
 
+pub(crate) fn ieee802154_pib_get_pending_mode() -> PendingMode {
+    critical_section::with(|cs| PIB.borrow_ref_mut(cs).as_mut().unwrap().pending_mode)
+}
+
 pub(crate) fn ieee802154_pib_set_short_address(index: u8, address: u16) {
     critical_section::with(|cs| {
         PIB.borrow_ref_mut(cs).as_mut().unwrap().short_addr[index as usize] = address;
@@ -166,6 +213,13 @@ pub(crate) fn ieee802154_pib_set_extended_address(
     });
 }
 
+#[cfg(feature = "embassy-net-driver")]
+pub(crate) fn ieee802154_pib_get_extended_address(
+    index: u8,
+) -> [u8; IEEE802154_FRAME_EXT_ADDR_SIZE] {
+    critical_section::with(|cs| PIB.borrow_ref_mut(cs).as_mut().unwrap().ext_addr[index as usize])
+}
+
 pub(crate) fn ieee802154_pib_set_cca_theshold(cca_threshold: i8) {
     critical_section::with(|cs| {
         PIB.borrow_ref_mut(cs).as_mut().unwrap().cca_threshold = cca_threshold;
@@ -178,6 +232,51 @@ pub(crate) fn ieee802154_pib_set_cca_mode(mode: CcaMode) {
     });
 }
 
+/// Configuration held by the PIB, with the settings kept outside of it set to
+/// their defaults
+pub(crate) fn ieee802154_pib_get_config() -> Config {
+    critical_section::with(|cs| {
+        let pib = PIB.borrow_ref(cs);
+        let pib = pib.as_ref().unwrap();
+
+        Config {
+            auto_ack_tx: pib.auto_ack_tx,
+            auto_ack_rx: pib.auto_ack_rx,
+            enhance_ack_tx: pib.enhance_ack_tx,
+            promiscuous: pib.promiscuous,
+            coordinator: pib.coordinator,
+            rx_when_idle: pib.rx_when_idle,
+            txpower: pib.txpower,
+            channel: pib.channel,
+            cca_threshold: pib.cca_threshold,
+            cca_mode: pib.cca_mode,
+            pan_id: Some(pib.panid[IEEE802154_MULTIPAN_0 as usize]),
+            short_addr: Some(pib.short_addr[IEEE802154_MULTIPAN_0 as usize]),
+            ext_addr: Some(u64::from_be_bytes(
+                pib.ext_addr[IEEE802154_MULTIPAN_0 as usize],
+            )),
+            pending_mode: pib.pending_mode,
+            ..Default::default()
+        }
+    })
+}
+
+pub(crate) fn check_tx_power(power: i8) -> Result<(), Error> {
+    if !(TX_POWER_MIN..=TX_POWER_MAX).contains(&power) {
+        return Err(Error::BadInput);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn check_cca_threshold(cca_threshold: i8) -> Result<(), Error> {
+    if !(CCA_THRESHOLD_MIN..=CCA_THRESHOLD_MAX).contains(&cca_threshold) {
+        return Err(Error::BadInput);
+    }
+
+    Ok(())
+}
+
 pub(crate) fn ieee802154_pib_update() {
     critical_section::with(|cs| {
         let mut pib = PIB.borrow_ref_mut(cs);
@@ -202,8 +301,8 @@ pub(crate) fn ieee802154_pib_update() {
     });
 }
 
-fn channel_to_freq(channel: u8) -> u8 {
-    (channel - 11) * 5 + 3
+fn channel_to_freq(channel: Channel) -> u8 {
+    (channel.number() - Channel::MIN.number()) * 5 + 3
 }
 
 fn ieee802154_set_multipan_hal(pib: &Pib) {
@@ -211,20 +310,68 @@ fn ieee802154_set_multipan_hal(pib: &Pib) {
         if (pib.multipan_mask & (1 << index)) != 0 {
             set_multipan_panid(index.into(), pib.panid[index]);
             set_multipan_short_addr(index.into(), pib.short_addr[index]);
-            set_multipan_ext_addr(index.into(), pib.ext_addr[index].as_ptr() as *const u8);
+            set_multipan_ext_addr(index.into(), pib.ext_addr[index].as_ptr());
         }
     }
 }
 
-fn ieee802154_txpower_convert(txpower: i8) -> u8 {
-    const IEEE802154_TXPOWER_VALUE_MAX: i8 = 13;
-    const IEEE802154_TXPOWER_VALUE_MIN: i8 = -32;
-
-    if txpower > IEEE802154_TXPOWER_VALUE_MAX {
+const fn ieee802154_txpower_convert(txpower: i8) -> u8 {
+    if txpower > TX_POWER_MAX {
         15
-    } else if txpower < IEEE802154_TXPOWER_VALUE_MIN {
+    } else if txpower < TX_POWER_MIN {
         0
     } else {
-        ((txpower - IEEE802154_TXPOWER_VALUE_MIN) / 3) as u8
+        ((txpower - TX_POWER_MIN) / TX_POWER_STEP) as u8
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tx_power_rounds_down_to_3_db_steps() {
+        for (dbm, step, effective) in [
+            (-32, 0, -32),
+            (-30, 0, -32),
+            (-29, 1, -29),
+            (0, 10, -2),
+            (10, 14, 10),
+            (12, 14, 10),
+            (13, 15, 13),
+        ] {
+            assert_eq!(
+                TxPower::from_dbm(dbm),
+                TxPower {
+                    dbm: effective,
+                    step
+                }
+            );
+            assert_eq!(step as i8, (dbm + 32) / 3);
+        }
+
+        // powers out of range are clamped
+        assert_eq!(TxPower::from_dbm(-40), TxPower::from_dbm(TX_POWER_MIN));
+        assert_eq!(TxPower::from_dbm(20), TxPower::from_dbm(TX_POWER_MAX));
+    }
+
+    #[test]
+    fn out_of_range_settings_are_rejected() {
+        for power in [TX_POWER_MIN, 0, TX_POWER_MAX] {
+            assert!(check_tx_power(power).is_ok());
+        }
+        for power in [i8::MIN, TX_POWER_MIN - 1, TX_POWER_MAX + 1, i8::MAX] {
+            assert!(matches!(check_tx_power(power), Err(Error::BadInput)));
+        }
+
+        for threshold in [CCA_THRESHOLD_MIN, -60, CCA_THRESHOLD_MAX] {
+            assert!(check_cca_threshold(threshold).is_ok());
+        }
+        for threshold in [i8::MIN, CCA_THRESHOLD_MIN - 1, CCA_THRESHOLD_MAX + 1] {
+            assert!(matches!(
+                check_cca_threshold(threshold),
+                Err(Error::BadInput)
+            ));
+        }
     }
 }
@@ -0,0 +1,423 @@
+//! Vendor-neutral radio traits
+//!
+//! The traits follow the `Transmit`, `Receive`, `Rssi`, `Channel` and `Power`
+//! traits of the `radio` crate, so code written against them can drive this
+//! radio as well as other IEEE 802.15.4 transceivers. Frames are passed
+//! without the FCS, which is added and checked by the radio.
+
+use core::fmt::Debug;
+
+use crate::{
+    pib::check_tx_power,
+    raw::{
+        ieee802154_energy_detect_if_idle, ieee802154_energy_detect_result,
+        ieee802154_energy_detect_stop, ieee802154_receive_if_idle, ieee802154_reconfigure,
+        ieee802154_rx_pending, ieee802154_tx_ack_requested, ieee802154_tx_outcome, set_channel,
+        set_tx_power, TxOutcome,
+    },
+    Error, Ieee802154, RxFault,
+};
+
+/// Largest frame which can be transmitted or received, without the FCS
+pub const MAX_FRAME_LEN: usize = 125;
+
+// Duration of the energy measurement of [`Rssi::poll_rssi`], 8 symbols as for
+// the energy detection of IEEE 802.15.4
+const RSSI_SYMBOLS: u32 = 8;
+
+// Polls for the result of the energy measurement before giving up on it, far
+// longer than the measurement takes
+const RSSI_POLLS: u32 = 10_000;
+
+/// Transmission of frames
+pub trait Transmit {
+    type Error: Debug;
+
+    /// Start transmitting `data`
+    fn start_transmit(&mut self, data: &[u8]) -> Result<(), Self::Error>;
+
+    /// Check whether the transmission started last has completed
+    fn check_transmit(&mut self) -> Result<bool, Self::Error>;
+}
+
+/// Reception of frames
+pub trait Receive {
+    type Error: Debug;
+    type Info: ReceiveInfo;
+
+    /// Start receiving frames
+    fn start_receive(&mut self) -> Result<(), Self::Error>;
+
+    /// Check whether a frame was received, restarting reception if `restart`
+    /// is set and it was stopped
+    fn check_receive(&mut self, restart: bool) -> Result<bool, Self::Error>;
+
+    /// Copy a received frame to `buffer`, returns its length and information
+    /// about the reception
+    fn get_received(&mut self, buffer: &mut [u8]) -> Result<(usize, Self::Info), Self::Error>;
+}
+
+/// Information about a received frame
+pub trait ReceiveInfo: Debug + Default {
+    /// RSSI the frame was received with, in dBm
+    fn rssi(&self) -> i16;
+}
+
+/// Signal strength measurement
+pub trait Rssi {
+    type Error: Debug;
+
+    /// Return the current RSSI, in dBm
+    fn poll_rssi(&mut self) -> Result<i16, Self::Error>;
+}
+
+/// Channel selection
+pub trait Channel {
+    type Channel: Debug;
+    type Error: Debug;
+
+    /// Select the channel used for transmitting and receiving
+    fn set_channel(&mut self, channel: &Self::Channel) -> Result<(), Self::Error>;
+}
+
+/// Transmit power selection
+pub trait Power {
+    type Error: Debug;
+
+    /// Set the transmit power, in dBm
+    fn set_power(&mut self, power: i8) -> Result<(), Self::Error>;
+}
+
+/// Why a transmission of [`Ieee802154`] failed
+#[derive(Debug, Clone, Copy)]
+pub enum TxError {
+    /// The frame was rejected before transmitting it
+    Frame(Error),
+    /// The frame requested an ACK, which was not received
+    NoAck,
+    /// The frame was not transmitted as the channel was busy
+    ChannelBusy,
+    /// The transmission was aborted, e.g. by a reception or coexistence
+    Aborted,
+}
+
+impl From<Error> for TxError {
+    fn from(err: Error) -> Self {
+        TxError::Frame(err)
+    }
+}
+
+/// Information about a frame received by [`Ieee802154`]
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RxInfo {
+    /// Receiver channel
+    pub channel: crate::Channel,
+    /// Received Signal Strength Indicator (RSSI)
+    pub rssi: i8,
+    /// Link Quality Indication (LQI), as reported by the radio
+    pub lqi: u8,
+    /// Why the frame would have been dropped, see
+    /// [`Config::rx_bad_frames`](crate::Config::rx_bad_frames)
+    pub fault: Option<RxFault>,
+}
+
+impl ReceiveInfo for RxInfo {
+    fn rssi(&self) -> i16 {
+        self.rssi as i16
+    }
+}
+
+impl<'a> Transmit for Ieee802154<'a> {
+    type Error = TxError;
+
+    fn start_transmit(&mut self, data: &[u8]) -> Result<(), Self::Error> {
+        if data.len() > MAX_FRAME_LEN {
+            return Err(Error::BadInput.into());
+        }
+
+        // room for the FCS, which is filled in by the radio
+        let mut frame = [0u8; MAX_FRAME_LEN + 2];
+        frame[..data.len()].copy_from_slice(data);
+
+        Ok(self.transmit_raw(&frame[..data.len() + 2])?)
+    }
+
+    /// Only completes once the ACK was received if the frame requested one,
+    /// the error of a failed transmission is returned until the next one
+    fn check_transmit(&mut self) -> Result<bool, Self::Error> {
+        match ieee802154_tx_outcome() {
+            TxOutcome::Pending => Ok(false),
+            TxOutcome::Sent => Ok(!ieee802154_tx_ack_requested()),
+            TxOutcome::Acked { .. } => Ok(true),
+            TxOutcome::NoAck => Err(TxError::NoAck),
+            TxOutcome::ChannelBusy => Err(TxError::ChannelBusy),
+            TxOutcome::Aborted => Err(TxError::Aborted),
+        }
+    }
+}
+
+impl<'a> Receive for Ieee802154<'a> {
+    type Error = Error;
+    type Info = RxInfo;
+
+    fn start_receive(&mut self) -> Result<(), Self::Error> {
+        Ieee802154::start_receive(self);
+        Ok(())
+    }
+
+    /// Reception is only restarted if the radio is idle, an ongoing
+    /// transmission, ACK or energy detection is not interrupted
+    fn check_receive(&mut self, restart: bool) -> Result<bool, Self::Error> {
+        if ieee802154_rx_pending() {
+            return Ok(true);
+        }
+
+        if restart {
+            ieee802154_receive_if_idle();
+        }
+
+        Ok(false)
+    }
+
+    /// Returns a length of `0` if no frame was received, and
+    /// [`Error::Incomplete`] if the frame does not fit into `buffer`, in which
+    /// case it is dropped.
+    fn get_received(&mut self, buffer: &mut [u8]) -> Result<(usize, Self::Info), Self::Error> {
+        let Some(token) = self.get_received_token() else {
+            return Ok((0, RxInfo::default()));
+        };
+
+        // the last two bytes hold the RSSI and LQI instead of the FCS
+        let data = token.data();
+        let frame = &data[..data.len().saturating_sub(2)];
+        if frame.len() > buffer.len() {
+            return Err(Error::Incomplete);
+        }
+        buffer[..frame.len()].copy_from_slice(frame);
+
+        let info = RxInfo {
+            channel: token.channel(),
+            rssi: token.rssi(),
+            lqi: token.hw_lqi(),
+            fault: token.fault(),
+        };
+
+        Ok((frame.len(), info))
+    }
+}
+
+impl<'a> Rssi for Ieee802154<'a> {
+    type Error = Error;
+
+    /// Measures the energy on the channel, [`Error::Incomplete`] while the
+    /// radio is transmitting or receiving, or if the radio aborted the
+    /// measurement or did not complete it in time
+    fn poll_rssi(&mut self) -> Result<i16, Self::Error> {
+        ieee802154_energy_detect_if_idle(RSSI_SYMBOLS)?;
+
+        for _ in 0..RSSI_POLLS {
+            if let Some(result) = ieee802154_energy_detect_result() {
+                return result.map(i16::from);
+            }
+            core::hint::spin_loop();
+        }
+
+        ieee802154_energy_detect_stop();
+        Err(Error::Incomplete)
+    }
+}
+
+impl<'a> Channel for Ieee802154<'a> {
+    type Channel = crate::Channel;
+    type Error = Error;
+
+    /// Takes effect right away, or once the ongoing transmission is done
+    fn set_channel(&mut self, channel: &crate::Channel) -> Result<(), Self::Error> {
+        set_channel(*channel);
+        ieee802154_reconfigure();
+        Ok(())
+    }
+}
+
+impl<'a> Power for Ieee802154<'a> {
+    type Error = Error;
+
+    /// Takes effect right away, or once the ongoing transmission is done,
+    /// [`Error::BadInput`] unless `power` is from -32 to 13 dBm
+    fn set_power(&mut self, power: i8) -> Result<(), Self::Error> {
+        check_tx_power(power)?;
+        set_tx_power(power);
+        ieee802154_reconfigure();
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "sim"))]
+mod tests {
+    use std::{
+        sync::{Arc, Barrier},
+        thread,
+        time::{Duration, Instant},
+        vec::Vec,
+    };
+
+    use super::*;
+    use crate::{
+        hal::{mock, sim},
+        raw::ieee802154_receiving,
+        Config,
+    };
+
+    fn node(medium: &sim::Medium, short_addr: u16) -> Ieee802154<'static> {
+        let mut radio = Ieee802154::new(mock::IEEE802154, &mut mock::RADIO_CLK);
+        medium.attach();
+        radio.set_config(Config {
+            channel: crate::Channel::new(15).unwrap(),
+            pan_id: Some(0x1234),
+            short_addr: Some(short_addr),
+            auto_ack_rx: true,
+            auto_ack_tx: true,
+            rx_when_idle: true,
+            ..Default::default()
+        });
+        radio.start_receive();
+        radio
+    }
+
+    fn data_frame(ack_request: bool, destination: u16, len: usize) -> Vec<u8> {
+        let mut frame = std::vec![if ack_request { 0x61 } else { 0x41 }, 0x88, 1, 0x34, 0x12];
+        frame.extend_from_slice(&destination.to_le_bytes());
+        frame.extend_from_slice(&[0x01, 0x00]);
+        frame.resize(len, 0x55);
+        frame
+    }
+
+    /// Every result of `check_transmit` until the transmission is done
+    fn check_until_done(radio: &mut Ieee802154<'static>) -> Vec<Result<bool, TxError>> {
+        let until = Instant::now() + Duration::from_millis(50);
+        let mut results = Vec::new();
+
+        while Instant::now() < until {
+            let result = radio.check_transmit();
+            results.push(result);
+            if !matches!(result, Ok(false)) {
+                break;
+            }
+        }
+
+        results
+    }
+
+    #[test]
+    fn check_transmit_waits_for_ack() {
+        let medium = sim::Medium::new(Default::default());
+        let barrier = Arc::new(Barrier::new(2));
+
+        let receiver = thread::spawn({
+            let (medium, barrier) = (medium.clone(), barrier.clone());
+            move || {
+                let _radio = node(&medium, 2);
+                barrier.wait();
+                sim::run_for(Duration::from_millis(30));
+            }
+        });
+
+        let mut radio = node(&medium, 1);
+        barrier.wait();
+        radio.start_transmit(&data_frame(true, 2, 20)).unwrap();
+        let results = check_until_done(&mut radio);
+        assert!(matches!(results.last(), Some(Ok(true))));
+        receiver.join().unwrap();
+
+        // no node has the address, the transmit done is not enough
+        radio.start_transmit(&data_frame(true, 3, 20)).unwrap();
+        let results = check_until_done(&mut radio);
+        assert!(matches!(results.last(), Some(Err(TxError::NoAck))));
+        assert!(results[..results.len() - 1]
+            .iter()
+            .all(|result| matches!(result, Ok(false))));
+        assert_eq!(radio.stats().tx_done, 2);
+        assert!(matches!(radio.check_transmit(), Err(TxError::NoAck)));
+
+        // without requesting an ACK the frame is done once sent
+        radio.start_transmit(&data_frame(false, 2, 20)).unwrap();
+        let results = check_until_done(&mut radio);
+        assert!(matches!(results.last(), Some(Ok(true))));
+
+        assert!(matches!(
+            radio.start_transmit(&[0; MAX_FRAME_LEN + 1]),
+            Err(TxError::Frame(Error::BadInput))
+        ));
+    }
+
+    #[test]
+    fn check_receive_does_not_interrupt_transmission() {
+        let medium = sim::Medium::new(Default::default());
+        let mut radio = node(&medium, 1);
+
+        // no node has the address, so the ACK is awaited until it times out
+        radio.start_transmit(&data_frame(true, 2, 20)).unwrap();
+        assert!(matches!(radio.check_receive(true), Ok(false)));
+        assert!(!ieee802154_receiving());
+
+        let results = check_until_done(&mut radio);
+        assert!(matches!(results.last(), Some(Err(TxError::NoAck))));
+        assert!(matches!(radio.check_receive(true), Ok(false)));
+        assert!(ieee802154_receiving());
+    }
+
+    #[test]
+    fn poll_rssi_measures_the_channel() {
+        let medium = sim::Medium::new(Default::default());
+        let barrier = Arc::new(Barrier::new(2));
+
+        let sender = thread::spawn({
+            let (medium, barrier) = (medium.clone(), barrier.clone());
+            move || {
+                let mut radio = node(&medium, 2);
+                barrier.wait();
+                radio
+                    .start_transmit(&data_frame(false, 0xffff, MAX_FRAME_LEN))
+                    .unwrap();
+                sim::run_for(Duration::from_millis(10));
+            }
+        });
+
+        // the radio is idle, neither receiving nor transmitting
+        let mut radio = Ieee802154::new(mock::IEEE802154, &mut mock::RADIO_CLK);
+        medium.attach();
+        radio.set_config(Config {
+            channel: crate::Channel::new(15).unwrap(),
+            ..Default::default()
+        });
+        assert_eq!(radio.poll_rssi().unwrap(), -100);
+        assert!(!ieee802154_receiving());
+
+        barrier.wait();
+        thread::sleep(Duration::from_millis(1));
+        assert_eq!(radio.poll_rssi().unwrap(), -50);
+        sender.join().unwrap();
+
+        radio.start_receive();
+        assert!(matches!(radio.poll_rssi(), Err(Error::Incomplete)));
+        assert!(ieee802154_receiving());
+
+        radio.start_transmit(&data_frame(false, 2, 20)).unwrap();
+        assert!(matches!(radio.poll_rssi(), Err(Error::Incomplete)));
+    }
+
+    #[test]
+    fn poll_rssi_gives_up_without_result() {
+        // not attached to a medium, so the measurement never completes
+        let mut radio = Ieee802154::new(mock::IEEE802154, &mut mock::RADIO_CLK);
+        radio.set_config(Config {
+            channel: crate::Channel::new(15).unwrap(),
+            rx_when_idle: true,
+            ..Default::default()
+        });
+
+        assert!(matches!(radio.poll_rssi(), Err(Error::Incomplete)));
+        // the radio went back to receiving while idle
+        assert!(ieee802154_receiving());
+    }
+}
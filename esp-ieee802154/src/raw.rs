@@ -1,52 +1,97 @@
 use core::cell::RefCell;
 
-use critical_section::Mutex;
-use esp_wifi_sys::include::{
-    esp_phy_calibration_data_t, esp_phy_calibration_mode_t_PHY_RF_CAL_FULL,
-    ieee802154_coex_event_t, ieee802154_coex_event_t_IEEE802154_IDLE,
-    ieee802154_coex_event_t_IEEE802154_LOW, ieee802154_coex_event_t_IEEE802154_MIDDLE,
-    register_chipv7_phy,
-};
+use byte::BytesExt;
+use critical_section::{CriticalSection, Mutex};
 use heapless::spsc::Queue;
+use ieee802154::mac::{Address, FrameContent, FrameType, FrameVersion, Header, ShortAddress};
 
 use crate::{
     frame::{frame_get_version, frame_is_ack_required, FRAME_VERSION_1, FRAME_VERSION_2},
     hal::*,
+    ie::{HeaderIe, Ies},
+    link_metrics::{self, MAX_PROBING_METRICS},
+    pending::ack_frame_pending,
     pib::*,
-};
-use esp_hal::{
-    interrupt::Priority,
-    peripherals::RADIO_CLK,
-    prelude::handler,
-    system::{RadioClockController, RadioPeripherals},
+    rssi_to_lqi,
+    stats::*,
+    Channel, Config, Error, Frame,
 };
 
 pub(crate) const FRAME_SIZE: usize = 129;
 
-const PHY_ENABLE_VERSION_PRINT: u32 = 1;
-
-static mut RX_BUFFER: [u8; FRAME_SIZE] = [0u8; FRAME_SIZE];
-static RX_QUEUE: Mutex<RefCell<Queue<RawReceived, 20>>> = Mutex::new(RefCell::new(Queue::new()));
+// Provides `RX_QUEUE_SIZE`, see `build.rs`
+include!(concat!(env!("OUT_DIR"), "/config.rs"));
+
+// One buffer per queued frame, plus the buffer the DMA is currently receiving
+// into and the buffer which may be borrowed by an `RxToken`
+const RX_BUFFER_COUNT: usize = RX_QUEUE_SIZE + 2;
+
+#[cfg_attr(feature = "sim", thread_local)]
+static mut RX_BUFFERS: [[u8; FRAME_SIZE]; RX_BUFFER_COUNT] = [[0u8; FRAME_SIZE]; RX_BUFFER_COUNT];
+#[cfg_attr(feature = "sim", thread_local)]
+static mut ENH_ACK_BUFFER: [u8; FRAME_SIZE] = [0u8; FRAME_SIZE];
+#[cfg_attr(feature = "sim", thread_local)]
+static RX_CURRENT: Mutex<RefCell<u8>> = Mutex::new(RefCell::new(0));
+// A `heapless::spsc::Queue` holds one element less than its size
+#[cfg_attr(feature = "sim", thread_local)]
+static RX_FREE: Mutex<RefCell<Queue<u8, { RX_BUFFER_COUNT + 1 }>>> =
+    Mutex::new(RefCell::new(Queue::new()));
+#[cfg_attr(feature = "sim", thread_local)]
+static RX_QUEUE: Mutex<RefCell<Queue<RxSlot, { RX_QUEUE_SIZE + 1 }>>> =
+    Mutex::new(RefCell::new(Queue::new()));
+#[cfg_attr(feature = "sim", thread_local)]
+static RX_QUEUE_POLICY: Mutex<RefCell<OverflowPolicy>> =
+    Mutex::new(RefCell::new(OverflowPolicy::DropNewest));
+#[cfg_attr(feature = "sim", thread_local)]
+static RX_BAD_FRAMES: Mutex<RefCell<bool>> = Mutex::new(RefCell::new(false));
+#[cfg_attr(feature = "sim", thread_local)]
+static RX_LAST_RSSI: Mutex<RefCell<i8>> = Mutex::new(RefCell::new(0));
+// Whether the SFD of a frame was received, and the frame is still being
+// received
+#[cfg_attr(feature = "sim", thread_local)]
+static RX_IN_PROGRESS: Mutex<RefCell<bool>> = Mutex::new(RefCell::new(false));
+#[cfg_attr(feature = "sim", thread_local)]
 static STATE: Mutex<RefCell<Ieee802154State>> = Mutex::new(RefCell::new(Ieee802154State::Idle));
-
-extern "C" {
-    fn bt_bb_v2_init_cmplx(print_version: u32); // from libbtbb.a
-
-    fn bt_bb_set_zb_tx_on_delay(time: u16); // from libbtbb.a
-
-    fn esp_coex_ieee802154_ack_pti_set(event: ieee802154_coex_event_t); // from ???
-
-    fn esp_coex_ieee802154_txrx_pti_set(event: ieee802154_coex_event_t); // from ???
-
-    fn phy_version_print(); // from libphy.a
-}
+#[cfg_attr(feature = "sim", thread_local)]
+static TX_OUTCOME: Mutex<RefCell<TxOutcome>> = Mutex::new(RefCell::new(TxOutcome::Sent));
+#[cfg_attr(feature = "sim", thread_local)]
+static ED_RESULT: Mutex<RefCell<Option<Result<i8, Error>>>> = Mutex::new(RefCell::new(None));
+// Address of the frame being transmitted
+#[cfg_attr(feature = "sim", thread_local)]
+static TX_FRAME: Mutex<RefCell<usize>> = Mutex::new(RefCell::new(0));
+// Whether the PIB changed while the radio was busy, and is to be applied once
+// the ongoing operation completes
+#[cfg_attr(feature = "sim", thread_local)]
+static PIB_UPDATE_PENDING: Mutex<RefCell<bool>> = Mutex::new(RefCell::new(false));
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum Ieee802154State {
     Idle,
     Receive,
     Transmit,
+    /// Waiting for the ACK of the transmitted frame
+    RxAck,
     TxAck,
+    TxEnhAck,
+    EnergyDetect,
+}
+
+/// What became of the most recent transmission
+#[allow(unused)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum TxOutcome {
+    /// The frame is being transmitted
+    Pending,
+    /// The frame was transmitted, an ACK may still be received
+    Sent,
+    /// An ACK was received for the frame
+    Acked { frame_pending: bool },
+    /// No ACK was received for the frame
+    NoAck,
+    /// The frame was not transmitted as the channel was busy
+    ChannelBusy,
+    /// The transmission was aborted for another reason
+    Aborted,
 }
 
 #[allow(unused)]
@@ -59,59 +104,98 @@ enum Ieee802154TxRxScene {
     RxAt,
 }
 
+/// What to do with a received frame when the receive queue is full
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OverflowPolicy {
+    /// Drop the frame which was just received
+    #[default]
+    DropNewest,
+    /// Drop the oldest frame in the queue to make room for the new one
+    DropOldest,
+}
+
+/// When a new configuration took effect, see
+/// [`Ieee802154::reconfigure`](crate::Ieee802154::reconfigure)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reconfiguration {
+    /// The radio was idle or receiving, and uses the configuration already
+    Applied,
+    /// The radio was transmitting, detecting energy or receiving a frame, and
+    /// applies the configuration once done
+    Deferred,
+}
+
+/// Why the radio rejected a received frame
+///
+/// Rejected frames are only delivered if `Config::rx_bad_frames` is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RxFault {
+    /// The CRC of the frame is invalid
+    CrcError,
+    /// The frame did not pass the address filter, it may be truncated
+    FilterFail,
+}
+
 /// A raw payload received on some channel
 #[derive(Debug)]
 pub struct RawReceived {
     /// Payload
     pub data: [u8; FRAME_SIZE],
     /// Receiver channel
-    pub channel: u8,
+    pub channel: Channel,
+    /// Why the frame was rejected by the radio, if it was
+    pub fault: Option<RxFault>,
+}
+
+/// A queued frame, owning its receive buffer until it is released
+#[derive(Debug)]
+pub(crate) struct RxSlot {
+    pub(crate) buffer: u8,
+    pub(crate) channel: Channel,
+    /// Multipan interfaces whose address filter accepted the frame
+    pub(crate) interfaces: u8,
+    pub(crate) fault: Option<RxFault>,
+}
+
+impl RxSlot {
+    /// The receive buffer holding the frame, starting with the length byte
+    ///
+    /// The buffer is not written to by the radio until it is handed back via
+    /// [`ieee802154_release`], which consumes the slot and so ends the borrow.
+    pub(crate) fn data(&self) -> &[u8; FRAME_SIZE] {
+        unsafe { &*core::ptr::addr_of!(RX_BUFFERS[self.buffer as usize]) }
+    }
 }
 
 pub(crate) fn esp_ieee802154_enable(radio_clock_control: &mut RADIO_CLK) {
-    radio_clock_control.init_clocks();
-    radio_clock_control.enable(RadioPeripherals::Phy);
-    radio_clock_control.enable(RadioPeripherals::Ieee802154);
+    enable_clocks(radio_clock_control);
 
-    esp_phy_enable();
-    esp_btbb_enable();
+    phy_enable();
+    btbb_enable();
+    ieee802154_rx_buffers_init();
     ieee802154_mac_init();
 
-    unsafe { phy_version_print() }; // libphy.a
+    phy_version_print();
     log::info!("date={:x}", mac_date());
 }
 
-fn esp_phy_enable() {
-    unsafe {
-        let mut calibration_data = esp_phy_calibration_data_t {
-            version: [0u8; 4],
-            mac: [0u8; 6],
-            opaque: [0u8; 1894],
-        };
+fn ieee802154_rx_buffers_init() {
+    critical_section::with(|cs| {
+        let mut free = RX_FREE.borrow_ref_mut(cs);
+        while free.dequeue().is_some() {}
 
-        register_chipv7_phy(
-            core::ptr::null(),
-            &mut calibration_data as *mut esp_phy_calibration_data_t,
-            esp_phy_calibration_mode_t_PHY_RF_CAL_FULL,
-        );
-    }
-}
+        let mut queue = RX_QUEUE.borrow_ref_mut(cs);
+        while queue.dequeue().is_some() {}
 
-fn esp_btbb_enable() {
-    unsafe { bt_bb_v2_init_cmplx(PHY_ENABLE_VERSION_PRINT) };
+        *RX_CURRENT.borrow_ref_mut(cs) = 0;
+        for buffer in 1..RX_BUFFER_COUNT {
+            free.enqueue(buffer as u8).ok();
+        }
+    });
 }
 
 fn ieee802154_mac_init() {
-    #[cfg(feature = "esp32c6")]
-    unsafe {
-        extern "C" {
-            static mut coex_pti_tab_ptr: u32;
-            static coex_pti_tab: u8;
-        }
-
-        // Manually set `coex_pti_tab_ptr` pointing to `coex_pti_tab`
-        core::ptr::addr_of_mut!(coex_pti_tab_ptr).write_volatile(&coex_pti_tab as *const _ as u32);
-    }
+    coex_init();
 
     ieee802154_pib_init();
 
@@ -127,41 +211,44 @@ fn ieee802154_mac_init() {
             | TxAbortReason::TxStop,
     );
     enable_rx_abort_events(
-        RxAbortReason::TxAckTimeout | RxAbortReason::TxAckCoexBreak | RxAbortReason::RxStop,
+        RxAbortReason::TxAckTimeout
+            | RxAbortReason::TxAckCoexBreak
+            | RxAbortReason::RxStop
+            | RxAbortReason::EdAbort
+            | RxAbortReason::EdCoexReject,
     );
 
     set_ed_sample_mode(EdSampleMode::Avg);
 
-    unsafe { esp_coex_ieee802154_ack_pti_set(ieee802154_coex_event_t_IEEE802154_MIDDLE) };
+    // Have the radio write the LQI in place of the second byte of the FCS,
+    // after the RSSI written in place of the first, and the frequency offset
+    // right after the frame. The length byte is left unchanged, so it still
+    // counts the two bytes of the FCS but not the frequency offset.
+    set_rx_append_lqi(true);
+    set_rx_append_freq_offset(true);
+
+    set_coex_ack_pti(CoexEvent::Middle);
     ieee802154_set_txrx_pti(Ieee802154TxRxScene::Idle);
 
-    unsafe {
-        bt_bb_set_zb_tx_on_delay(50); // set tx on delay for libbtbb.a
-    }
+    set_tx_on_delay(50);
     set_rx_on_delay(50);
 
     // memset(s_rx_frame, 0, sizeof(s_rx_frame));
     // s_ieee802154_state = IEEE802154_STATE_IDLE;
 
-    unsafe {
-        esp_hal::interrupt::bind_interrupt(
-            esp_hal::peripherals::Interrupt::ZB_MAC,
-            ZB_MAC.handler(),
-        );
-    }
-    esp_hal::interrupt::enable(esp_hal::peripherals::Interrupt::ZB_MAC, ZB_MAC.priority()).unwrap();
+    enable_interrupt();
 }
 
 fn ieee802154_set_txrx_pti(txrx_scene: Ieee802154TxRxScene) {
     match txrx_scene {
         Ieee802154TxRxScene::Idle => {
-            unsafe { esp_coex_ieee802154_txrx_pti_set(ieee802154_coex_event_t_IEEE802154_IDLE) };
+            set_coex_txrx_pti(CoexEvent::Idle);
         }
         Ieee802154TxRxScene::Tx | Ieee802154TxRxScene::Rx => {
-            unsafe { esp_coex_ieee802154_txrx_pti_set(ieee802154_coex_event_t_IEEE802154_LOW) };
+            set_coex_txrx_pti(CoexEvent::Low);
         }
         Ieee802154TxRxScene::TxAt | Ieee802154TxRxScene::RxAt => {
-            unsafe { esp_coex_ieee802154_txrx_pti_set(ieee802154_coex_event_t_IEEE802154_MIDDLE) };
+            set_coex_txrx_pti(CoexEvent::Middle);
         }
     }
 }
@@ -169,7 +256,8 @@ fn ieee802154_set_txrx_pti(txrx_scene: Ieee802154TxRxScene) {
 pub fn tx_init(frame: *const u8) {
     let tx_frame = frame;
     stop_current_operation();
-    ieee802154_pib_update();
+    critical_section::with(|cs| *TX_FRAME.borrow_ref_mut(cs) = tx_frame as usize);
+    pib_update();
     ieee802154_sec_update();
 
     set_tx_addr(tx_frame);
@@ -187,11 +275,12 @@ pub fn ieee802154_transmit(frame: *const u8, cca: bool) -> i32 {
         tx_init(frame);
 
         ieee802154_set_txrx_pti(Ieee802154TxRxScene::Tx);
+        *TX_OUTCOME.borrow_ref_mut(cs) = TxOutcome::Pending;
 
         if cca {
-            // disable_events(IEEE802154_EVENT_ED_DONE);
-            // set_cmd(IEEE802154_CMD_CCA_TX_START);
-            // ieee802154_state = IEEE802154_STATE_TX_CCA;
+            // the radio aborts the transmission if the channel is busy
+            set_cmd(Command::CcaTxStart);
+            *STATE.borrow_ref_mut(cs) = Ieee802154State::Transmit;
         } else {
             set_cmd(Command::TxStart);
             // if (ieee802154_frame_get_type(frame) == IEEE802154_FRAME_TYPE_ACK
@@ -222,19 +311,238 @@ pub fn ieee802154_receive() -> i32 {
     0 // ESP-OK
 }
 
-pub fn ieee802154_poll() -> Option<RawReceived> {
+/// Start receiving if the radio is idle, without stopping an ongoing
+/// transmission, ACK or energy detection
+pub(crate) fn ieee802154_receive_if_idle() {
+    critical_section::with(|cs| {
+        if *STATE.borrow_ref(cs) == Ieee802154State::Idle {
+            ieee802154_receive();
+        }
+    });
+}
+
+pub(crate) fn ieee802154_poll() -> Option<RxSlot> {
+    backend_pump();
+
     critical_section::with(|cs| {
         let mut queue = RX_QUEUE.borrow_ref_mut(cs);
         queue.dequeue()
     })
 }
 
+/// Whether a received frame is waiting to be polled
+pub(crate) fn ieee802154_rx_pending() -> bool {
+    backend_pump();
+
+    critical_section::with(|cs| !RX_QUEUE.borrow_ref(cs).is_empty())
+}
+
+/// Whether the radio is receiving, and not busy with a received frame
+#[cfg(all(test, feature = "mock"))]
+pub(crate) fn ieee802154_receiving() -> bool {
+    backend_pump();
+
+    critical_section::with(|cs| *STATE.borrow_ref(cs) == Ieee802154State::Receive)
+}
+
+/// Whether the radio was started and keeps receiving whenever it does not
+/// transmit
+#[cfg(feature = "embassy-net-driver")]
+pub(crate) fn ieee802154_listening() -> bool {
+    backend_pump();
+
+    critical_section::with(|cs| {
+        ieee802154_pib_get_rx_when_idle() && *STATE.borrow_ref(cs) != Ieee802154State::Idle
+    })
+}
+
+/// Whether a transmission was started and has not completed yet
+#[cfg(any(feature = "smoltcp", feature = "embassy-net-driver"))]
+pub(crate) fn ieee802154_tx_pending() -> bool {
+    backend_pump();
+
+    critical_section::with(|cs| {
+        matches!(
+            *STATE.borrow_ref(cs),
+            Ieee802154State::Transmit | Ieee802154State::RxAck
+        )
+    })
+}
+
+/// Whether the radio is transmitting, either a frame or the ACK of a received
+/// frame
+pub(crate) fn ieee802154_tx_busy() -> bool {
+    backend_pump();
+
+    critical_section::with(|cs| {
+        matches!(
+            *STATE.borrow_ref(cs),
+            Ieee802154State::Transmit
+                | Ieee802154State::RxAck
+                | Ieee802154State::TxAck
+                | Ieee802154State::TxEnhAck
+        )
+    })
+}
+
+/// What became of the most recent transmission
+pub(crate) fn ieee802154_tx_outcome() -> TxOutcome {
+    backend_pump();
+
+    critical_section::with(|cs| *TX_OUTCOME.borrow_ref(cs))
+}
+
+/// Whether an ACK is received for the most recent transmission before its
+/// outcome is final
+pub(crate) fn ieee802154_tx_ack_requested() -> bool {
+    critical_section::with(tx_ack_requested)
+}
+
+/// Measure the energy on the current channel for `duration` symbols, the
+/// result is returned by [`ieee802154_energy_detect_result`]
+#[cfg(any(feature = "openthread", feature = "spinel"))]
+pub(crate) fn ieee802154_energy_detect(duration: u32) {
+    critical_section::with(|cs| {
+        stop_current_operation();
+        pib_update();
+        energy_detect_start(cs, duration);
+    });
+}
+
+/// Measure the energy on the current channel for `duration` symbols if the
+/// radio is idle, without stopping an ongoing transmission or reception
+pub(crate) fn ieee802154_energy_detect_if_idle(duration: u32) -> Result<(), Error> {
+    backend_pump();
+
+    critical_section::with(|cs| {
+        if *STATE.borrow_ref(cs) != Ieee802154State::Idle {
+            return Err(Error::Incomplete);
+        }
+
+        pib_update();
+        energy_detect_start(cs, duration);
+        Ok(())
+    })
+}
+
+fn energy_detect_start(cs: CriticalSection<'_>, duration: u32) {
+    *ED_RESULT.borrow_ref_mut(cs) = None;
+    set_ed_duration(duration);
+    set_cmd(Command::EdStart);
+    *STATE.borrow_ref_mut(cs) = Ieee802154State::EnergyDetect;
+}
+
+/// The energy measured by the most recent energy detection, in dBm, once it
+/// is done, or `Error::Incomplete` if the radio aborted it
+pub(crate) fn ieee802154_energy_detect_result() -> Option<Result<i8, Error>> {
+    backend_pump();
+
+    critical_section::with(|cs| ED_RESULT.borrow_ref_mut(cs).take())
+}
+
+/// Give up on an ongoing energy detection
+pub(crate) fn ieee802154_energy_detect_stop() {
+    let detecting = critical_section::with(|cs| {
+        let detecting = *STATE.borrow_ref(cs) == Ieee802154State::EnergyDetect;
+        if detecting {
+            stop_current_operation();
+        }
+        detecting
+    });
+
+    if detecting {
+        next_operation();
+    }
+}
+
+/// Stop receiving, or the ongoing operation
+#[cfg(any(feature = "openthread", feature = "spinel"))]
+pub(crate) fn ieee802154_sleep() {
+    critical_section::with(|cs| {
+        stop_current_operation();
+        if *PIB_UPDATE_PENDING.borrow_ref(cs) {
+            pib_update();
+        }
+        *STATE.borrow_ref_mut(cs) = Ieee802154State::Idle;
+    });
+}
+
+/// Apply the PIB to the radio right away if it is idle or receiving, or else
+/// once the ongoing operation completes, without aborting it
+///
+/// Receiving is restarted, the frames in the receive queue are kept. A frame
+/// whose SFD was received, or whose events are not handled yet, is not
+/// discarded by restarting, the PIB is applied once it is handled.
+pub(crate) fn ieee802154_reconfigure() -> Reconfiguration {
+    critical_section::with(|cs| {
+        let state = *STATE.borrow_ref(cs);
+        match state {
+            Ieee802154State::Idle => {
+                pib_update();
+                Reconfiguration::Applied
+            }
+            Ieee802154State::Receive if rx_restartable(cs) => {
+                stop_current_operation();
+                pib_update();
+                enable_rx();
+                Reconfiguration::Applied
+            }
+            _ => {
+                *PIB_UPDATE_PENDING.borrow_ref_mut(cs) = true;
+                Reconfiguration::Deferred
+            }
+        }
+    })
+}
+
+/// Whether a configuration deferred by [`ieee802154_reconfigure`] has not been
+/// applied yet
+pub(crate) fn ieee802154_reconfigure_pending() -> bool {
+    backend_pump();
+
+    critical_section::with(|cs| *PIB_UPDATE_PENDING.borrow_ref(cs))
+}
+
+/// RSSI of the most recently received frame
+#[cfg(any(feature = "openthread", feature = "spinel"))]
+pub(crate) fn ieee802154_last_rssi() -> i8 {
+    critical_section::with(|cs| *RX_LAST_RSSI.borrow_ref(cs))
+}
+
+// The host backends have no interrupts, they deliver frames and complete
+// transmissions when the driver polls
+#[inline(always)]
+fn backend_pump() {
+    #[cfg(feature = "sim")]
+    crate::hal::sim::pump();
+    #[cfg(feature = "std")]
+    crate::hal::socket::pump();
+    #[cfg(feature = "capture")]
+    crate::hal::capture::pump();
+}
+
+/// Hand the buffer of a polled frame back to the radio
+pub(crate) fn ieee802154_release(slot: RxSlot) {
+    critical_section::with(|cs| {
+        RX_FREE.borrow_ref_mut(cs).enqueue(slot.buffer).ok();
+    });
+}
+
 fn rx_init() {
     stop_current_operation();
-    ieee802154_pib_update();
+    pib_update();
+}
+
+/// Write the PIB to the radio, which must not be busy
+fn pib_update() {
+    critical_section::with(|cs| {
+        ieee802154_pib_update();
+        *PIB_UPDATE_PENDING.borrow_ref_mut(cs) = false;
+    });
 }
 
 fn enable_rx() {
+    critical_section::with(|cs| *RX_IN_PROGRESS.borrow_ref_mut(cs) = false);
     set_next_rx_buffer();
     ieee802154_set_txrx_pti(Ieee802154TxRxScene::Rx);
 
@@ -243,6 +551,12 @@ fn enable_rx() {
     // ieee802154_state = IEEE802154_STATE_RX;
 }
 
+/// Whether restarting the reception discards no frame, none being received
+/// or waiting for its events to be handled
+fn rx_restartable(cs: CriticalSection<'_>) -> bool {
+    !*RX_IN_PROGRESS.borrow_ref(cs) && get_events() == 0
+}
+
 fn stop_current_operation() {
     let events = get_events();
     set_cmd(Command::Stop);
@@ -250,8 +564,10 @@ fn stop_current_operation() {
 }
 
 fn set_next_rx_buffer() {
+    let current = critical_section::with(|cs| *RX_CURRENT.borrow_ref(cs));
+
     unsafe {
-        set_rx_addr(RX_BUFFER.as_mut_ptr() as *mut u8);
+        set_rx_addr(core::ptr::addr_of_mut!(RX_BUFFERS[current as usize]) as *mut u8);
     }
 }
 
@@ -283,11 +599,10 @@ pub fn set_tx_power(power: i8) {
     ieee802154_pib_set_tx_power(power);
 }
 
-pub fn set_channel(channel: u8) {
+pub fn set_channel(channel: Channel) {
     ieee802154_pib_set_channel(channel);
 }
 
-#[allow(unused)]
 pub fn set_pending_mode(mode: PendingMode) {
     ieee802154_pib_set_pending_mode(mode);
 }
@@ -305,6 +620,11 @@ pub fn set_extended_address(index: u8, address: [u8; IEEE802154_FRAME_EXT_ADDR_S
     ieee802154_pib_set_extended_address(index, address);
 }
 
+#[cfg(feature = "embassy-net-driver")]
+pub fn get_extended_address(index: u8) -> [u8; IEEE802154_FRAME_EXT_ADDR_SIZE] {
+    ieee802154_pib_get_extended_address(index)
+}
+
 pub fn set_cca_theshold(cca_threshold: i8) {
     ieee802154_pib_set_cca_theshold(cca_threshold);
 }
@@ -317,6 +637,39 @@ pub fn set_panid(index: u8, id: u16) {
     ieee802154_pib_set_panid(index, id);
 }
 
+pub fn set_rx_bad_frames(enable: bool) {
+    critical_section::with(|cs| {
+        *RX_BAD_FRAMES.borrow_ref_mut(cs) = enable;
+    });
+
+    let events = RxAbortReason::CrcError | RxAbortReason::FilterFail;
+    if enable {
+        enable_rx_abort_events(events);
+    } else {
+        disable_rx_abort_events(events);
+    }
+}
+
+pub fn set_rx_queue_policy(policy: OverflowPolicy) {
+    critical_section::with(|cs| {
+        *RX_QUEUE_POLICY.borrow_ref_mut(cs) = policy;
+    });
+}
+
+pub fn get_tx_power() -> i8 {
+    ieee802154_pib_get_tx_power()
+}
+
+pub fn get_config() -> Config {
+    let config = ieee802154_pib_get_config();
+
+    critical_section::with(|cs| Config {
+        rx_queue_policy: *RX_QUEUE_POLICY.borrow_ref(cs),
+        rx_bad_frames: *RX_BAD_FRAMES.borrow_ref(cs),
+        ..config
+    })
+}
+
 #[inline(always)]
 fn ieee802154_sec_update() {
     let is_security = false;
@@ -326,7 +679,12 @@ fn ieee802154_sec_update() {
 
 fn next_operation() {
     let previous_operation = critical_section::with(|cs| {
-        let state = STATE.borrow_ref(cs).clone();
+        let state = *STATE.borrow_ref(cs);
+
+        // the operation is done, so a deferred configuration can be applied
+        if *PIB_UPDATE_PENDING.borrow_ref(cs) {
+            pib_update();
+        }
 
         if ieee802154_pib_get_rx_when_idle() {
             enable_rx();
@@ -340,14 +698,13 @@ fn next_operation() {
 
     match previous_operation {
         Ieee802154State::Receive => crate::rx_available(),
-        Ieee802154State::Transmit => crate::tx_done(),
-        Ieee802154State::TxAck => crate::tx_done(),
+        Ieee802154State::Transmit | Ieee802154State::RxAck => crate::tx_done(),
+        Ieee802154State::TxAck | Ieee802154State::TxEnhAck => crate::tx_done(),
         _ => (),
     }
 }
 
-#[handler(priority = "Priority::Priority1")]
-fn ZB_MAC() {
+pub(crate) fn ieee802154_isr() {
     log::trace!("ZB_MAC interrupt");
 
     let events = get_events();
@@ -359,6 +716,11 @@ fn ZB_MAC() {
         // IEEE802154_STATE_TX && IEEE802154_STATE_TX_CCA && IEEE802154_STATE_TX_ENH_ACK
         // for isr processing delay
         log::trace!("rx sfd done");
+        critical_section::with(|cs| {
+            if *STATE.borrow_ref(cs) == Ieee802154State::Receive {
+                *RX_IN_PROGRESS.borrow_ref_mut(cs) = true;
+            }
+        });
     }
 
     if events & Event::TxSfdDone != 0 {
@@ -369,40 +731,90 @@ fn ZB_MAC() {
 
     if events & Event::TxDone != 0 {
         log::trace!("tx done");
-        next_operation();
+        let awaiting_ack = critical_section::with(|cs| {
+            // the ACK of a received frame is not a transmission of the driver
+            if matches!(
+                *STATE.borrow_ref(cs),
+                Ieee802154State::TxAck | Ieee802154State::TxEnhAck
+            ) {
+                return false;
+            }
+
+            stats_tx_done();
+            *TX_OUTCOME.borrow_ref_mut(cs) = TxOutcome::Sent;
+
+            // the radio receives the ACK by itself, the transmission is done
+            // once the ACK is received or timed out
+            let awaiting_ack = tx_ack_requested(cs);
+            if awaiting_ack {
+                *STATE.borrow_ref_mut(cs) = Ieee802154State::RxAck;
+            }
+
+            awaiting_ack
+        });
+
+        if !awaiting_ack {
+            next_operation();
+        }
     }
 
     if events & Event::RxDone != 0 {
         log::trace!("rx done");
-        unsafe {
-            log::trace!("Received raw {:x?}", RX_BUFFER);
-            critical_section::with(|cs| {
-                let mut queue = RX_QUEUE.borrow_ref_mut(cs);
-                if !queue.is_full() {
-                    let item = RawReceived {
-                        data: RX_BUFFER,
-                        channel: freq_to_channel(get_freq()),
-                    };
-                    queue.enqueue(item).ok();
-                } else {
-                    log::warn!("Receive queue full");
-                }
-
-                let frm = &RX_BUFFER[1..][..RX_BUFFER[0] as usize];
-                if will_auto_send_ack(frm) {
-                    *STATE.borrow_ref_mut(cs) = Ieee802154State::TxAck;
-                } else if should_send_enhanced_ack(frm) {
-                    // TODO
-                } else {
-                    // esp_ieee802154_coex_pti_set(IEEE802154_IDLE_RX);
-                    next_operation();
-                }
-            });
-        }
+        critical_section::with(|cs| {
+            *RX_IN_PROGRESS.borrow_ref_mut(cs) = false;
+            let frame = rx_done_swap_buffers(cs, None);
+            log::trace!("Received raw {:x?}", frame);
+
+            let frm = &frame[1..][..frame[0] as usize];
+
+            // decide the frame pending bit of the ACK sent for the frame
+            let frame_pending = ack_frame_pending(frm);
+            set_pending_bit(frame_pending);
+
+            // the offsets of the frame control field include the length byte
+            if will_auto_send_ack(frame) {
+                *STATE.borrow_ref_mut(cs) = Ieee802154State::TxAck;
+            } else if should_send_enhanced_ack(frame)
+                && enh_ack_generate(frm, frame_pending).is_ok()
+            {
+                ieee802154_set_txrx_pti(Ieee802154TxRxScene::Tx);
+                set_tx_addr(core::ptr::addr_of!(ENH_ACK_BUFFER) as *const u8);
+                set_cmd(Command::TxStart);
+                *STATE.borrow_ref_mut(cs) = Ieee802154State::TxEnhAck;
+            } else {
+                // esp_ieee802154_coex_pti_set(IEEE802154_IDLE_RX);
+                next_operation();
+            }
+        });
     }
 
     if events & Event::AckRxDone != 0 {
-        log::info!("EventAckRxDone");
+        log::trace!("EventAckRxDone");
+        let awaiting_ack = critical_section::with(|cs| {
+            if *STATE.borrow_ref(cs) != Ieee802154State::RxAck {
+                return false;
+            }
+
+            // the ACK is received into the current buffer, without being queued
+            let current = *RX_CURRENT.borrow_ref(cs);
+            let ack = unsafe { &*core::ptr::addr_of!(RX_BUFFERS[current as usize]) };
+            *TX_OUTCOME.borrow_ref_mut(cs) = TxOutcome::Acked {
+                frame_pending: ack[1] & FRAME_PENDING_BIT != 0,
+            };
+
+            // the radio writes the RSSI in place of the first byte of the FCS,
+            // a length without the FCS is a corrupted ACK
+            if ack[0] >= 2 && ack[0] as usize <= FRAME_SIZE {
+                let rssi = ack[ack[0] as usize - 1] as i8;
+                link_metrics::record_ack(tx_destination(cs), rssi_to_lqi(rssi), rssi);
+            }
+
+            true
+        });
+
+        if awaiting_ack {
+            next_operation();
+        }
     }
 
     if events & Event::AckTxDone != 0 {
@@ -412,17 +824,231 @@ fn ZB_MAC() {
 
     if events & Event::TxAbort != 0 {
         log::trace!("TxAbort");
+        stats_tx_abort();
+        let reason = get_tx_abort_reason();
         abort_tx();
+
+        let outcome = if reason == TxAbortReason::RxAckTimeout as u8 {
+            TxOutcome::NoAck
+        } else if reason == TxAbortReason::CcaFailed as u8 || reason == TxAbortReason::CcaBusy as u8
+        {
+            TxOutcome::ChannelBusy
+        } else {
+            TxOutcome::Aborted
+        };
+
+        let transmitting = critical_section::with(|cs| {
+            *TX_OUTCOME.borrow_ref_mut(cs) = outcome;
+            matches!(
+                *STATE.borrow_ref(cs),
+                Ieee802154State::Transmit | Ieee802154State::RxAck
+            )
+        });
+
+        // the frame was not transmitted or not ACKed, which completes the
+        // transmission
+        if transmitting {
+            next_operation();
+        }
+    }
+
+    if events & Event::EdDone != 0 {
+        log::trace!("EdDone");
+        let rssi = get_ed_rss();
+        critical_section::with(|cs| *ED_RESULT.borrow_ref_mut(cs) = Some(Ok(rssi)));
+        next_operation();
     }
 
     if events & Event::RxAbort != 0 {
         log::trace!("RxAbort");
+        critical_section::with(|cs| *RX_IN_PROGRESS.borrow_ref_mut(cs) = false);
+        let reason = get_rx_abort_reason();
         abort_rx();
+
+        if reason == RxAbortReason::EdAbort as u8 || reason == RxAbortReason::EdCoexReject as u8 {
+            let detecting = critical_section::with(|cs| {
+                *ED_RESULT.borrow_ref_mut(cs) = Some(Err(Error::Incomplete));
+                *STATE.borrow_ref(cs) == Ieee802154State::EnergyDetect
+            });
+
+            if detecting {
+                next_operation();
+            }
+        }
+
+        let fault = if reason == RxAbortReason::CrcError as u8 {
+            Some(RxFault::CrcError)
+        } else if reason == RxAbortReason::FilterFail as u8 {
+            Some(RxFault::FilterFail)
+        } else {
+            None
+        };
+
+        if fault.is_some() && critical_section::with(|cs| *RX_BAD_FRAMES.borrow_ref(cs)) {
+            // the radio restarts receiving by itself, just hand it a new buffer
+            critical_section::with(|cs| {
+                rx_done_swap_buffers(cs, fault);
+            });
+            set_next_rx_buffer();
+            crate::rx_available();
+        }
     }
+
+    // apply the PIB deferred while a frame was being received, if the radio
+    // keeps receiving after handling it, as when the frame was rejected
+    critical_section::with(|cs| {
+        if *STATE.borrow_ref(cs) == Ieee802154State::Receive
+            && *PIB_UPDATE_PENDING.borrow_ref(cs)
+            && rx_restartable(cs)
+        {
+            stop_current_operation();
+            pib_update();
+            enable_rx();
+        }
+    });
 }
 
-fn freq_to_channel(freq: u8) -> u8 {
-    (freq - 3) / 5 + 11
+/// Queue the frame which was just received and point the radio to a free
+/// buffer for the next reception, returns the received frame
+///
+/// The frame can only be borrowed for the critical section, after which its
+/// buffer may be released by the consumer of the queue.
+fn rx_done_swap_buffers<'cs>(
+    cs: CriticalSection<'cs>,
+    fault: Option<RxFault>,
+) -> &'cs [u8; FRAME_SIZE] {
+    let mut current = RX_CURRENT.borrow_ref_mut(cs);
+    let buffer = *current;
+    let slot = RxSlot {
+        buffer,
+        // the radio only receives on the frequencies of channels
+        channel: freq_to_channel(get_freq()).unwrap_or_default(),
+        interfaces: ieee802154_pib_get_multipan_mask() & !get_filter_fail_status(),
+        fault,
+    };
+
+    let data = slot.data();
+    let len = data[0] as usize;
+    if (2..FRAME_SIZE).contains(&len) {
+        // the radio writes the RSSI in place of the first byte of the FCS
+        *RX_LAST_RSSI.borrow_ref_mut(cs) = data[len - 1] as i8;
+    }
+
+    let mut queue = RX_QUEUE.borrow_ref_mut(cs);
+    let mut free = RX_FREE.borrow_ref_mut(cs);
+    if queue.is_full() {
+        log::warn!("Receive queue full");
+        stats_rx_queue_full();
+
+        if *RX_QUEUE_POLICY.borrow_ref(cs) == OverflowPolicy::DropOldest {
+            if let Some(oldest) = queue.dequeue() {
+                free.enqueue(oldest.buffer).ok();
+            }
+        }
+    }
+
+    // If there is no buffer left the frame is dropped, and its buffer is
+    // received into again
+    if !queue.is_full() {
+        if let Some(next) = free.dequeue() {
+            queue.enqueue(slot).ok();
+            *current = next;
+            stats_rx_queued();
+        } else {
+            log::warn!("No free receive buffer");
+            stats_rx_queue_full();
+        }
+    }
+
+    unsafe { &*core::ptr::addr_of!(RX_BUFFERS[buffer as usize]) }
+}
+
+// In the first byte of the frame control field
+const FRAME_PENDING_BIT: u8 = 1 << 4;
+
+fn freq_to_channel(freq: u8) -> Option<Channel> {
+    Channel::new(freq.checked_sub(3)? / 5 + Channel::MIN.number()).ok()
+}
+
+/// Whether the radio waits for an ACK after transmitting the frame, which it
+/// only does with auto ACK enabled, broadcasts are not ACKed even if they
+/// request it
+fn tx_ack_requested(cs: CriticalSection<'_>) -> bool {
+    let frame = *TX_FRAME.borrow_ref(cs) as *const [u8; FRAME_SIZE];
+    if frame.is_null() {
+        return false;
+    }
+
+    let frame = unsafe { &*frame };
+    ieee802154_pib_get_auto_ack_rx()
+        && frame_is_ack_required(frame)
+        && !matches!(
+            tx_destination(cs),
+            Some(Address::Short(_, ShortAddress::BROADCAST))
+        )
+}
+
+/// The destination of the frame being transmitted
+fn tx_destination(cs: CriticalSection<'_>) -> Option<Address> {
+    let frame = *TX_FRAME.borrow_ref(cs) as *const [u8; FRAME_SIZE];
+    if frame.is_null() {
+        return None;
+    }
+
+    let frame = unsafe { &*frame };
+    let header: Header = frame[1..].read(&mut 0).ok()?;
+    header.destination
+}
+
+/// Write the enhanced ACK for `frame`, given without the length byte, to the
+/// enhanced ACK buffer
+///
+/// The ACK carries the Link Metrics IE if enhanced-ACK based probing is
+/// configured for the sender of the frame.
+fn enh_ack_generate(frame: &[u8], frame_pending: bool) -> Result<(), Error> {
+    let received: Header = frame.read(&mut 0)?;
+
+    // the radio writes the RSSI in place of the first byte of the FCS
+    let rssi = frame[frame.len() - 2] as i8;
+    let mut values = [0u8; MAX_PROBING_METRICS];
+    let len = link_metrics::probe(received.source, rssi_to_lqi(rssi), rssi, &mut values);
+
+    let header = Header {
+        frame_type: FrameType::Acknowledgement,
+        frame_pending,
+        ack_request: false,
+        pan_id_compress: false,
+        seq_no_suppress: false,
+        ie_present: false,
+        version: FrameVersion::Ieee802154,
+        seq: received.seq,
+        destination: received.source,
+        source: None,
+        auxiliary_security_header: None,
+    };
+    let content = FrameContent::Acknowledgement;
+
+    let ack = if len > 0 {
+        let ie = [HeaderIe::LinkMetrics(&values[..len])];
+        let ies = Ies {
+            header: ie[..].into(),
+            payload: Default::default(),
+        };
+        Frame::with_ies(header, content, &ies, &[])?
+    } else {
+        Frame {
+            header,
+            content,
+            payload: Default::default(),
+            footer: [0; 2],
+        }
+    };
+
+    let buffer = unsafe { &mut *core::ptr::addr_of_mut!(ENH_ACK_BUFFER) };
+    let len = ack.encode(&mut buffer[1..])?;
+    buffer[0] = len as u8;
+
+    Ok(())
 }
 
 fn will_auto_send_ack(frame: &[u8]) -> bool {
@@ -434,3 +1060,387 @@ fn should_send_enhanced_ack(frame: &[u8]) -> bool {
         && frame_get_version(frame) <= FRAME_VERSION_2
         && get_tx_enhance_ack()
 }
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use std::sync::MutexGuard;
+
+    use super::*;
+    use crate::hal::mock;
+
+    // data frames from 0x0001 to 0x0002 in PAN 0x1234, with and without ACK
+    // request
+    fn data_frame(sequence: u8, ack_request: bool) -> [u8; 12] {
+        let fcf = if ack_request { 0x61 } else { 0x41 };
+        [
+            fcf, 0x88, sequence, 0x34, 0x12, 0x02, 0x00, 0x01, 0x00, 0xaa, 0x00, 0x00,
+        ]
+    }
+
+    fn enable() -> MutexGuard<'static, ()> {
+        let lock = mock::test_lock();
+
+        esp_ieee802154_enable(&mut mock::RADIO_CLK);
+        critical_section::with(|cs| {
+            *STATE.borrow_ref_mut(cs) = Ieee802154State::Idle;
+            *TX_OUTCOME.borrow_ref_mut(cs) = TxOutcome::Sent;
+            *RX_QUEUE_POLICY.borrow_ref_mut(cs) = OverflowPolicy::DropNewest;
+        });
+        stats_reset();
+
+        lock
+    }
+
+    fn state() -> Ieee802154State {
+        critical_section::with(|cs| *STATE.borrow_ref(cs))
+    }
+
+    fn outcome() -> TxOutcome {
+        critical_section::with(|cs| *TX_OUTCOME.borrow_ref(cs))
+    }
+
+    fn transmit(frame: &[u8]) {
+        // the radio refers to the frame until the transmission is done
+        let buffer = std::boxed::Box::leak(std::boxed::Box::new([0u8; FRAME_SIZE]));
+        buffer[0] = frame.len() as u8;
+        buffer[1..][..frame.len()].copy_from_slice(frame);
+        ieee802154_transmit(buffer.as_ptr(), false);
+    }
+
+    fn poll_sequences() -> std::vec::Vec<u8> {
+        let mut sequences = std::vec::Vec::new();
+        while let Some(slot) = ieee802154_poll() {
+            sequences.push(slot.data()[3]);
+            ieee802154_release(slot);
+        }
+
+        sequences
+    }
+
+    #[test]
+    fn rx_done_queues_frame() {
+        let _lock = enable();
+        ieee802154_receive();
+
+        let frame = data_frame(7, false);
+        assert!(mock::receive(&frame[..10], -60, 0x80));
+
+        let slot = ieee802154_poll().unwrap();
+        let data = slot.data();
+        assert_eq!(data[0], 12);
+        assert_eq!(&data[1..][..10], &frame[..10]);
+        assert_eq!(data[11] as i8, -60);
+        assert_eq!(data[12], 0x80);
+        assert_eq!(
+            critical_section::with(|cs| *RX_LAST_RSSI.borrow_ref(cs)),
+            -60
+        );
+        ieee802154_release(slot);
+
+        assert!(ieee802154_poll().is_none());
+        assert_eq!(stats_get().rx_queued, 1);
+        // the radio is not re-armed unless receiving while idle
+        assert_eq!(state(), Ieee802154State::Idle);
+    }
+
+    #[test]
+    fn rx_token_reports_full_frame() {
+        let _lock = enable();
+        ieee802154_pib_set_channel(Channel::new(26).unwrap());
+        ieee802154_pib_update();
+        ieee802154_receive();
+
+        let mut frame = [0x5a; 125];
+        frame[..10].copy_from_slice(&data_frame(9, false)[..10]);
+        mock::set_freq_offset(-7);
+        assert!(mock::receive(&frame, -42, 0x9c));
+
+        let token = crate::frame::RxToken::new(ieee802154_poll().unwrap());
+        assert_eq!(token.raw()[0], 127);
+        assert_eq!(token.data().len(), 127);
+        assert_eq!(&token.data()[..125], &frame[..]);
+        assert_eq!(token.channel(), Channel::new(26).unwrap());
+        assert_eq!(token.rssi(), -42);
+        assert_eq!(token.lqi(), rssi_to_lqi(-42));
+        assert_eq!(token.hw_lqi(), 0x9c);
+        assert_eq!(token.freq_offset(), -7);
+        assert_eq!(token.interfaces(), 0x01);
+        assert!(token.fault().is_none());
+        assert!(matches!(token.frame(), Ok(frame) if frame.header.seq == 9));
+    }
+
+    #[test]
+    fn next_operation_receives_when_idle() {
+        let _lock = enable();
+        ieee802154_pib_set_rx_when_idle(true);
+
+        transmit(&data_frame(1, false));
+        assert_eq!(state(), Ieee802154State::Transmit);
+        assert_eq!(outcome(), TxOutcome::Pending);
+
+        mock::transmit_done();
+        assert_eq!(state(), Ieee802154State::Receive);
+        assert_eq!(mock::registers().cmd, Command::RxStart as u8);
+
+        assert!(mock::receive(&data_frame(2, false)[..10], -50, 0xff));
+        assert_eq!(state(), Ieee802154State::Receive);
+        assert_eq!(poll_sequences(), [2]);
+    }
+
+    #[test]
+    fn tx_done_sets_outcome() {
+        let _lock = enable();
+
+        transmit(&data_frame(1, false));
+        mock::transmit_done();
+
+        assert_eq!(outcome(), TxOutcome::Sent);
+        assert_eq!(state(), Ieee802154State::Idle);
+        assert_eq!(stats_get().tx_done, 1);
+        assert_eq!(
+            &mock::transmitted().unwrap()[..],
+            &data_frame(1, false)[..10]
+        );
+    }
+
+    #[test]
+    fn tx_abort_sets_outcome() {
+        let _lock = enable();
+
+        transmit(&data_frame(1, true));
+        mock::transmit_done();
+        assert_eq!(state(), Ieee802154State::RxAck);
+        mock::ack_timeout();
+        assert_eq!(outcome(), TxOutcome::NoAck);
+        assert_eq!(state(), Ieee802154State::Idle);
+
+        transmit(&data_frame(2, false));
+        mock::channel_busy();
+        assert_eq!(outcome(), TxOutcome::ChannelBusy);
+        assert_eq!(state(), Ieee802154State::Idle);
+
+        let stats = stats_get();
+        assert_eq!((stats.tx_done, stats.tx_abort), (1, 2));
+        assert_eq!((stats.rx_ack_timeout, stats.cca_busy), (1, 1));
+    }
+
+    #[test]
+    fn ack_rx_done_sets_outcome() {
+        let _lock = enable();
+        ieee802154_pib_set_rx_when_idle(true);
+
+        transmit(&data_frame(3, true));
+        mock::transmit_done();
+        // the radio waits for the ACK instead of receiving
+        assert_eq!(state(), Ieee802154State::RxAck);
+        assert_eq!(outcome(), TxOutcome::Sent);
+        assert_ne!(mock::registers().cmd, Command::RxStart as u8);
+
+        // ACK with the frame pending bit set
+        assert!(mock::ack_received(&[0x12, 0x00, 3], -40));
+        assert_eq!(
+            outcome(),
+            TxOutcome::Acked {
+                frame_pending: true
+            }
+        );
+        assert_eq!(state(), Ieee802154State::Receive);
+        // the ACK is not queued as a received frame
+        assert!(ieee802154_poll().is_none());
+    }
+
+    #[test]
+    fn ack_is_only_awaited_if_requested() {
+        let _lock = enable();
+
+        // broadcasts are not ACKed
+        let mut frame = data_frame(4, true);
+        frame[5..7].copy_from_slice(&[0xff, 0xff]);
+        transmit(&frame);
+        mock::transmit_done();
+        assert_eq!(state(), Ieee802154State::Idle);
+
+        // nor waited for without auto ACK
+        ieee802154_pib_set_auto_ack_rx(false);
+        transmit(&data_frame(5, true));
+        mock::transmit_done();
+        assert_eq!(state(), Ieee802154State::Idle);
+        assert_eq!(outcome(), TxOutcome::Sent);
+
+        // an ACK which is not waited for is ignored
+        assert!(mock::ack_received(&[0x02, 0x00, 5], -40));
+        assert_eq!(outcome(), TxOutcome::Sent);
+        ieee802154_pib_set_auto_ack_rx(true);
+    }
+
+    #[test]
+    fn ack_is_sent_if_requested() {
+        let _lock = enable();
+        ieee802154_pib_set_auto_ack_tx(true);
+        ieee802154_pib_update();
+
+        ieee802154_receive();
+        assert!(mock::receive(&data_frame(6, true)[..10], -50, 0xff));
+        assert_eq!(state(), Ieee802154State::TxAck);
+        mock::transmit_done();
+        assert_eq!(state(), Ieee802154State::Idle);
+        // sending the ACK is not a transmission
+        assert_eq!(stats_get().tx_done, 0);
+
+        ieee802154_receive();
+        assert!(mock::receive(&data_frame(7, false)[..10], -50, 0xff));
+        assert_eq!(state(), Ieee802154State::Idle);
+        assert_eq!(poll_sequences(), [6, 7]);
+    }
+
+    #[test]
+    fn reconfigure_restarts_reception() {
+        let _lock = enable();
+        ieee802154_pib_set_rx_when_idle(true);
+        ieee802154_receive();
+        assert!(mock::receive(&data_frame(1, false)[..10], -50, 0xff));
+
+        ieee802154_pib_set_short_address(0, 0x0042);
+        assert_eq!(ieee802154_reconfigure(), Reconfiguration::Applied);
+        assert_eq!(mock::registers().short_addr[0], 0x0042);
+        assert_eq!(state(), Ieee802154State::Receive);
+        assert_eq!(mock::registers().cmd, Command::RxStart as u8);
+        // the received frames are kept
+        assert_eq!(poll_sequences(), [1]);
+
+        ieee802154_pib_set_short_address(0, 0);
+        assert_eq!(ieee802154_reconfigure(), Reconfiguration::Applied);
+    }
+
+    #[test]
+    fn reconfigure_waits_for_received_frame() {
+        let _lock = enable();
+        ieee802154_pib_set_rx_when_idle(true);
+        ieee802154_receive();
+
+        // the frame is being received
+        assert!(mock::frame_started());
+        ieee802154_pib_set_short_address(0, 0x0042);
+        assert_eq!(ieee802154_reconfigure(), Reconfiguration::Deferred);
+        assert_eq!(mock::registers().short_addr[0], 0);
+        assert!(mock::receive(&data_frame(2, false)[..10], -50, 0xff));
+        assert!(!ieee802154_reconfigure_pending());
+        assert_eq!(mock::registers().short_addr[0], 0x0042);
+
+        // the frame was received, but its events are not handled yet
+        ieee802154_pib_set_short_address(0, 0);
+        mock::without_interrupts(|| {
+            assert!(mock::receive(&data_frame(3, false)[..10], -50, 0xff));
+            assert_eq!(ieee802154_reconfigure(), Reconfiguration::Deferred);
+        });
+        assert!(!ieee802154_reconfigure_pending());
+        assert_eq!(mock::registers().short_addr[0], 0);
+        assert_eq!(poll_sequences(), [2, 3]);
+
+        // the frame is rejected, and the radio keeps receiving
+        assert!(mock::frame_started());
+        ieee802154_pib_set_short_address(0, 0x0042);
+        assert_eq!(ieee802154_reconfigure(), Reconfiguration::Deferred);
+        mock::raise_events(Event::RxAbort as u16);
+        assert!(!ieee802154_reconfigure_pending());
+        assert_eq!(mock::registers().short_addr[0], 0x0042);
+        assert_eq!(state(), Ieee802154State::Receive);
+        assert_eq!(mock::registers().cmd, Command::RxStart as u8);
+
+        ieee802154_pib_set_short_address(0, 0);
+        assert_eq!(ieee802154_reconfigure(), Reconfiguration::Applied);
+    }
+
+    fn fill_queue(policy: OverflowPolicy) -> std::vec::Vec<u8> {
+        set_rx_queue_policy(policy);
+        ieee802154_pib_set_rx_when_idle(true);
+        ieee802154_receive();
+
+        for sequence in 0..RX_QUEUE_SIZE as u8 + 2 {
+            assert!(mock::receive(&data_frame(sequence, false)[..10], -50, 0xff));
+        }
+        assert_eq!(stats_get().rx_queue_full, 2);
+
+        poll_sequences()
+    }
+
+    #[test]
+    fn full_queue_drops_newest() {
+        let _lock = enable();
+
+        let expected: std::vec::Vec<u8> = (0..RX_QUEUE_SIZE as u8).collect();
+        assert_eq!(fill_queue(OverflowPolicy::DropNewest), expected);
+    }
+
+    #[test]
+    fn full_queue_drops_oldest() {
+        let _lock = enable();
+
+        let expected: std::vec::Vec<u8> = (2..RX_QUEUE_SIZE as u8 + 2).collect();
+        assert_eq!(fill_queue(OverflowPolicy::DropOldest), expected);
+    }
+
+    #[test]
+    fn frame_is_dropped_without_free_buffer() {
+        let _lock = enable();
+        ieee802154_pib_set_rx_when_idle(true);
+        ieee802154_receive();
+
+        // hold every buffer but the one received into
+        let mut held = std::vec::Vec::new();
+        for sequence in 0..=RX_QUEUE_SIZE as u8 {
+            assert!(mock::receive(&data_frame(sequence, false)[..10], -50, 0xff));
+            held.push(ieee802154_poll().unwrap());
+        }
+
+        assert!(mock::receive(&data_frame(0xfe, false)[..10], -50, 0xff));
+        assert!(ieee802154_poll().is_none());
+        assert_eq!(stats_get().rx_queue_full, 1);
+
+        held.into_iter().for_each(ieee802154_release);
+        assert!(mock::receive(&data_frame(0xff, false)[..10], -50, 0xff));
+        assert_eq!(poll_sequences(), [0xff]);
+        assert_eq!(stats_get().rx_queued, RX_QUEUE_SIZE as u32 + 2);
+    }
+
+    #[test]
+    fn energy_detect_only_starts_when_idle() {
+        let _lock = enable();
+        ieee802154_pib_set_rx_when_idle(false);
+        ieee802154_receive();
+
+        assert!(matches!(
+            ieee802154_energy_detect_if_idle(8),
+            Err(Error::Incomplete)
+        ));
+        assert!(ieee802154_receiving());
+
+        ieee802154_energy_detect_stop();
+        assert!(ieee802154_receiving());
+        stop_current_operation();
+        critical_section::with(|cs| *STATE.borrow_ref_mut(cs) = Ieee802154State::Idle);
+
+        ieee802154_energy_detect_if_idle(8).unwrap();
+        assert!(mock::energy_detect_done(-70));
+        assert!(matches!(ieee802154_energy_detect_result(), Some(Ok(-70))));
+        assert!(ieee802154_energy_detect_result().is_none());
+        assert_eq!(state(), Ieee802154State::Idle);
+    }
+
+    #[test]
+    fn aborted_energy_detect_reports_error() {
+        let _lock = enable();
+        ieee802154_pib_set_rx_when_idle(false);
+
+        ieee802154_energy_detect_if_idle(8).unwrap();
+        assert_eq!(state(), Ieee802154State::EnergyDetect);
+        assert!(mock::energy_detect_aborted());
+
+        assert!(matches!(
+            ieee802154_energy_detect_result(),
+            Some(Err(Error::Incomplete))
+        ));
+        assert_eq!(state(), Ieee802154State::Idle);
+        assert_eq!(stats_get().ed_abort, 1);
+    }
+}
@@ -0,0 +1,23 @@
+//! Pseudo-random numbers, which don't need to be secure
+
+/// Random numbers for the CSMA-CA backoffs and the percent filter of beacon
+/// requests
+#[derive(Debug, Clone)]
+pub(crate) struct Rng(u32);
+
+impl Rng {
+    pub(crate) fn new(seed: [u8; 8]) -> Self {
+        let seed = u32::from_le_bytes([seed[4], seed[5], seed[6], seed[7]])
+            ^ u32::from_le_bytes([seed[0], seed[1], seed[2], seed[3]]);
+
+        Self(seed | 1)
+    }
+
+    pub(crate) fn next(&mut self) -> u32 {
+        // xorshift32
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+}
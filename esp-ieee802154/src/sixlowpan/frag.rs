@@ -0,0 +1,543 @@
+//! Fragmentation and reassembly (RFC 4944)
+
+use byte::{check_len, BytesExt, BE};
+use heapless::Vec;
+use ieee802154::mac::{Address, Header};
+
+use super::{addresses, compress, decompress, MAX_COMPRESSED_HEADER_LEN};
+use crate::{Error, Frame};
+
+/// Length of the longest packet which is reassembled, the minimum MTU of IPv6
+pub const MAX_PACKET_LEN: usize = 1280;
+/// Time after which a packet whose fragments are missing is dropped, in
+/// microseconds
+pub const REASSEMBLY_TIMEOUT_US: u64 = 60_000_000;
+
+const DISPATCH_FRAG1: u8 = 0xc0;
+const DISPATCH_FRAGN: u8 = 0xe0;
+const DISPATCH_FRAG_MASK: u8 = 0xf8;
+const FRAG1_HEADER_LEN: usize = 4;
+const FRAGN_HEADER_LEN: usize = 5;
+/// Largest datagram size of the fragment headers
+const MAX_DATAGRAM_SIZE: usize = 0x07ff;
+/// Fragment offsets are in units of 8 bytes
+const UNIT: usize = 8;
+const UNITS: usize = MAX_PACKET_LEN / UNIT;
+
+/// Splits an IPv6 packet into the payloads of the frames carrying it
+///
+/// The headers of the packet are compressed, and the packet is sent in a
+/// single frame if it fits, or in fragments otherwise.
+#[derive(Debug)]
+pub struct Fragmenter<'p> {
+    packet: &'p [u8],
+    header: [u8; MAX_COMPRESSED_HEADER_LEN],
+    header_len: usize,
+    /// Length of the headers of the packet replaced by the compressed ones
+    replaced_len: usize,
+    tag: u16,
+    /// Offset in the packet of the next fragment, `None` before the first
+    offset: Option<usize>,
+}
+
+impl<'p> Fragmenter<'p> {
+    /// Fragmenter of the IPv6 `packet` sent in frames with the MAC addresses
+    /// of `header`
+    ///
+    /// `contexts` are the 64-bit prefixes of the contexts of stateful
+    /// compression, indexed by their identifier, and `tag` identifies the
+    /// fragments of the packet, it should be incremented for each packet.
+    pub fn new(
+        packet: &'p [u8],
+        header: &Header,
+        contexts: &[[u8; 8]],
+        tag: u16,
+    ) -> Result<Self, Error> {
+        let (source, destination) = addresses(header)?;
+
+        let mut compressed = [0; MAX_COMPRESSED_HEADER_LEN];
+        let (header_len, replaced_len) =
+            compress(packet, &source, &destination, contexts, &mut compressed)?;
+
+        Ok(Self {
+            packet,
+            header: compressed,
+            header_len,
+            replaced_len,
+            tag,
+            offset: None,
+        })
+    }
+
+    /// Whether the payloads of all the frames have been written
+    pub fn is_done(&self) -> bool {
+        self.offset == Some(self.packet.len())
+    }
+
+    /// Write the payload of the next frame into `bytes`, returns its length,
+    /// or `None` once the whole packet has been written
+    ///
+    /// `bytes` should be as long as the longest payload of the frames, see
+    /// [`max_payload_len`](super::max_payload_len).
+    pub fn next_payload(&mut self, bytes: &mut [u8]) -> Result<Option<usize>, Error> {
+        let size = self.packet.len();
+        let offset = &mut 0;
+
+        let Some(start) = self.offset else {
+            let rest = &self.packet[self.replaced_len..];
+
+            // the whole packet in a single frame
+            if self.header_len + rest.len() <= bytes.len() {
+                bytes.write(offset, &self.header[..self.header_len])?;
+                bytes.write(offset, rest)?;
+                self.offset = Some(size);
+
+                return Ok(Some(*offset));
+            }
+
+            if size > MAX_DATAGRAM_SIZE {
+                return Err(Error::BadInput);
+            }
+
+            // the fragments after the first one start at multiples of 8 bytes
+            // of the uncompressed packet
+            let available = bytes
+                .len()
+                .checked_sub(FRAG1_HEADER_LEN + self.header_len)
+                .ok_or(Error::Incomplete)?;
+            let end = (self.replaced_len + available) / UNIT * UNIT;
+            if end <= self.replaced_len {
+                return Err(Error::Incomplete);
+            }
+
+            bytes.write_with(offset, ((DISPATCH_FRAG1 as u16) << 8) | size as u16, BE)?;
+            bytes.write_with(offset, self.tag, BE)?;
+            bytes.write(offset, &self.header[..self.header_len])?;
+            bytes.write(offset, &self.packet[self.replaced_len..end])?;
+            self.offset = Some(end);
+
+            return Ok(Some(*offset));
+        };
+
+        if start == size {
+            return Ok(None);
+        }
+
+        let available = bytes
+            .len()
+            .checked_sub(FRAGN_HEADER_LEN)
+            .ok_or(Error::Incomplete)?;
+        let end = if size - start <= available {
+            size
+        } else {
+            start + available / UNIT * UNIT
+        };
+        if end == start {
+            return Err(Error::Incomplete);
+        }
+
+        bytes.write_with(offset, ((DISPATCH_FRAGN as u16) << 8) | size as u16, BE)?;
+        bytes.write_with(offset, self.tag, BE)?;
+        bytes.write(offset, (start / UNIT) as u8)?;
+        bytes.write(offset, &self.packet[start..end])?;
+        self.offset = Some(end);
+
+        Ok(Some(*offset))
+    }
+}
+
+/// Packet being reassembled
+#[derive(Debug)]
+struct Reassembly {
+    source: Address,
+    destination: Address,
+    size: usize,
+    tag: u16,
+    /// Bit `i` is set once the 8 bytes at offset `8 * i` have been received
+    received: [u32; UNITS.div_ceil(32)],
+    expires_at: u64,
+    buffer: [u8; MAX_PACKET_LEN],
+}
+
+impl Reassembly {
+    fn add(&mut self, offset: usize, data: &[u8]) -> Result<(), Error> {
+        let end = offset + data.len();
+        if end > self.size {
+            return Err(Error::BadInput);
+        }
+
+        self.buffer[offset..end].copy_from_slice(data);
+        self.mark(offset, end);
+
+        Ok(())
+    }
+
+    fn mark(&mut self, start: usize, end: usize) {
+        for unit in start / UNIT..end.div_ceil(UNIT) {
+            self.received[unit / 32] |= 1 << (unit % 32);
+        }
+    }
+
+    fn is_complete(&self) -> bool {
+        (0..self.size.div_ceil(UNIT)).all(|unit| self.received[unit / 32] & (1 << (unit % 32)) != 0)
+    }
+}
+
+/// Gets the IPv6 packets back from the payloads of the received frames
+///
+/// Up to `N` fragmented packets are reassembled at the same time, and the
+/// packets whose fragments are still missing after [`REASSEMBLY_TIMEOUT_US`]
+/// are dropped.
+#[derive(Debug, Default)]
+pub struct Reassembler<const N: usize> {
+    reassemblies: Vec<Reassembly, N>,
+}
+
+impl<const N: usize> Reassembler<N> {
+    pub const fn new() -> Self {
+        Self {
+            reassemblies: Vec::new(),
+        }
+    }
+
+    /// Drop the packets whose reassembly timed out at `now`, in microseconds
+    pub fn expire(&mut self, now: u64) {
+        self.reassemblies
+            .retain(|reassembly| now < reassembly.expires_at);
+    }
+
+    /// Process the payload of a received frame, at `now` in microseconds
+    ///
+    /// `contexts` are the 64-bit prefixes of the contexts of stateful
+    /// compression, indexed by their identifier. Returns the length of the
+    /// packet written into `bytes` once it is complete, `None` if fragments are
+    /// still missing. Fragments of new packets are reported as [`Error::Full`]
+    /// when `N` packets are already being reassembled.
+    pub fn receive(
+        &mut self,
+        frame: &Frame,
+        contexts: &[[u8; 8]],
+        now: u64,
+        bytes: &mut [u8],
+    ) -> Result<Option<usize>, Error> {
+        self.expire(now);
+
+        let (source, destination) = addresses(&frame.header)?;
+        let payload = &frame.payload[..];
+        let dispatch = *payload.first().ok_or(Error::Incomplete)?;
+
+        let (header_len, first) = match dispatch & DISPATCH_FRAG_MASK {
+            DISPATCH_FRAG1 => (FRAG1_HEADER_LEN, true),
+            DISPATCH_FRAGN => (FRAGN_HEADER_LEN, false),
+            _ => {
+                let (consumed, written) =
+                    decompress(payload, &source, &destination, contexts, None, bytes)?;
+                let mut offset = written;
+                bytes.write(&mut offset, &payload[consumed..])?;
+
+                return Ok(Some(offset));
+            }
+        };
+
+        check_len(payload, header_len)?;
+        let size =
+            (u16::from_be_bytes([payload[0], payload[1]]) & MAX_DATAGRAM_SIZE as u16) as usize;
+        let tag = u16::from_be_bytes([payload[2], payload[3]]);
+        if size > MAX_PACKET_LEN {
+            return Err(Error::BadInput);
+        }
+
+        let index = match self.reassemblies.iter().position(|reassembly| {
+            reassembly.source == source
+                && reassembly.destination == destination
+                && reassembly.size == size
+                && reassembly.tag == tag
+        }) {
+            Some(index) => index,
+            None => {
+                self.reassemblies
+                    .push(Reassembly {
+                        source,
+                        destination,
+                        size,
+                        tag,
+                        received: Default::default(),
+                        expires_at: now + REASSEMBLY_TIMEOUT_US,
+                        buffer: [0; MAX_PACKET_LEN],
+                    })
+                    .map_err(|_| Error::Full)?;
+                self.reassemblies.len() - 1
+            }
+        };
+        let reassembly = &mut self.reassemblies[index];
+
+        let result = if first {
+            let payload = &payload[header_len..];
+            decompress(
+                payload,
+                &source,
+                &destination,
+                contexts,
+                Some(size),
+                &mut reassembly.buffer,
+            )
+            .and_then(|(consumed, written)| {
+                // the decompressed headers
+                reassembly.mark(0, written);
+                reassembly.add(written, &payload[consumed..])
+            })
+        } else {
+            reassembly.add(payload[4] as usize * UNIT, &payload[header_len..])
+        };
+
+        if let Err(err) = result {
+            self.reassemblies.swap_remove(index);
+            return Err(err);
+        }
+        if !reassembly.is_complete() {
+            return Ok(None);
+        }
+
+        let reassembly = self.reassemblies.swap_remove(index);
+        bytes.write(&mut 0, &reassembly.buffer[..size])?;
+
+        Ok(Some(size))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ieee802154::mac::FrameContent;
+
+    use super::*;
+    use crate::sixlowpan::{
+        max_payload_len,
+        tests::{extended, header, link_local, packet, short},
+        IPV6_HEADER_LEN, UDP_HEADER_LEN,
+    };
+
+    fn frame(header: &Header, payload: &[u8]) -> Frame {
+        Frame {
+            header: *header,
+            content: FrameContent::Data,
+            payload: Vec::from_slice(payload).unwrap(),
+            footer: [0; 2],
+        }
+    }
+
+    /// The frames carrying `packet`
+    fn frames(packet: &[u8], header: &Header, tag: u16) -> std::vec::Vec<Frame> {
+        let mut fragmenter = Fragmenter::new(packet, header, &[], tag).unwrap();
+        let mut payload = std::vec![0; max_payload_len(header)];
+
+        let mut frames = std::vec::Vec::new();
+        while let Some(len) = fragmenter.next_payload(&mut payload).unwrap() {
+            frames.push(frame(header, &payload[..len]));
+        }
+        assert!(fragmenter.is_done());
+
+        frames
+    }
+
+    fn udp(header: &Header, len: usize) -> std::vec::Vec<u8> {
+        let (source, destination) = addresses(header).unwrap();
+        packet(
+            link_local(&source),
+            link_local(&destination),
+            Some((19788, 19788)),
+            len,
+        )
+    }
+
+    #[test]
+    fn small_packets_are_sent_in_a_single_frame() {
+        let header = header(extended(1), extended(2));
+        let packet = udp(&header, 50);
+
+        let frames = frames(&packet, &header, 1);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload.len(), 2 + 7 + 50);
+
+        let mut bytes = [0; MAX_PACKET_LEN];
+        let mut reassembler = Reassembler::<1>::new();
+        let len = reassembler
+            .receive(&frames[0], &[], 0, &mut bytes)
+            .unwrap()
+            .unwrap();
+        assert_eq!(bytes[..len], packet[..]);
+    }
+
+    #[test]
+    fn large_packets_are_fragmented() {
+        let header = header(extended(1), extended(2));
+        let packet = udp(&header, MAX_PACKET_LEN - IPV6_HEADER_LEN - UDP_HEADER_LEN);
+
+        let frames = frames(&packet, &header, 0x1234);
+        assert_eq!(frames[0].payload[..4], [0xc5, 0x00, 0x12, 0x34]);
+        for (frame, next) in frames[1..].iter().zip(&frames[2..]) {
+            assert_eq!(frame.payload[..4], [0xe5, 0x00, 0x12, 0x34]);
+            // the fragments follow each other
+            let len = frame.payload.len() - FRAGN_HEADER_LEN;
+            assert_eq!(len % UNIT, 0);
+            assert_eq!(
+                next.payload[4] as usize,
+                frame.payload[4] as usize + len / UNIT
+            );
+        }
+
+        // in any order, with duplicates
+        let mut bytes = [0; MAX_PACKET_LEN];
+        let mut reassembler = Reassembler::<1>::new();
+        let (last, rest) = frames.split_last().unwrap();
+        for frame in rest.iter().rev().chain(rest) {
+            assert!(reassembler
+                .receive(frame, &[], 0, &mut bytes)
+                .unwrap()
+                .is_none());
+        }
+        let len = reassembler
+            .receive(last, &[], 0, &mut bytes)
+            .unwrap()
+            .unwrap();
+        assert_eq!(bytes[..len], packet[..]);
+    }
+
+    #[test]
+    fn packets_are_reassembled_separately() {
+        let (first, second) = (
+            header(extended(1), extended(2)),
+            header(short(3), extended(2)),
+        );
+        let (first_packet, second_packet) = (udp(&first, 300), udp(&second, 200));
+        let (first_frames, second_frames) = (
+            frames(&first_packet, &first, 7),
+            frames(&second_packet, &second, 7),
+        );
+
+        let mut bytes = [0; MAX_PACKET_LEN];
+        let mut reassembler = Reassembler::<2>::new();
+        for (first, second) in first_frames[1..].iter().zip(&second_frames[1..]) {
+            assert!(reassembler
+                .receive(first, &[], 0, &mut bytes)
+                .unwrap()
+                .is_none());
+            assert!(reassembler
+                .receive(second, &[], 0, &mut bytes)
+                .unwrap()
+                .is_none());
+        }
+        for frame in &first_frames[second_frames.len()..] {
+            assert!(reassembler
+                .receive(frame, &[], 0, &mut bytes)
+                .unwrap()
+                .is_none());
+        }
+
+        // no room for a third packet
+        let third = header(short(4), extended(2));
+        let third_frames = frames(&udp(&third, 200), &third, 7);
+        assert!(matches!(
+            reassembler.receive(&third_frames[0], &[], 0, &mut bytes),
+            Err(Error::Full)
+        ));
+
+        let len = reassembler
+            .receive(&second_frames[0], &[], 0, &mut bytes)
+            .unwrap()
+            .unwrap();
+        assert_eq!(bytes[..len], second_packet[..]);
+        let len = reassembler
+            .receive(&first_frames[0], &[], 0, &mut bytes)
+            .unwrap()
+            .unwrap();
+        assert_eq!(bytes[..len], first_packet[..]);
+    }
+
+    #[test]
+    fn incomplete_packets_time_out() {
+        let header = header(extended(1), extended(2));
+        let frames = frames(&udp(&header, 300), &header, 7);
+
+        let mut bytes = [0; MAX_PACKET_LEN];
+        let mut reassembler = Reassembler::<1>::new();
+        for frame in &frames[1..] {
+            assert!(reassembler
+                .receive(frame, &[], 0, &mut bytes)
+                .unwrap()
+                .is_none());
+        }
+
+        // the fragments received before the timeout were dropped
+        assert!(reassembler
+            .receive(&frames[0], &[], REASSEMBLY_TIMEOUT_US, &mut bytes)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn malformed_fragments_are_rejected() {
+        let header = header(extended(1), extended(2));
+        let frames = frames(&udp(&header, 300), &header, 7);
+        let mut bytes = [0; MAX_PACKET_LEN];
+        let mut reassembler = Reassembler::<1>::new();
+
+        // truncated fragment headers
+        for len in 0..FRAGN_HEADER_LEN {
+            let frame = frame(&header, &frames[1].payload[..len]);
+            assert!(matches!(
+                reassembler.receive(&frame, &[], 0, &mut bytes),
+                Err(Error::Incomplete)
+            ));
+        }
+
+        // beyond the size of the packet
+        let mut payload = frames[1].payload.clone();
+        payload[4] = 0xff;
+        assert!(matches!(
+            reassembler.receive(&frame(&header, &payload), &[], 0, &mut bytes),
+            Err(Error::BadInput)
+        ));
+
+        // too large
+        payload[..2].copy_from_slice(&[0xe5, 0x01]);
+        payload[4] = 0;
+        assert!(matches!(
+            reassembler.receive(&frame(&header, &payload), &[], 0, &mut bytes),
+            Err(Error::BadInput)
+        ));
+
+        // no source address
+        let mut frame = frames[1].clone();
+        frame.header.source = None;
+        assert!(matches!(
+            reassembler.receive(&frame, &[], 0, &mut bytes),
+            Err(Error::BadInput)
+        ));
+
+        // the rejected fragments didn't start a reassembly
+        assert!(reassembler
+            .receive(&frames[1], &[], 0, &mut bytes)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn packets_must_fit_in_the_fragments() {
+        let header = header(extended(1), extended(2));
+        let packet = udp(&header, 300);
+
+        let mut fragmenter = Fragmenter::new(&packet, &header, &[], 0).unwrap();
+        let mut payload = [0; FRAG1_HEADER_LEN + 2 + 7];
+        assert!(matches!(
+            fragmenter.next_payload(&mut payload),
+            Err(Error::Incomplete)
+        ));
+
+        let mut header = header;
+        header.destination = None;
+        assert!(matches!(
+            Fragmenter::new(&packet, &header, &[], 0),
+            Err(Error::BadInput)
+        ));
+    }
+}
@@ -0,0 +1,733 @@
+//! IPv6 and UDP header compression (RFC 6282)
+
+use byte::{check_len, BytesExt, BE};
+use ieee802154::mac::Address;
+
+use super::{interface_identifier, IPV6_HEADER_LEN, UDP, UDP_HEADER_LEN};
+use crate::Error;
+
+/// Length of the longest compressed IPv6 and UDP headers
+pub const MAX_COMPRESSED_HEADER_LEN: usize = 48;
+
+const DISPATCH_IPV6: u8 = 0x41;
+const DISPATCH_IPHC: u8 = 0x60;
+const DISPATCH_IPHC_MASK: u8 = 0xe0;
+
+const TF_SHIFT: u8 = 3;
+const NH: u8 = 0x04;
+const HLIM_MASK: u8 = 0x03;
+const CID: u8 = 0x80;
+const SAC: u8 = 0x40;
+const SAM_SHIFT: u8 = 4;
+const MULTICAST: u8 = 0x08;
+const DAC: u8 = 0x04;
+const MODE_MASK: u8 = 0x03;
+
+const NHC_UDP: u8 = 0xf0;
+const NHC_UDP_MASK: u8 = 0xf8;
+const NHC_UDP_CHECKSUM: u8 = 0x04;
+const NHC_UDP_PORTS_MASK: u8 = 0x03;
+const UDP_PORTS_8: u16 = 0xf000;
+const UDP_PORTS_4: u16 = 0xf0b0;
+
+const LINK_LOCAL_PREFIX: [u8; 8] = [0xfe, 0x80, 0, 0, 0, 0, 0, 0];
+
+/// Compressed address, with its mode, its context if it has one, and the bits
+/// carried inline
+struct CompressedAddress {
+    mode: u8,
+    context: Option<u8>,
+    inline: [u8; 16],
+    len: usize,
+}
+
+impl CompressedAddress {
+    fn new(mode: u8, context: Option<u8>, inline: &[u8]) -> Self {
+        let mut address = Self {
+            mode,
+            context,
+            inline: [0; 16],
+            len: inline.len(),
+        };
+        address.inline[..inline.len()].copy_from_slice(inline);
+        address
+    }
+}
+
+fn compress_unicast(
+    address: &[u8; 16],
+    link_address: &Address,
+    contexts: &[[u8; 8]],
+) -> CompressedAddress {
+    let context = if address[..8] == LINK_LOCAL_PREFIX {
+        None
+    } else if let Some(id) = contexts
+        .iter()
+        .take(16)
+        .position(|prefix| address[..8] == *prefix)
+    {
+        Some(id as u8)
+    } else {
+        return CompressedAddress::new(0b00, None, address);
+    };
+
+    let iid = &address[8..];
+    if *iid == interface_identifier(link_address) {
+        CompressedAddress::new(0b11, context, &[])
+    } else if iid[..6] == [0, 0, 0, 0xff, 0xfe, 0] {
+        CompressedAddress::new(0b10, context, &iid[6..])
+    } else {
+        CompressedAddress::new(0b01, context, iid)
+    }
+}
+
+fn compress_multicast(address: &[u8; 16]) -> CompressedAddress {
+    let zeros = |range: core::ops::Range<usize>| address[range].iter().all(|&b| b == 0);
+
+    if address[1] == 0x02 && zeros(2..15) {
+        CompressedAddress::new(0b11, None, &address[15..])
+    } else if zeros(2..13) {
+        let mut inline = [address[1], 0, 0, 0];
+        inline[1..].copy_from_slice(&address[13..]);
+        CompressedAddress::new(0b10, None, &inline)
+    } else if zeros(2..11) {
+        let mut inline = [address[1], 0, 0, 0, 0, 0];
+        inline[1..].copy_from_slice(&address[11..]);
+        CompressedAddress::new(0b01, None, &inline)
+    } else {
+        CompressedAddress::new(0b00, None, address)
+    }
+}
+
+/// Compress the IPv6 header of `packet`, and the UDP header following it, into
+/// `bytes`
+///
+/// `source` and `destination` are the MAC addresses of the frame carrying the
+/// packet, and `contexts` the 64-bit prefixes of the contexts of stateful
+/// compression, indexed by their identifier. Returns the length of the
+/// compressed headers, and the length of the headers of `packet` they replace.
+pub fn compress(
+    packet: &[u8],
+    source: &Address,
+    destination: &Address,
+    contexts: &[[u8; 8]],
+    bytes: &mut [u8],
+) -> Result<(usize, usize), Error> {
+    check_len(packet, IPV6_HEADER_LEN)?;
+    if packet[0] >> 4 != 6 || packet.len() - IPV6_HEADER_LEN > u16::MAX as usize {
+        return Err(Error::BadInput);
+    }
+
+    let traffic_class = (packet[0] << 4) | (packet[1] >> 4);
+    let flow_label = u32::from_be_bytes([0, packet[1] & 0x0f, packet[2], packet[3]]);
+    let next_header = packet[6];
+    let hop_limit = packet[7];
+    let source_address: &[u8; 16] = packet[8..24].try_into().unwrap();
+    let destination_address: &[u8; 16] = packet[24..40].try_into().unwrap();
+
+    // the ECN is followed by the DSCP in the compressed traffic class
+    let ecn = traffic_class & 0x03;
+    let dscp = traffic_class >> 2;
+    let udp = next_header == UDP && packet.len() >= IPV6_HEADER_LEN + UDP_HEADER_LEN;
+
+    let source_address = if *source_address == [0; 16] {
+        // the unspecified address
+        CompressedAddress::new(0b00, Some(0), &[])
+    } else {
+        compress_unicast(source_address, source, contexts)
+    };
+    let multicast = destination_address[0] == 0xff;
+    let destination_address = if multicast {
+        compress_multicast(destination_address)
+    } else {
+        compress_unicast(destination_address, destination, contexts)
+    };
+
+    let tf = match (traffic_class, flow_label) {
+        (0, 0) => 0b11,
+        (_, 0) => 0b10,
+        _ if dscp == 0 => 0b01,
+        _ => 0b00,
+    };
+    let hlim = match hop_limit {
+        1 => 0b01,
+        64 => 0b10,
+        255 => 0b11,
+        _ => 0b00,
+    };
+
+    let mut iphc = [DISPATCH_IPHC | (tf << TF_SHIFT) | hlim, 0];
+    if udp {
+        iphc[0] |= NH;
+    }
+    let source_context = source_address.context.unwrap_or(0);
+    let destination_context = destination_address.context.unwrap_or(0);
+    if source_context != 0 || destination_context != 0 {
+        iphc[1] |= CID;
+    }
+    if source_address.context.is_some() {
+        iphc[1] |= SAC;
+    }
+    iphc[1] |= source_address.mode << SAM_SHIFT;
+    if multicast {
+        iphc[1] |= MULTICAST;
+    }
+    if destination_address.context.is_some() {
+        iphc[1] |= DAC;
+    }
+    iphc[1] |= destination_address.mode;
+
+    let offset = &mut 0;
+    bytes.write(offset, &iphc[..])?;
+    if iphc[1] & CID != 0 {
+        bytes.write(offset, (source_context << 4) | destination_context)?;
+    }
+
+    let flow_label = flow_label.to_be_bytes();
+    match tf {
+        0b00 => {
+            bytes.write(offset, (ecn << 6) | dscp)?;
+            bytes.write(offset, &flow_label[1..])?;
+        }
+        0b01 => {
+            bytes.write(offset, (ecn << 6) | flow_label[1])?;
+            bytes.write(offset, &flow_label[2..])?;
+        }
+        0b10 => bytes.write(offset, (ecn << 6) | dscp)?,
+        _ => {}
+    }
+    if !udp {
+        bytes.write(offset, next_header)?;
+    }
+    if hlim == 0b00 {
+        bytes.write(offset, hop_limit)?;
+    }
+    bytes.write(offset, &source_address.inline[..source_address.len])?;
+    bytes.write(
+        offset,
+        &destination_address.inline[..destination_address.len],
+    )?;
+
+    if !udp {
+        return Ok((*offset, IPV6_HEADER_LEN));
+    }
+
+    let udp_header = &packet[IPV6_HEADER_LEN..];
+    let source_port = u16::from_be_bytes([udp_header[0], udp_header[1]]);
+    let destination_port = u16::from_be_bytes([udp_header[2], udp_header[3]]);
+
+    if source_port & 0xfff0 == UDP_PORTS_4 && destination_port & 0xfff0 == UDP_PORTS_4 {
+        bytes.write(offset, NHC_UDP | 0b11)?;
+        bytes.write(
+            offset,
+            (((source_port & 0x0f) << 4) | (destination_port & 0x0f)) as u8,
+        )?;
+    } else if source_port & 0xff00 == UDP_PORTS_8 {
+        bytes.write(offset, NHC_UDP | 0b10)?;
+        bytes.write(offset, source_port as u8)?;
+        bytes.write_with(offset, destination_port, BE)?;
+    } else if destination_port & 0xff00 == UDP_PORTS_8 {
+        bytes.write(offset, NHC_UDP | 0b01)?;
+        bytes.write_with(offset, source_port, BE)?;
+        bytes.write(offset, destination_port as u8)?;
+    } else {
+        bytes.write(offset, NHC_UDP)?;
+        bytes.write_with(offset, source_port, BE)?;
+        bytes.write_with(offset, destination_port, BE)?;
+    }
+    // the checksum is always carried inline
+    bytes.write(offset, &udp_header[6..8])?;
+
+    Ok((*offset, IPV6_HEADER_LEN + UDP_HEADER_LEN))
+}
+
+fn context(contexts: &[[u8; 8]], id: u8) -> Result<&[u8; 8], Error> {
+    contexts.get(id as usize).ok_or(Error::BadInput)
+}
+
+fn decompress_unicast(
+    payload: &[u8],
+    offset: &mut usize,
+    mode: u8,
+    prefix: &[u8; 8],
+    link_address: &Address,
+) -> Result<[u8; 16], Error> {
+    let mut address = [0u8; 16];
+    address[..8].copy_from_slice(prefix);
+
+    match mode {
+        0b00 => address.copy_from_slice(read_bytes(payload, offset, 16)?),
+        0b01 => address[8..].copy_from_slice(read_bytes(payload, offset, 8)?),
+        0b10 => {
+            address[11..14].copy_from_slice(&[0xff, 0xfe, 0]);
+            address[14..].copy_from_slice(read_bytes(payload, offset, 2)?);
+        }
+        _ => address[8..].copy_from_slice(&interface_identifier(link_address)),
+    }
+
+    Ok(address)
+}
+
+fn decompress_multicast(payload: &[u8], offset: &mut usize, mode: u8) -> Result<[u8; 16], Error> {
+    let mut address = [0u8; 16];
+    address[0] = 0xff;
+
+    match mode {
+        0b00 => address.copy_from_slice(read_bytes(payload, offset, 16)?),
+        0b01 => {
+            address[1] = payload.read(offset)?;
+            address[11..].copy_from_slice(read_bytes(payload, offset, 5)?);
+        }
+        0b10 => {
+            address[1] = payload.read(offset)?;
+            address[13..].copy_from_slice(read_bytes(payload, offset, 3)?);
+        }
+        _ => {
+            address[1] = 0x02;
+            address[15] = payload.read(offset)?;
+        }
+    }
+
+    Ok(address)
+}
+
+fn read_bytes<'a>(payload: &'a [u8], offset: &mut usize, len: usize) -> Result<&'a [u8], Error> {
+    Ok(payload.read_with(offset, byte::ctx::Bytes::Len(len))?)
+}
+
+/// Decompress the headers at the start of the 6LoWPAN `payload` into `bytes`
+///
+/// `source` and `destination` are the MAC addresses of the frame carrying the
+/// packet, and `contexts` the 64-bit prefixes of the contexts of stateful
+/// compression, indexed by their identifier. The length fields are set from
+/// `datagram_size`, the length of the uncompressed packet, or from the length
+/// of `payload` if it is `None`. Returns the length of the compressed headers,
+/// and the length of the headers written to `bytes`, which are followed by the
+/// rest of `payload` in the packet.
+///
+/// Uncompressed IPv6 headers are left as they are, and UDP headers whose
+/// checksum is elided are reported as [`Error::BadInput`].
+pub fn decompress(
+    payload: &[u8],
+    source: &Address,
+    destination: &Address,
+    contexts: &[[u8; 8]],
+    datagram_size: Option<usize>,
+    bytes: &mut [u8],
+) -> Result<(usize, usize), Error> {
+    let offset = &mut 0;
+    let dispatch: u8 = payload.read(offset)?;
+
+    if dispatch == DISPATCH_IPV6 {
+        return Ok((*offset, 0));
+    }
+    if dispatch & DISPATCH_IPHC_MASK != DISPATCH_IPHC {
+        return Err(Error::BadInput);
+    }
+    let iphc = [dispatch, payload.read(offset)?];
+
+    let (source_context, destination_context) = if iphc[1] & CID != 0 {
+        let cid: u8 = payload.read(offset)?;
+        (cid >> 4, cid & 0x0f)
+    } else {
+        (0, 0)
+    };
+
+    let (mut traffic_class, mut flow_label) = (0u8, 0u32);
+    match (iphc[0] >> TF_SHIFT) & MODE_MASK {
+        0b00 => {
+            let inline = read_bytes(payload, offset, 4)?;
+            traffic_class = inline[0];
+            flow_label = u32::from_be_bytes([0, inline[1] & 0x0f, inline[2], inline[3]]);
+        }
+        0b01 => {
+            let inline = read_bytes(payload, offset, 3)?;
+            traffic_class = inline[0] & 0xc0;
+            flow_label = u32::from_be_bytes([0, inline[0] & 0x0f, inline[1], inline[2]]);
+        }
+        0b10 => traffic_class = payload.read(offset)?,
+        _ => {}
+    }
+    // back from ECN followed by DSCP
+    let traffic_class = traffic_class.rotate_left(2);
+
+    let udp = iphc[0] & NH != 0;
+    let next_header = if udp { UDP } else { payload.read(offset)? };
+    let hop_limit: u8 = match iphc[0] & HLIM_MASK {
+        0b00 => payload.read(offset)?,
+        0b01 => 1,
+        0b10 => 64,
+        _ => 255,
+    };
+
+    let source_mode = (iphc[1] >> SAM_SHIFT) & MODE_MASK;
+    let source_address = if iphc[1] & SAC == 0 {
+        decompress_unicast(payload, offset, source_mode, &LINK_LOCAL_PREFIX, source)?
+    } else if source_mode == 0b00 {
+        // the unspecified address
+        [0; 16]
+    } else {
+        let prefix = context(contexts, source_context)?;
+        decompress_unicast(payload, offset, source_mode, prefix, source)?
+    };
+
+    let destination_mode = iphc[1] & MODE_MASK;
+    let destination_address = match (iphc[1] & MULTICAST != 0, iphc[1] & DAC != 0) {
+        (false, false) => decompress_unicast(
+            payload,
+            offset,
+            destination_mode,
+            &LINK_LOCAL_PREFIX,
+            destination,
+        )?,
+        (false, true) if destination_mode != 0b00 => {
+            let prefix = context(contexts, destination_context)?;
+            decompress_unicast(payload, offset, destination_mode, prefix, destination)?
+        }
+        (true, false) => decompress_multicast(payload, offset, destination_mode)?,
+        // reserved, and unicast-prefix-based multicast addresses
+        _ => return Err(Error::BadInput),
+    };
+
+    let mut udp_header = None;
+    if udp {
+        let nhc: u8 = payload.read(offset)?;
+        if nhc & NHC_UDP_MASK != NHC_UDP || nhc & NHC_UDP_CHECKSUM != 0 {
+            return Err(Error::BadInput);
+        }
+
+        let (source_port, destination_port) = match nhc & NHC_UDP_PORTS_MASK {
+            0b00 => (
+                payload.read_with::<u16>(offset, BE)?,
+                payload.read_with::<u16>(offset, BE)?,
+            ),
+            0b01 => (
+                payload.read_with::<u16>(offset, BE)?,
+                UDP_PORTS_8 | payload.read::<u8>(offset)? as u16,
+            ),
+            0b10 => (
+                UDP_PORTS_8 | payload.read::<u8>(offset)? as u16,
+                payload.read_with::<u16>(offset, BE)?,
+            ),
+            _ => {
+                let ports: u8 = payload.read(offset)?;
+                (
+                    UDP_PORTS_4 | (ports >> 4) as u16,
+                    UDP_PORTS_4 | (ports & 0x0f) as u16,
+                )
+            }
+        };
+        let checksum: u16 = payload.read_with(offset, BE)?;
+
+        udp_header = Some((source_port, destination_port, checksum));
+    }
+
+    let header_len = if udp {
+        IPV6_HEADER_LEN + UDP_HEADER_LEN
+    } else {
+        IPV6_HEADER_LEN
+    };
+    let datagram_size = datagram_size.unwrap_or(header_len + payload.len() - *offset);
+    let payload_len = datagram_size
+        .checked_sub(IPV6_HEADER_LEN)
+        .and_then(|len| u16::try_from(len).ok())
+        .ok_or(Error::BadInput)?;
+
+    let written = &mut 0;
+    bytes.write_with(
+        written,
+        (6 << 28) | ((traffic_class as u32) << 20) | flow_label,
+        BE,
+    )?;
+    bytes.write_with(written, payload_len, BE)?;
+    bytes.write(written, next_header)?;
+    bytes.write(written, hop_limit)?;
+    bytes.write(written, &source_address[..])?;
+    bytes.write(written, &destination_address[..])?;
+
+    if let Some((source_port, destination_port, checksum)) = udp_header {
+        bytes.write_with(written, source_port, BE)?;
+        bytes.write_with(written, destination_port, BE)?;
+        bytes.write_with(written, payload_len, BE)?;
+        bytes.write_with(written, checksum, BE)?;
+    }
+
+    Ok((*offset, *written))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sixlowpan::tests::{extended, link_local, packet, short};
+
+    const ALL_NODES: [u8; 16] = [0xff, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    const GLOBAL: [u8; 16] = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn source() -> Address {
+        extended(0x1122_3344_5566_7788)
+    }
+
+    fn destination() -> Address {
+        extended(0x0102_0304_0506_0708)
+    }
+
+    /// Compress the headers of `packet`, checking their length and that they
+    /// decompress to the original ones
+    fn round_trip(
+        packet: &[u8],
+        source: &Address,
+        destination: &Address,
+        contexts: &[[u8; 8]],
+        expected_len: usize,
+    ) {
+        let mut compressed = [0; MAX_COMPRESSED_HEADER_LEN];
+        let (len, replaced) =
+            compress(packet, source, destination, contexts, &mut compressed).unwrap();
+        assert_eq!(len, expected_len, "{:02x?}", &compressed[..len]);
+
+        let mut payload = compressed[..len].to_vec();
+        payload.extend_from_slice(&packet[replaced..]);
+
+        let mut decompressed = [0; 64];
+        assert_eq!(
+            decompress(
+                &payload,
+                source,
+                destination,
+                contexts,
+                None,
+                &mut decompressed
+            )
+            .unwrap(),
+            (len, replaced)
+        );
+        assert_eq!(decompressed[..replaced], packet[..replaced]);
+    }
+
+    #[test]
+    fn addresses_are_elided_from_mac_addresses() {
+        let (source, destination) = (source(), destination());
+
+        let udp = packet(
+            link_local(&source),
+            link_local(&destination),
+            Some((19788, 19788)),
+            10,
+        );
+        round_trip(&udp, &source, &destination, &[], 2 + 7);
+
+        // ICMPv6 with a hop limit of 255
+        let (source, destination) = (short(0x0001), short(0xfffe));
+        let mut packet = packet(link_local(&source), link_local(&destination), None, 8);
+        packet[7] = 255;
+        round_trip(&packet, &source, &destination, &[], 2 + 1);
+    }
+
+    #[test]
+    fn traffic_class_and_flow_label_are_carried_inline() {
+        let (source, destination) = (source(), destination());
+        let mut packet = packet(
+            link_local(&source),
+            link_local(&destination),
+            Some((0xf012, 1000)),
+            3,
+        );
+
+        // ECN, DSCP and flow label
+        packet[..4].copy_from_slice(&[0x6b, 0x81, 0x23, 0x45]);
+        round_trip(&packet, &source, &destination, &[], 2 + 4 + 1 + 3 + 2);
+        // ECN and flow label
+        packet[..4].copy_from_slice(&[0x60, 0x11, 0x23, 0x45]);
+        round_trip(&packet, &source, &destination, &[], 2 + 3 + 1 + 3 + 2);
+        // ECN and DSCP
+        packet[..4].copy_from_slice(&[0x60, 0x40, 0, 0]);
+        round_trip(&packet, &source, &destination, &[], 2 + 1 + 1 + 3 + 2);
+    }
+
+    #[test]
+    fn ports_are_compressed() {
+        let (source, destination) = (source(), destination());
+        let (from, to) = (link_local(&source), link_local(&destination));
+
+        round_trip(
+            &packet(from, ALL_NODES, Some((0xf0b1, 0xf0b2)), 3),
+            &source,
+            &destination,
+            &[],
+            2 + 1 + 1 + 1 + 2,
+        );
+        round_trip(
+            &packet(from, to, Some((1000, 0xf012)), 3),
+            &source,
+            &destination,
+            &[],
+            2 + 1 + 3 + 2,
+        );
+        round_trip(
+            &packet(from, to, Some((0xf012, 1000)), 3),
+            &source,
+            &destination,
+            &[],
+            2 + 1 + 3 + 2,
+        );
+    }
+
+    #[test]
+    fn other_addresses_are_carried_inline() {
+        let (source, destination) = (source(), destination());
+
+        // interface identifiers of 16 bits and other ones
+        let mut other = link_local(&short(0xfffe));
+        other[15] = 0x42;
+        let mut flipped = link_local(&source);
+        flipped[9] ^= 0x01;
+        round_trip(
+            &packet(other, flipped, None, 5),
+            &source,
+            &destination,
+            &[],
+            2 + 1 + 2 + 8,
+        );
+
+        // global and multicast addresses of 8, 32, 48 and 128 bits
+        let mut multicast = [0; 16];
+        multicast[..2].copy_from_slice(&[0xff, 0x05]);
+        multicast[13..].copy_from_slice(&[1, 2, 3]);
+        round_trip(
+            &packet(GLOBAL, multicast, Some((5683, 5683)), 4),
+            &source,
+            &destination,
+            &[],
+            2 + 16 + 4 + 7,
+        );
+        multicast[11] = 9;
+        round_trip(
+            &packet(GLOBAL, multicast, Some((5683, 5683)), 4),
+            &source,
+            &destination,
+            &[],
+            2 + 16 + 6 + 7,
+        );
+        multicast[3] = 9;
+        round_trip(
+            &packet(GLOBAL, multicast, Some((5683, 5683)), 4),
+            &source,
+            &destination,
+            &[],
+            2 + 16 + 16 + 7,
+        );
+        round_trip(
+            &packet(GLOBAL, ALL_NODES, None, 4),
+            &source,
+            &destination,
+            &[],
+            2 + 1 + 16 + 1,
+        );
+
+        // unspecified source
+        round_trip(
+            &packet([0; 16], ALL_NODES, Some((19788, 19788)), 4),
+            &source,
+            &destination,
+            &[],
+            2 + 1 + 1 + 7 - 1,
+        );
+    }
+
+    #[test]
+    fn prefixes_are_elided_with_contexts() {
+        let (source, destination) = (source(), destination());
+        let mesh_local = [0xfd, 0xde, 0xad, 0x00, 0xbe, 0xef, 0x00, 0x00];
+
+        let mut from = [0; 16];
+        from[..8].copy_from_slice(&mesh_local);
+        from[8..].copy_from_slice(&interface_identifier(&source));
+        // anycast locator of the leader
+        let mut to = from;
+        to[8..].copy_from_slice(&[0, 0, 0, 0xff, 0xfe, 0, 0xfc, 0x00]);
+
+        let packet = packet(from, to, Some((19788, 19788)), 4);
+        round_trip(&packet, &source, &destination, &[mesh_local], 2 + 2 + 7);
+        // with the context identifiers inline
+        let contexts = [[0; 8], [1; 8], mesh_local];
+        round_trip(&packet, &source, &destination, &contexts, 3 + 2 + 7);
+    }
+
+    #[test]
+    fn truncated_headers_are_rejected() {
+        let (source, destination) = (source(), destination());
+        let mut packet = packet(GLOBAL, link_local(&short(0x0042)), Some((1000, 2000)), 0);
+        packet[..4].copy_from_slice(&[0x6b, 0x81, 0x23, 0x45]);
+
+        let mut compressed = [0; MAX_COMPRESSED_HEADER_LEN];
+        let (len, _) = compress(&packet, &source, &destination, &[], &mut compressed).unwrap();
+        let mut bytes = [0; 64];
+        for len in 0..len {
+            assert!(matches!(
+                decompress(
+                    &compressed[..len],
+                    &source,
+                    &destination,
+                    &[],
+                    None,
+                    &mut bytes
+                ),
+                Err(Error::Incomplete)
+            ));
+        }
+
+        // not even an IPv6 header
+        assert!(matches!(
+            compress(
+                &packet[..IPV6_HEADER_LEN - 1],
+                &source,
+                &destination,
+                &[],
+                &mut compressed
+            ),
+            Err(Error::Incomplete)
+        ));
+        // no room for the decompressed headers
+        assert!(decompress(
+            &compressed[..len],
+            &source,
+            &destination,
+            &[],
+            None,
+            &mut bytes[..40]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        let (source, destination) = (source(), destination());
+        let mut bytes = [0; 64];
+
+        // mesh header, elided UDP checksum and unknown context
+        for payload in [
+            &[0x80, 0x00][..],
+            &[0x7e, 0x33, 0xf7, 0x12],
+            &[0x7a, 0x73, 0x3a],
+        ] {
+            assert!(matches!(
+                decompress(payload, &source, &destination, &[], None, &mut bytes),
+                Err(Error::BadInput)
+            ));
+        }
+
+        // uncompressed IPv6 headers are left as they are
+        assert_eq!(
+            decompress(&[0x41, 0x60], &source, &destination, &[], None, &mut bytes).unwrap(),
+            (1, 0)
+        );
+
+        // not IPv6
+        let mut packet = packet(GLOBAL, GLOBAL, None, 0);
+        packet[0] = 0x40;
+        assert!(matches!(
+            compress(&packet, &source, &destination, &[], &mut bytes),
+            Err(Error::BadInput)
+        ));
+    }
+}
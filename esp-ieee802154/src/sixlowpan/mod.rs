@@ -0,0 +1,160 @@
+//! 6LoWPAN, used with the `sixlowpan` feature
+//!
+//! IPv6 packets are carried in the payload of IEEE 802.15.4 data frames, with
+//! their IPv6 and UDP headers compressed as in RFC 6282, and split into
+//! fragments as in RFC 4944 when they don't fit in a single frame.
+//! [`Fragmenter`] writes the payloads of the frames of a packet, and
+//! [`Reassembler`] gets the packets back from the received frames. The
+//! addresses elided from the compressed headers are derived from the MAC
+//! addresses of the frame [`Header`]. This module doesn't use the radio, so it
+//! can be tested on the host.
+
+mod frag;
+mod iphc;
+
+use ieee802154::mac::{Address, Header};
+
+pub use self::{
+    frag::{Fragmenter, Reassembler, MAX_PACKET_LEN, REASSEMBLY_TIMEOUT_US},
+    iphc::{compress, decompress, MAX_COMPRESSED_HEADER_LEN},
+};
+use crate::Error;
+
+/// Length of the IPv6 header
+pub const IPV6_HEADER_LEN: usize = 40;
+/// Length of the UDP header
+pub const UDP_HEADER_LEN: usize = 8;
+/// Next header value of UDP
+pub const UDP: u8 = 17;
+
+const MAX_PSDU_LEN: usize = 127;
+const FCS_LEN: usize = 2;
+
+/// The length of the longest payload of a frame with the header
+pub fn max_payload_len(header: &Header) -> usize {
+    MAX_PSDU_LEN - header.get_octet_size() - FCS_LEN
+}
+
+/// The interface identifier of the IPv6 addresses derived from a MAC address
+///
+/// Extended addresses give the EUI-64 with its universal/local bit flipped,
+/// and short addresses give `0000:00ff:fe00:XXXX`.
+pub fn interface_identifier(address: &Address) -> [u8; 8] {
+    match *address {
+        Address::Short(_, short) => {
+            let [high, low] = short.0.to_be_bytes();
+            [0, 0, 0, 0xff, 0xfe, 0, high, low]
+        }
+        Address::Extended(_, extended) => {
+            let mut iid = extended.0.to_be_bytes();
+            iid[0] ^= 0x02;
+            iid
+        }
+    }
+}
+
+/// The source and destination MAC addresses of a frame header
+fn addresses(header: &Header) -> Result<(Address, Address), Error> {
+    match (header.source, header.destination) {
+        (Some(source), Some(destination)) => Ok((source, destination)),
+        _ => Err(Error::BadInput),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ieee802154::mac::{ExtendedAddress, FrameType, FrameVersion, PanId, ShortAddress};
+
+    use super::*;
+
+    pub(super) fn extended(address: u64) -> Address {
+        Address::Extended(PanId(0x1234), ExtendedAddress(address))
+    }
+
+    pub(super) fn short(address: u16) -> Address {
+        Address::Short(PanId(0x1234), ShortAddress(address))
+    }
+
+    /// Header of a data frame from `source` to `destination`
+    pub(super) fn header(source: Address, destination: Address) -> Header {
+        Header {
+            frame_type: FrameType::Data,
+            frame_pending: false,
+            ack_request: true,
+            pan_id_compress: true,
+            seq_no_suppress: false,
+            ie_present: false,
+            version: FrameVersion::Ieee802154_2006,
+            seq: 1,
+            destination: Some(destination),
+            source: Some(source),
+            auxiliary_security_header: None,
+        }
+    }
+
+    /// The link-local IPv6 address derived from the MAC address
+    pub(super) fn link_local(address: &Address) -> [u8; 16] {
+        let mut ip = [0; 16];
+        ip[..2].copy_from_slice(&[0xfe, 0x80]);
+        ip[8..].copy_from_slice(&interface_identifier(address));
+        ip
+    }
+
+    /// IPv6 packet with a hop limit of 64, carrying `payload_len` bytes of UDP
+    /// if `ports` are given, or of ICMPv6 otherwise
+    pub(super) fn packet(
+        source: [u8; 16],
+        destination: [u8; 16],
+        ports: Option<(u16, u16)>,
+        payload_len: usize,
+    ) -> std::vec::Vec<u8> {
+        let data_len = payload_len;
+        let payload_len = data_len + ports.map_or(0, |_| UDP_HEADER_LEN);
+
+        let mut packet = std::vec![0x60, 0, 0, 0];
+        packet.extend_from_slice(&(payload_len as u16).to_be_bytes());
+        packet.extend_from_slice(&[if ports.is_some() { UDP } else { 58 }, 64]);
+        packet.extend_from_slice(&source);
+        packet.extend_from_slice(&destination);
+
+        if let Some((source_port, destination_port)) = ports {
+            packet.extend_from_slice(&source_port.to_be_bytes());
+            packet.extend_from_slice(&destination_port.to_be_bytes());
+            packet.extend_from_slice(&(payload_len as u16).to_be_bytes());
+            packet.extend_from_slice(&[0xab, 0xcd]);
+        }
+        packet.extend((0..data_len).map(|i| i as u8));
+
+        packet
+    }
+
+    #[test]
+    fn interface_identifiers_are_derived_from_mac_addresses() {
+        assert_eq!(
+            interface_identifier(&extended(0x1122_3344_5566_7788)),
+            [0x13, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88]
+        );
+        assert_eq!(
+            interface_identifier(&short(0xabcd)),
+            [0, 0, 0, 0xff, 0xfe, 0, 0xab, 0xcd]
+        );
+    }
+
+    #[test]
+    fn payloads_fill_the_frame() {
+        // both PAN IDs are counted, even when the source one is elided
+        assert_eq!(
+            max_payload_len(&header(extended(1), extended(2))),
+            127 - 23 - 2
+        );
+        assert_eq!(max_payload_len(&header(short(1), short(2))), 127 - 11 - 2);
+    }
+
+    #[test]
+    fn addresses_are_required() {
+        let mut header = header(short(1), short(2));
+        assert!(addresses(&header).is_ok());
+        header.source = None;
+        assert!(matches!(addresses(&header), Err(Error::BadInput)));
+    }
+}
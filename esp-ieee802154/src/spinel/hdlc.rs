@@ -0,0 +1,271 @@
+//! HDLC-lite framing of Spinel frames on a serial line
+//!
+//! Frames are delimited by flag bytes, end with a CRC-16/X-25 FCS, and the
+//! flag, escape, XON/XOFF and `0xf8` bytes are escaped.
+
+use heapless::Vec;
+
+use crate::Error;
+
+const FLAG: u8 = 0x7e;
+const ESCAPE: u8 = 0x7d;
+const XON: u8 = 0x11;
+const XOFF: u8 = 0x13;
+const SPECIAL: u8 = 0xf8;
+const ESCAPE_XOR: u8 = 0x20;
+
+const FCS_INIT: u16 = 0xffff;
+const FCS_POLY: u16 = 0x8408;
+// Remainder of a frame followed by its FCS
+const FCS_GOOD: u16 = 0xf0b8;
+
+fn fcs_update(fcs: u16, byte: u8) -> u16 {
+    let mut fcs = fcs ^ byte as u16;
+    for _ in 0..8 {
+        fcs = if fcs & 1 != 0 {
+            (fcs >> 1) ^ FCS_POLY
+        } else {
+            fcs >> 1
+        };
+    }
+
+    fcs
+}
+
+fn needs_escape(byte: u8) -> bool {
+    matches!(byte, FLAG | ESCAPE | XON | XOFF | SPECIAL)
+}
+
+/// Encode `frame` into `out`, including the FCS and the flags around it
+///
+/// Returns [`Error::Full`] if `out` is too small, which never happens with
+/// twice the frame length plus six bytes.
+pub fn encode<const N: usize>(frame: &[u8], out: &mut Vec<u8, N>) -> Result<(), Error> {
+    fn push<const N: usize>(out: &mut Vec<u8, N>, byte: u8) -> Result<(), Error> {
+        if needs_escape(byte) {
+            out.push(ESCAPE).map_err(|_| Error::Full)?;
+            out.push(byte ^ ESCAPE_XOR).map_err(|_| Error::Full)
+        } else {
+            out.push(byte).map_err(|_| Error::Full)
+        }
+    }
+
+    out.push(FLAG).map_err(|_| Error::Full)?;
+
+    let mut fcs = FCS_INIT;
+    for &byte in frame {
+        fcs = fcs_update(fcs, byte);
+        push(out, byte)?;
+    }
+
+    for byte in (!fcs).to_le_bytes() {
+        push(out, byte)?;
+    }
+
+    out.push(FLAG).map_err(|_| Error::Full)
+}
+
+/// Decoder of the frames in a stream of bytes
+pub struct Decoder<const N: usize> {
+    buffer: Vec<u8, N>,
+    fcs: u16,
+    escaped: bool,
+    overflow: bool,
+    done: bool,
+}
+
+impl<const N: usize> Default for Decoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Decoder<N> {
+    pub const fn new() -> Self {
+        Self {
+            buffer: Vec::new(),
+            fcs: FCS_INIT,
+            escaped: false,
+            overflow: false,
+            done: false,
+        }
+    }
+
+    /// Feed the next byte, returns the frame it completes, without the FCS
+    ///
+    /// Frames longer than `N` bytes, including the FCS, are reported as
+    /// [`Error::Full`], frames with a wrong FCS as [`Error::BadInput`] and
+    /// frames too short to hold an FCS as [`Error::Incomplete`].
+    pub fn push(&mut self, byte: u8) -> Option<Result<&[u8], Error>> {
+        if self.done {
+            self.reset();
+        }
+
+        match byte {
+            FLAG => {
+                if self.buffer.is_empty() && !self.overflow {
+                    // flags between frames
+                    self.escaped = false;
+                    return None;
+                }
+
+                self.done = true;
+
+                let result = if self.overflow {
+                    Err(Error::Full)
+                } else if self.escaped || self.buffer.len() < 2 {
+                    Err(Error::Incomplete)
+                } else if self.fcs != FCS_GOOD {
+                    Err(Error::BadInput)
+                } else {
+                    Ok(&self.buffer[..self.buffer.len() - 2])
+                };
+
+                Some(result)
+            }
+            ESCAPE => {
+                self.escaped = true;
+                None
+            }
+            byte => {
+                let byte = if core::mem::take(&mut self.escaped) {
+                    byte ^ ESCAPE_XOR
+                } else {
+                    byte
+                };
+
+                self.fcs = fcs_update(self.fcs, byte);
+                if self.buffer.push(byte).is_err() {
+                    self.overflow = true;
+                }
+
+                None
+            }
+        }
+    }
+
+    fn reset(&mut self) {
+        self.buffer.clear();
+        self.fcs = FCS_INIT;
+        self.escaped = false;
+        self.overflow = false;
+        self.done = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The results of the frames completed by `bytes`
+    fn decode<const N: usize>(
+        decoder: &mut Decoder<N>,
+        bytes: &[u8],
+    ) -> std::vec::Vec<Result<std::vec::Vec<u8>, Error>> {
+        let mut results = std::vec::Vec::new();
+        for &byte in bytes {
+            if let Some(result) = decoder.push(byte) {
+                results.push(result.map(|frame| frame.to_vec()));
+            }
+        }
+
+        results
+    }
+
+    #[test]
+    fn special_bytes_are_escaped() {
+        let mut out = Vec::<u8, 32>::new();
+        encode(&[0x7e, 0x7d, 0x11, 0x13, 0xf8, 0x20], &mut out).unwrap();
+
+        assert_eq!(
+            out[..12],
+            [0x7e, 0x7d, 0x5e, 0x7d, 0x5d, 0x7d, 0x31, 0x7d, 0x33, 0x7d, 0xd8, 0x20]
+        );
+        assert_eq!(out.last(), Some(&FLAG));
+        // only the delimiting flags are left unescaped
+        assert_eq!(out.iter().filter(|&&byte| byte == FLAG).count(), 2);
+    }
+
+    #[test]
+    fn fcs_is_crc16_x25() {
+        let mut out = Vec::<u8, 32>::new();
+        encode(b"123456789", &mut out).unwrap();
+
+        // check value of CRC-16/X-25
+        assert_eq!(out[10..], [0x6e, 0x90, FLAG]);
+
+        let fcs = b"123456789\x6e\x90"
+            .iter()
+            .fold(FCS_INIT, |fcs, &byte| fcs_update(fcs, byte));
+        assert_eq!(fcs, FCS_GOOD);
+    }
+
+    #[test]
+    fn encoded_frames_are_decoded() {
+        let frames: [&[u8]; 3] = [&[0x81, 0x02, 0x01], &[0x7e, 0x7d, 0xf8], &[0x00; 64]];
+
+        let mut stream = std::vec![FLAG, FLAG];
+        for frame in frames {
+            let mut out = Vec::<u8, 256>::new();
+            encode(frame, &mut out).unwrap();
+            stream.extend_from_slice(&out);
+        }
+
+        let mut decoder = Decoder::<66>::new();
+        let decoded = decode(&mut decoder, &stream);
+        assert_eq!(decoded.len(), 3);
+        for (decoded, frame) in decoded.into_iter().zip(frames) {
+            assert_eq!(decoded.unwrap(), frame);
+        }
+    }
+
+    #[test]
+    fn encoding_fails_without_room() {
+        let mut out = Vec::<u8, 8>::new();
+        assert!(matches!(encode(&[0x7e; 4], &mut out), Err(Error::Full)));
+    }
+
+    #[test]
+    fn malformed_frames_are_reported() {
+        let mut out = Vec::<u8, 16>::new();
+        encode(&[0x81, 0x02, 0x01], &mut out).unwrap();
+        let mut decoder = Decoder::<8>::new();
+
+        // a wrong FCS
+        let mut garbled = out.clone();
+        garbled[2] ^= 0x01;
+        assert!(matches!(
+            decode(&mut decoder, &garbled)[..],
+            [Err(Error::BadInput)]
+        ));
+
+        // too short to hold an FCS
+        assert!(matches!(
+            decode(&mut decoder, &[FLAG, 0x81, FLAG])[..],
+            [Err(Error::Incomplete)]
+        ));
+
+        // cut off after an escape
+        assert!(matches!(
+            decode(&mut decoder, &[FLAG, 0x81, 0x02, 0x01, ESCAPE, FLAG])[..],
+            [Err(Error::Incomplete)]
+        ));
+
+        // longer than the buffer
+        let mut long = Vec::<u8, 32>::new();
+        encode(&[0x00; 8], &mut long).unwrap();
+        assert!(matches!(
+            decode(&mut decoder, &long)[..],
+            [Err(Error::Full)]
+        ));
+
+        // the decoder recovers for the next frame
+        assert_eq!(
+            decode(&mut decoder, &out)
+                .into_iter()
+                .map(Result::unwrap)
+                .collect::<std::vec::Vec<_>>(),
+            [[0x81, 0x02, 0x01]]
+        );
+    }
+}
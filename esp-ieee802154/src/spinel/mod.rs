@@ -0,0 +1,514 @@
+//! Spinel protocol, used with the `spinel` feature
+//!
+//! Spinel is the protocol between an OpenThread host, like `ot-br-posix`, and
+//! its Radio Co-Processor (RCP). [`Rcp`] serves as the RCP on top of
+//! [`Ieee802154`](crate::Ieee802154), mapping the Spinel properties to driver
+//! calls. The [`hdlc`] framing and the frame codec in this module don't use
+//! the radio, so they can be tested on the host.
+//!
+//! A frame consists of a header byte, a command, for most commands a
+//! property, and the value of the property. Commands, properties and values
+//! are encoded with [`Writer`] and decoded with [`Reader`].
+
+pub mod hdlc;
+mod rcp;
+
+pub use rcp::Rcp;
+
+use crate::Error;
+
+pub const PROTOCOL_VERSION_MAJOR: u32 = 4;
+pub const PROTOCOL_VERSION_MINOR: u32 = 3;
+
+/// Commands
+pub mod cmd {
+    pub const NOOP: u32 = 0;
+    pub const RESET: u32 = 1;
+    pub const PROP_VALUE_GET: u32 = 2;
+    pub const PROP_VALUE_SET: u32 = 3;
+    pub const PROP_VALUE_INSERT: u32 = 4;
+    pub const PROP_VALUE_REMOVE: u32 = 5;
+    pub const PROP_VALUE_IS: u32 = 6;
+    pub const PROP_VALUE_INSERTED: u32 = 7;
+    pub const PROP_VALUE_REMOVED: u32 = 8;
+}
+
+/// Properties
+pub mod prop {
+    pub const LAST_STATUS: u32 = 0;
+    pub const PROTOCOL_VERSION: u32 = 1;
+    pub const NCP_VERSION: u32 = 2;
+    pub const INTERFACE_TYPE: u32 = 3;
+    pub const VENDOR_ID: u32 = 4;
+    pub const CAPS: u32 = 5;
+    pub const INTERFACE_COUNT: u32 = 6;
+    pub const HWADDR: u32 = 8;
+
+    pub const PHY_ENABLED: u32 = 0x20;
+    pub const PHY_CHAN: u32 = 0x21;
+    pub const PHY_CHAN_SUPPORTED: u32 = 0x22;
+    pub const PHY_FREQ: u32 = 0x23;
+    pub const PHY_CCA_THRESHOLD: u32 = 0x24;
+    pub const PHY_TX_POWER: u32 = 0x25;
+    pub const PHY_RSSI: u32 = 0x26;
+    pub const PHY_RX_SENSITIVITY: u32 = 0x27;
+
+    pub const MAC_SCAN_STATE: u32 = 0x30;
+    pub const MAC_SCAN_MASK: u32 = 0x31;
+    pub const MAC_SCAN_PERIOD: u32 = 0x32;
+    pub const MAC_15_4_LADDR: u32 = 0x34;
+    pub const MAC_15_4_SADDR: u32 = 0x35;
+    pub const MAC_15_4_PANID: u32 = 0x36;
+    pub const MAC_RAW_STREAM_ENABLED: u32 = 0x37;
+    pub const MAC_PROMISCUOUS_MODE: u32 = 0x38;
+    pub const MAC_ENERGY_SCAN_RESULT: u32 = 0x39;
+
+    pub const STREAM_DEBUG: u32 = 0x70;
+    pub const STREAM_RAW: u32 = 0x71;
+
+    pub const RCP_API_VERSION: u32 = 0x800;
+    pub const RCP_MIN_HOST_API_VERSION: u32 = 0x801;
+
+    pub const RADIO_CAPS: u32 = 0x120b;
+
+    pub const MAC_SRC_MATCH_ENABLED: u32 = 0x1303;
+    pub const MAC_SRC_MATCH_SHORT_ADDRESSES: u32 = 0x1304;
+    pub const MAC_SRC_MATCH_EXTENDED_ADDRESSES: u32 = 0x1305;
+}
+
+/// Values of [`prop::LAST_STATUS`]
+pub mod status {
+    pub const OK: u32 = 0;
+    pub const FAILURE: u32 = 1;
+    pub const UNIMPLEMENTED: u32 = 2;
+    pub const INVALID_ARGUMENT: u32 = 3;
+    pub const INVALID_STATE: u32 = 4;
+    pub const INVALID_COMMAND: u32 = 5;
+    pub const PARSE_ERROR: u32 = 9;
+    pub const NOMEM: u32 = 11;
+    pub const BUSY: u32 = 12;
+    pub const PROP_NOT_FOUND: u32 = 13;
+    pub const NO_ACK: u32 = 17;
+    pub const CCA_FAILURE: u32 = 18;
+    pub const ITEM_NOT_FOUND: u32 = 20;
+    pub const ABORT: u32 = 35;
+    pub const RESET_POWER_ON: u32 = 112;
+    pub const RESET_SOFTWARE: u32 = 114;
+}
+
+/// Values of [`prop::CAPS`]
+pub mod cap {
+    pub const IEEE802154_2450MHZ_OQPSK: u32 = 24;
+    pub const CONFIG_RADIO: u32 = 34;
+    pub const RCP_API_VERSION: u32 = 64;
+    pub const RCP_MIN_HOST_API_VERSION: u32 = 65;
+    pub const MAC_RAW: u32 = 513;
+}
+
+/// Values of [`prop::MAC_SCAN_STATE`]
+pub mod scan_state {
+    pub const IDLE: u8 = 0;
+    pub const BEACON: u8 = 1;
+    pub const ENERGY: u8 = 2;
+}
+
+/// Bits of the flags in the metadata of [`prop::STREAM_RAW`] frames
+pub mod md_flag {
+    pub const TX: u16 = 0x0001;
+    pub const BAD_FCS: u16 = 0x0004;
+    pub const DUPE: u16 = 0x0008;
+    pub const ACKED_FP: u16 = 0x0010;
+    pub const ACKED_SEC: u16 = 0x0020;
+}
+
+/// Interface type of a Thread RCP, the value of [`prop::INTERFACE_TYPE`]
+pub const PROTOCOL_TYPE_THREAD: u32 = 3;
+
+const HEADER_FLAG: u8 = 0x80;
+const HEADER_FLAG_MASK: u8 = 0xc0;
+const HEADER_IID_SHIFT: u8 = 4;
+const HEADER_IID_MASK: u8 = 0x03;
+const HEADER_TID_MASK: u8 = 0x0f;
+
+/// Frame header
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Header {
+    /// Interface identifier
+    pub iid: u8,
+    /// Transaction identifier, 0 for unsolicited frames
+    pub tid: u8,
+}
+
+impl Header {
+    pub fn from_byte(byte: u8) -> Result<Self, Error> {
+        if byte & HEADER_FLAG_MASK != HEADER_FLAG {
+            return Err(Error::BadInput);
+        }
+
+        Ok(Self {
+            iid: (byte >> HEADER_IID_SHIFT) & HEADER_IID_MASK,
+            tid: byte & HEADER_TID_MASK,
+        })
+    }
+
+    pub fn to_byte(self) -> u8 {
+        HEADER_FLAG
+            | (self.iid & HEADER_IID_MASK) << HEADER_IID_SHIFT
+            | (self.tid & HEADER_TID_MASK)
+    }
+}
+
+/// A decoded frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub header: Header,
+    pub command: u32,
+    /// The property, and the value following it, for the `PROP_VALUE_*`
+    /// commands
+    pub payload: &'a [u8],
+}
+
+impl<'a> Frame<'a> {
+    pub fn decode(data: &'a [u8]) -> Result<Self, Error> {
+        let mut reader = Reader::new(data);
+        let header = Header::from_byte(reader.read_u8()?)?;
+        let command = reader.read_uint_packed()?;
+
+        Ok(Self {
+            header,
+            command,
+            payload: reader.remaining(),
+        })
+    }
+}
+
+/// Decoder of the Spinel data types
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// The data not read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], Error> {
+        let (bytes, rest) = self
+            .data
+            .split_first_chunk::<N>()
+            .ok_or(Error::Incomplete)?;
+        self.data = rest;
+
+        Ok(*bytes)
+    }
+
+    /// `C`
+    pub fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    /// `c`
+    pub fn read_i8(&mut self) -> Result<i8, Error> {
+        Ok(self.read_u8()? as i8)
+    }
+
+    /// `b`
+    pub fn read_bool(&mut self) -> Result<bool, Error> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(Error::BadInput),
+        }
+    }
+
+    /// `S`
+    pub fn read_u16(&mut self) -> Result<u16, Error> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    /// `L`
+    pub fn read_u32(&mut self) -> Result<u32, Error> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    /// `X`
+    pub fn read_u64(&mut self) -> Result<u64, Error> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    /// `E`, an EUI-64 or extended address
+    pub fn read_eui64(&mut self) -> Result<[u8; 8], Error> {
+        self.take()
+    }
+
+    /// `i`, an unsigned integer of up to 21 bits, 7 bits per byte
+    pub fn read_uint_packed(&mut self) -> Result<u32, Error> {
+        let mut value = 0u32;
+        for shift in (0..21).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= ((byte & 0x7f) as u32) << shift;
+
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(Error::BadInput)
+    }
+
+    /// `d`, data prefixed with its length
+    pub fn read_data(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.read_u16()? as usize;
+        if len > self.data.len() {
+            return Err(Error::Incomplete);
+        }
+
+        let (data, rest) = self.data.split_at(len);
+        self.data = rest;
+
+        Ok(data)
+    }
+}
+
+/// Encoder of the Spinel data types
+pub struct Writer<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, len: 0 }
+    }
+
+    /// Start a frame with `header`, `command` and `property`
+    pub fn frame(
+        buffer: &'a mut [u8],
+        header: Header,
+        command: u32,
+        property: u32,
+    ) -> Result<Self, Error> {
+        let mut writer = Self::new(buffer);
+        writer.write_u8(header.to_byte())?;
+        writer.write_uint_packed(command)?;
+        writer.write_uint_packed(property)?;
+
+        Ok(writer)
+    }
+
+    /// The data written so far
+    pub fn finish(self) -> &'a [u8] {
+        &self.buffer[..self.len]
+    }
+
+    /// Raw bytes, like the `D` data type
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.buffer
+            .get_mut(self.len..self.len + bytes.len())
+            .ok_or(Error::Full)?
+            .copy_from_slice(bytes);
+        self.len += bytes.len();
+
+        Ok(())
+    }
+
+    pub fn write_u8(&mut self, value: u8) -> Result<(), Error> {
+        self.write_bytes(&[value])
+    }
+
+    pub fn write_i8(&mut self, value: i8) -> Result<(), Error> {
+        self.write_u8(value as u8)
+    }
+
+    pub fn write_bool(&mut self, value: bool) -> Result<(), Error> {
+        self.write_u8(value as u8)
+    }
+
+    pub fn write_u16(&mut self, value: u16) -> Result<(), Error> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    pub fn write_u32(&mut self, value: u32) -> Result<(), Error> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    pub fn write_u64(&mut self, value: u64) -> Result<(), Error> {
+        self.write_bytes(&value.to_le_bytes())
+    }
+
+    pub fn write_eui64(&mut self, value: &[u8; 8]) -> Result<(), Error> {
+        self.write_bytes(value)
+    }
+
+    pub fn write_uint_packed(&mut self, mut value: u32) -> Result<(), Error> {
+        if value >= 1 << 21 {
+            return Err(Error::BadInput);
+        }
+
+        loop {
+            let byte = (value & 0x7f) as u8;
+            value >>= 7;
+
+            if value == 0 {
+                return self.write_u8(byte);
+            }
+            self.write_u8(byte | 0x80)?;
+        }
+    }
+
+    /// `d`, data prefixed with its length
+    pub fn write_data(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.write_u16(data.len() as u16)?;
+        self.write_bytes(data)
+    }
+
+    /// `U`, a zero terminated UTF-8 string
+    pub fn write_utf8(&mut self, value: &str) -> Result<(), Error> {
+        self.write_bytes(value.as_bytes())?;
+        self.write_u8(0)
+    }
+
+    /// `t(...)`, a structure prefixed with its length
+    pub fn write_struct(
+        &mut self,
+        f: impl FnOnce(&mut Self) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let start = self.len;
+        self.write_u16(0)?;
+        f(self)?;
+
+        let len = (self.len - start - 2) as u16;
+        self.buffer[start..][..2].copy_from_slice(&len.to_le_bytes());
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_uints_round_trip() {
+        let values: [(u32, &[u8]); 6] = [
+            (0, &[0x00]),
+            (0x7f, &[0x7f]),
+            (0x80, &[0x80, 0x01]),
+            (1337, &[0xb9, 0x0a]),
+            (0x3fff, &[0xff, 0x7f]),
+            ((1 << 21) - 1, &[0xff, 0xff, 0x7f]),
+        ];
+
+        for (value, encoded) in values {
+            let mut buffer = [0; 3];
+            let mut writer = Writer::new(&mut buffer);
+            writer.write_uint_packed(value).unwrap();
+            assert_eq!(writer.finish(), encoded);
+
+            let mut reader = Reader::new(encoded);
+            assert_eq!(reader.read_uint_packed().unwrap(), value);
+            assert!(reader.is_empty());
+        }
+    }
+
+    #[test]
+    fn malformed_packed_uints_are_rejected() {
+        let mut buffer = [0; 4];
+        assert!(matches!(
+            Writer::new(&mut buffer).write_uint_packed(1 << 21),
+            Err(Error::BadInput)
+        ));
+
+        // more than 21 bits
+        assert!(matches!(
+            Reader::new(&[0x80, 0x80, 0x80, 0x01]).read_uint_packed(),
+            Err(Error::BadInput)
+        ));
+        // cut off
+        assert!(matches!(
+            Reader::new(&[0x80, 0x80]).read_uint_packed(),
+            Err(Error::Incomplete)
+        ));
+    }
+
+    #[test]
+    fn data_round_trips() {
+        let mut buffer = [0; 16];
+        let mut writer = Writer::new(&mut buffer);
+        writer.write_data(&[0x7e, 0x7d, 0x11]).unwrap();
+        writer.write_data(&[]).unwrap();
+        writer.write_struct(|w| w.write_u16(0xbeef)).unwrap();
+        let encoded = writer.finish();
+        assert_eq!(encoded, [3, 0, 0x7e, 0x7d, 0x11, 0, 0, 2, 0, 0xef, 0xbe]);
+
+        let mut reader = Reader::new(encoded);
+        assert_eq!(reader.read_data().unwrap(), [0x7e, 0x7d, 0x11]);
+        assert!(reader.read_data().unwrap().is_empty());
+        assert_eq!(reader.read_data().unwrap(), [0xef, 0xbe]);
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn malformed_data_is_rejected() {
+        // the length exceeds the remaining data
+        let mut reader = Reader::new(&[4, 0, 1, 2, 3]);
+        assert!(matches!(reader.read_data(), Err(Error::Incomplete)));
+        assert!(matches!(
+            Reader::new(&[4]).read_data(),
+            Err(Error::Incomplete)
+        ));
+
+        assert!(matches!(
+            Reader::new(&[2]).read_bool(),
+            Err(Error::BadInput)
+        ));
+        assert!(matches!(
+            Reader::new(&[1, 2, 3]).read_u32(),
+            Err(Error::Incomplete)
+        ));
+
+        let mut buffer = [0; 4];
+        assert!(matches!(
+            Writer::new(&mut buffer).write_data(&[1, 2, 3]),
+            Err(Error::Full)
+        ));
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        let mut buffer = [0; 8];
+        let header = Header { iid: 1, tid: 7 };
+        let mut writer =
+            Writer::frame(&mut buffer, header, cmd::PROP_VALUE_SET, prop::RADIO_CAPS).unwrap();
+        writer.write_u8(11).unwrap();
+        let encoded = writer.finish();
+        assert_eq!(encoded, [0x97, 0x03, 0x8b, 0x24, 11]);
+
+        let frame = Frame::decode(encoded).unwrap();
+        assert_eq!(frame.header, header);
+        assert_eq!(frame.command, cmd::PROP_VALUE_SET);
+        let mut reader = Reader::new(frame.payload);
+        assert_eq!(reader.read_uint_packed().unwrap(), prop::RADIO_CAPS);
+        assert_eq!(reader.read_u8().unwrap(), 11);
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        // the header flag is missing
+        assert!(matches!(Frame::decode(&[0x01, 0x02]), Err(Error::BadInput)));
+        assert!(matches!(Frame::decode(&[]), Err(Error::Incomplete)));
+        assert!(matches!(
+            Frame::decode(&[0x81, 0x82]),
+            Err(Error::Incomplete)
+        ));
+    }
+}
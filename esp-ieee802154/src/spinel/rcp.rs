@@ -0,0 +1,978 @@
+use heapless::Vec;
+
+use super::{
+    cap, cmd, hdlc, md_flag, prop, scan_state, status, Frame, Header, Reader, Writer,
+    PROTOCOL_TYPE_THREAD, PROTOCOL_VERSION_MAJOR, PROTOCOL_VERSION_MINOR,
+};
+use crate::{
+    ack::{ack_frame, ack_requested, acked_with_frame_pending},
+    csma::{Outcome, Step, Transmission},
+    raw::{
+        ieee802154_energy_detect, ieee802154_energy_detect_result, ieee802154_last_rssi,
+        ieee802154_sleep, ieee802154_transmit, set_cca_theshold, set_channel, set_extended_address,
+        set_panid, set_pending_mode, set_promiscuous, set_short_address, set_tx_power,
+    },
+    rng::Rng,
+    rssi_to_lqi, Channel, ChannelMask, Config, Error, Ieee802154, PendingMode, TxPower,
+};
+
+const MAX_FRAME_LEN: usize = 256;
+const MAX_ENCODED_LEN: usize = 2 * (MAX_FRAME_LEN + 2) + 2;
+const MAX_PSDU_LEN: usize = 127;
+const FCS_LEN: usize = 2;
+
+const RCP_API_VERSION: u32 = 10;
+const RCP_MIN_HOST_API_VERSION: u32 = 4;
+
+const CAPS: [u32; 5] = [
+    cap::IEEE802154_2450MHZ_OQPSK,
+    cap::CONFIG_RADIO,
+    cap::RCP_API_VERSION,
+    cap::RCP_MIN_HOST_API_VERSION,
+    cap::MAC_RAW,
+];
+
+// ACK timeout, energy scan, transmit retries and CSMA backoff, in the bits of
+// the OpenThread radio capabilities
+const RADIO_CAPS: u32 = 0x0f;
+
+// Typical receive sensitivity of the ESP32-C6, the ESP32-H2 is slightly better
+const RECEIVE_SENSITIVITY: i8 = -104;
+const RSSI_INVALID: i8 = 127;
+
+// macMaxCSMABackoffs and macMaxFrameRetries, unless given by the host
+const MAX_CSMA_BACKOFFS: u8 = 4;
+const MAX_FRAME_RETRIES: u8 = 3;
+
+const SYMBOL_US: u32 = 16;
+
+const UNSOLICITED: Header = Header { iid: 0, tid: 0 };
+
+/// Spinel status replied to a request
+struct Status(u32);
+
+impl From<Error> for Status {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Full => Status(status::NOMEM),
+            Error::Incomplete | Error::BadInput => Status(status::PARSE_ERROR),
+        }
+    }
+}
+
+/// Radio Co-Processor speaking Spinel over HDLC-lite
+///
+/// Bytes received from the host are passed to [`Rcp::receive`], and
+/// [`Rcp::process`] has to be called regularly to report received frames,
+/// finished transmissions and energy scans. Both pass the encoded frames to
+/// send to the host to `write`.
+pub struct Rcp<'a> {
+    driver: Ieee802154<'a>,
+    eui64: [u8; 8],
+    now: fn() -> u64,
+    decoder: hdlc::Decoder<MAX_FRAME_LEN>,
+    last_status: u32,
+    enabled: bool,
+    raw_stream: bool,
+    channel: Channel,
+    tx_power: i8,
+    cca_threshold: i8,
+    promiscuous: u8,
+    pan_id: u16,
+    short_address: u16,
+    ext_address: [u8; 8],
+    src_match: bool,
+    scan_mask: ChannelMask,
+    scan_period: u16,
+    scan: Option<Channel>,
+    transmit: Option<(Transmission, Header)>,
+    rng: Rng,
+}
+
+impl<'a> Rcp<'a> {
+    /// `eui64` is the factory assigned EUI-64 of the device, and `now`
+    /// returns the time in microseconds, used for the CSMA-CA backoffs and to
+    /// timestamp received frames.
+    pub fn new(driver: Ieee802154<'a>, eui64: [u8; 8], now: fn() -> u64) -> Self {
+        let mut rcp = Self {
+            driver,
+            eui64,
+            now,
+            decoder: hdlc::Decoder::new(),
+            last_status: status::OK,
+            enabled: false,
+            raw_stream: false,
+            channel: Channel::MIN,
+            tx_power: 0,
+            cca_threshold: 0,
+            promiscuous: 0,
+            pan_id: 0xffff,
+            short_address: 0xfffe,
+            ext_address: [0; 8],
+            src_match: false,
+            scan_mask: ChannelMask::empty(),
+            scan_period: 0,
+            scan: None,
+            transmit: None,
+            rng: Rng::new(eui64),
+        };
+        rcp.reset();
+
+        rcp
+    }
+
+    /// Announce the power on reset to the host
+    pub fn start(&mut self, mut write: impl FnMut(&[u8])) {
+        self.send_status(&mut write, UNSOLICITED, status::RESET_POWER_ON);
+    }
+
+    /// Handle a byte received from the host
+    pub fn receive(&mut self, byte: u8, mut write: impl FnMut(&[u8])) {
+        let mut frame = [0u8; MAX_FRAME_LEN];
+        let len = match self.decoder.push(byte) {
+            None => return,
+            Some(Ok(data)) => {
+                frame[..data.len()].copy_from_slice(data);
+                data.len()
+            }
+            Some(Err(err)) => {
+                log::warn!("Dropping malformed Spinel frame: {:?}", err);
+                return;
+            }
+        };
+
+        self.handle(&frame[..len], &mut write);
+    }
+
+    /// Report received frames, finished transmissions and energy scans to the
+    /// host, and continue the ongoing transmission
+    pub fn process(&mut self, mut write: impl FnMut(&[u8])) {
+        self.process_scan(&mut write);
+        self.process_transmit(&mut write);
+        self.process_receive(&mut write);
+    }
+
+    fn reset(&mut self) {
+        ieee802154_sleep();
+
+        let config = Config {
+            auto_ack_tx: true,
+            auto_ack_rx: true,
+            rx_when_idle: true,
+            channel: Channel::MIN,
+            pending_mode: PendingMode::Disable,
+            ..Default::default()
+        };
+        self.driver.set_config(config);
+        self.driver.clear_pending_short_addresses();
+        self.driver.clear_pending_ext_addresses();
+        ieee802154_sleep();
+
+        self.enabled = false;
+        self.raw_stream = false;
+        self.channel = config.channel;
+        self.tx_power = config.txpower;
+        self.cca_threshold = config.cca_threshold;
+        self.promiscuous = 0;
+        self.pan_id = 0xffff;
+        self.short_address = 0xfffe;
+        self.ext_address = [0; 8];
+        self.src_match = false;
+        self.scan_mask = ChannelMask::empty();
+        self.scan_period = 0;
+        self.scan = None;
+        self.transmit = None;
+    }
+
+    fn handle(&mut self, data: &[u8], write: &mut impl FnMut(&[u8])) {
+        let frame = match Frame::decode(data) {
+            Ok(frame) => frame,
+            Err(err) => {
+                log::warn!("Dropping malformed Spinel frame: {:?}", err);
+                return;
+            }
+        };
+
+        let header = frame.header;
+        match frame.command {
+            cmd::NOOP => self.send_status(write, header, status::OK),
+            cmd::RESET => {
+                self.reset();
+                self.send_status(write, header, status::RESET_SOFTWARE);
+            }
+            cmd::PROP_VALUE_GET
+            | cmd::PROP_VALUE_SET
+            | cmd::PROP_VALUE_INSERT
+            | cmd::PROP_VALUE_REMOVE => {
+                let mut reader = Reader::new(frame.payload);
+                let Ok(property) = reader.read_uint_packed() else {
+                    self.send_status(write, header, status::PARSE_ERROR);
+                    return;
+                };
+                let value = reader.remaining();
+
+                let result = match frame.command {
+                    cmd::PROP_VALUE_GET => Ok(cmd::PROP_VALUE_IS),
+                    cmd::PROP_VALUE_SET if property == prop::STREAM_RAW => {
+                        // replied to once the transmission is done
+                        match self.start_transmit(header, &mut reader) {
+                            Ok(()) => return,
+                            Err(status) => Err(status),
+                        }
+                    }
+                    cmd::PROP_VALUE_SET => {
+                        self.set(property, &mut reader).map(|()| cmd::PROP_VALUE_IS)
+                    }
+                    cmd::PROP_VALUE_INSERT => self
+                        .insert(property, &mut reader)
+                        .map(|()| cmd::PROP_VALUE_INSERTED),
+                    _ => self
+                        .remove(property, &mut reader)
+                        .map(|()| cmd::PROP_VALUE_REMOVED),
+                };
+
+                match result {
+                    Ok(cmd::PROP_VALUE_IS) => {
+                        if send(write, header, cmd::PROP_VALUE_IS, property, |w| {
+                            self.value(property, w)
+                        })
+                        .is_err()
+                        {
+                            self.send_status(write, header, status::PROP_NOT_FOUND);
+                        }
+                    }
+                    Ok(command) => {
+                        send(write, header, command, property, |w| w.write_bytes(value)).ok();
+                    }
+                    Err(Status(status)) => self.send_status(write, header, status),
+                }
+            }
+            _ => self.send_status(write, header, status::INVALID_COMMAND),
+        }
+    }
+
+    /// Encode the value of `property`, fails for unknown properties
+    fn value(&self, property: u32, w: &mut Writer) -> Result<(), Error> {
+        match property {
+            prop::LAST_STATUS => w.write_uint_packed(self.last_status),
+            prop::PROTOCOL_VERSION => {
+                w.write_uint_packed(PROTOCOL_VERSION_MAJOR)?;
+                w.write_uint_packed(PROTOCOL_VERSION_MINOR)
+            }
+            prop::NCP_VERSION => {
+                w.write_utf8(concat!("esp-ieee802154/", env!("CARGO_PKG_VERSION")))
+            }
+            prop::INTERFACE_TYPE => w.write_uint_packed(PROTOCOL_TYPE_THREAD),
+            prop::VENDOR_ID => w.write_uint_packed(0),
+            prop::CAPS => CAPS.iter().try_for_each(|&cap| w.write_uint_packed(cap)),
+            prop::HWADDR => w.write_eui64(&self.eui64),
+            prop::PHY_ENABLED => w.write_bool(self.enabled),
+            prop::PHY_CHAN => w.write_u8(self.channel.into()),
+            prop::PHY_CHAN_SUPPORTED => {
+                Channel::all().try_for_each(|channel| w.write_u8(channel.into()))
+            }
+            prop::PHY_CCA_THRESHOLD => w.write_i8(self.cca_threshold),
+            prop::PHY_TX_POWER => w.write_i8(TxPower::from_dbm(self.tx_power).dbm),
+            prop::PHY_RSSI => w.write_i8(ieee802154_last_rssi()),
+            prop::PHY_RX_SENSITIVITY => w.write_i8(RECEIVE_SENSITIVITY),
+            prop::MAC_SCAN_STATE => w.write_u8(if self.scan.is_some() {
+                scan_state::ENERGY
+            } else {
+                scan_state::IDLE
+            }),
+            prop::MAC_SCAN_MASK => self
+                .scan_mask
+                .iter()
+                .try_for_each(|channel| w.write_u8(channel.into())),
+            prop::MAC_SCAN_PERIOD => w.write_u16(self.scan_period),
+            prop::MAC_15_4_LADDR => w.write_eui64(&self.ext_address),
+            prop::MAC_15_4_SADDR => w.write_u16(self.short_address),
+            prop::MAC_15_4_PANID => w.write_u16(self.pan_id),
+            prop::MAC_RAW_STREAM_ENABLED => w.write_bool(self.raw_stream),
+            prop::MAC_PROMISCUOUS_MODE => w.write_u8(self.promiscuous),
+            prop::MAC_SRC_MATCH_ENABLED => w.write_bool(self.src_match),
+            prop::RCP_API_VERSION => w.write_uint_packed(RCP_API_VERSION),
+            prop::RCP_MIN_HOST_API_VERSION => w.write_uint_packed(RCP_MIN_HOST_API_VERSION),
+            prop::RADIO_CAPS => w.write_uint_packed(RADIO_CAPS),
+            _ => Err(Error::BadInput),
+        }
+    }
+
+    fn set(&mut self, property: u32, r: &mut Reader) -> Result<(), Status> {
+        match property {
+            prop::PHY_ENABLED => {
+                self.enabled = r.read_bool()?;
+                self.update_receive();
+            }
+            prop::PHY_CHAN => {
+                self.channel = read_channel(r)?;
+                set_channel(self.channel);
+                self.update_receive();
+            }
+            prop::PHY_CCA_THRESHOLD => {
+                self.cca_threshold = r.read_i8()?;
+                set_cca_theshold(self.cca_threshold);
+            }
+            prop::PHY_TX_POWER => {
+                self.tx_power = r.read_i8()?;
+                set_tx_power(self.tx_power);
+            }
+            prop::MAC_SCAN_MASK => {
+                let mut mask = ChannelMask::empty();
+                while !r.is_empty() {
+                    mask.insert(read_channel(r)?);
+                }
+                self.scan_mask = mask;
+            }
+            prop::MAC_SCAN_PERIOD => self.scan_period = r.read_u16()?,
+            prop::MAC_SCAN_STATE => match r.read_u8()? {
+                scan_state::IDLE => {
+                    self.scan = None;
+                    set_channel(self.channel);
+                    self.update_receive();
+                }
+                scan_state::ENERGY => self.start_scan()?,
+                _ => return Err(Status(status::UNIMPLEMENTED)),
+            },
+            prop::MAC_15_4_LADDR => {
+                // big-endian, like the radio takes it
+                self.ext_address = r.read_eui64()?;
+                set_extended_address(0, self.ext_address);
+                self.update_receive();
+            }
+            prop::MAC_15_4_SADDR => {
+                self.short_address = r.read_u16()?;
+                set_short_address(0, self.short_address);
+                self.update_receive();
+            }
+            prop::MAC_15_4_PANID => {
+                self.pan_id = r.read_u16()?;
+                set_panid(0, self.pan_id);
+                self.update_receive();
+            }
+            prop::MAC_RAW_STREAM_ENABLED => {
+                self.raw_stream = r.read_bool()?;
+                self.update_receive();
+            }
+            prop::MAC_PROMISCUOUS_MODE => {
+                self.promiscuous = r.read_u8()?;
+                set_promiscuous(self.promiscuous != 0);
+                self.update_receive();
+            }
+            prop::MAC_SRC_MATCH_ENABLED => {
+                self.src_match = r.read_bool()?;
+                set_pending_mode(if self.src_match {
+                    PendingMode::Enable
+                } else {
+                    PendingMode::Disable
+                });
+                self.update_receive();
+            }
+            prop::MAC_SRC_MATCH_SHORT_ADDRESSES => {
+                self.driver.clear_pending_short_addresses();
+                while !r.is_empty() {
+                    self.driver.add_pending_short_address(r.read_u16()?)?;
+                }
+            }
+            prop::MAC_SRC_MATCH_EXTENDED_ADDRESSES => {
+                self.driver.clear_pending_ext_addresses();
+                while !r.is_empty() {
+                    let address = u64::from_le_bytes(r.read_eui64()?);
+                    self.driver.add_pending_ext_address(address)?;
+                }
+            }
+            _ => return Err(Status(status::PROP_NOT_FOUND)),
+        }
+
+        Ok(())
+    }
+
+    fn insert(&mut self, property: u32, r: &mut Reader) -> Result<(), Status> {
+        match property {
+            prop::MAC_SRC_MATCH_SHORT_ADDRESSES => {
+                self.driver.add_pending_short_address(r.read_u16()?)?;
+            }
+            // little-endian, unlike the extended address of the radio
+            prop::MAC_SRC_MATCH_EXTENDED_ADDRESSES => {
+                let address = u64::from_le_bytes(r.read_eui64()?);
+                self.driver.add_pending_ext_address(address)?;
+            }
+            _ => return Err(Status(status::PROP_NOT_FOUND)),
+        }
+
+        Ok(())
+    }
+
+    fn remove(&mut self, property: u32, r: &mut Reader) -> Result<(), Status> {
+        let removed = match property {
+            prop::MAC_SRC_MATCH_SHORT_ADDRESSES => {
+                self.driver.remove_pending_short_address(r.read_u16()?)
+            }
+            prop::MAC_SRC_MATCH_EXTENDED_ADDRESSES => {
+                let address = u64::from_le_bytes(r.read_eui64()?);
+                self.driver.remove_pending_ext_address(address)
+            }
+            _ => return Err(Status(status::PROP_NOT_FOUND)),
+        };
+
+        if removed {
+            Ok(())
+        } else {
+            Err(Status(status::ITEM_NOT_FOUND))
+        }
+    }
+
+    /// Start transmitting the frame of a `STREAM_RAW` set request
+    ///
+    /// The frame is followed by optional metadata, of which the channel, the
+    /// maximum number of CSMA backoffs and of frame retries, and whether to
+    /// use CSMA-CA are used.
+    fn start_transmit(&mut self, header: Header, r: &mut Reader) -> Result<(), Status> {
+        if !self.enabled {
+            return Err(Status(status::INVALID_STATE));
+        }
+        if self.transmit.is_some() || self.scan.is_some() {
+            return Err(Status(status::BUSY));
+        }
+
+        let psdu = r.read_data()?;
+        if !(FCS_LEN + 1..=MAX_PSDU_LEN).contains(&psdu.len()) {
+            return Err(Status(status::INVALID_ARGUMENT));
+        }
+
+        let channel = if r.is_empty() {
+            self.channel
+        } else {
+            read_channel(r)?
+        };
+        let max_csma_backoffs = r.read_u8().unwrap_or(MAX_CSMA_BACKOFFS);
+        let max_retries = r.read_u8().unwrap_or(MAX_FRAME_RETRIES);
+        let csma_ca = r.read_bool().unwrap_or(true);
+
+        let buffer = &mut self.driver.transmit_buffer;
+        buffer[1..][..psdu.len()].copy_from_slice(psdu);
+        buffer[0] = psdu.len() as u8;
+        set_channel(channel);
+
+        let now = (self.now)();
+        let transmission = Transmission::new(
+            csma_ca,
+            ack_requested(psdu),
+            max_csma_backoffs,
+            max_retries,
+            now,
+            &mut self.rng,
+        );
+        self.transmit = Some((transmission, header));
+
+        Ok(())
+    }
+
+    fn start_scan(&mut self) -> Result<(), Status> {
+        if !self.enabled {
+            return Err(Status(status::INVALID_STATE));
+        }
+        if self.transmit.is_some() {
+            return Err(Status(status::BUSY));
+        }
+
+        let channel = self.scan_mask.iter().next().unwrap_or(self.channel);
+        self.energy_detect(channel);
+
+        Ok(())
+    }
+
+    fn energy_detect(&mut self, channel: Channel) {
+        set_channel(channel);
+        ieee802154_energy_detect(self.scan_period as u32 * 1000 / SYMBOL_US);
+        self.scan = Some(channel);
+    }
+
+    /// Receive if enabled, and apply changed addresses and filters
+    fn update_receive(&mut self) {
+        if self.transmit.is_some() || self.scan.is_some() {
+            return;
+        }
+
+        ieee802154_sleep();
+        if self.enabled && self.raw_stream {
+            self.driver.start_receive();
+        }
+    }
+
+    fn process_scan(&mut self, write: &mut impl FnMut(&[u8])) {
+        let Some(channel) = self.scan else {
+            return;
+        };
+        let Some(result) = ieee802154_energy_detect_result() else {
+            return;
+        };
+        // an aborted scan measured nothing on the channel
+        let rssi = result.unwrap_or(RSSI_INVALID);
+
+        send(
+            write,
+            UNSOLICITED,
+            cmd::PROP_VALUE_IS,
+            prop::MAC_ENERGY_SCAN_RESULT,
+            |w| {
+                w.write_u8(channel.into())?;
+                w.write_i8(rssi)
+            },
+        )
+        .ok();
+
+        let next = self.scan_mask.iter().find(|next| *next > channel);
+        match next {
+            Some(next) => self.energy_detect(next),
+            None => {
+                self.scan = None;
+                set_channel(self.channel);
+                self.update_receive();
+
+                send(
+                    write,
+                    UNSOLICITED,
+                    cmd::PROP_VALUE_IS,
+                    prop::MAC_SCAN_STATE,
+                    |w| w.write_u8(scan_state::IDLE),
+                )
+                .ok();
+            }
+        }
+    }
+
+    fn process_transmit(&mut self, write: &mut impl FnMut(&[u8])) {
+        let now = (self.now)();
+        let Some((transmission, header)) = self.transmit.as_mut() else {
+            return;
+        };
+        let header = *header;
+
+        let outcome = match transmission.poll(now, &mut self.rng) {
+            Step::Wait => return,
+            Step::Start { cca, .. } => {
+                ieee802154_transmit(self.driver.transmit_buffer.as_ptr(), cca);
+                return;
+            }
+            Step::Done(outcome) => outcome,
+        };
+        self.transmit = None;
+
+        let (status, frame_pending) = match outcome {
+            Outcome::Sent => (status::OK, false),
+            Outcome::Acked { frame_pending } => (status::OK, frame_pending),
+            Outcome::NoAck => (status::NO_ACK, false),
+            Outcome::ChannelAccessFailure => (status::CCA_FAILURE, false),
+            Outcome::Aborted => (status::ABORT, false),
+        };
+        self.last_status = status;
+
+        // the transmit buffer starts with the length, followed by the PSDU
+        let ack = ack_frame(&self.driver.transmit_buffer[1..], frame_pending);
+        let rssi = ieee802154_last_rssi();
+        let channel = self.channel;
+
+        send(write, header, cmd::PROP_VALUE_IS, prop::LAST_STATUS, |w| {
+            w.write_uint_packed(status)?;
+            w.write_bool(frame_pending)?;
+            // header updated
+            w.write_bool(false)?;
+
+            if let Outcome::Acked { .. } = outcome {
+                write_radio_frame(w, &ack, rssi, 0, channel, rssi_to_lqi(rssi), now)?;
+            }
+
+            Ok(())
+        })
+        .ok();
+
+        set_channel(self.channel);
+        self.update_receive();
+    }
+
+    fn process_receive(&mut self, write: &mut impl FnMut(&[u8])) {
+        while let Some(token) = self.driver.get_received_token() {
+            if token.fault().is_some() || !self.raw_stream {
+                continue;
+            }
+
+            // the last two bytes hold the RSSI and LQI, in place of the FCS
+            let data = token.data();
+            let mut psdu = [0u8; MAX_PSDU_LEN];
+            psdu[..data.len()].copy_from_slice(data);
+            let len = data.len().max(FCS_LEN);
+            psdu[len - FCS_LEN..len].fill(0);
+
+            let frame = &psdu[..len - FCS_LEN];
+            let flags = if acked_with_frame_pending(frame) {
+                md_flag::ACKED_FP
+            } else {
+                0
+            };
+
+            let now = (self.now)();
+            send(
+                write,
+                UNSOLICITED,
+                cmd::PROP_VALUE_IS,
+                prop::STREAM_RAW,
+                |w| {
+                    write_radio_frame(
+                        w,
+                        &psdu[..len],
+                        token.rssi(),
+                        flags,
+                        token.channel(),
+                        token.lqi(),
+                        now,
+                    )
+                },
+            )
+            .ok();
+        }
+    }
+
+    fn send_status(&mut self, write: &mut impl FnMut(&[u8]), header: Header, status: u32) {
+        self.last_status = status;
+
+        send(write, header, cmd::PROP_VALUE_IS, prop::LAST_STATUS, |w| {
+            w.write_uint_packed(status)
+        })
+        .ok();
+    }
+}
+
+fn read_channel(r: &mut Reader) -> Result<Channel, Status> {
+    Channel::new(r.read_u8()?).map_err(|_| Status(status::INVALID_ARGUMENT))
+}
+
+/// Encode a frame with its metadata, as in `STREAM_RAW`
+fn write_radio_frame(
+    w: &mut Writer,
+    psdu: &[u8],
+    rssi: i8,
+    flags: u16,
+    channel: Channel,
+    lqi: u8,
+    timestamp: u64,
+) -> Result<(), Error> {
+    w.write_data(psdu)?;
+    w.write_i8(rssi)?;
+    // noise floor
+    w.write_i8(RSSI_INVALID)?;
+    w.write_u16(flags)?;
+
+    w.write_struct(|w| {
+        w.write_u8(channel.into())?;
+        w.write_u8(lqi)?;
+        w.write_u64(timestamp)
+    })?;
+    // receive error
+    w.write_struct(|w| w.write_uint_packed(status::OK))
+}
+
+/// Encode a frame and pass it to `write`, fails if the value can't be encoded
+fn send(
+    write: &mut impl FnMut(&[u8]),
+    header: Header,
+    command: u32,
+    property: u32,
+    value: impl FnOnce(&mut Writer) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let mut buffer = [0u8; MAX_FRAME_LEN];
+    let mut writer = Writer::frame(&mut buffer, header, command, property)?;
+    value(&mut writer)?;
+
+    let mut encoded = Vec::<u8, MAX_ENCODED_LEN>::new();
+    hdlc::encode(writer.finish(), &mut encoded)?;
+    write(&encoded);
+
+    Ok(())
+}
+
+#[cfg(all(test, feature = "mock"))]
+mod tests {
+    use std::vec::Vec as StdVec;
+
+    use super::*;
+    use crate::hal::mock;
+
+    /// A decoded reply: transaction, command, property and value
+    type Reply = (u8, u32, u32, StdVec<u8>);
+
+    const EUI64: [u8; 8] = [1, 2, 3, 4, 5, 6, 7, 8];
+
+    fn now() -> u64 {
+        0
+    }
+
+    struct Host {
+        rcp: Rcp<'static>,
+        decoder: hdlc::Decoder<MAX_FRAME_LEN>,
+        replies: StdVec<Reply>,
+    }
+
+    impl Host {
+        fn new() -> Self {
+            let driver = Ieee802154::new(crate::hal::IEEE802154, &mut crate::hal::RADIO_CLK);
+            let mut host = Self {
+                rcp: Rcp::new(driver, EUI64, now),
+                decoder: hdlc::Decoder::new(),
+                replies: StdVec::new(),
+            };
+
+            let Self {
+                rcp,
+                decoder,
+                replies,
+            } = &mut host;
+            rcp.start(|bytes| collect(decoder, replies, bytes));
+            assert_eq!(
+                host.replies.drain(..).collect::<StdVec<_>>(),
+                [status(0, status::RESET_POWER_ON)]
+            );
+
+            host
+        }
+
+        /// Pass the bytes to the RCP, returning its replies
+        fn send_bytes(&mut self, bytes: &[u8]) -> StdVec<Reply> {
+            let Self {
+                rcp,
+                decoder,
+                replies,
+            } = self;
+            for &byte in bytes {
+                rcp.receive(byte, |bytes| collect(decoder, replies, bytes));
+            }
+
+            self.replies.drain(..).collect()
+        }
+
+        fn request(
+            &mut self,
+            tid: u8,
+            command: u32,
+            property: u32,
+            value: impl FnOnce(&mut Writer) -> Result<(), Error>,
+        ) -> StdVec<Reply> {
+            let mut buffer = [0; MAX_FRAME_LEN];
+            let header = Header { iid: 0, tid };
+            let mut writer = Writer::frame(&mut buffer, header, command, property).unwrap();
+            value(&mut writer).unwrap();
+
+            let mut encoded = Vec::<u8, MAX_ENCODED_LEN>::new();
+            hdlc::encode(writer.finish(), &mut encoded).unwrap();
+            self.send_bytes(&encoded)
+        }
+
+        fn process(&mut self) -> StdVec<Reply> {
+            let Self {
+                rcp,
+                decoder,
+                replies,
+            } = self;
+            rcp.process(|bytes| collect(decoder, replies, bytes));
+
+            self.replies.drain(..).collect()
+        }
+    }
+
+    fn collect(
+        decoder: &mut hdlc::Decoder<MAX_FRAME_LEN>,
+        replies: &mut StdVec<Reply>,
+        bytes: &[u8],
+    ) {
+        for &byte in bytes {
+            if let Some(frame) = decoder.push(byte) {
+                let frame = Frame::decode(frame.unwrap()).unwrap();
+                let mut reader = Reader::new(frame.payload);
+                let property = reader.read_uint_packed().unwrap();
+                replies.push((
+                    frame.header.tid,
+                    frame.command,
+                    property,
+                    reader.remaining().to_vec(),
+                ));
+            }
+        }
+    }
+
+    fn status(tid: u8, status: u32) -> Reply {
+        (
+            tid,
+            cmd::PROP_VALUE_IS,
+            prop::LAST_STATUS,
+            std::vec![status as u8],
+        )
+    }
+
+    #[test]
+    fn requests_are_answered() {
+        let _lock = mock::test_lock();
+        let mut host = Host::new();
+        assert_eq!(host.process(), []);
+
+        assert_eq!(
+            host.request(1, cmd::PROP_VALUE_GET, prop::PROTOCOL_VERSION, |_| Ok(())),
+            [(
+                1,
+                cmd::PROP_VALUE_IS,
+                prop::PROTOCOL_VERSION,
+                std::vec![4, 3]
+            )]
+        );
+        assert_eq!(
+            host.request(2, cmd::PROP_VALUE_GET, prop::HWADDR, |_| Ok(())),
+            [(2, cmd::PROP_VALUE_IS, prop::HWADDR, EUI64.to_vec())]
+        );
+        assert_eq!(
+            host.request(3, cmd::PROP_VALUE_GET, 0x3fff, |_| Ok(())),
+            [status(3, status::PROP_NOT_FOUND)]
+        );
+        assert_eq!(
+            host.request(4, cmd::PROP_VALUE_SET, prop::PHY_CHAN, |w| w.write_u8(30)),
+            [status(4, status::INVALID_ARGUMENT)]
+        );
+        assert_eq!(
+            host.request(5, cmd::PROP_VALUE_SET, prop::PHY_CHAN, |w| w.write_u8(15)),
+            [(5, cmd::PROP_VALUE_IS, prop::PHY_CHAN, std::vec![15])]
+        );
+        assert_eq!(
+            host.request(
+                6,
+                cmd::PROP_VALUE_INSERT,
+                prop::MAC_SRC_MATCH_SHORT_ADDRESSES,
+                |w| { w.write_u16(0x0002) }
+            ),
+            [(
+                6,
+                cmd::PROP_VALUE_INSERTED,
+                prop::MAC_SRC_MATCH_SHORT_ADDRESSES,
+                std::vec![2, 0]
+            )]
+        );
+        assert_eq!(
+            host.request(
+                7,
+                cmd::PROP_VALUE_REMOVE,
+                prop::MAC_SRC_MATCH_SHORT_ADDRESSES,
+                |w| { w.write_u16(0x0003) }
+            ),
+            [status(7, status::ITEM_NOT_FOUND)]
+        );
+        assert_eq!(
+            host.request(8, cmd::PROP_VALUE_INSERTED, prop::PHY_CHAN, |_| Ok(())),
+            [status(8, status::INVALID_COMMAND)]
+        );
+        // a transmission before the radio is enabled
+        assert_eq!(
+            host.request(9, cmd::PROP_VALUE_SET, prop::STREAM_RAW, |w| {
+                w.write_data(&[0x41, 0x88, 1, 0, 0])
+            }),
+            [status(9, status::INVALID_STATE)]
+        );
+    }
+
+    #[test]
+    fn malformed_requests_are_dropped() {
+        let _lock = mock::test_lock();
+        let mut host = Host::new();
+
+        // a wrong FCS, a frame without the header flag and a truncated one
+        let mut noop = Vec::<u8, 16>::new();
+        hdlc::encode(&[0x81, cmd::NOOP as u8], &mut noop).unwrap();
+        let mut garbled = noop.clone();
+        garbled[1] ^= 0x01;
+        assert_eq!(host.send_bytes(&garbled), []);
+
+        let mut no_header = Vec::<u8, 16>::new();
+        hdlc::encode(&[0x01, cmd::NOOP as u8], &mut no_header).unwrap();
+        assert_eq!(host.send_bytes(&no_header), []);
+        assert_eq!(host.send_bytes(&[0x7e, 0x81, 0x7e]), []);
+
+        // a property which can't be parsed
+        let mut buffer = [0; 8];
+        let mut writer = Writer::new(&mut buffer);
+        writer
+            .write_u8(Header { iid: 0, tid: 3 }.to_byte())
+            .unwrap();
+        writer.write_uint_packed(cmd::PROP_VALUE_GET).unwrap();
+        writer.write_u8(0x80).unwrap();
+        let mut encoded = Vec::<u8, 32>::new();
+        hdlc::encode(writer.finish(), &mut encoded).unwrap();
+        assert_eq!(host.send_bytes(&encoded), [status(3, status::PARSE_ERROR)]);
+
+        // the RCP still answers well-formed requests
+        assert_eq!(host.send_bytes(&noop), [status(1, status::OK)]);
+    }
+
+    #[test]
+    fn raw_frames_are_transmitted_and_received() {
+        let _lock = mock::test_lock();
+        let mut host = Host::new();
+
+        host.request(1, cmd::PROP_VALUE_SET, prop::PHY_ENABLED, |w| {
+            w.write_bool(true)
+        });
+        host.request(2, cmd::PROP_VALUE_SET, prop::MAC_15_4_PANID, |w| {
+            w.write_u16(0x1234)
+        });
+        host.request(3, cmd::PROP_VALUE_SET, prop::MAC_15_4_SADDR, |w| {
+            w.write_u16(0x0001)
+        });
+        host.request(4, cmd::PROP_VALUE_SET, prop::MAC_RAW_STREAM_ENABLED, |w| {
+            w.write_bool(true)
+        });
+
+        // data frame from 0x0001 to 0x0002, requesting an ACK, without CSMA-CA
+        let psdu = [
+            0x61, 0x88, 7, 0x34, 0x12, 0x02, 0x00, 0x01, 0x00, 0xaa, 0, 0,
+        ];
+        let replies = host.request(5, cmd::PROP_VALUE_SET, prop::STREAM_RAW, |w| {
+            w.write_data(&psdu)?;
+            w.write_u8(15)?;
+            w.write_u8(0)?;
+            w.write_u8(0)?;
+            w.write_bool(false)
+        });
+        // replied to once the transmission is done
+        assert_eq!(replies, []);
+
+        assert_eq!(host.process(), []);
+        assert_eq!(mock::transmitted().unwrap(), psdu[..psdu.len() - 2]);
+        mock::transmit_done();
+        assert!(mock::ack_received(&[0x12, 0x00, 7], -40));
+
+        let replies = host.process();
+        assert_eq!(replies.len(), 1);
+        let (tid, command, property, value) = &replies[0];
+        assert_eq!(
+            (*tid, *command, *property),
+            (5, cmd::PROP_VALUE_IS, prop::LAST_STATUS)
+        );
+        // OK with the frame pending bit of the ACK, followed by the ACK
+        let mut reader = Reader::new(value);
+        assert_eq!(reader.read_uint_packed().unwrap(), status::OK);
+        assert!(reader.read_bool().unwrap());
+        assert!(!reader.read_bool().unwrap());
+        assert_eq!(reader.read_data().unwrap(), [0x12, 0x00, 7, 0, 0]);
+
+        // the radio receives again, and frames are passed to the host
+        let frame = [0x41, 0x88, 9, 0x34, 0x12, 0x01, 0x00, 0x02, 0x00, 0xbb];
+        assert!(mock::receive(&frame, -50, 200));
+        let replies = host.process();
+        assert_eq!(replies.len(), 1);
+        let (tid, command, property, value) = &replies[0];
+        assert_eq!(
+            (*tid, *command, *property),
+            (0, cmd::PROP_VALUE_IS, prop::STREAM_RAW)
+        );
+        let mut reader = Reader::new(value);
+        let mut expected = frame.to_vec();
+        expected.extend_from_slice(&[0, 0]);
+        assert_eq!(reader.read_data().unwrap(), expected);
+        assert_eq!(reader.read_i8().unwrap(), -50);
+    }
+}
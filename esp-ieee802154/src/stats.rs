@@ -0,0 +1,152 @@
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+
+use crate::hal::{clear_error_counts, get_error_count, ErrorCounter};
+
+#[cfg_attr(feature = "sim", thread_local)]
+static STATS: Mutex<RefCell<SoftwareStats>> = Mutex::new(RefCell::new(SoftwareStats::new()));
+
+/// Radio statistics
+///
+/// Combines the error counters maintained by the radio hardware with the
+/// counters maintained by the driver itself. The hardware counters are 16 bits
+/// wide and are reset together with the driver counters by
+/// [`Ieee802154::reset_stats`](crate::Ieee802154::reset_stats).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct RadioStats {
+    /// Frames which were received and placed into the receive queue
+    pub rx_queued: u32,
+    /// Frames which were dropped because the receive queue was full, see
+    /// [`OverflowPolicy`](crate::OverflowPolicy), or because all receive
+    /// buffers were in use
+    pub rx_queue_full: u32,
+    /// Transmissions which completed successfully
+    pub tx_done: u32,
+    /// Transmissions which were aborted
+    pub tx_abort: u32,
+    /// Frames of the smoltcp or embassy-net stack which were dropped as they
+    /// exceed the MTU
+    pub tx_oversize: u32,
+    /// Received frames with an invalid CRC
+    pub crc_error: u16,
+    /// Receptions aborted because no SFD was detected in time
+    pub sfd_timeout: u16,
+    /// Received frames rejected by the address filter
+    pub rx_filter_fail: u16,
+    /// Receptions aborted because the RSSI was too low
+    pub no_rss_detect: u16,
+    /// Receptions restarted by the hardware
+    pub rx_restart: u16,
+    /// Receptions aborted by the coexistence arbiter
+    pub rx_abort_coex: u16,
+    /// CCA attempts which found the channel busy
+    pub cca_busy: u16,
+    /// CCA attempts which failed
+    pub cca_fail: u16,
+    /// Transmissions for which no ACK was received in time
+    pub rx_ack_timeout: u16,
+    /// ACK receptions aborted by the coexistence arbiter
+    pub rx_ack_abort_coex: u16,
+    /// Transmissions interrupted by the coexistence arbiter
+    pub tx_break_coex: u16,
+    /// ACK transmissions aborted by the coexistence arbiter
+    pub tx_ack_abort_coex: u16,
+    /// Transmissions aborted due to a security error
+    pub tx_security_error: u16,
+    /// Energy detection scans aborted
+    pub ed_abort: u16,
+    /// Energy detection scans aborted by the coexistence arbiter
+    pub ed_scan_coex: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct SoftwareStats {
+    rx_queued: u32,
+    rx_queue_full: u32,
+    tx_done: u32,
+    tx_abort: u32,
+    tx_oversize: u32,
+}
+
+impl SoftwareStats {
+    const fn new() -> Self {
+        Self {
+            rx_queued: 0,
+            rx_queue_full: 0,
+            tx_done: 0,
+            tx_abort: 0,
+            tx_oversize: 0,
+        }
+    }
+}
+
+pub(crate) fn stats_get() -> RadioStats {
+    let sw = critical_section::with(|cs| *STATS.borrow_ref(cs));
+
+    RadioStats {
+        rx_queued: sw.rx_queued,
+        rx_queue_full: sw.rx_queue_full,
+        tx_done: sw.tx_done,
+        tx_abort: sw.tx_abort,
+        tx_oversize: sw.tx_oversize,
+        crc_error: get_error_count(ErrorCounter::CrcError),
+        sfd_timeout: get_error_count(ErrorCounter::SfdTimeout),
+        rx_filter_fail: get_error_count(ErrorCounter::RxFilterFail),
+        no_rss_detect: get_error_count(ErrorCounter::NoRssDetect),
+        rx_restart: get_error_count(ErrorCounter::RxRestart),
+        rx_abort_coex: get_error_count(ErrorCounter::RxAbortCoex),
+        cca_busy: get_error_count(ErrorCounter::CcaBusy),
+        cca_fail: get_error_count(ErrorCounter::CcaFail),
+        rx_ack_timeout: get_error_count(ErrorCounter::RxAckTimeout),
+        rx_ack_abort_coex: get_error_count(ErrorCounter::RxAckAbortCoex),
+        tx_break_coex: get_error_count(ErrorCounter::TxBreakCoex),
+        tx_ack_abort_coex: get_error_count(ErrorCounter::TxAckAbortCoex),
+        tx_security_error: get_error_count(ErrorCounter::TxSecurityError),
+        ed_abort: get_error_count(ErrorCounter::EdAbort),
+        ed_scan_coex: get_error_count(ErrorCounter::EdScanCoex),
+    }
+}
+
+pub(crate) fn stats_reset() {
+    critical_section::with(|cs| {
+        *STATS.borrow_ref_mut(cs) = SoftwareStats::new();
+        clear_error_counts(ErrorCounter::mask());
+    });
+}
+
+pub(crate) fn stats_rx_queued() {
+    critical_section::with(|cs| {
+        let mut stats = STATS.borrow_ref_mut(cs);
+        stats.rx_queued = stats.rx_queued.wrapping_add(1);
+    });
+}
+
+pub(crate) fn stats_rx_queue_full() {
+    critical_section::with(|cs| {
+        let mut stats = STATS.borrow_ref_mut(cs);
+        stats.rx_queue_full = stats.rx_queue_full.wrapping_add(1);
+    });
+}
+
+pub(crate) fn stats_tx_done() {
+    critical_section::with(|cs| {
+        let mut stats = STATS.borrow_ref_mut(cs);
+        stats.tx_done = stats.tx_done.wrapping_add(1);
+    });
+}
+
+pub(crate) fn stats_tx_abort() {
+    critical_section::with(|cs| {
+        let mut stats = STATS.borrow_ref_mut(cs);
+        stats.tx_abort = stats.tx_abort.wrapping_add(1);
+    });
+}
+
+#[cfg(any(feature = "smoltcp", feature = "embassy-net-driver"))]
+pub(crate) fn stats_tx_oversize() {
+    critical_section::with(|cs| {
+        let mut stats = STATS.borrow_ref_mut(cs);
+        stats.tx_oversize = stats.tx_oversize.wrapping_add(1);
+    });
+}
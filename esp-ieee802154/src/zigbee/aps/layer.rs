@@ -0,0 +1,796 @@
+use heapless::{Deque, Vec};
+use ieee802154::mac::{self, FrameContent, FrameVersion, PanId, ShortAddress};
+
+use super::{BindingTable, DeliveryMode, Fragmentation, Frame, FrameType, GroupTable, Header};
+use crate::{
+    frame::FRAME_SIZE,
+    raw::ieee802154_tx_busy,
+    zigbee::nwk::{self, BROADCAST_RX_ON_WHEN_IDLE},
+    Error, Ieee802154,
+};
+
+// aMaxPhyPacketSize without the FCS
+const MAX_PSDU_LEN: usize = 125;
+// frame control, sequence number, PAN ID and short addresses, with PAN ID
+// compression
+const MAC_HEADER_LEN: usize = 9;
+// frame control, addresses, radius and sequence number
+const NWK_HEADER_LEN: usize = 8;
+// frame control, endpoints, cluster, profile and counter
+const APS_HEADER_LEN: usize = 8;
+// extended frame control and block number
+const EXTENDED_HEADER_LEN: usize = 2;
+
+/// Largest payload of a unicast frame which is not fragmented
+pub const MAX_PAYLOAD_LEN: usize = MAX_PSDU_LEN - MAC_HEADER_LEN - NWK_HEADER_LEN - APS_HEADER_LEN;
+/// Largest payload sent or received, fragmented if longer than
+/// [`MAX_PAYLOAD_LEN`]
+pub const MAX_ASDU_LEN: usize = 256;
+
+pub const BINDING_TABLE_SIZE: usize = 16;
+pub const GROUP_TABLE_SIZE: usize = 16;
+
+const FRAGMENT_LEN: usize = MAX_PAYLOAD_LEN - EXTENDED_HEADER_LEN;
+const MAX_BLOCKS: usize = MAX_ASDU_LEN.div_ceil(FRAGMENT_LEN);
+
+// nwkMaxDepth * 2
+const DEFAULT_RADIUS: u8 = 30;
+// apsAckWaitDuration, with a sleepy end device polling its parent
+const ACK_WAIT_US: u64 = 1_500_000;
+// apsMaxFrameRetries
+const MAX_FRAME_RETRIES: u8 = 3;
+const REASSEMBLY_TIMEOUT_US: u64 = 3 * ACK_WAIT_US;
+// apsMaxWindowSize
+const MAX_WINDOW_SIZE: u8 = 8;
+const DUPLICATE_TABLE_SIZE: usize = 8;
+// after receiving a frame, until its sender has received the MAC ACK and is
+// receiving again
+const TURNAROUND_US: u64 = 1_000;
+
+const BROADCAST_ADDRESS: u16 = 0xffff;
+
+/// Addresses of the device in the network it has joined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Network {
+    pub pan_id: u16,
+    /// Short address
+    pub address: u16,
+}
+
+/// Where a frame is sent to, or was received at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Destination {
+    Unicast {
+        address: u16,
+        endpoint: u8,
+    },
+    /// Broadcast to a broadcast address, like
+    /// [`BROADCAST_RX_ON_WHEN_IDLE`](nwk::BROADCAST_RX_ON_WHEN_IDLE)
+    Broadcast {
+        address: u16,
+        endpoint: u8,
+    },
+    Group(u16),
+}
+
+/// Frame to send, see [`Aps::send`]
+#[derive(Debug, Clone, Copy)]
+pub struct DataRequest<'p> {
+    pub destination: Destination,
+    /// Neighbor to send unicasts through, like the parent of an end device,
+    /// `None` if the destination is a neighbor
+    pub next_hop: Option<u16>,
+    pub profile: u16,
+    pub cluster: u16,
+    pub source_endpoint: u8,
+    /// Whether the destination acknowledges the frame, ignored for
+    /// broadcasts and groups
+    ///
+    /// Payloads longer than [`MAX_PAYLOAD_LEN`] are only sent with
+    /// acknowledgements, since they are fragmented.
+    pub ack_request: bool,
+    pub payload: &'p [u8],
+}
+
+/// How sending a frame ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    /// Sent, without requesting an acknowledgement
+    Sent,
+    Acked,
+    /// Not acknowledged after all retries
+    NoAck,
+}
+
+/// Frame received from another device
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Indication {
+    /// Short address of the source
+    pub source: u16,
+    pub source_endpoint: u8,
+    pub destination: Destination,
+    pub profile: u16,
+    pub cluster: u16,
+    /// Link Quality Indication (LQI) of the last received block
+    pub lqi: u8,
+    pub payload: Vec<u8, MAX_ASDU_LEN>,
+}
+
+/// What happened, returned by [`Aps::poll`]
+// there is no allocator to box the indication in
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Indication(Indication),
+    /// Sending the frame with the APS counter returned by [`Aps::send`]
+    /// ended
+    Confirm {
+        counter: u8,
+        status: Status,
+    },
+}
+
+/// Frame being sent, in one or more blocks
+struct Outgoing {
+    destination: Destination,
+    next_hop: u16,
+    profile: u16,
+    cluster: u16,
+    source_endpoint: u8,
+    counter: u8,
+    ack_request: bool,
+    payload: Vec<u8, MAX_ASDU_LEN>,
+    fragmented: bool,
+    blocks: u8,
+    window_start: u8,
+    sent: u32,
+    acked: u32,
+    deadline: Option<u64>,
+    retries: u8,
+}
+
+impl Outgoing {
+    fn window(&self, window_size: u8) -> u32 {
+        window_mask(self.window_start, window_size, self.blocks)
+    }
+}
+
+/// Fragmented frame being received
+struct Reassembly {
+    source: u16,
+    counter: u8,
+    blocks: u8,
+    block_len: usize,
+    window_start: u8,
+    received: u32,
+    len: usize,
+    data: [u8; MAX_ASDU_LEN],
+    deadline: u64,
+}
+
+/// APS acknowledgement waiting for the radio
+struct Ack {
+    destination: u16,
+    next_hop: u16,
+    header: Header,
+}
+
+/// Bits of the blocks in the window starting at `start`
+fn window_mask(start: u8, window_size: u8, blocks: u8) -> u32 {
+    let end = (start + window_size).min(blocks);
+    (start..end).fold(0, |mask, block| mask | 1 << block)
+}
+
+/// APS layer on top of [`Ieee802154`]
+///
+/// Frames are sent unsecured, and secured frames are dropped when received,
+/// as encrypting and decrypting them is left to the application. Only
+/// frames addressed to the device are received, the device doesn't route
+/// frames.
+///
+/// [`Aps::poll`] has to be called regularly to send the frames passed to
+/// [`Aps::send`] and to receive frames.
+pub struct Aps<'a> {
+    driver: Ieee802154<'a>,
+    network: Network,
+    now: fn() -> u64,
+    mac_sequence: u8,
+    nwk_sequence: u8,
+    counter: u8,
+    window_size: u8,
+    outgoing: Option<Outgoing>,
+    reassembly: Option<Reassembly>,
+    ack: Option<Ack>,
+    quiet_until: u64,
+    duplicates: Deque<(u16, u8), DUPLICATE_TABLE_SIZE>,
+    bindings: BindingTable<BINDING_TABLE_SIZE>,
+    groups: GroupTable<GROUP_TABLE_SIZE>,
+}
+
+impl<'a> Aps<'a> {
+    /// `driver` has to be configured for `network`, and `now` returns the
+    /// time in microseconds, used for the acknowledgement and reassembly
+    /// timeouts.
+    pub fn new(mut driver: Ieee802154<'a>, network: Network, now: fn() -> u64) -> Self {
+        driver.start_receive();
+
+        Self {
+            driver,
+            network,
+            now,
+            mac_sequence: 0,
+            nwk_sequence: 0,
+            counter: 0,
+            window_size: 1,
+            outgoing: None,
+            reassembly: None,
+            ack: None,
+            quiet_until: 0,
+            duplicates: Deque::new(),
+            bindings: BindingTable::new(),
+            groups: GroupTable::new(),
+        }
+    }
+
+    /// The driver, e.g. to change its configuration
+    pub fn driver(&mut self) -> &mut Ieee802154<'a> {
+        &mut self.driver
+    }
+
+    /// Change the addresses of the device, after rejoining the network
+    pub fn set_network(&mut self, network: Network) {
+        self.network = network;
+    }
+
+    /// Set the number of blocks acknowledged at once, from 1 to 8, which
+    /// has to match the other devices, 1 by default
+    pub fn set_window_size(&mut self, window_size: u8) {
+        self.window_size = window_size.clamp(1, MAX_WINDOW_SIZE);
+    }
+
+    pub fn bindings(&self) -> &BindingTable<BINDING_TABLE_SIZE> {
+        &self.bindings
+    }
+
+    pub fn bindings_mut(&mut self) -> &mut BindingTable<BINDING_TABLE_SIZE> {
+        &mut self.bindings
+    }
+
+    pub fn groups(&self) -> &GroupTable<GROUP_TABLE_SIZE> {
+        &self.groups
+    }
+
+    /// Frames sent to a group are only received if an endpoint of the device
+    /// is a member of the group
+    pub fn groups_mut(&mut self) -> &mut GroupTable<GROUP_TABLE_SIZE> {
+        &mut self.groups
+    }
+
+    /// Start sending a frame, returns its APS counter
+    ///
+    /// The end of the transmission is reported by [`Aps::poll`]. Returns
+    /// [`Error::Full`] while another frame is being sent, and
+    /// [`Error::BadInput`] if the payload is too long.
+    pub fn send(&mut self, request: &DataRequest) -> Result<u8, Error> {
+        if self.outgoing.is_some() {
+            return Err(Error::Full);
+        }
+
+        let (next_hop, unicast) = match request.destination {
+            Destination::Unicast { address, .. } => (request.next_hop.unwrap_or(address), true),
+            Destination::Broadcast { .. } | Destination::Group(_) => (BROADCAST_ADDRESS, false),
+        };
+        let ack_request = unicast && request.ack_request;
+
+        let fragmented = request.payload.len() > MAX_PAYLOAD_LEN;
+        if fragmented && !ack_request {
+            return Err(Error::BadInput);
+        }
+        // the group address is one byte longer than the destination endpoint
+        if let Destination::Group(_) = request.destination {
+            if request.payload.len() > MAX_PAYLOAD_LEN - 1 {
+                return Err(Error::BadInput);
+            }
+        }
+        let payload = Vec::from_slice(request.payload).map_err(|_| Error::BadInput)?;
+        let blocks = if fragmented {
+            payload.len().div_ceil(FRAGMENT_LEN) as u8
+        } else {
+            1
+        };
+
+        let counter = self.counter;
+        self.counter = self.counter.wrapping_add(1);
+
+        self.outgoing = Some(Outgoing {
+            destination: request.destination,
+            next_hop,
+            profile: request.profile,
+            cluster: request.cluster,
+            source_endpoint: request.source_endpoint,
+            counter,
+            ack_request,
+            payload,
+            fragmented,
+            blocks,
+            window_start: 0,
+            sent: 0,
+            acked: 0,
+            deadline: None,
+            retries: 0,
+        });
+
+        Ok(counter)
+    }
+
+    /// Send the pending frames and receive frames, returns what happened
+    pub fn poll(&mut self) -> Option<Event> {
+        let now = (self.now)();
+
+        if let Some(reassembly) = &self.reassembly {
+            if now >= reassembly.deadline {
+                log::warn!(
+                    "Dropping incomplete fragmented frame from {:#06x}",
+                    reassembly.source
+                );
+                self.reassembly = None;
+            }
+        }
+
+        if now >= self.quiet_until && !ieee802154_tx_busy() {
+            if let Some(ack) = self.ack.take() {
+                self.send_ack(ack);
+            } else if let Some(event) = self.process_outgoing(now) {
+                return Some(event);
+            }
+        }
+
+        // an acknowledgement has to be sent before receiving further frames
+        while self.ack.is_none() {
+            let received = match self.driver.get_received()? {
+                Ok(received) if received.fault.is_none() => received,
+                _ => continue,
+            };
+            self.quiet_until = now + TURNAROUND_US;
+
+            let next_hop = match received.frame.header.source {
+                Some(mac::Address::Short(_, ShortAddress(address))) => Some(address),
+                _ => None,
+            };
+
+            if let Some(event) = self.receive(&received.frame.payload, next_hop, received.lqi, now)
+            {
+                return Some(event);
+            }
+        }
+
+        None
+    }
+
+    fn process_outgoing(&mut self, now: u64) -> Option<Event> {
+        let outgoing = self.outgoing.as_mut()?;
+        let window = outgoing.window(self.window_size);
+
+        let unsent = window & !outgoing.sent & !outgoing.acked;
+        if unsent != 0 {
+            let block = unsent.trailing_zeros() as u8;
+            outgoing.sent |= 1 << block;
+
+            let (fragmentation, range) = if outgoing.fragmented {
+                let start = block as usize * FRAGMENT_LEN;
+                let end = (start + FRAGMENT_LEN).min(outgoing.payload.len());
+                let fragmentation = Fragmentation {
+                    first: block == 0,
+                    block: if block == 0 { outgoing.blocks } else { block },
+                    ack_bitfield: 0,
+                };
+
+                (Some(fragmentation), start..end)
+            } else {
+                (None, 0..outgoing.payload.len())
+            };
+
+            let (delivery_mode, destination, destination_endpoint, group) =
+                match outgoing.destination {
+                    Destination::Unicast { address, endpoint } => {
+                        (DeliveryMode::Unicast, address, Some(endpoint), None)
+                    }
+                    Destination::Broadcast { address, endpoint } => {
+                        (DeliveryMode::Broadcast, address, Some(endpoint), None)
+                    }
+                    Destination::Group(group) => (
+                        DeliveryMode::Group,
+                        BROADCAST_RX_ON_WHEN_IDLE,
+                        None,
+                        Some(group),
+                    ),
+                };
+
+            let header = Header {
+                frame_type: FrameType::Data,
+                delivery_mode,
+                ack_format: false,
+                security: false,
+                ack_request: outgoing.ack_request,
+                destination_endpoint,
+                group,
+                cluster: Some(outgoing.cluster),
+                profile: Some(outgoing.profile),
+                source_endpoint: Some(outgoing.source_endpoint),
+                counter: outgoing.counter,
+                fragmentation,
+            };
+
+            let mut payload = [0u8; MAX_PAYLOAD_LEN];
+            let len = range.len();
+            payload[..len].copy_from_slice(&outgoing.payload[range]);
+            let next_hop = outgoing.next_hop;
+
+            if let Err(err) = self.send_frame(next_hop, destination, header, &payload[..len]) {
+                log::warn!("Sending APS frame failed: {:?}", err);
+            }
+
+            return None;
+        }
+
+        let counter = outgoing.counter;
+        if !outgoing.ack_request {
+            self.outgoing = None;
+            return Some(Event::Confirm {
+                counter,
+                status: Status::Sent,
+            });
+        }
+
+        match outgoing.deadline {
+            None => outgoing.deadline = Some(now + ACK_WAIT_US),
+            Some(deadline) if now < deadline => {}
+            Some(_) if outgoing.retries < MAX_FRAME_RETRIES => {
+                // resend the blocks of the window which weren't acknowledged
+                outgoing.retries += 1;
+                outgoing.sent = outgoing.acked;
+                outgoing.deadline = None;
+            }
+            Some(_) => {
+                self.outgoing = None;
+                return Some(Event::Confirm {
+                    counter,
+                    status: Status::NoAck,
+                });
+            }
+        }
+
+        None
+    }
+
+    fn receive(
+        &mut self,
+        payload: &[u8],
+        next_hop: Option<u16>,
+        lqi: u8,
+        now: u64,
+    ) -> Option<Event> {
+        let nwk = nwk::Frame::decode(payload).ok()?;
+        if nwk.header.frame_type != nwk::FrameType::Data {
+            return None;
+        }
+        if nwk.header.security {
+            log::warn!("Dropping NWK secured frame from {:#06x}", nwk.header.source);
+            return None;
+        }
+
+        let destination = nwk.header.destination;
+        // broadcast addresses are above 0xfff7
+        if destination != self.network.address && destination < 0xfff8 {
+            return None;
+        }
+
+        let source = nwk.header.source;
+        let next_hop = next_hop.unwrap_or(source);
+
+        let frame = match Frame::decode(nwk.payload) {
+            Ok(frame) => frame,
+            Err(err) => {
+                log::warn!("Dropping malformed APS frame: {:?}", err);
+                return None;
+            }
+        };
+        if frame.header.security {
+            log::warn!("Dropping APS secured frame from {:#06x}", source);
+            return None;
+        }
+
+        match frame.header.frame_type {
+            FrameType::Ack => self.receive_ack(source, &frame.header),
+            FrameType::Data => {
+                let indication = Indication {
+                    source,
+                    source_endpoint: frame.header.source_endpoint?,
+                    destination: match frame.header.delivery_mode {
+                        DeliveryMode::Unicast => Destination::Unicast {
+                            address: destination,
+                            endpoint: frame.header.destination_endpoint?,
+                        },
+                        DeliveryMode::Broadcast => Destination::Broadcast {
+                            address: destination,
+                            endpoint: frame.header.destination_endpoint?,
+                        },
+                        DeliveryMode::Group => {
+                            let group = frame.header.group?;
+                            if !self.groups.contains(group) {
+                                return None;
+                            }
+                            Destination::Group(group)
+                        }
+                    },
+                    profile: frame.header.profile?,
+                    cluster: frame.header.cluster?,
+                    lqi,
+                    payload: Vec::new(),
+                };
+
+                self.receive_data(indication, next_hop, &frame.header, frame.payload, now)
+            }
+            FrameType::Command | FrameType::InterPan => None,
+        }
+    }
+
+    fn receive_ack(&mut self, source: u16, header: &Header) -> Option<Event> {
+        let outgoing = self.outgoing.as_mut()?;
+        let Destination::Unicast { address, .. } = outgoing.destination else {
+            return None;
+        };
+        if source != address || header.counter != outgoing.counter || header.ack_format {
+            return None;
+        }
+
+        if let Some(fragmentation) = header.fragmentation {
+            if fragmentation.block >= outgoing.blocks {
+                return None;
+            }
+            outgoing.acked |= (fragmentation.ack_bitfield as u32) << fragmentation.block;
+        } else {
+            outgoing.acked |= 1;
+        }
+
+        let window = outgoing.window(self.window_size);
+        if outgoing.acked & window != window {
+            return None;
+        }
+
+        outgoing.window_start += self.window_size;
+        if outgoing.window_start < outgoing.blocks {
+            // continue with the next window
+            outgoing.sent = outgoing.acked;
+            outgoing.deadline = None;
+            outgoing.retries = 0;
+            return None;
+        }
+
+        let counter = outgoing.counter;
+        self.outgoing = None;
+
+        Some(Event::Confirm {
+            counter,
+            status: Status::Acked,
+        })
+    }
+
+    fn receive_data(
+        &mut self,
+        mut indication: Indication,
+        next_hop: u16,
+        header: &Header,
+        payload: &[u8],
+        now: u64,
+    ) -> Option<Event> {
+        let source = indication.source;
+        let duplicate = self
+            .duplicates
+            .iter()
+            .any(|&d| d == (source, header.counter));
+        let ack = |window: Option<(u8, u8)>| {
+            header.ack_request.then(|| Ack {
+                destination: source,
+                next_hop,
+                header: Header {
+                    frame_type: FrameType::Ack,
+                    delivery_mode: DeliveryMode::Unicast,
+                    ack_format: false,
+                    security: false,
+                    ack_request: false,
+                    destination_endpoint: header.source_endpoint,
+                    group: None,
+                    cluster: header.cluster,
+                    profile: header.profile,
+                    source_endpoint: header.destination_endpoint,
+                    counter: header.counter,
+                    fragmentation: window.map(|(start, ack_bitfield)| Fragmentation {
+                        first: start == 0,
+                        block: start,
+                        ack_bitfield,
+                    }),
+                },
+            })
+        };
+
+        let Some(fragmentation) = header.fragmentation else {
+            if matches!(indication.destination, Destination::Unicast { .. }) {
+                self.ack = ack(None);
+            }
+            if duplicate {
+                return None;
+            }
+
+            indication.payload = Vec::from_slice(payload).ok()?;
+            self.remember(source, header.counter);
+            return Some(Event::Indication(indication));
+        };
+
+        let block = if fragmentation.first {
+            0
+        } else {
+            fragmentation.block
+        };
+        let window_size = self.window_size;
+        let window_start = block - block % window_size;
+
+        if duplicate {
+            // the acknowledgement of the window was lost
+            self.ack = ack(Some((window_start, u8::MAX >> (8 - window_size))));
+            return None;
+        }
+
+        if fragmentation.first {
+            let blocks = fragmentation.block;
+            let restart = !matches!(
+                &self.reassembly,
+                Some(r) if r.source == source && r.counter == header.counter
+            );
+
+            if restart {
+                if blocks == 0
+                    || blocks as usize > MAX_BLOCKS
+                    || (blocks as usize - 1) * payload.len() >= MAX_ASDU_LEN
+                {
+                    log::warn!("Dropping fragmented frame of {} blocks", blocks);
+                    return None;
+                }
+
+                self.reassembly = Some(Reassembly {
+                    source,
+                    counter: header.counter,
+                    blocks,
+                    block_len: payload.len(),
+                    window_start: 0,
+                    received: 0,
+                    len: 0,
+                    data: [0; MAX_ASDU_LEN],
+                    deadline: now,
+                });
+            }
+        }
+
+        let reassembly = match self.reassembly.as_mut() {
+            Some(r) if r.source == source && r.counter == header.counter => r,
+            // the first block was missed, the sender retries it
+            _ => return None,
+        };
+
+        let last = block + 1 == reassembly.blocks;
+        let offset = block as usize * reassembly.block_len;
+        if block >= reassembly.blocks
+            || (!last && payload.len() != reassembly.block_len)
+            || payload.len() > reassembly.block_len
+            || offset + payload.len() > MAX_ASDU_LEN
+        {
+            log::warn!("Dropping malformed block {} from {:#06x}", block, source);
+            return None;
+        }
+
+        reassembly.data[offset..][..payload.len()].copy_from_slice(payload);
+        reassembly.received |= 1 << block;
+        reassembly.deadline = now + REASSEMBLY_TIMEOUT_US;
+        if last {
+            reassembly.len = offset + payload.len();
+        }
+
+        if block < reassembly.window_start {
+            let received = (reassembly.received >> window_start) as u8;
+            self.ack = ack(Some((window_start, received)));
+            return None;
+        }
+
+        let start = reassembly.window_start;
+        let window = window_mask(start, window_size, reassembly.blocks);
+        if reassembly.received & window == window {
+            reassembly.window_start += window_size;
+            self.ack = ack(Some((start, (reassembly.received >> start) as u8)));
+        }
+
+        let complete = window_mask(0, reassembly.blocks, reassembly.blocks);
+        if reassembly.received != complete {
+            return None;
+        }
+
+        indication.payload = Vec::from_slice(&reassembly.data[..reassembly.len]).ok()?;
+        self.reassembly = None;
+        self.remember(source, header.counter);
+
+        Some(Event::Indication(indication))
+    }
+
+    fn remember(&mut self, source: u16, counter: u8) {
+        if self.duplicates.is_full() {
+            self.duplicates.pop_front();
+        }
+        let _ = self.duplicates.push_back((source, counter));
+    }
+
+    fn send_ack(&mut self, ack: Ack) {
+        if let Err(err) = self.send_frame(ack.next_hop, ack.destination, ack.header, &[]) {
+            log::warn!("Sending APS acknowledgement failed: {:?}", err);
+        }
+    }
+
+    /// Send an APS frame in a NWK frame in a MAC frame
+    fn send_frame(
+        &mut self,
+        next_hop: u16,
+        destination: u16,
+        header: Header,
+        payload: &[u8],
+    ) -> Result<(), Error> {
+        let mut aps = [0u8; MAX_PSDU_LEN];
+        let len = Frame {
+            header,
+            auxiliary_security_header: None,
+            payload,
+        }
+        .encode(&mut aps)?;
+
+        let sequence = self.nwk_sequence;
+        self.nwk_sequence = self.nwk_sequence.wrapping_add(1);
+
+        let mut nwk_payload = [0u8; MAX_PSDU_LEN];
+        let len = nwk::Frame {
+            header: nwk::Header::data(destination, self.network.address, DEFAULT_RADIUS, sequence),
+            auxiliary_security_header: None,
+            payload: &aps[..len],
+        }
+        .encode(&mut nwk_payload)?;
+
+        if MAC_HEADER_LEN + len > MAX_PSDU_LEN {
+            return Err(Error::BadInput);
+        }
+
+        let pan_id = PanId(self.network.pan_id);
+        let sequence = self.mac_sequence;
+        self.mac_sequence = self.mac_sequence.wrapping_add(1);
+
+        let frame = crate::Frame {
+            header: mac::Header {
+                frame_type: mac::FrameType::Data,
+                frame_pending: false,
+                ack_request: next_hop != BROADCAST_ADDRESS,
+                pan_id_compress: true,
+                seq_no_suppress: false,
+                ie_present: false,
+                version: FrameVersion::Ieee802154_2003,
+                seq: sequence,
+                destination: Some(mac::Address::Short(pan_id, ShortAddress(next_hop))),
+                source: Some(mac::Address::Short(
+                    pan_id,
+                    ShortAddress(self.network.address),
+                )),
+                auxiliary_security_header: None,
+            },
+            content: FrameContent::Data,
+            payload: Vec::<u8, FRAME_SIZE>::from_slice(&nwk_payload[..len])
+                .map_err(|_| Error::BadInput)?,
+            footer: [0u8; 2],
+        };
+
+        self.driver.transmit(&frame)
+    }
+}
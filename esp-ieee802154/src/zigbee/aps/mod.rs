@@ -0,0 +1,533 @@
+//! Zigbee application support (APS) layer
+//!
+//! The APS frames are carried in the payload of NWK data frames, see
+//! [`nwk::Frame`](super::nwk::Frame). [`Frame`] encodes and decodes them,
+//! [`Aps`] sends and receives them over the driver, with acknowledgements,
+//! retries and fragmentation, and keeps the [`BindingTable`] and the
+//! [`GroupTable`].
+
+mod layer;
+mod tables;
+
+use byte::{BytesExt, TryRead, TryWrite, LE};
+
+pub use self::{
+    layer::{
+        Aps, DataRequest, Destination, Event, Indication, Network, Status, BINDING_TABLE_SIZE,
+        GROUP_TABLE_SIZE, MAX_ASDU_LEN, MAX_PAYLOAD_LEN,
+    },
+    tables::{Binding, BindingDestination, BindingTable, GroupTable},
+};
+use super::AuxiliarySecurityHeader;
+use crate::Error;
+
+/// Endpoint of the Zigbee device object (ZDO)
+pub const ZDO_ENDPOINT: u8 = 0x00;
+/// Endpoint addressing all active endpoints of a device
+pub const BROADCAST_ENDPOINT: u8 = 0xff;
+
+const FRAME_TYPE_MASK: u8 = 0x03;
+const DELIVERY_MODE_SHIFT: u8 = 2;
+const DELIVERY_MODE_MASK: u8 = 0x0c;
+const ACK_FORMAT: u8 = 0x10;
+const SECURITY: u8 = 0x20;
+const ACK_REQUEST: u8 = 0x40;
+const EXTENDED_HEADER: u8 = 0x80;
+
+const FRAGMENTATION_MASK: u8 = 0x03;
+const FRAGMENTATION_FIRST: u8 = 1;
+const FRAGMENTATION_SUBSEQUENT: u8 = 2;
+
+/// APS frame type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    Data,
+    Command,
+    Ack,
+    /// Inter-PAN frame, without endpoints and APS counter
+    InterPan,
+}
+
+/// APS delivery mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryMode {
+    Unicast,
+    Broadcast,
+    /// Delivered to the endpoints which are members of the group
+    Group,
+}
+
+/// Extended header of fragmented frames
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fragmentation {
+    /// Whether this is the first block
+    pub first: bool,
+    /// The number of blocks in the first block of a data frame, the block
+    /// number in the other blocks, and the first block of the acknowledged
+    /// window in acknowledgements
+    pub block: u8,
+    /// In acknowledgements, bit `i` is set if block `block + i` was received
+    pub ack_bitfield: u8,
+}
+
+/// APS header
+///
+/// The fields which are not part of the header of the frame type and the
+/// delivery mode are `None`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub frame_type: FrameType,
+    pub delivery_mode: DeliveryMode,
+    /// Acknowledgement of a command frame, which has neither endpoints nor
+    /// cluster and profile
+    pub ack_format: bool,
+    /// Whether the frame is secured, followed by an
+    /// [`AuxiliarySecurityHeader`]
+    pub security: bool,
+    pub ack_request: bool,
+    /// Present in unicast and broadcast data frames and acknowledgements
+    pub destination_endpoint: Option<u8>,
+    /// Present in group data frames
+    pub group: Option<u16>,
+    /// Present in data frames and acknowledgements
+    pub cluster: Option<u16>,
+    /// Present in data frames and acknowledgements
+    pub profile: Option<u16>,
+    /// Present in data frames and acknowledgements
+    pub source_endpoint: Option<u8>,
+    /// APS counter, 0 in inter-PAN frames
+    pub counter: u8,
+    pub fragmentation: Option<Fragmentation>,
+}
+
+impl Header {
+    fn has_endpoints(&self) -> bool {
+        match self.frame_type {
+            FrameType::Data => true,
+            FrameType::Ack => !self.ack_format,
+            FrameType::Command | FrameType::InterPan => false,
+        }
+    }
+
+    fn has_cluster(&self) -> bool {
+        self.has_endpoints() || self.frame_type == FrameType::InterPan
+    }
+
+    fn has_destination_endpoint(&self) -> bool {
+        self.has_endpoints() && self.delivery_mode != DeliveryMode::Group
+    }
+
+    fn has_group(&self) -> bool {
+        self.delivery_mode == DeliveryMode::Group
+            && matches!(self.frame_type, FrameType::Data | FrameType::InterPan)
+    }
+}
+
+fn required<T>(field: Option<T>) -> byte::Result<T> {
+    field.ok_or(byte::Error::BadInput {
+        err: "Missing header field",
+    })
+}
+
+impl TryRead<'_> for Header {
+    fn try_read(bytes: &[u8], _ctx: ()) -> byte::Result<(Self, usize)> {
+        let offset = &mut 0;
+        let control: u8 = bytes.read(offset)?;
+
+        let frame_type = match control & FRAME_TYPE_MASK {
+            0 => FrameType::Data,
+            1 => FrameType::Command,
+            2 => FrameType::Ack,
+            _ => FrameType::InterPan,
+        };
+        let delivery_mode = match (control & DELIVERY_MODE_MASK) >> DELIVERY_MODE_SHIFT {
+            0 => DeliveryMode::Unicast,
+            2 => DeliveryMode::Broadcast,
+            3 => DeliveryMode::Group,
+            _ => {
+                return Err(byte::Error::BadInput {
+                    err: "Unknown delivery mode",
+                })
+            }
+        };
+
+        let mut header = Self {
+            frame_type,
+            delivery_mode,
+            ack_format: control & ACK_FORMAT != 0,
+            security: control & SECURITY != 0,
+            ack_request: control & ACK_REQUEST != 0,
+            destination_endpoint: None,
+            group: None,
+            cluster: None,
+            profile: None,
+            source_endpoint: None,
+            counter: 0,
+            fragmentation: None,
+        };
+
+        if header.has_destination_endpoint() {
+            header.destination_endpoint = Some(bytes.read(offset)?);
+        }
+        if header.has_group() {
+            header.group = Some(bytes.read_with(offset, LE)?);
+        }
+        if header.has_cluster() {
+            header.cluster = Some(bytes.read_with(offset, LE)?);
+            header.profile = Some(bytes.read_with(offset, LE)?);
+        }
+        if header.has_endpoints() {
+            header.source_endpoint = Some(bytes.read(offset)?);
+        }
+
+        if frame_type == FrameType::InterPan {
+            return Ok((header, *offset));
+        }
+
+        header.counter = bytes.read(offset)?;
+
+        if control & EXTENDED_HEADER != 0 {
+            let extended: u8 = bytes.read(offset)?;
+            let first = match extended & FRAGMENTATION_MASK {
+                0 => None,
+                FRAGMENTATION_FIRST => Some(true),
+                FRAGMENTATION_SUBSEQUENT => Some(false),
+                _ => {
+                    return Err(byte::Error::BadInput {
+                        err: "Unknown fragmentation",
+                    })
+                }
+            };
+
+            if let Some(first) = first {
+                let block = bytes.read(offset)?;
+                let ack_bitfield = if frame_type == FrameType::Ack {
+                    bytes.read(offset)?
+                } else {
+                    0
+                };
+
+                header.fragmentation = Some(Fragmentation {
+                    first,
+                    block,
+                    ack_bitfield,
+                });
+            }
+        }
+
+        Ok((header, *offset))
+    }
+}
+
+impl TryWrite for Header {
+    fn try_write(self, bytes: &mut [u8], _ctx: ()) -> byte::Result<usize> {
+        let offset = &mut 0;
+
+        let mut control = match self.frame_type {
+            FrameType::Data => 0,
+            FrameType::Command => 1,
+            FrameType::Ack => 2,
+            FrameType::InterPan => 3,
+        };
+        control |= match self.delivery_mode {
+            DeliveryMode::Unicast => 0,
+            DeliveryMode::Broadcast => 2,
+            DeliveryMode::Group => 3,
+        } << DELIVERY_MODE_SHIFT;
+
+        for (set, flag) in [
+            (self.ack_format, ACK_FORMAT),
+            (self.security, SECURITY),
+            (self.ack_request, ACK_REQUEST),
+            (self.fragmentation.is_some(), EXTENDED_HEADER),
+        ] {
+            if set {
+                control |= flag;
+            }
+        }
+
+        bytes.write(offset, control)?;
+
+        if self.has_destination_endpoint() {
+            bytes.write(offset, required(self.destination_endpoint)?)?;
+        }
+        if self.has_group() {
+            bytes.write_with(offset, required(self.group)?, LE)?;
+        }
+        if self.has_cluster() {
+            bytes.write_with(offset, required(self.cluster)?, LE)?;
+            bytes.write_with(offset, required(self.profile)?, LE)?;
+        }
+        if self.has_endpoints() {
+            bytes.write(offset, required(self.source_endpoint)?)?;
+        }
+
+        if self.frame_type == FrameType::InterPan {
+            return Ok(*offset);
+        }
+
+        bytes.write(offset, self.counter)?;
+
+        if let Some(fragmentation) = self.fragmentation {
+            let extended = if fragmentation.first {
+                FRAGMENTATION_FIRST
+            } else {
+                FRAGMENTATION_SUBSEQUENT
+            };
+
+            bytes.write(offset, extended)?;
+            bytes.write(offset, fragmentation.block)?;
+            if self.frame_type == FrameType::Ack {
+                bytes.write(offset, fragmentation.ack_bitfield)?;
+            }
+        }
+
+        Ok(*offset)
+    }
+}
+
+/// APS frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<'p> {
+    pub header: Header,
+    /// Present if the `security` flag of the header is set
+    pub auxiliary_security_header: Option<AuxiliarySecurityHeader>,
+    /// Payload, encrypted and followed by the MIC if the frame is secured,
+    /// starting with the command identifier in command frames
+    pub payload: &'p [u8],
+}
+
+impl<'p> Frame<'p> {
+    /// Decode an APS frame, usually the payload of a NWK data frame
+    pub fn decode(bytes: &'p [u8]) -> Result<Self, Error> {
+        let offset = &mut 0;
+        let header: Header = bytes.read(offset)?;
+
+        let auxiliary_security_header = if header.security {
+            Some(bytes.read(offset)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            header,
+            auxiliary_security_header,
+            payload: &bytes[*offset..],
+        })
+    }
+
+    /// Encode the frame into `bytes`, returns the length of the frame
+    ///
+    /// The `security` flag of the header is set from
+    /// `auxiliary_security_header`, and fields missing from the header are
+    /// reported as [`Error::BadInput`].
+    pub fn encode(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        let offset = &mut 0;
+
+        let mut header = self.header.clone();
+        header.security = self.auxiliary_security_header.is_some();
+        bytes.write(offset, header)?;
+
+        if let Some(security) = self.auxiliary_security_header {
+            bytes.write(offset, security)?;
+        }
+        bytes.write(offset, self.payload)?;
+
+        Ok(*offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zigbee::KeyIdentifier;
+
+    // ZCL toggle of the on/off cluster, unicast and requesting an APS ACK
+    const TOGGLE: [u8; 11] = [
+        0x40, 0x01, 0x06, 0x00, 0x04, 0x01, 0x01, 0x17, 0x01, 0x2a, 0x02,
+    ];
+
+    fn data_header(delivery_mode: DeliveryMode) -> Header {
+        let group = delivery_mode == DeliveryMode::Group;
+
+        Header {
+            frame_type: FrameType::Data,
+            delivery_mode,
+            ack_format: false,
+            security: false,
+            ack_request: !group,
+            destination_endpoint: (!group).then_some(3),
+            group: group.then_some(0x0042),
+            cluster: Some(0x0006),
+            profile: Some(0x0104),
+            source_endpoint: Some(1),
+            counter: 9,
+            fragmentation: None,
+        }
+    }
+
+    fn headers() -> [Header; 7] {
+        let ack = Header {
+            frame_type: FrameType::Ack,
+            ack_request: false,
+            ..data_header(DeliveryMode::Unicast)
+        };
+
+        [
+            data_header(DeliveryMode::Unicast),
+            data_header(DeliveryMode::Broadcast),
+            data_header(DeliveryMode::Group),
+            Header {
+                fragmentation: Some(Fragmentation {
+                    first: true,
+                    block: 3,
+                    ack_bitfield: 0,
+                }),
+                ..data_header(DeliveryMode::Unicast)
+            },
+            Header {
+                fragmentation: Some(Fragmentation {
+                    first: false,
+                    block: 2,
+                    ack_bitfield: 0x03,
+                }),
+                ..ack.clone()
+            },
+            // acknowledgement of a command
+            Header {
+                ack_format: true,
+                destination_endpoint: None,
+                cluster: None,
+                profile: None,
+                source_endpoint: None,
+                ..ack
+            },
+            Header {
+                frame_type: FrameType::InterPan,
+                ack_request: false,
+                source_endpoint: None,
+                destination_endpoint: None,
+                counter: 0,
+                ..data_header(DeliveryMode::Group)
+            },
+        ]
+    }
+
+    #[test]
+    fn data_frame_round_trips() {
+        let frame = Frame::decode(&TOGGLE).unwrap();
+        assert_eq!(
+            frame.header,
+            Header {
+                destination_endpoint: Some(1),
+                counter: 0x17,
+                ..data_header(DeliveryMode::Unicast)
+            }
+        );
+        assert_eq!(frame.auxiliary_security_header, None);
+        assert_eq!(frame.payload, [0x01, 0x2a, 0x02]);
+
+        let mut bytes = [0; 32];
+        let len = frame.encode(&mut bytes).unwrap();
+        assert_eq!(bytes[..len], TOGGLE);
+    }
+
+    #[test]
+    fn headers_round_trip() {
+        let mut bytes = [0; 32];
+        for header in headers() {
+            let frame = Frame {
+                header,
+                auxiliary_security_header: None,
+                payload: &[0xaa],
+            };
+            let len = frame.encode(&mut bytes).unwrap();
+            assert_eq!(Frame::decode(&bytes[..len]).unwrap(), frame);
+        }
+    }
+
+    #[test]
+    fn secured_command_round_trips() {
+        // transport key command, secured with the key-transport key
+        let command = [
+            0x21, 0x05, 0x30, 0x01, 0, 0, 0, 8, 7, 6, 5, 4, 3, 2, 1, 0xee,
+        ];
+        let frame = Frame::decode(&command).unwrap();
+        assert_eq!(frame.header.frame_type, FrameType::Command);
+        assert!(frame.header.security);
+        assert_eq!(
+            frame.auxiliary_security_header,
+            Some(AuxiliarySecurityHeader {
+                level: 0,
+                key_identifier: KeyIdentifier::KeyTransport,
+                frame_counter: 1,
+                source: Some(0x0102_0304_0506_0708),
+            })
+        );
+        assert_eq!(frame.payload, [0xee]);
+
+        let mut bytes = [0; 32];
+        let len = frame.encode(&mut bytes).unwrap();
+        assert_eq!(bytes[..len], command);
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        let mut bytes = [0; 32];
+        for header in headers() {
+            let frame = Frame {
+                header,
+                auxiliary_security_header: Some(AuxiliarySecurityHeader {
+                    level: 5,
+                    key_identifier: KeyIdentifier::Network(2),
+                    frame_counter: 7,
+                    source: None,
+                }),
+                payload: &[],
+            };
+            let len = frame.encode(&mut bytes).unwrap();
+
+            for len in 0..len {
+                assert!(matches!(
+                    Frame::decode(&bytes[..len]),
+                    Err(Error::Incomplete)
+                ));
+            }
+            assert!(frame.encode(&mut bytes[..len - 1]).is_err());
+        }
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        // reserved delivery mode
+        let mut frame = TOGGLE;
+        frame[0] |= 0x04;
+        assert!(matches!(Frame::decode(&frame), Err(Error::BadInput)));
+
+        // reserved fragmentation
+        let frame = [0x80, 0x01, 0x06, 0x00, 0x04, 0x01, 0x01, 0x17, 0x03, 0x00];
+        assert!(matches!(Frame::decode(&frame), Err(Error::BadInput)));
+
+        // a data frame without cluster can't be encoded
+        let frame = Frame {
+            header: Header {
+                cluster: None,
+                ..data_header(DeliveryMode::Unicast)
+            },
+            auxiliary_security_header: None,
+            payload: &[],
+        };
+        assert!(matches!(frame.encode(&mut [0; 32]), Err(Error::BadInput)));
+
+        // nor a group frame without group
+        let frame = Frame {
+            header: Header {
+                group: None,
+                ..data_header(DeliveryMode::Group)
+            },
+            auxiliary_security_header: None,
+            payload: &[],
+        };
+        assert!(matches!(frame.encode(&mut [0; 32]), Err(Error::BadInput)));
+    }
+}
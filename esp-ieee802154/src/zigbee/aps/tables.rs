@@ -0,0 +1,202 @@
+//! Binding and group tables
+
+use heapless::Vec;
+
+use crate::Error;
+
+/// Where a binding sends the frames to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BindingDestination {
+    Group(u16),
+    Device { address: u64, endpoint: u8 },
+}
+
+/// Binding of a cluster of a source endpoint to a destination
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Binding {
+    /// Extended address of the source device
+    pub source: u64,
+    pub source_endpoint: u8,
+    pub cluster: u16,
+    pub destination: BindingDestination,
+}
+
+/// Binding table, of up to `N` bindings
+#[derive(Debug, Clone, Default)]
+pub struct BindingTable<const N: usize> {
+    bindings: Vec<Binding, N>,
+}
+
+impl<const N: usize> BindingTable<N> {
+    pub const fn new() -> Self {
+        Self {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Add a binding, returns [`Error::Full`] if the table is full
+    ///
+    /// Adding a binding which is already in the table succeeds.
+    pub fn add(&mut self, binding: Binding) -> Result<(), Error> {
+        if self.bindings.contains(&binding) {
+            return Ok(());
+        }
+
+        self.bindings.push(binding).map_err(|_| Error::Full)
+    }
+
+    /// Remove a binding, returns whether it was in the table
+    pub fn remove(&mut self, binding: &Binding) -> bool {
+        if let Some(index) = self.bindings.iter().position(|b| b == binding) {
+            self.bindings.swap_remove(index);
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.bindings.clear();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Binding> {
+        self.bindings.iter()
+    }
+
+    /// The destinations the cluster of the source endpoint is bound to
+    pub fn destinations(
+        &self,
+        source: u64,
+        source_endpoint: u8,
+        cluster: u16,
+    ) -> impl Iterator<Item = BindingDestination> + '_ {
+        self.bindings
+            .iter()
+            .filter(move |b| {
+                b.source == source && b.source_endpoint == source_endpoint && b.cluster == cluster
+            })
+            .map(|b| b.destination)
+    }
+}
+
+/// Group table, of up to `N` memberships of an endpoint in a group
+#[derive(Debug, Clone, Default)]
+pub struct GroupTable<const N: usize> {
+    memberships: Vec<(u16, u8), N>,
+}
+
+impl<const N: usize> GroupTable<N> {
+    pub const fn new() -> Self {
+        Self {
+            memberships: Vec::new(),
+        }
+    }
+
+    /// Add an endpoint to a group, returns [`Error::Full`] if the table is
+    /// full
+    pub fn add(&mut self, group: u16, endpoint: u8) -> Result<(), Error> {
+        if self.memberships.contains(&(group, endpoint)) {
+            return Ok(());
+        }
+
+        self.memberships
+            .push((group, endpoint))
+            .map_err(|_| Error::Full)
+    }
+
+    /// Remove an endpoint from a group, returns whether it was a member
+    pub fn remove(&mut self, group: u16, endpoint: u8) -> bool {
+        let len = self.memberships.len();
+        self.memberships.retain(|&m| m != (group, endpoint));
+        self.memberships.len() != len
+    }
+
+    /// Remove an endpoint from all groups
+    pub fn remove_endpoint(&mut self, endpoint: u8) {
+        self.memberships.retain(|&(_, e)| e != endpoint);
+    }
+
+    pub fn clear(&mut self) {
+        self.memberships.clear();
+    }
+
+    /// Whether an endpoint of the device is a member of the group
+    pub fn contains(&self, group: u16) -> bool {
+        self.memberships.iter().any(|&(g, _)| g == group)
+    }
+
+    /// The endpoints which are members of the group
+    pub fn endpoints(&self, group: u16) -> impl Iterator<Item = u8> + '_ {
+        self.memberships
+            .iter()
+            .filter(move |&&(g, _)| g == group)
+            .map(|&(_, endpoint)| endpoint)
+    }
+
+    /// The groups the endpoint is a member of
+    pub fn groups(&self, endpoint: u8) -> impl Iterator<Item = u16> + '_ {
+        self.memberships
+            .iter()
+            .filter(move |&&(_, e)| e == endpoint)
+            .map(|&(group, _)| group)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_table_keeps_unique_bindings() {
+        let mut table = BindingTable::<2>::new();
+        let group = Binding {
+            source: 1,
+            source_endpoint: 1,
+            cluster: 0x0006,
+            destination: BindingDestination::Group(5),
+        };
+        let device = Binding {
+            destination: BindingDestination::Device {
+                address: 9,
+                endpoint: 2,
+            },
+            ..group
+        };
+
+        table.add(group).unwrap();
+        table.add(group).unwrap();
+        table.add(device).unwrap();
+        assert!(matches!(
+            table.add(Binding {
+                cluster: 0x0008,
+                ..group
+            }),
+            Err(Error::Full)
+        ));
+
+        assert_eq!(table.destinations(1, 1, 0x0006).count(), 2);
+        assert_eq!(table.destinations(1, 1, 0x0008).count(), 0);
+        assert!(table.remove(&group));
+        assert!(!table.remove(&group));
+        assert_eq!(table.iter().collect::<std::vec::Vec<_>>(), [&device]);
+    }
+
+    #[test]
+    fn group_table_tracks_endpoints() {
+        let mut table = GroupTable::<3>::new();
+        table.add(1, 1).unwrap();
+        table.add(1, 2).unwrap();
+        table.add(2, 2).unwrap();
+        assert!(matches!(table.add(3, 1), Err(Error::Full)));
+
+        assert_eq!(table.endpoints(1).collect::<std::vec::Vec<_>>(), [1, 2]);
+        assert_eq!(table.groups(2).collect::<std::vec::Vec<_>>(), [1, 2]);
+
+        table.remove_endpoint(2);
+        assert!(!table.contains(2));
+        assert!(table.contains(1));
+        assert!(!table.remove(1, 2));
+        assert!(table.remove(1, 1));
+        assert!(!table.contains(1));
+    }
+}
@@ -0,0 +1,135 @@
+//! Zigbee frames, used with the `zigbee` feature
+//!
+//! The Zigbee layers are carried in the payload of IEEE 802.15.4 data frames,
+//! see [`Frame::payload`](crate::Frame::payload). The codecs in this module
+//! don't use the radio, so they can be tested on the host.
+
+pub mod aps;
+pub mod nwk;
+pub mod zcl;
+
+use byte::{check_len, BytesExt, TryRead, TryWrite, LE};
+
+/// Key used to secure a frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyIdentifier {
+    /// Link key
+    Data,
+    /// Network key, with its sequence number
+    Network(u8),
+    /// Key-transport key
+    KeyTransport,
+    /// Key-load key
+    KeyLoad,
+}
+
+/// Auxiliary security header of secured NWK and APS frames
+///
+/// The header is followed by the encrypted payload and the MIC, whose length
+/// depends on the security level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuxiliarySecurityHeader {
+    /// Security level, usually transmitted as 0 and replaced by the configured
+    /// one before processing the frame
+    pub level: u8,
+    pub key_identifier: KeyIdentifier,
+    pub frame_counter: u32,
+    /// Extended address of the device which secured the frame, if included in
+    /// the header
+    pub source: Option<u64>,
+}
+
+const SECURITY_LEVEL_MASK: u8 = 0x07;
+const SECURITY_KEY_ID_SHIFT: u8 = 3;
+const SECURITY_KEY_ID_MASK: u8 = 0x18;
+const SECURITY_EXTENDED_NONCE: u8 = 0x20;
+
+impl AuxiliarySecurityHeader {
+    /// Length of the MIC following the encrypted payload, in bytes
+    pub fn mic_len(&self) -> usize {
+        match self.level & 0x03 {
+            0 => 0,
+            1 => 4,
+            2 => 8,
+            _ => 16,
+        }
+    }
+}
+
+impl TryRead<'_> for AuxiliarySecurityHeader {
+    fn try_read(bytes: &[u8], _ctx: ()) -> byte::Result<(Self, usize)> {
+        let offset = &mut 0;
+        let control: u8 = bytes.read(offset)?;
+        let frame_counter = bytes.read_with(offset, LE)?;
+
+        let source = if control & SECURITY_EXTENDED_NONCE != 0 {
+            Some(bytes.read_with(offset, LE)?)
+        } else {
+            None
+        };
+
+        let key_identifier = match (control & SECURITY_KEY_ID_MASK) >> SECURITY_KEY_ID_SHIFT {
+            0 => KeyIdentifier::Data,
+            1 => KeyIdentifier::Network(bytes.read(offset)?),
+            2 => KeyIdentifier::KeyTransport,
+            _ => KeyIdentifier::KeyLoad,
+        };
+
+        let header = Self {
+            level: control & SECURITY_LEVEL_MASK,
+            key_identifier,
+            frame_counter,
+            source,
+        };
+
+        Ok((header, *offset))
+    }
+}
+
+impl TryWrite for AuxiliarySecurityHeader {
+    fn try_write(self, bytes: &mut [u8], _ctx: ()) -> byte::Result<usize> {
+        let offset = &mut 0;
+
+        let key_id = match self.key_identifier {
+            KeyIdentifier::Data => 0,
+            KeyIdentifier::Network(_) => 1,
+            KeyIdentifier::KeyTransport => 2,
+            KeyIdentifier::KeyLoad => 3,
+        };
+        let mut control = (self.level & SECURITY_LEVEL_MASK) | (key_id << SECURITY_KEY_ID_SHIFT);
+        if self.source.is_some() {
+            control |= SECURITY_EXTENDED_NONCE;
+        }
+
+        bytes.write(offset, control)?;
+        bytes.write_with(offset, self.frame_counter, LE)?;
+        if let Some(source) = self.source {
+            bytes.write_with(offset, source, LE)?;
+        }
+        if let KeyIdentifier::Network(sequence) = self.key_identifier {
+            bytes.write(offset, sequence)?;
+        }
+
+        Ok(*offset)
+    }
+}
+
+/// Read a list of `count` little-endian addresses
+fn read_addresses<const N: usize>(
+    bytes: &[u8],
+    offset: &mut usize,
+    count: usize,
+) -> byte::Result<heapless::Vec<u16, N>> {
+    check_len(&bytes[*offset..], count * 2)?;
+
+    let mut addresses = heapless::Vec::new();
+    for _ in 0..count {
+        addresses
+            .push(bytes.read_with(offset, LE)?)
+            .map_err(|_| byte::Error::BadInput {
+                err: "Too many addresses",
+            })?;
+    }
+
+    Ok(addresses)
+}
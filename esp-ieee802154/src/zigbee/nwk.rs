@@ -0,0 +1,1196 @@
+//! Zigbee network (NWK) layer frames
+//!
+//! A NWK frame is made of a [`Header`], an [`AuxiliarySecurityHeader`] if the
+//! frame is secured, and the payload: an APS frame for data frames, or a
+//! [`Command`] for command frames. Secured payloads are neither decrypted nor
+//! encrypted here.
+//!
+//! ```no_run
+//! # fn f(frame: &esp_ieee802154::Frame) -> Result<(), esp_ieee802154::Error> {
+//! use esp_ieee802154::zigbee::nwk::{self, Command};
+//!
+//! let nwk = nwk::Frame::decode(&frame.payload)?;
+//! if let Some(Command::Leave { rejoin, .. }) = nwk.command()? {
+//!     // ...
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use byte::{check_len, BytesExt, TryRead, TryWrite, LE};
+use heapless::Vec;
+
+use super::{read_addresses, AuxiliarySecurityHeader};
+use crate::Error;
+
+/// Zigbee PRO
+pub const PROTOCOL_VERSION: u8 = 2;
+
+/// Broadcast to all devices
+pub const BROADCAST_ALL: u16 = 0xffff;
+/// Broadcast to the devices with their receiver on when idle
+pub const BROADCAST_RX_ON_WHEN_IDLE: u16 = 0xfffd;
+/// Broadcast to the routers and the coordinator
+pub const BROADCAST_ROUTERS: u16 = 0xfffc;
+
+/// Maximum number of relays in a source route or route record
+pub const MAX_RELAYS: usize = 16;
+/// Maximum number of entries in a link status or network report command
+pub const MAX_ENTRIES: usize = 31;
+/// Maximum number of entries in a link power delta command
+pub const MAX_POWER_DELTAS: usize = 16;
+
+const FRAME_TYPE_MASK: u16 = 0x0003;
+const PROTOCOL_VERSION_SHIFT: u16 = 2;
+const PROTOCOL_VERSION_MASK: u16 = 0x003c;
+const DISCOVER_ROUTE_SHIFT: u16 = 6;
+const DISCOVER_ROUTE_MASK: u16 = 0x00c0;
+const MULTICAST: u16 = 0x0100;
+const SECURITY: u16 = 0x0200;
+const SOURCE_ROUTE: u16 = 0x0400;
+const DESTINATION_IEEE: u16 = 0x0800;
+const SOURCE_IEEE: u16 = 0x1000;
+const END_DEVICE_INITIATOR: u16 = 0x2000;
+
+/// NWK frame type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    Data,
+    Command,
+    /// Inter-PAN frame, whose header only consists of the frame control
+    InterPan,
+}
+
+/// Whether route discovery may be started for the frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiscoverRoute {
+    Suppress,
+    Enable,
+}
+
+/// Multicast mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MulticastMode {
+    /// Sent by a device which is not a member of the group
+    NonMember,
+    /// Sent by a member of the group
+    Member,
+}
+
+/// Multicast control field
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulticastControl {
+    pub mode: MulticastMode,
+    /// Remaining hops while relayed by non-members, up to 7
+    pub non_member_radius: u8,
+    /// Initial value of `non_member_radius`, up to 7
+    pub max_non_member_radius: u8,
+}
+
+/// Source route subframe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceRoute {
+    /// Index in `relays` of the next relay
+    pub relay_index: u8,
+    /// Relays, from the one closest to the destination to the one closest to
+    /// the source
+    pub relays: Vec<u16, MAX_RELAYS>,
+}
+
+/// NWK header
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+    pub frame_type: FrameType,
+    pub protocol_version: u8,
+    pub discover_route: DiscoverRoute,
+    pub multicast: Option<MulticastControl>,
+    /// Whether the frame is secured, followed by an
+    /// [`AuxiliarySecurityHeader`]
+    pub security: bool,
+    pub source_route: Option<SourceRoute>,
+    pub end_device_initiator: bool,
+    /// Short address or group ID of the destination
+    pub destination: u16,
+    pub source: u16,
+    pub radius: u8,
+    pub sequence: u8,
+    pub destination_ieee: Option<u64>,
+    pub source_ieee: Option<u64>,
+}
+
+impl Header {
+    /// Header of a data frame, without optional fields
+    pub fn data(destination: u16, source: u16, radius: u8, sequence: u8) -> Self {
+        Self {
+            frame_type: FrameType::Data,
+            protocol_version: PROTOCOL_VERSION,
+            discover_route: DiscoverRoute::Enable,
+            multicast: None,
+            security: false,
+            source_route: None,
+            end_device_initiator: false,
+            destination,
+            source,
+            radius,
+            sequence,
+            destination_ieee: None,
+            source_ieee: None,
+        }
+    }
+
+    /// Header of a command frame, without optional fields
+    pub fn command(destination: u16, source: u16, radius: u8, sequence: u8) -> Self {
+        Self {
+            frame_type: FrameType::Command,
+            discover_route: DiscoverRoute::Suppress,
+            ..Self::data(destination, source, radius, sequence)
+        }
+    }
+}
+
+impl TryRead<'_> for Header {
+    fn try_read(bytes: &[u8], _ctx: ()) -> byte::Result<(Self, usize)> {
+        let offset = &mut 0;
+        let control: u16 = bytes.read_with(offset, LE)?;
+
+        let frame_type = match control & FRAME_TYPE_MASK {
+            0 => FrameType::Data,
+            1 => FrameType::Command,
+            3 => FrameType::InterPan,
+            _ => {
+                return Err(byte::Error::BadInput {
+                    err: "Unknown frame type",
+                })
+            }
+        };
+        let protocol_version = ((control & PROTOCOL_VERSION_MASK) >> PROTOCOL_VERSION_SHIFT) as u8;
+        let discover_route = match (control & DISCOVER_ROUTE_MASK) >> DISCOVER_ROUTE_SHIFT {
+            0 => DiscoverRoute::Suppress,
+            1 => DiscoverRoute::Enable,
+            _ => {
+                return Err(byte::Error::BadInput {
+                    err: "Unknown route discovery",
+                })
+            }
+        };
+
+        let mut header = Self {
+            frame_type,
+            protocol_version,
+            discover_route,
+            multicast: None,
+            security: control & SECURITY != 0,
+            source_route: None,
+            end_device_initiator: control & END_DEVICE_INITIATOR != 0,
+            destination: 0,
+            source: 0,
+            radius: 0,
+            sequence: 0,
+            destination_ieee: None,
+            source_ieee: None,
+        };
+
+        if frame_type == FrameType::InterPan {
+            return Ok((header, *offset));
+        }
+
+        header.destination = bytes.read_with(offset, LE)?;
+        header.source = bytes.read_with(offset, LE)?;
+        header.radius = bytes.read(offset)?;
+        header.sequence = bytes.read(offset)?;
+
+        if control & DESTINATION_IEEE != 0 {
+            header.destination_ieee = Some(bytes.read_with(offset, LE)?);
+        }
+        if control & SOURCE_IEEE != 0 {
+            header.source_ieee = Some(bytes.read_with(offset, LE)?);
+        }
+
+        if control & MULTICAST != 0 {
+            let multicast: u8 = bytes.read(offset)?;
+            let mode = match multicast & 0x03 {
+                0 => MulticastMode::NonMember,
+                1 => MulticastMode::Member,
+                _ => {
+                    return Err(byte::Error::BadInput {
+                        err: "Unknown multicast mode",
+                    })
+                }
+            };
+
+            header.multicast = Some(MulticastControl {
+                mode,
+                non_member_radius: (multicast >> 2) & 0x07,
+                max_non_member_radius: (multicast >> 5) & 0x07,
+            });
+        }
+
+        if control & SOURCE_ROUTE != 0 {
+            let count: u8 = bytes.read(offset)?;
+            let relay_index = bytes.read(offset)?;
+            let relays = read_addresses(bytes, offset, count as usize)?;
+
+            header.source_route = Some(SourceRoute {
+                relay_index,
+                relays,
+            });
+        }
+
+        Ok((header, *offset))
+    }
+}
+
+impl TryWrite for Header {
+    fn try_write(self, bytes: &mut [u8], _ctx: ()) -> byte::Result<usize> {
+        let offset = &mut 0;
+
+        let mut control = match self.frame_type {
+            FrameType::Data => 0,
+            FrameType::Command => 1,
+            FrameType::InterPan => 3,
+        };
+        control |=
+            ((self.protocol_version as u16) << PROTOCOL_VERSION_SHIFT) & PROTOCOL_VERSION_MASK;
+        control |= match self.discover_route {
+            DiscoverRoute::Suppress => 0,
+            DiscoverRoute::Enable => 1 << DISCOVER_ROUTE_SHIFT,
+        };
+
+        if self.frame_type == FrameType::InterPan {
+            bytes.write_with(offset, control, LE)?;
+            return Ok(*offset);
+        }
+
+        for (set, flag) in [
+            (self.multicast.is_some(), MULTICAST),
+            (self.security, SECURITY),
+            (self.source_route.is_some(), SOURCE_ROUTE),
+            (self.destination_ieee.is_some(), DESTINATION_IEEE),
+            (self.source_ieee.is_some(), SOURCE_IEEE),
+            (self.end_device_initiator, END_DEVICE_INITIATOR),
+        ] {
+            if set {
+                control |= flag;
+            }
+        }
+
+        bytes.write_with(offset, control, LE)?;
+        bytes.write_with(offset, self.destination, LE)?;
+        bytes.write_with(offset, self.source, LE)?;
+        bytes.write(offset, self.radius)?;
+        bytes.write(offset, self.sequence)?;
+
+        if let Some(address) = self.destination_ieee {
+            bytes.write_with(offset, address, LE)?;
+        }
+        if let Some(address) = self.source_ieee {
+            bytes.write_with(offset, address, LE)?;
+        }
+
+        if let Some(multicast) = self.multicast {
+            let mode = match multicast.mode {
+                MulticastMode::NonMember => 0,
+                MulticastMode::Member => 1,
+            };
+            bytes.write(
+                offset,
+                mode | (multicast.non_member_radius & 0x07) << 2
+                    | (multicast.max_non_member_radius & 0x07) << 5,
+            )?;
+        }
+
+        if let Some(route) = self.source_route {
+            bytes.write(offset, route.relays.len() as u8)?;
+            bytes.write(offset, route.relay_index)?;
+            for relay in route.relays {
+                bytes.write_with(offset, relay, LE)?;
+            }
+        }
+
+        Ok(*offset)
+    }
+}
+
+/// NWK frame
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame<'p> {
+    pub header: Header,
+    /// Present if the `security` flag of the header is set
+    pub auxiliary_security_header: Option<AuxiliarySecurityHeader>,
+    /// Payload, encrypted and followed by the MIC if the frame is secured
+    pub payload: &'p [u8],
+}
+
+impl<'p> Frame<'p> {
+    /// Decode a NWK frame, usually the payload of a received MAC frame
+    pub fn decode(bytes: &'p [u8]) -> Result<Self, Error> {
+        let offset = &mut 0;
+        let header: Header = bytes.read(offset)?;
+
+        let auxiliary_security_header = if header.security {
+            Some(bytes.read(offset)?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            header,
+            auxiliary_security_header,
+            payload: &bytes[*offset..],
+        })
+    }
+
+    /// Encode the frame into `bytes`, returns the length of the frame
+    ///
+    /// The `security` flag of the header is set from
+    /// `auxiliary_security_header`.
+    pub fn encode(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        let offset = &mut 0;
+
+        let mut header = self.header.clone();
+        header.security = self.auxiliary_security_header.is_some();
+        bytes.write(offset, header)?;
+
+        if let Some(security) = self.auxiliary_security_header {
+            bytes.write(offset, security)?;
+        }
+        bytes.write(offset, self.payload)?;
+
+        Ok(*offset)
+    }
+
+    /// The command carried by an unsecured command frame
+    ///
+    /// Returns `None` for other frame types and secured frames.
+    pub fn command(&self) -> Result<Option<Command>, Error> {
+        if self.header.frame_type != FrameType::Command || self.header.security {
+            return Ok(None);
+        }
+
+        Command::decode(self.payload).map(Some)
+    }
+}
+
+/// Many-to-one route request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManyToOne {
+    /// Not a many-to-one route request
+    No,
+    /// Sent by a concentrator which stores the source routes
+    RouteRecordTable,
+    /// Sent by a concentrator which doesn't store the source routes
+    NoRouteRecordTable,
+}
+
+/// Entry of a link status command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkStatusEntry {
+    pub address: u16,
+    /// Incoming link cost, from 1 to 7
+    pub incoming_cost: u8,
+    /// Outgoing link cost, from 1 to 7, 0 if unknown
+    pub outgoing_cost: u8,
+}
+
+/// Type of a link power delta command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerDeltaType {
+    Notification,
+    Request,
+    Response,
+}
+
+/// Entry of a link power delta command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PowerDelta {
+    pub address: u16,
+    /// Transmit power change requested from the neighbor, in dB
+    pub delta: i8,
+}
+
+/// NWK command
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    RouteRequest {
+        many_to_one: ManyToOne,
+        /// Whether `destination` is a group ID
+        multicast: bool,
+        id: u8,
+        destination: u16,
+        path_cost: u8,
+        destination_ieee: Option<u64>,
+    },
+    RouteReply {
+        /// Whether `responder` is a group ID
+        multicast: bool,
+        id: u8,
+        originator: u16,
+        responder: u16,
+        path_cost: u8,
+        originator_ieee: Option<u64>,
+        responder_ieee: Option<u64>,
+    },
+    NetworkStatus {
+        status: u8,
+        destination: u16,
+    },
+    Leave {
+        rejoin: bool,
+        /// Whether the recipient is asked to leave, rather than the sender
+        /// announcing it leaves
+        request: bool,
+        remove_children: bool,
+    },
+    RouteRecord {
+        relays: Vec<u16, MAX_RELAYS>,
+    },
+    RejoinRequest {
+        capability: u8,
+    },
+    RejoinResponse {
+        address: u16,
+        status: u8,
+    },
+    LinkStatus {
+        first: bool,
+        last: bool,
+        entries: Vec<LinkStatusEntry, MAX_ENTRIES>,
+    },
+    /// PAN ID conflict report
+    NetworkReport {
+        extended_pan_id: u64,
+        pan_ids: Vec<u16, MAX_ENTRIES>,
+    },
+    /// PAN ID update
+    NetworkUpdate {
+        extended_pan_id: u64,
+        update_id: u8,
+        pan_id: u16,
+    },
+    EndDeviceTimeoutRequest {
+        timeout: u8,
+        configuration: u8,
+    },
+    EndDeviceTimeoutResponse {
+        status: u8,
+        parent_information: u8,
+    },
+    LinkPowerDelta {
+        kind: PowerDeltaType,
+        deltas: Vec<PowerDelta, MAX_POWER_DELTAS>,
+    },
+}
+
+mod command_id {
+    pub const ROUTE_REQUEST: u8 = 0x01;
+    pub const ROUTE_REPLY: u8 = 0x02;
+    pub const NETWORK_STATUS: u8 = 0x03;
+    pub const LEAVE: u8 = 0x04;
+    pub const ROUTE_RECORD: u8 = 0x05;
+    pub const REJOIN_REQUEST: u8 = 0x06;
+    pub const REJOIN_RESPONSE: u8 = 0x07;
+    pub const LINK_STATUS: u8 = 0x08;
+    pub const NETWORK_REPORT: u8 = 0x09;
+    pub const NETWORK_UPDATE: u8 = 0x0a;
+    pub const END_DEVICE_TIMEOUT_REQUEST: u8 = 0x0b;
+    pub const END_DEVICE_TIMEOUT_RESPONSE: u8 = 0x0c;
+    pub const LINK_POWER_DELTA: u8 = 0x0d;
+}
+
+const ROUTE_MANY_TO_ONE_SHIFT: u8 = 3;
+const ROUTE_MANY_TO_ONE_MASK: u8 = 0x18;
+const ROUTE_REQUEST_DESTINATION_IEEE: u8 = 0x20;
+const ROUTE_REPLY_ORIGINATOR_IEEE: u8 = 0x10;
+const ROUTE_REPLY_RESPONDER_IEEE: u8 = 0x20;
+const ROUTE_MULTICAST: u8 = 0x40;
+
+const LEAVE_REMOVE_CHILDREN: u8 = 0x80;
+const LEAVE_REQUEST: u8 = 0x40;
+const LEAVE_REJOIN: u8 = 0x20;
+
+const LINK_STATUS_COUNT_MASK: u8 = 0x1f;
+const LINK_STATUS_FIRST: u8 = 0x20;
+const LINK_STATUS_LAST: u8 = 0x40;
+
+const REPORT_COUNT_MASK: u8 = 0x1f;
+const REPORT_TYPE_SHIFT: u8 = 5;
+
+impl Command {
+    /// Decode the payload of a command frame
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        Ok(bytes.read::<Command>(&mut 0)?)
+    }
+
+    /// Encode the command into `bytes`, returns its length
+    pub fn encode(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        Ok(self.clone().try_write(bytes, ())?)
+    }
+}
+
+impl TryRead<'_> for Command {
+    fn try_read(bytes: &[u8], _ctx: ()) -> byte::Result<(Self, usize)> {
+        let offset = &mut 0;
+        let id: u8 = bytes.read(offset)?;
+
+        let command = match id {
+            command_id::ROUTE_REQUEST => {
+                let options: u8 = bytes.read(offset)?;
+                let many_to_one =
+                    match (options & ROUTE_MANY_TO_ONE_MASK) >> ROUTE_MANY_TO_ONE_SHIFT {
+                        0 => ManyToOne::No,
+                        1 => ManyToOne::RouteRecordTable,
+                        2 => ManyToOne::NoRouteRecordTable,
+                        _ => {
+                            return Err(byte::Error::BadInput {
+                                err: "Unknown many-to-one",
+                            })
+                        }
+                    };
+                let id = bytes.read(offset)?;
+                let destination = bytes.read_with(offset, LE)?;
+                let path_cost = bytes.read(offset)?;
+                let destination_ieee = if options & ROUTE_REQUEST_DESTINATION_IEEE != 0 {
+                    Some(bytes.read_with(offset, LE)?)
+                } else {
+                    None
+                };
+
+                Command::RouteRequest {
+                    many_to_one,
+                    multicast: options & ROUTE_MULTICAST != 0,
+                    id,
+                    destination,
+                    path_cost,
+                    destination_ieee,
+                }
+            }
+            command_id::ROUTE_REPLY => {
+                let options: u8 = bytes.read(offset)?;
+                let id = bytes.read(offset)?;
+                let originator = bytes.read_with(offset, LE)?;
+                let responder = bytes.read_with(offset, LE)?;
+                let path_cost = bytes.read(offset)?;
+                let originator_ieee = if options & ROUTE_REPLY_ORIGINATOR_IEEE != 0 {
+                    Some(bytes.read_with(offset, LE)?)
+                } else {
+                    None
+                };
+                let responder_ieee = if options & ROUTE_REPLY_RESPONDER_IEEE != 0 {
+                    Some(bytes.read_with(offset, LE)?)
+                } else {
+                    None
+                };
+
+                Command::RouteReply {
+                    multicast: options & ROUTE_MULTICAST != 0,
+                    id,
+                    originator,
+                    responder,
+                    path_cost,
+                    originator_ieee,
+                    responder_ieee,
+                }
+            }
+            command_id::NETWORK_STATUS => Command::NetworkStatus {
+                status: bytes.read(offset)?,
+                destination: bytes.read_with(offset, LE)?,
+            },
+            command_id::LEAVE => {
+                let options: u8 = bytes.read(offset)?;
+
+                Command::Leave {
+                    rejoin: options & LEAVE_REJOIN != 0,
+                    request: options & LEAVE_REQUEST != 0,
+                    remove_children: options & LEAVE_REMOVE_CHILDREN != 0,
+                }
+            }
+            command_id::ROUTE_RECORD => {
+                let count: u8 = bytes.read(offset)?;
+
+                Command::RouteRecord {
+                    relays: read_addresses(bytes, offset, count as usize)?,
+                }
+            }
+            command_id::REJOIN_REQUEST => Command::RejoinRequest {
+                capability: bytes.read(offset)?,
+            },
+            command_id::REJOIN_RESPONSE => Command::RejoinResponse {
+                address: bytes.read_with(offset, LE)?,
+                status: bytes.read(offset)?,
+            },
+            command_id::LINK_STATUS => {
+                let options: u8 = bytes.read(offset)?;
+                let count = (options & LINK_STATUS_COUNT_MASK) as usize;
+                check_len(&bytes[*offset..], count * 3)?;
+
+                let mut entries = Vec::new();
+                for _ in 0..count {
+                    let address = bytes.read_with(offset, LE)?;
+                    let cost: u8 = bytes.read(offset)?;
+                    // `count` is at most 31, the capacity of `entries`
+                    let _ = entries.push(LinkStatusEntry {
+                        address,
+                        incoming_cost: cost & 0x07,
+                        outgoing_cost: (cost >> 4) & 0x07,
+                    });
+                }
+
+                Command::LinkStatus {
+                    first: options & LINK_STATUS_FIRST != 0,
+                    last: options & LINK_STATUS_LAST != 0,
+                    entries,
+                }
+            }
+            command_id::NETWORK_REPORT | command_id::NETWORK_UPDATE => {
+                let options: u8 = bytes.read(offset)?;
+                if options >> REPORT_TYPE_SHIFT != 0 {
+                    return Err(byte::Error::BadInput {
+                        err: "Unknown report type",
+                    });
+                }
+                let count = (options & REPORT_COUNT_MASK) as usize;
+                let extended_pan_id = bytes.read_with(offset, LE)?;
+
+                if id == command_id::NETWORK_REPORT {
+                    Command::NetworkReport {
+                        extended_pan_id,
+                        pan_ids: read_addresses(bytes, offset, count)?,
+                    }
+                } else {
+                    if count != 1 {
+                        return Err(byte::Error::BadInput {
+                            err: "Expected a single PAN ID",
+                        });
+                    }
+
+                    Command::NetworkUpdate {
+                        extended_pan_id,
+                        update_id: bytes.read(offset)?,
+                        pan_id: bytes.read_with(offset, LE)?,
+                    }
+                }
+            }
+            command_id::END_DEVICE_TIMEOUT_REQUEST => Command::EndDeviceTimeoutRequest {
+                timeout: bytes.read(offset)?,
+                configuration: bytes.read(offset)?,
+            },
+            command_id::END_DEVICE_TIMEOUT_RESPONSE => Command::EndDeviceTimeoutResponse {
+                status: bytes.read(offset)?,
+                parent_information: bytes.read(offset)?,
+            },
+            command_id::LINK_POWER_DELTA => {
+                let options: u8 = bytes.read(offset)?;
+                let kind = match options & 0x03 {
+                    0 => PowerDeltaType::Notification,
+                    1 => PowerDeltaType::Request,
+                    2 => PowerDeltaType::Response,
+                    _ => {
+                        return Err(byte::Error::BadInput {
+                            err: "Unknown power delta type",
+                        })
+                    }
+                };
+                let count: u8 = bytes.read(offset)?;
+                check_len(&bytes[*offset..], count as usize * 3)?;
+
+                let mut deltas = Vec::new();
+                for _ in 0..count {
+                    let delta = PowerDelta {
+                        address: bytes.read_with(offset, LE)?,
+                        delta: bytes.read(offset)?,
+                    };
+                    deltas.push(delta).map_err(|_| byte::Error::BadInput {
+                        err: "Too many power deltas",
+                    })?;
+                }
+
+                Command::LinkPowerDelta { kind, deltas }
+            }
+            _ => {
+                return Err(byte::Error::BadInput {
+                    err: "Unknown command",
+                })
+            }
+        };
+
+        Ok((command, *offset))
+    }
+}
+
+impl TryWrite for Command {
+    fn try_write(self, bytes: &mut [u8], _ctx: ()) -> byte::Result<usize> {
+        let offset = &mut 0;
+
+        match self {
+            Command::RouteRequest {
+                many_to_one,
+                multicast,
+                id,
+                destination,
+                path_cost,
+                destination_ieee,
+            } => {
+                let mut options = match many_to_one {
+                    ManyToOne::No => 0,
+                    ManyToOne::RouteRecordTable => 1,
+                    ManyToOne::NoRouteRecordTable => 2,
+                } << ROUTE_MANY_TO_ONE_SHIFT;
+                if destination_ieee.is_some() {
+                    options |= ROUTE_REQUEST_DESTINATION_IEEE;
+                }
+                if multicast {
+                    options |= ROUTE_MULTICAST;
+                }
+
+                bytes.write(offset, command_id::ROUTE_REQUEST)?;
+                bytes.write(offset, options)?;
+                bytes.write(offset, id)?;
+                bytes.write_with(offset, destination, LE)?;
+                bytes.write(offset, path_cost)?;
+                if let Some(address) = destination_ieee {
+                    bytes.write_with(offset, address, LE)?;
+                }
+            }
+            Command::RouteReply {
+                multicast,
+                id,
+                originator,
+                responder,
+                path_cost,
+                originator_ieee,
+                responder_ieee,
+            } => {
+                let mut options = 0;
+                if originator_ieee.is_some() {
+                    options |= ROUTE_REPLY_ORIGINATOR_IEEE;
+                }
+                if responder_ieee.is_some() {
+                    options |= ROUTE_REPLY_RESPONDER_IEEE;
+                }
+                if multicast {
+                    options |= ROUTE_MULTICAST;
+                }
+
+                bytes.write(offset, command_id::ROUTE_REPLY)?;
+                bytes.write(offset, options)?;
+                bytes.write(offset, id)?;
+                bytes.write_with(offset, originator, LE)?;
+                bytes.write_with(offset, responder, LE)?;
+                bytes.write(offset, path_cost)?;
+                for address in [originator_ieee, responder_ieee].into_iter().flatten() {
+                    bytes.write_with(offset, address, LE)?;
+                }
+            }
+            Command::NetworkStatus {
+                status,
+                destination,
+            } => {
+                bytes.write(offset, command_id::NETWORK_STATUS)?;
+                bytes.write(offset, status)?;
+                bytes.write_with(offset, destination, LE)?;
+            }
+            Command::Leave {
+                rejoin,
+                request,
+                remove_children,
+            } => {
+                let mut options = 0;
+                if rejoin {
+                    options |= LEAVE_REJOIN;
+                }
+                if request {
+                    options |= LEAVE_REQUEST;
+                }
+                if remove_children {
+                    options |= LEAVE_REMOVE_CHILDREN;
+                }
+
+                bytes.write(offset, command_id::LEAVE)?;
+                bytes.write(offset, options)?;
+            }
+            Command::RouteRecord { relays } => {
+                bytes.write(offset, command_id::ROUTE_RECORD)?;
+                bytes.write(offset, relays.len() as u8)?;
+                for relay in relays {
+                    bytes.write_with(offset, relay, LE)?;
+                }
+            }
+            Command::RejoinRequest { capability } => {
+                bytes.write(offset, command_id::REJOIN_REQUEST)?;
+                bytes.write(offset, capability)?;
+            }
+            Command::RejoinResponse { address, status } => {
+                bytes.write(offset, command_id::REJOIN_RESPONSE)?;
+                bytes.write_with(offset, address, LE)?;
+                bytes.write(offset, status)?;
+            }
+            Command::LinkStatus {
+                first,
+                last,
+                entries,
+            } => {
+                let mut options = entries.len() as u8;
+                if first {
+                    options |= LINK_STATUS_FIRST;
+                }
+                if last {
+                    options |= LINK_STATUS_LAST;
+                }
+
+                bytes.write(offset, command_id::LINK_STATUS)?;
+                bytes.write(offset, options)?;
+                for entry in entries {
+                    bytes.write_with(offset, entry.address, LE)?;
+                    bytes.write(
+                        offset,
+                        (entry.incoming_cost & 0x07) | (entry.outgoing_cost & 0x07) << 4,
+                    )?;
+                }
+            }
+            Command::NetworkReport {
+                extended_pan_id,
+                pan_ids,
+            } => {
+                bytes.write(offset, command_id::NETWORK_REPORT)?;
+                bytes.write(offset, pan_ids.len() as u8)?;
+                bytes.write_with(offset, extended_pan_id, LE)?;
+                for pan_id in pan_ids {
+                    bytes.write_with(offset, pan_id, LE)?;
+                }
+            }
+            Command::NetworkUpdate {
+                extended_pan_id,
+                update_id,
+                pan_id,
+            } => {
+                bytes.write(offset, command_id::NETWORK_UPDATE)?;
+                bytes.write(offset, 1u8)?;
+                bytes.write_with(offset, extended_pan_id, LE)?;
+                bytes.write(offset, update_id)?;
+                bytes.write_with(offset, pan_id, LE)?;
+            }
+            Command::EndDeviceTimeoutRequest {
+                timeout,
+                configuration,
+            } => {
+                bytes.write(offset, command_id::END_DEVICE_TIMEOUT_REQUEST)?;
+                bytes.write(offset, timeout)?;
+                bytes.write(offset, configuration)?;
+            }
+            Command::EndDeviceTimeoutResponse {
+                status,
+                parent_information,
+            } => {
+                bytes.write(offset, command_id::END_DEVICE_TIMEOUT_RESPONSE)?;
+                bytes.write(offset, status)?;
+                bytes.write(offset, parent_information)?;
+            }
+            Command::LinkPowerDelta { kind, deltas } => {
+                let kind: u8 = match kind {
+                    PowerDeltaType::Notification => 0,
+                    PowerDeltaType::Request => 1,
+                    PowerDeltaType::Response => 2,
+                };
+
+                bytes.write(offset, command_id::LINK_POWER_DELTA)?;
+                bytes.write(offset, kind)?;
+                bytes.write(offset, deltas.len() as u8)?;
+                for delta in deltas {
+                    bytes.write_with(offset, delta.address, LE)?;
+                    bytes.write(offset, delta.delta)?;
+                }
+            }
+        }
+
+        Ok(*offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zigbee::KeyIdentifier;
+
+    // secured data frame to the coordinator, with the source IEEE address
+    const SECURED: [u8; 32] = [
+        0x48, 0x12, 0x00, 0x00, 0x34, 0x12, 0x1e, 0x7a, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22,
+        0x11, 0x28, 0x01, 0x00, 0x00, 0x00, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11, 0x00,
+        0xaa, 0xbb,
+    ];
+
+    fn header() -> Header {
+        Header {
+            multicast: Some(MulticastControl {
+                mode: MulticastMode::Member,
+                non_member_radius: 3,
+                max_non_member_radius: 7,
+            }),
+            source_route: Some(SourceRoute {
+                relay_index: 1,
+                relays: Vec::from_slice(&[0x0001, 0x0002, 0x0003]).unwrap(),
+            }),
+            end_device_initiator: true,
+            destination_ieee: Some(0x0011_2233_4455_6677),
+            source_ieee: Some(0x8899_aabb_ccdd_eeff),
+            ..Header::data(0xfffd, 0x0001, 5, 9)
+        }
+    }
+
+    fn commands() -> [Command; 14] {
+        [
+            Command::RouteRequest {
+                many_to_one: ManyToOne::RouteRecordTable,
+                multicast: false,
+                id: 3,
+                destination: BROADCAST_ROUTERS,
+                path_cost: 0,
+                destination_ieee: None,
+            },
+            Command::RouteRequest {
+                many_to_one: ManyToOne::No,
+                multicast: true,
+                id: 4,
+                destination: 0x0007,
+                path_cost: 2,
+                destination_ieee: Some(99),
+            },
+            Command::RouteReply {
+                multicast: false,
+                id: 1,
+                originator: 0x0002,
+                responder: 0x0003,
+                path_cost: 4,
+                originator_ieee: Some(5),
+                responder_ieee: Some(6),
+            },
+            Command::NetworkStatus {
+                status: 0x0b,
+                destination: 0x1234,
+            },
+            Command::Leave {
+                rejoin: true,
+                request: true,
+                remove_children: false,
+            },
+            Command::RouteRecord {
+                relays: Vec::from_slice(&[0x0001, 0x0002]).unwrap(),
+            },
+            Command::RejoinRequest { capability: 0x8e },
+            Command::RejoinResponse {
+                address: 0x4444,
+                status: 0,
+            },
+            Command::LinkStatus {
+                first: true,
+                last: false,
+                entries: Vec::from_slice(&[LinkStatusEntry {
+                    address: 0x0000,
+                    incoming_cost: 1,
+                    outgoing_cost: 7,
+                }])
+                .unwrap(),
+            },
+            Command::NetworkReport {
+                extended_pan_id: 1,
+                pan_ids: Vec::from_slice(&[0x0001, 0x0002, 0x0003]).unwrap(),
+            },
+            Command::NetworkUpdate {
+                extended_pan_id: 1,
+                update_id: 2,
+                pan_id: 0x1a62,
+            },
+            Command::EndDeviceTimeoutRequest {
+                timeout: 8,
+                configuration: 0,
+            },
+            Command::EndDeviceTimeoutResponse {
+                status: 0,
+                parent_information: 3,
+            },
+            Command::LinkPowerDelta {
+                kind: PowerDeltaType::Request,
+                deltas: Vec::from_slice(&[PowerDelta {
+                    address: 0x0001,
+                    delta: -3,
+                }])
+                .unwrap(),
+            },
+        ]
+    }
+
+    #[test]
+    fn secured_frame_round_trips() {
+        let frame = Frame::decode(&SECURED).unwrap();
+        assert_eq!(frame.header.frame_type, FrameType::Data);
+        assert_eq!(frame.header.protocol_version, PROTOCOL_VERSION);
+        assert_eq!(frame.header.discover_route, DiscoverRoute::Enable);
+        assert!(frame.header.security);
+        assert_eq!(
+            (frame.header.destination, frame.header.source),
+            (0x0000, 0x1234)
+        );
+        assert_eq!((frame.header.radius, frame.header.sequence), (30, 0x7a));
+        assert_eq!(frame.header.source_ieee, Some(0x1122_3344_5566_7788));
+        assert_eq!(
+            frame.auxiliary_security_header,
+            Some(AuxiliarySecurityHeader {
+                level: 0,
+                key_identifier: KeyIdentifier::Network(0),
+                frame_counter: 1,
+                source: Some(0x1122_3344_5566_7788),
+            })
+        );
+        assert_eq!(frame.payload, [0xaa, 0xbb]);
+        assert!(matches!(frame.command(), Ok(None)));
+
+        let mut bytes = [0; 64];
+        let len = frame.encode(&mut bytes).unwrap();
+        assert_eq!(bytes[..len], SECURED);
+    }
+
+    #[test]
+    fn optional_fields_round_trip() {
+        let frame = Frame {
+            header: header(),
+            auxiliary_security_header: None,
+            payload: &[1, 2, 3],
+        };
+
+        let mut bytes = [0; 64];
+        let len = frame.encode(&mut bytes).unwrap();
+        assert_eq!(Frame::decode(&bytes[..len]).unwrap(), frame);
+
+        // inter-PAN frames only keep the frame control
+        let frame = Frame {
+            header: Header {
+                frame_type: FrameType::InterPan,
+                ..header()
+            },
+            auxiliary_security_header: None,
+            payload: &[9],
+        };
+        let len = frame.encode(&mut bytes).unwrap();
+        assert_eq!(bytes[..len], [0x4b, 0x00, 9]);
+        let decoded = Frame::decode(&bytes[..len]).unwrap();
+        assert_eq!(decoded.header.frame_type, FrameType::InterPan);
+        assert_eq!(decoded.payload, [9]);
+    }
+
+    #[test]
+    fn commands_round_trip() {
+        let mut bytes = [0; 64];
+        for command in commands() {
+            let len = command.encode(&mut bytes).unwrap();
+            assert_eq!(Command::decode(&bytes[..len]).unwrap(), command);
+        }
+
+        // link status as sent by a coordinator
+        let link_status = [0x08, 0x61, 0x01, 0x00, 0x71];
+        assert_eq!(
+            Command::decode(&link_status).unwrap(),
+            Command::LinkStatus {
+                first: true,
+                last: true,
+                entries: Vec::from_slice(&[LinkStatusEntry {
+                    address: 0x0001,
+                    incoming_cost: 1,
+                    outgoing_cost: 7,
+                }])
+                .unwrap(),
+            }
+        );
+
+        let frame = Frame {
+            header: Header::command(BROADCAST_ROUTERS, 0x0000, 1, 1),
+            auxiliary_security_header: None,
+            payload: &link_status,
+        };
+        let len = frame.encode(&mut bytes).unwrap();
+        assert!(matches!(
+            Frame::decode(&bytes[..len]).unwrap().command(),
+            Ok(Some(Command::LinkStatus { .. }))
+        ));
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        let frame = Frame {
+            header: header(),
+            auxiliary_security_header: Some(AuxiliarySecurityHeader {
+                level: 5,
+                key_identifier: KeyIdentifier::Network(1),
+                frame_counter: 7,
+                source: Some(1),
+            }),
+            payload: &[],
+        };
+        let mut bytes = [0; 64];
+        let len = frame.encode(&mut bytes).unwrap();
+
+        // the headers have no optional trailing fields
+        for len in 0..len {
+            assert!(matches!(
+                Frame::decode(&bytes[..len]),
+                Err(Error::Incomplete)
+            ));
+        }
+        assert!(frame.encode(&mut bytes[..len - 1]).is_err());
+
+        for command in commands() {
+            let len = command.encode(&mut bytes).unwrap();
+            for len in 0..len {
+                assert!(matches!(
+                    Command::decode(&bytes[..len]),
+                    Err(Error::Incomplete)
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        // reserved frame type and route discovery
+        assert!(matches!(
+            Frame::decode(&[0x0a, 0x00, 0, 0, 0, 0, 1, 1]),
+            Err(Error::BadInput)
+        ));
+        assert!(matches!(
+            Frame::decode(&[0x88, 0x00, 0, 0, 0, 0, 1, 1]),
+            Err(Error::BadInput)
+        ));
+        // reserved multicast mode
+        assert!(matches!(
+            Frame::decode(&[0x08, 0x01, 0, 0, 0, 0, 1, 1, 0x02]),
+            Err(Error::BadInput)
+        ));
+        // more relays than a source route holds
+        let mut route = std::vec![0x08, 0x04, 0, 0, 0, 0, 1, 1, 17, 0];
+        route.resize(route.len() + 17 * 2, 0);
+        assert!(matches!(Frame::decode(&route), Err(Error::BadInput)));
+
+        // unknown command, many-to-one, report type and power delta type
+        assert!(matches!(Command::decode(&[0x42]), Err(Error::BadInput)));
+        assert!(matches!(
+            Command::decode(&[0x01, 0x18, 1, 0, 0, 0]),
+            Err(Error::BadInput)
+        ));
+        assert!(matches!(
+            Command::decode(&[0x09, 0x20, 0, 0, 0, 0, 0, 0, 0, 0]),
+            Err(Error::BadInput)
+        ));
+        assert!(matches!(
+            Command::decode(&[0x0d, 0x03, 0]),
+            Err(Error::BadInput)
+        ));
+        // a PAN ID update with other than one PAN ID
+        assert!(matches!(
+            Command::decode(&[0x0a, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0]),
+            Err(Error::BadInput)
+        ));
+        // more entries than announced
+        assert!(matches!(
+            Command::decode(&[0x05, 3, 1, 0]),
+            Err(Error::Incomplete)
+        ));
+    }
+}
@@ -0,0 +1,57 @@
+//! Basic cluster, with the identification of the device
+
+/// Cluster identifier
+pub const ID: u16 = 0x0000;
+
+/// Attribute identifiers
+pub mod attributes {
+    /// `uint8`, revision of the ZCL the device implements
+    pub const ZCL_VERSION: u16 = 0x0000;
+    /// `uint8`
+    pub const APPLICATION_VERSION: u16 = 0x0001;
+    /// `uint8`
+    pub const STACK_VERSION: u16 = 0x0002;
+    /// `uint8`
+    pub const HW_VERSION: u16 = 0x0003;
+    /// `string`, up to 32 characters
+    pub const MANUFACTURER_NAME: u16 = 0x0004;
+    /// `string`, up to 32 characters
+    pub const MODEL_IDENTIFIER: u16 = 0x0005;
+    /// `string`, up to 16 characters, starting with the date as `YYYYMMDD`
+    pub const DATE_CODE: u16 = 0x0006;
+    /// `enum8`, see [`power_source`](super::power_source)
+    pub const POWER_SOURCE: u16 = 0x0007;
+    /// `string`, up to 16 characters
+    pub const LOCATION_DESCRIPTION: u16 = 0x0010;
+    /// `enum8`
+    pub const PHYSICAL_ENVIRONMENT: u16 = 0x0011;
+    /// `bool`
+    pub const DEVICE_ENABLED: u16 = 0x0012;
+    /// `map8`
+    pub const ALARM_MASK: u16 = 0x0013;
+    /// `map8`
+    pub const DISABLE_LOCAL_CONFIG: u16 = 0x0014;
+    /// `string`, up to 16 characters
+    pub const SW_BUILD_ID: u16 = 0x4000;
+}
+
+/// Values of the power source attribute
+///
+/// Bit 7 is set if the device also has a battery backup.
+pub mod power_source {
+    pub const UNKNOWN: u8 = 0x00;
+    pub const MAINS_SINGLE_PHASE: u8 = 0x01;
+    pub const MAINS_THREE_PHASE: u8 = 0x02;
+    pub const BATTERY: u8 = 0x03;
+    pub const DC_SOURCE: u8 = 0x04;
+    pub const EMERGENCY_MAINS_CONSTANTLY_POWERED: u8 = 0x05;
+    pub const EMERGENCY_MAINS_AND_TRANSFER_SWITCH: u8 = 0x06;
+    pub const BATTERY_BACKUP: u8 = 0x80;
+}
+
+/// Command identifiers
+pub mod commands {
+    /// Reset all the attributes of all the clusters of the endpoint to their
+    /// default values, without payload
+    pub const RESET_TO_FACTORY_DEFAULTS: u8 = 0x00;
+}
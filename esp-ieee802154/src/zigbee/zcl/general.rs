@@ -0,0 +1,844 @@
+//! General commands, common to all clusters
+
+use core::fmt;
+
+use byte::{BytesExt, LE};
+
+use super::types::read_data_type;
+use super::{status, DataType, Value};
+use crate::Error;
+
+pub const READ_ATTRIBUTES: u8 = 0x00;
+pub const READ_ATTRIBUTES_RESPONSE: u8 = 0x01;
+pub const WRITE_ATTRIBUTES: u8 = 0x02;
+pub const WRITE_ATTRIBUTES_UNDIVIDED: u8 = 0x03;
+pub const WRITE_ATTRIBUTES_RESPONSE: u8 = 0x04;
+pub const WRITE_ATTRIBUTES_NO_RESPONSE: u8 = 0x05;
+pub const CONFIGURE_REPORTING: u8 = 0x06;
+pub const CONFIGURE_REPORTING_RESPONSE: u8 = 0x07;
+pub const REPORT_ATTRIBUTES: u8 = 0x0a;
+pub const DEFAULT_RESPONSE: u8 = 0x0b;
+pub const DISCOVER_ATTRIBUTES: u8 = 0x0c;
+pub const DISCOVER_ATTRIBUTES_RESPONSE: u8 = 0x0d;
+pub const DISCOVER_COMMANDS_RECEIVED: u8 = 0x11;
+pub const DISCOVER_COMMANDS_RECEIVED_RESPONSE: u8 = 0x12;
+pub const DISCOVER_COMMANDS_GENERATED: u8 = 0x13;
+pub const DISCOVER_COMMANDS_GENERATED_RESPONSE: u8 = 0x14;
+pub const DISCOVER_ATTRIBUTES_EXTENDED: u8 = 0x15;
+pub const DISCOVER_ATTRIBUTES_EXTENDED_RESPONSE: u8 = 0x16;
+
+/// Record of a list in the payload of a command
+pub trait Record<'a>: Sized + Copy {
+    fn read(bytes: &'a [u8], offset: &mut usize) -> byte::Result<Self>;
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()>;
+}
+
+/// Attribute or command identifier
+impl Record<'_> for u8 {
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        bytes.read(offset)
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        bytes.write(offset, *self)
+    }
+}
+
+/// Attribute identifier
+impl Record<'_> for u16 {
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        bytes.read_with(offset, LE)
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        bytes.write_with(offset, *self, LE)
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Inner<'a, T> {
+    /// Records decoded while iterating, which were checked when decoding the
+    /// command
+    Encoded(&'a [u8]),
+    Slice(&'a [T]),
+}
+
+/// List of records, either received, or to send
+///
+/// Received records are decoded while iterating over them, so that they don't
+/// need to be stored. Records to send are created from a slice.
+#[derive(Clone, Copy)]
+pub struct Records<'a, T>(Inner<'a, T>);
+
+impl<'a, T: Record<'a>> Records<'a, T> {
+    pub fn iter(&self) -> impl Iterator<Item = T> + 'a {
+        let mut encoded = match self.0 {
+            Inner::Encoded(bytes) => bytes,
+            Inner::Slice(_) => &[],
+        };
+        let mut slice = match self.0 {
+            Inner::Encoded(_) => <&[T]>::default().iter(),
+            Inner::Slice(slice) => slice.iter(),
+        };
+
+        core::iter::from_fn(move || {
+            if let Some(record) = slice.next() {
+                return Some(*record);
+            }
+            if encoded.is_empty() {
+                return None;
+            }
+
+            let offset = &mut 0;
+            let record = T::read(encoded, offset).ok()?;
+            encoded = &encoded[*offset..];
+
+            Some(record)
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self.0 {
+            Inner::Encoded(bytes) => bytes.is_empty(),
+            Inner::Slice(slice) => slice.is_empty(),
+        }
+    }
+
+    /// Check the records encoded in `bytes`
+    fn decode(bytes: &'a [u8]) -> byte::Result<Self> {
+        let offset = &mut 0;
+        while *offset < bytes.len() {
+            T::read(bytes, offset)?;
+        }
+
+        Ok(Self(Inner::Encoded(bytes)))
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        match self.0 {
+            Inner::Encoded(encoded) => bytes.write(offset, encoded),
+            Inner::Slice(slice) => slice
+                .iter()
+                .try_for_each(|record| record.write(bytes, offset)),
+        }
+    }
+}
+
+impl<'a, T> From<&'a [T]> for Records<'a, T> {
+    fn from(slice: &'a [T]) -> Self {
+        Self(Inner::Slice(slice))
+    }
+}
+
+impl<'a, T: Record<'a>> PartialEq for Records<'a, T>
+where
+    T: PartialEq,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.iter().eq(other.iter())
+    }
+}
+
+impl<'a, T: Record<'a>> fmt::Debug for Records<'a, T>
+where
+    T: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+/// Attribute of a read attributes response
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReadAttributeStatus<'a> {
+    pub attribute: u16,
+    /// The value of the attribute, or the status explaining why it couldn't be
+    /// read
+    pub value: Result<Value<'a>, u8>,
+}
+
+impl<'a> Record<'a> for ReadAttributeStatus<'a> {
+    fn read(bytes: &'a [u8], offset: &mut usize) -> byte::Result<Self> {
+        let attribute = bytes.read_with(offset, LE)?;
+        let value = match bytes.read::<u8>(offset)? {
+            status::SUCCESS => Ok(bytes.read_with(offset, ())?),
+            status => Err(status),
+        };
+
+        Ok(Self { attribute, value })
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        bytes.write_with(offset, self.attribute, LE)?;
+        match self.value {
+            Ok(value) => {
+                bytes.write(offset, status::SUCCESS)?;
+                bytes.write(offset, value)
+            }
+            Err(status) => bytes.write(offset, status),
+        }
+    }
+}
+
+/// Attribute and its value, written or reported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttributeRecord<'a> {
+    pub attribute: u16,
+    pub value: Value<'a>,
+}
+
+impl<'a> Record<'a> for AttributeRecord<'a> {
+    fn read(bytes: &'a [u8], offset: &mut usize) -> byte::Result<Self> {
+        Ok(Self {
+            attribute: bytes.read_with(offset, LE)?,
+            value: bytes.read_with(offset, ())?,
+        })
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        bytes.write_with(offset, self.attribute, LE)?;
+        bytes.write(offset, self.value)
+    }
+}
+
+/// Attribute which couldn't be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WriteAttributeStatus {
+    pub status: u8,
+    pub attribute: u16,
+}
+
+impl Record<'_> for WriteAttributeStatus {
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        Ok(Self {
+            status: bytes.read(offset)?,
+            attribute: bytes.read_with(offset, LE)?,
+        })
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        bytes.write(offset, self.status)?;
+        bytes.write_with(offset, self.attribute, LE)
+    }
+}
+
+/// Direction of the reports of an attribute
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportDirection {
+    /// Reports sent by the receiver of the configuration
+    Reported,
+    /// Reports the receiver of the configuration expects
+    Received,
+}
+
+impl ReportDirection {
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        match bytes.read::<u8>(offset)? {
+            0 => Ok(Self::Reported),
+            1 => Ok(Self::Received),
+            _ => Err(byte::Error::BadInput {
+                err: "Unknown report direction",
+            }),
+        }
+    }
+}
+
+/// Reporting configuration of an attribute
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportingConfiguration<'a> {
+    /// Configuration of the reports sent by the receiver
+    Reported {
+        attribute: u16,
+        data_type: DataType,
+        /// Minimum interval between reports, in seconds
+        min_interval: u16,
+        /// Maximum interval between reports, in seconds, 0xffff to disable
+        /// the reports
+        max_interval: u16,
+        /// Change of the value which triggers a report, present for analog
+        /// data types only
+        reportable_change: Option<Value<'a>>,
+    },
+    /// Configuration of the reports the receiver expects
+    Received {
+        attribute: u16,
+        /// Maximum interval between reports, in seconds, 0 to disable the
+        /// timeout
+        timeout: u16,
+    },
+}
+
+impl<'a> Record<'a> for ReportingConfiguration<'a> {
+    fn read(bytes: &'a [u8], offset: &mut usize) -> byte::Result<Self> {
+        let direction = ReportDirection::read(bytes, offset)?;
+        let attribute = bytes.read_with(offset, LE)?;
+
+        match direction {
+            ReportDirection::Reported => {
+                let data_type = read_data_type(bytes, offset)?;
+                let min_interval = bytes.read_with(offset, LE)?;
+                let max_interval = bytes.read_with(offset, LE)?;
+                let reportable_change = if data_type.is_analog() {
+                    Some(bytes.read_with(offset, data_type)?)
+                } else {
+                    None
+                };
+
+                Ok(Self::Reported {
+                    attribute,
+                    data_type,
+                    min_interval,
+                    max_interval,
+                    reportable_change,
+                })
+            }
+            ReportDirection::Received => Ok(Self::Received {
+                attribute,
+                timeout: bytes.read_with(offset, LE)?,
+            }),
+        }
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        match *self {
+            Self::Reported {
+                attribute,
+                data_type,
+                min_interval,
+                max_interval,
+                reportable_change,
+            } => {
+                bytes.write(offset, 0u8)?;
+                bytes.write_with(offset, attribute, LE)?;
+                bytes.write(offset, data_type as u8)?;
+                bytes.write_with(offset, min_interval, LE)?;
+                bytes.write_with(offset, max_interval, LE)?;
+
+                if data_type.is_analog() {
+                    let change = reportable_change.ok_or(byte::Error::BadInput {
+                        err: "Missing reportable change",
+                    })?;
+                    if change.data_type() != data_type {
+                        return Err(byte::Error::BadInput {
+                            err: "Mismatched reportable change",
+                        });
+                    }
+                    change.write_untyped(bytes, offset)?;
+                }
+
+                Ok(())
+            }
+            Self::Received { attribute, timeout } => {
+                bytes.write(offset, 1u8)?;
+                bytes.write_with(offset, attribute, LE)?;
+                bytes.write_with(offset, timeout, LE)
+            }
+        }
+    }
+}
+
+/// Reporting configuration which couldn't be applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConfigureReportingStatus {
+    pub status: u8,
+    pub direction: ReportDirection,
+    pub attribute: u16,
+}
+
+impl Record<'_> for ConfigureReportingStatus {
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        Ok(Self {
+            status: bytes.read(offset)?,
+            direction: ReportDirection::read(bytes, offset)?,
+            attribute: bytes.read_with(offset, LE)?,
+        })
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        bytes.write(offset, self.status)?;
+        bytes.write(offset, self.direction as u8)?;
+        bytes.write_with(offset, self.attribute, LE)
+    }
+}
+
+/// Attribute of a discover attributes response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscoveredAttribute {
+    pub attribute: u16,
+    pub data_type: DataType,
+}
+
+impl Record<'_> for DiscoveredAttribute {
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        Ok(Self {
+            attribute: bytes.read_with(offset, LE)?,
+            data_type: read_data_type(bytes, offset)?,
+        })
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        bytes.write_with(offset, self.attribute, LE)?;
+        bytes.write(offset, self.data_type as u8)
+    }
+}
+
+/// Attribute of a discover attributes extended response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiscoveredAttributeExtended {
+    pub attribute: u16,
+    pub data_type: DataType,
+    /// Bit 0 is set if the attribute is readable, bit 1 if writable and bit
+    /// 2 if reportable
+    pub access_control: u8,
+}
+
+impl Record<'_> for DiscoveredAttributeExtended {
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        Ok(Self {
+            attribute: bytes.read_with(offset, LE)?,
+            data_type: read_data_type(bytes, offset)?,
+            access_control: bytes.read(offset)?,
+        })
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        bytes.write_with(offset, self.attribute, LE)?;
+        bytes.write(offset, self.data_type as u8)?;
+        bytes.write(offset, self.access_control)
+    }
+}
+
+/// General command
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command<'a> {
+    ReadAttributes(Records<'a, u16>),
+    ReadAttributesResponse(Records<'a, ReadAttributeStatus<'a>>),
+    WriteAttributes(Records<'a, AttributeRecord<'a>>),
+    /// Write the attributes only if all of them can be written
+    WriteAttributesUndivided(Records<'a, AttributeRecord<'a>>),
+    /// The attributes which couldn't be written, empty if all were
+    WriteAttributesResponse(Records<'a, WriteAttributeStatus>),
+    WriteAttributesNoResponse(Records<'a, AttributeRecord<'a>>),
+    ConfigureReporting(Records<'a, ReportingConfiguration<'a>>),
+    /// The configurations which couldn't be applied, empty if all were
+    ConfigureReportingResponse(Records<'a, ConfigureReportingStatus>),
+    ReportAttributes(Records<'a, AttributeRecord<'a>>),
+    /// Response to a command which has no other response, when it failed or
+    /// the default response wasn't disabled
+    DefaultResponse {
+        command: u8,
+        status: u8,
+    },
+    DiscoverAttributes {
+        start: u16,
+        max: u8,
+    },
+    DiscoverAttributesResponse {
+        /// Whether there are no more attributes to discover
+        complete: bool,
+        attributes: Records<'a, DiscoveredAttribute>,
+    },
+    DiscoverCommandsReceived {
+        start: u8,
+        max: u8,
+    },
+    DiscoverCommandsReceivedResponse {
+        complete: bool,
+        commands: Records<'a, u8>,
+    },
+    DiscoverCommandsGenerated {
+        start: u8,
+        max: u8,
+    },
+    DiscoverCommandsGeneratedResponse {
+        complete: bool,
+        commands: Records<'a, u8>,
+    },
+    DiscoverAttributesExtended {
+        start: u16,
+        max: u8,
+    },
+    DiscoverAttributesExtendedResponse {
+        complete: bool,
+        attributes: Records<'a, DiscoveredAttributeExtended>,
+    },
+}
+
+impl<'a> Command<'a> {
+    /// The command identifier, of the ZCL header
+    pub fn id(&self) -> u8 {
+        match self {
+            Command::ReadAttributes(_) => READ_ATTRIBUTES,
+            Command::ReadAttributesResponse(_) => READ_ATTRIBUTES_RESPONSE,
+            Command::WriteAttributes(_) => WRITE_ATTRIBUTES,
+            Command::WriteAttributesUndivided(_) => WRITE_ATTRIBUTES_UNDIVIDED,
+            Command::WriteAttributesResponse(_) => WRITE_ATTRIBUTES_RESPONSE,
+            Command::WriteAttributesNoResponse(_) => WRITE_ATTRIBUTES_NO_RESPONSE,
+            Command::ConfigureReporting(_) => CONFIGURE_REPORTING,
+            Command::ConfigureReportingResponse(_) => CONFIGURE_REPORTING_RESPONSE,
+            Command::ReportAttributes(_) => REPORT_ATTRIBUTES,
+            Command::DefaultResponse { .. } => DEFAULT_RESPONSE,
+            Command::DiscoverAttributes { .. } => DISCOVER_ATTRIBUTES,
+            Command::DiscoverAttributesResponse { .. } => DISCOVER_ATTRIBUTES_RESPONSE,
+            Command::DiscoverCommandsReceived { .. } => DISCOVER_COMMANDS_RECEIVED,
+            Command::DiscoverCommandsReceivedResponse { .. } => DISCOVER_COMMANDS_RECEIVED_RESPONSE,
+            Command::DiscoverCommandsGenerated { .. } => DISCOVER_COMMANDS_GENERATED,
+            Command::DiscoverCommandsGeneratedResponse { .. } => {
+                DISCOVER_COMMANDS_GENERATED_RESPONSE
+            }
+            Command::DiscoverAttributesExtended { .. } => DISCOVER_ATTRIBUTES_EXTENDED,
+            Command::DiscoverAttributesExtendedResponse { .. } => {
+                DISCOVER_ATTRIBUTES_EXTENDED_RESPONSE
+            }
+        }
+    }
+
+    /// Decode the payload of the general command `id`
+    ///
+    /// Unknown commands are reported as [`Error::BadInput`], to which the
+    /// receiver answers with [`status::UNSUP_COMMAND`].
+    pub fn decode(id: u8, payload: &'a [u8]) -> Result<Self, Error> {
+        let offset = &mut 0;
+
+        let command = match id {
+            READ_ATTRIBUTES => Command::ReadAttributes(Records::decode(payload)?),
+            READ_ATTRIBUTES_RESPONSE => Command::ReadAttributesResponse(Records::decode(payload)?),
+            WRITE_ATTRIBUTES => Command::WriteAttributes(Records::decode(payload)?),
+            WRITE_ATTRIBUTES_UNDIVIDED => {
+                Command::WriteAttributesUndivided(Records::decode(payload)?)
+            }
+            // a single success status if all attributes were written
+            WRITE_ATTRIBUTES_RESPONSE => {
+                Command::WriteAttributesResponse(Records::decode(all_succeeded(payload))?)
+            }
+            WRITE_ATTRIBUTES_NO_RESPONSE => {
+                Command::WriteAttributesNoResponse(Records::decode(payload)?)
+            }
+            CONFIGURE_REPORTING => Command::ConfigureReporting(Records::decode(payload)?),
+            CONFIGURE_REPORTING_RESPONSE => {
+                Command::ConfigureReportingResponse(Records::decode(all_succeeded(payload))?)
+            }
+            REPORT_ATTRIBUTES => Command::ReportAttributes(Records::decode(payload)?),
+            DEFAULT_RESPONSE => Command::DefaultResponse {
+                command: payload.read(offset)?,
+                status: payload.read(offset)?,
+            },
+            DISCOVER_ATTRIBUTES => Command::DiscoverAttributes {
+                start: payload.read_with(offset, LE)?,
+                max: payload.read(offset)?,
+            },
+            DISCOVER_ATTRIBUTES_RESPONSE => Command::DiscoverAttributesResponse {
+                complete: payload.read::<u8>(offset)? != 0,
+                attributes: Records::decode(&payload[*offset..])?,
+            },
+            DISCOVER_COMMANDS_RECEIVED => Command::DiscoverCommandsReceived {
+                start: payload.read(offset)?,
+                max: payload.read(offset)?,
+            },
+            DISCOVER_COMMANDS_RECEIVED_RESPONSE => Command::DiscoverCommandsReceivedResponse {
+                complete: payload.read::<u8>(offset)? != 0,
+                commands: Records::decode(&payload[*offset..])?,
+            },
+            DISCOVER_COMMANDS_GENERATED => Command::DiscoverCommandsGenerated {
+                start: payload.read(offset)?,
+                max: payload.read(offset)?,
+            },
+            DISCOVER_COMMANDS_GENERATED_RESPONSE => Command::DiscoverCommandsGeneratedResponse {
+                complete: payload.read::<u8>(offset)? != 0,
+                commands: Records::decode(&payload[*offset..])?,
+            },
+            DISCOVER_ATTRIBUTES_EXTENDED => Command::DiscoverAttributesExtended {
+                start: payload.read_with(offset, LE)?,
+                max: payload.read(offset)?,
+            },
+            DISCOVER_ATTRIBUTES_EXTENDED_RESPONSE => Command::DiscoverAttributesExtendedResponse {
+                complete: payload.read::<u8>(offset)? != 0,
+                attributes: Records::decode(&payload[*offset..])?,
+            },
+            _ => return Err(Error::BadInput),
+        };
+
+        Ok(command)
+    }
+
+    /// Encode the payload of the command into `bytes`, returns its length
+    pub fn encode(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        let offset = &mut 0;
+
+        match self {
+            Command::ReadAttributes(records) => records.write(bytes, offset)?,
+            Command::ReadAttributesResponse(records) => records.write(bytes, offset)?,
+            Command::WriteAttributes(records)
+            | Command::WriteAttributesUndivided(records)
+            | Command::WriteAttributesNoResponse(records)
+            | Command::ReportAttributes(records) => records.write(bytes, offset)?,
+            Command::WriteAttributesResponse(records) => {
+                if records.is_empty() {
+                    bytes.write(offset, status::SUCCESS)?;
+                } else {
+                    records.write(bytes, offset)?;
+                }
+            }
+            Command::ConfigureReporting(records) => records.write(bytes, offset)?,
+            Command::ConfigureReportingResponse(records) => {
+                if records.is_empty() {
+                    bytes.write(offset, status::SUCCESS)?;
+                } else {
+                    records.write(bytes, offset)?;
+                }
+            }
+            Command::DefaultResponse { command, status } => {
+                bytes.write(offset, *command)?;
+                bytes.write(offset, *status)?;
+            }
+            Command::DiscoverAttributes { start, max }
+            | Command::DiscoverAttributesExtended { start, max } => {
+                bytes.write_with(offset, *start, LE)?;
+                bytes.write(offset, *max)?;
+            }
+            Command::DiscoverCommandsReceived { start, max }
+            | Command::DiscoverCommandsGenerated { start, max } => {
+                bytes.write(offset, *start)?;
+                bytes.write(offset, *max)?;
+            }
+            Command::DiscoverAttributesResponse {
+                complete,
+                attributes,
+            } => {
+                bytes.write(offset, *complete as u8)?;
+                attributes.write(bytes, offset)?;
+            }
+            Command::DiscoverCommandsReceivedResponse { complete, commands }
+            | Command::DiscoverCommandsGeneratedResponse { complete, commands } => {
+                bytes.write(offset, *complete as u8)?;
+                commands.write(bytes, offset)?;
+            }
+            Command::DiscoverAttributesExtendedResponse {
+                complete,
+                attributes,
+            } => {
+                bytes.write(offset, *complete as u8)?;
+                attributes.write(bytes, offset)?;
+            }
+        }
+
+        Ok(*offset)
+    }
+}
+
+/// The records of a response which is a single success status if all the
+/// records succeeded
+fn all_succeeded(payload: &[u8]) -> &[u8] {
+    if payload == [status::SUCCESS] {
+        &[]
+    } else {
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::zigbee::zcl::{encode, Direction, Frame};
+
+    /// Decode the general command of the ZCL frame `bytes`, checking that it
+    /// encodes to the same bytes
+    fn round_trip(bytes: &[u8]) -> Command<'_> {
+        let frame = Frame::decode(bytes).unwrap();
+        let command = frame.general_command().unwrap().unwrap();
+        assert_eq!(command.id(), frame.header.command);
+
+        let mut encoded = [0; 128];
+        let len = encode(&frame.header, &mut encoded, |bytes| command.encode(bytes)).unwrap();
+        assert_eq!(encoded[..len], *bytes);
+
+        command
+    }
+
+    #[test]
+    fn read_attributes_round_trip() {
+        let command = round_trip(&[0x00, 0x01, 0x00, 0x04, 0x00, 0x05, 0x00]);
+        let attributes = [0x0004, 0x0005];
+        assert_eq!(command, Command::ReadAttributes(attributes[..].into()));
+
+        let mut bytes = [0; 8];
+        let len = Command::ReadAttributes(attributes[..].into())
+            .encode(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes[..len], [0x04, 0x00, 0x05, 0x00]);
+    }
+
+    #[test]
+    fn read_attributes_response_round_trips() {
+        let bytes = [
+            0x18, 0x01, 0x01, 0x04, 0x00, 0x00, 0x42, 0x05, b'E', b's', b'p', b'r', b'e', 0x05,
+            0x00, 0x86,
+        ];
+        let frame = Frame::decode(&bytes).unwrap();
+        assert_eq!(frame.header.direction, Direction::ServerToClient);
+        assert!(frame.header.disable_default_response);
+
+        let Command::ReadAttributesResponse(records) = round_trip(&bytes) else {
+            panic!("not a read attributes response");
+        };
+        assert_eq!(
+            records.iter().collect::<std::vec::Vec<_>>(),
+            [
+                ReadAttributeStatus {
+                    attribute: 0x0004,
+                    value: Ok(Value::CharString("Espre")),
+                },
+                ReadAttributeStatus {
+                    attribute: 0x0005,
+                    value: Err(status::UNSUPPORTED_ATTRIBUTE),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn reporting_round_trips() {
+        let Command::ReportAttributes(records) =
+            round_trip(&[0x18, 0x02, 0x0a, 0x00, 0x00, 0x29, 0x34, 0x08])
+        else {
+            panic!("not a report");
+        };
+        let record = records.iter().next().unwrap();
+        assert_eq!(record.value, Value::i16(2100));
+
+        let Command::ConfigureReporting(records) = round_trip(&[
+            0x00, 0x03, 0x06, 0x00, 0x00, 0x00, 0x29, 0x0a, 0x00, 0x2c, 0x01, 0x32, 0x00, 0x00,
+            0x06, 0x00, 0x10, 0x00, 0x00, 0x00, 0x00, 0x01, 0x01, 0x00, 0x3c, 0x00,
+        ]) else {
+            panic!("not a reporting configuration");
+        };
+        assert_eq!(
+            records.iter().collect::<std::vec::Vec<_>>(),
+            [
+                ReportingConfiguration::Reported {
+                    attribute: 0x0000,
+                    data_type: DataType::Int16,
+                    min_interval: 10,
+                    max_interval: 300,
+                    reportable_change: Some(Value::i16(50)),
+                },
+                ReportingConfiguration::Reported {
+                    attribute: 0x0006,
+                    data_type: DataType::Bool,
+                    min_interval: 0,
+                    max_interval: 0,
+                    reportable_change: None,
+                },
+                ReportingConfiguration::Received {
+                    attribute: 0x0001,
+                    timeout: 60,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn responses_round_trip() {
+        // all configurations and writes succeeded
+        let Command::ConfigureReportingResponse(records) = round_trip(&[0x18, 0x03, 0x07, 0x00])
+        else {
+            panic!("not a configure reporting response");
+        };
+        assert!(records.is_empty());
+        let Command::ConfigureReportingResponse(records) =
+            round_trip(&[0x18, 0x03, 0x07, 0x8c, 0x00, 0x05, 0x00])
+        else {
+            panic!("not a configure reporting response");
+        };
+        assert_eq!(records.iter().next().unwrap().attribute, 0x0005);
+        round_trip(&[0x18, 0x03, 0x04, 0x00]);
+        round_trip(&[0x18, 0x03, 0x04, 0x88, 0x04, 0x00]);
+
+        assert_eq!(
+            round_trip(&[0x18, 0x04, 0x0b, 0x01, 0x00]),
+            Command::DefaultResponse {
+                command: 0x01,
+                status: status::SUCCESS,
+            }
+        );
+    }
+
+    #[test]
+    fn discovery_round_trips() {
+        assert_eq!(
+            round_trip(&[0x04, 0x34, 0x12, 0x05, 0x0c, 0x00, 0x00, 0x10]),
+            Command::DiscoverAttributes { start: 0, max: 16 }
+        );
+
+        let Command::DiscoverAttributesResponse {
+            complete,
+            attributes,
+        } = round_trip(&[0x18, 0x05, 0x0d, 0x01, 0x00, 0x00, 0x10, 0x00, 0x40, 0x21])
+        else {
+            panic!("not a discover attributes response");
+        };
+        assert!(complete);
+        assert_eq!(
+            attributes.iter().collect::<std::vec::Vec<_>>(),
+            [
+                DiscoveredAttribute {
+                    attribute: 0x0000,
+                    data_type: DataType::Bool,
+                },
+                DiscoveredAttribute {
+                    attribute: 0x4000,
+                    data_type: DataType::Uint16,
+                },
+            ]
+        );
+
+        round_trip(&[0x00, 0x06, 0x11, 0x00, 0x10]);
+        round_trip(&[0x18, 0x06, 0x12, 0x01, 0x00, 0x01, 0x02]);
+        round_trip(&[0x18, 0x06, 0x16, 0x00, 0x00, 0x00, 0x10, 0x05]);
+    }
+
+    #[test]
+    fn malformed_commands_are_rejected() {
+        assert!(matches!(Command::decode(0x30, &[]), Err(Error::BadInput)));
+
+        // truncated records and values
+        assert!(matches!(
+            Command::decode(READ_ATTRIBUTES, &[0x04, 0x00, 0x05]),
+            Err(Error::Incomplete)
+        ));
+        assert!(matches!(
+            Command::decode(REPORT_ATTRIBUTES, &[0x00, 0x00, 0x21, 0x01]),
+            Err(Error::Incomplete)
+        ));
+        assert!(matches!(
+            Command::decode(DEFAULT_RESPONSE, &[0x01]),
+            Err(Error::Incomplete)
+        ));
+        assert!(matches!(
+            Command::decode(DISCOVER_ATTRIBUTES_RESPONSE, &[]),
+            Err(Error::Incomplete)
+        ));
+
+        // unknown data type
+        assert!(matches!(
+            Command::decode(REPORT_ATTRIBUTES, &[0x00, 0x00, 0x07, 0x01]),
+            Err(Error::BadInput)
+        ));
+    }
+
+    #[test]
+    fn analog_reporting_needs_a_reportable_change() {
+        let configuration = [ReportingConfiguration::Reported {
+            attribute: 0x0000,
+            data_type: DataType::Int16,
+            min_interval: 0,
+            max_interval: 0,
+            reportable_change: None,
+        }];
+
+        assert!(Command::ConfigureReporting(configuration[..].into())
+            .encode(&mut [0; 32])
+            .is_err());
+    }
+}
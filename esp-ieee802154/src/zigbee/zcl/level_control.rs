@@ -0,0 +1,310 @@
+//! Level control cluster
+
+use byte::{BytesExt, LE};
+
+use crate::Error;
+
+/// Cluster identifier
+pub const ID: u16 = 0x0008;
+
+/// Attribute identifiers
+pub mod attributes {
+    /// `uint8`
+    pub const CURRENT_LEVEL: u16 = 0x0000;
+    /// `uint16`, in tenths of a second
+    pub const REMAINING_TIME: u16 = 0x0001;
+    /// `uint8`
+    pub const MIN_LEVEL: u16 = 0x0002;
+    /// `uint8`
+    pub const MAX_LEVEL: u16 = 0x0003;
+    /// `map8`
+    pub const OPTIONS: u16 = 0x000f;
+    /// `uint16`, in tenths of a second
+    pub const ON_OFF_TRANSITION_TIME: u16 = 0x0010;
+    /// `uint8`
+    pub const ON_LEVEL: u16 = 0x0011;
+    /// `uint16`, in tenths of a second
+    pub const ON_TRANSITION_TIME: u16 = 0x0012;
+    /// `uint16`, in tenths of a second
+    pub const OFF_TRANSITION_TIME: u16 = 0x0013;
+    /// `uint8`, in units per second
+    pub const DEFAULT_MOVE_RATE: u16 = 0x0014;
+    /// `uint8`
+    pub const START_UP_CURRENT_LEVEL: u16 = 0x4000;
+}
+
+/// Command identifiers
+///
+/// The commands with on/off also switch the on/off cluster of the endpoint.
+pub mod commands {
+    pub const MOVE_TO_LEVEL: u8 = 0x00;
+    pub const MOVE: u8 = 0x01;
+    pub const STEP: u8 = 0x02;
+    pub const STOP: u8 = 0x03;
+    pub const MOVE_TO_LEVEL_WITH_ON_OFF: u8 = 0x04;
+    pub const MOVE_WITH_ON_OFF: u8 = 0x05;
+    pub const STEP_WITH_ON_OFF: u8 = 0x06;
+    pub const STOP_WITH_ON_OFF: u8 = 0x07;
+}
+
+/// Direction of a move or step
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Up,
+    Down,
+}
+
+impl Mode {
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        match bytes.read::<u8>(offset)? {
+            0 => Ok(Mode::Up),
+            1 => Ok(Mode::Down),
+            _ => Err(byte::Error::BadInput {
+                err: "Unknown mode",
+            }),
+        }
+    }
+}
+
+/// Bits of the options attribute the command overrides
+///
+/// Senders implementing revisions of the ZCL before 6 don't send the options,
+/// which are then decoded as 0, overriding no bits.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Options {
+    pub mask: u8,
+    pub overrides: u8,
+}
+
+impl Options {
+    fn read(bytes: &[u8], offset: &mut usize) -> byte::Result<Self> {
+        if *offset == bytes.len() {
+            return Ok(Self::default());
+        }
+
+        Ok(Self {
+            mask: bytes.read(offset)?,
+            overrides: bytes.read(offset)?,
+        })
+    }
+
+    fn write(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        bytes.write(offset, self.mask)?;
+        bytes.write(offset, self.overrides)
+    }
+}
+
+/// Command of the cluster, sent by clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    MoveToLevel {
+        with_on_off: bool,
+        level: u8,
+        /// In tenths of a second, 0xffff for the on/off transition time
+        transition_time: u16,
+        options: Options,
+    },
+    Move {
+        with_on_off: bool,
+        mode: Mode,
+        /// In units per second, 0xff for the default move rate
+        rate: u8,
+        options: Options,
+    },
+    Step {
+        with_on_off: bool,
+        mode: Mode,
+        step: u8,
+        /// In tenths of a second, 0xffff to step as fast as possible
+        transition_time: u16,
+        options: Options,
+    },
+    Stop {
+        with_on_off: bool,
+        options: Options,
+    },
+}
+
+impl Command {
+    /// The command identifier, of the ZCL header
+    pub fn id(&self) -> u8 {
+        let (id, with_on_off) = match *self {
+            Command::MoveToLevel { with_on_off, .. } => (commands::MOVE_TO_LEVEL, with_on_off),
+            Command::Move { with_on_off, .. } => (commands::MOVE, with_on_off),
+            Command::Step { with_on_off, .. } => (commands::STEP, with_on_off),
+            Command::Stop { with_on_off, .. } => (commands::STOP, with_on_off),
+        };
+
+        // the commands with on/off follow the ones without
+        if with_on_off {
+            id + commands::MOVE_TO_LEVEL_WITH_ON_OFF
+        } else {
+            id
+        }
+    }
+
+    /// Decode the payload of the command `id`
+    pub fn decode(id: u8, payload: &[u8]) -> Result<Self, Error> {
+        let offset = &mut 0;
+        let with_on_off = id >= commands::MOVE_TO_LEVEL_WITH_ON_OFF;
+
+        let command = match id {
+            commands::MOVE_TO_LEVEL | commands::MOVE_TO_LEVEL_WITH_ON_OFF => Command::MoveToLevel {
+                with_on_off,
+                level: payload.read(offset)?,
+                transition_time: payload.read_with(offset, LE)?,
+                options: Options::read(payload, offset)?,
+            },
+            commands::MOVE | commands::MOVE_WITH_ON_OFF => Command::Move {
+                with_on_off,
+                mode: Mode::read(payload, offset)?,
+                rate: payload.read(offset)?,
+                options: Options::read(payload, offset)?,
+            },
+            commands::STEP | commands::STEP_WITH_ON_OFF => Command::Step {
+                with_on_off,
+                mode: Mode::read(payload, offset)?,
+                step: payload.read(offset)?,
+                transition_time: payload.read_with(offset, LE)?,
+                options: Options::read(payload, offset)?,
+            },
+            commands::STOP | commands::STOP_WITH_ON_OFF => Command::Stop {
+                with_on_off,
+                options: Options::read(payload, offset)?,
+            },
+            _ => return Err(Error::BadInput),
+        };
+
+        Ok(command)
+    }
+
+    /// Encode the payload of the command into `bytes`, returns its length
+    pub fn encode(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        let offset = &mut 0;
+
+        let options = match *self {
+            Command::MoveToLevel {
+                level,
+                transition_time,
+                options,
+                ..
+            } => {
+                bytes.write(offset, level)?;
+                bytes.write_with(offset, transition_time, LE)?;
+                options
+            }
+            Command::Move {
+                mode,
+                rate,
+                options,
+                ..
+            } => {
+                bytes.write(offset, mode as u8)?;
+                bytes.write(offset, rate)?;
+                options
+            }
+            Command::Step {
+                mode,
+                step,
+                transition_time,
+                options,
+                ..
+            } => {
+                bytes.write(offset, mode as u8)?;
+                bytes.write(offset, step)?;
+                bytes.write_with(offset, transition_time, LE)?;
+                options
+            }
+            Command::Stop { options, .. } => options,
+        };
+        options.write(bytes, offset)?;
+
+        Ok(*offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_round_trip() {
+        let mut bytes = [0; 8];
+        for command in [
+            Command::MoveToLevel {
+                with_on_off: true,
+                level: 0x80,
+                transition_time: 10,
+                options: Options::default(),
+            },
+            Command::Move {
+                with_on_off: false,
+                mode: Mode::Down,
+                rate: 20,
+                options: Options {
+                    mask: 1,
+                    overrides: 1,
+                },
+            },
+            Command::Step {
+                with_on_off: true,
+                mode: Mode::Up,
+                step: 5,
+                transition_time: 0xffff,
+                options: Options::default(),
+            },
+            Command::Stop {
+                with_on_off: false,
+                options: Options::default(),
+            },
+        ] {
+            let len = command.encode(&mut bytes).unwrap();
+            assert_eq!(
+                Command::decode(command.id(), &bytes[..len]).unwrap(),
+                command
+            );
+        }
+
+        assert_eq!(
+            Command::Stop {
+                with_on_off: true,
+                options: Options::default(),
+            }
+            .id(),
+            commands::STOP_WITH_ON_OFF
+        );
+    }
+
+    #[test]
+    fn options_are_optional() {
+        // as sent before revision 6 of the ZCL
+        assert_eq!(
+            Command::decode(commands::MOVE_TO_LEVEL_WITH_ON_OFF, &[0x80, 0x0a, 0x00]).unwrap(),
+            Command::MoveToLevel {
+                with_on_off: true,
+                level: 0x80,
+                transition_time: 10,
+                options: Options::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn malformed_commands_are_rejected() {
+        assert!(matches!(Command::decode(0x08, &[]), Err(Error::BadInput)));
+        // unknown mode
+        assert!(matches!(
+            Command::decode(commands::MOVE, &[0x02, 0x01]),
+            Err(Error::BadInput)
+        ));
+        // truncated, including options without the override
+        assert!(matches!(
+            Command::decode(commands::STEP, &[0x00, 0x05, 0xff]),
+            Err(Error::Incomplete)
+        ));
+        assert!(matches!(
+            Command::decode(commands::STOP, &[0x01]),
+            Err(Error::Incomplete)
+        ));
+    }
+}
@@ -0,0 +1,337 @@
+//! Zigbee cluster library (ZCL)
+//!
+//! The ZCL frames are carried in the payload of APS data frames, see
+//! [`aps::Indication::payload`](super::aps::Indication::payload) and
+//! [`aps::DataRequest::payload`](super::aps::DataRequest::payload). A
+//! [`Frame`] starts with a [`Header`], followed by the payload of a
+//! [`general::Command`], which are common to all clusters, or of a command of
+//! the cluster, such as an [`on_off::Command`].
+
+pub mod basic;
+pub mod general;
+pub mod level_control;
+pub mod on_off;
+pub mod temperature_measurement;
+mod types;
+
+use byte::{BytesExt, TryRead, TryWrite, LE};
+
+pub use self::types::{DataType, Date, Elements, TimeOfDay, Value};
+use crate::Error;
+
+/// Profile of Zigbee home automation, and of Zigbee 3.0 devices
+pub const HOME_AUTOMATION_PROFILE: u16 = 0x0104;
+
+const FRAME_TYPE_MASK: u8 = 0x03;
+const MANUFACTURER_SPECIFIC: u8 = 0x04;
+const SERVER_TO_CLIENT: u8 = 0x08;
+const DISABLE_DEFAULT_RESPONSE: u8 = 0x10;
+
+/// Status codes of ZCL commands
+pub mod status {
+    pub const SUCCESS: u8 = 0x00;
+    pub const FAILURE: u8 = 0x01;
+    pub const NOT_AUTHORIZED: u8 = 0x7e;
+    pub const MALFORMED_COMMAND: u8 = 0x80;
+    pub const UNSUP_COMMAND: u8 = 0x81;
+    pub const INVALID_FIELD: u8 = 0x85;
+    pub const UNSUPPORTED_ATTRIBUTE: u8 = 0x86;
+    pub const INVALID_VALUE: u8 = 0x87;
+    pub const READ_ONLY: u8 = 0x88;
+    pub const INSUFFICIENT_SPACE: u8 = 0x89;
+    pub const NOT_FOUND: u8 = 0x8b;
+    pub const UNREPORTABLE_ATTRIBUTE: u8 = 0x8c;
+    pub const INVALID_DATA_TYPE: u8 = 0x8d;
+    pub const INVALID_SELECTOR: u8 = 0x8e;
+    pub const TIMEOUT: u8 = 0x94;
+    pub const ABORT: u8 = 0x95;
+    pub const INVALID_IMAGE: u8 = 0x96;
+    pub const WAIT_FOR_DATA: u8 = 0x97;
+    pub const NO_IMAGE_AVAILABLE: u8 = 0x98;
+    pub const REQUIRE_MORE_IMAGE: u8 = 0x99;
+    pub const NOTIFICATION_PENDING: u8 = 0x9a;
+    pub const UNSUPPORTED_CLUSTER: u8 = 0xc3;
+}
+
+/// ZCL frame type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameType {
+    /// Command common to all clusters, see [`general::Command`]
+    General,
+    /// Command specific to the cluster
+    ClusterSpecific,
+}
+
+/// Direction of a command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Sent by the client side of the cluster, such as a switch
+    ClientToServer,
+    /// Sent by the server side of the cluster, such as a light
+    ServerToClient,
+}
+
+/// ZCL header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    pub frame_type: FrameType,
+    /// Manufacturer code of manufacturer specific commands and attributes
+    pub manufacturer: Option<u16>,
+    pub direction: Direction,
+    /// Whether the receiver should not send a default response when the
+    /// command succeeds
+    pub disable_default_response: bool,
+    /// Transaction sequence number, repeated in the response
+    pub sequence: u8,
+    pub command: u8,
+}
+
+impl Header {
+    /// Header of a general command sent by a client
+    pub fn general(command: u8, sequence: u8) -> Self {
+        Self {
+            frame_type: FrameType::General,
+            manufacturer: None,
+            direction: Direction::ClientToServer,
+            disable_default_response: false,
+            sequence,
+            command,
+        }
+    }
+
+    /// Header of a cluster specific command sent by a client
+    pub fn cluster_specific(command: u8, sequence: u8) -> Self {
+        Self {
+            frame_type: FrameType::ClusterSpecific,
+            ..Self::general(command, sequence)
+        }
+    }
+
+    /// Header of a general command responding to the command of `self`
+    ///
+    /// The response has the opposite direction and the same sequence number
+    /// and manufacturer code, and doesn't ask for a default response.
+    pub fn response(&self, command: u8) -> Self {
+        Self {
+            frame_type: FrameType::General,
+            manufacturer: self.manufacturer,
+            direction: match self.direction {
+                Direction::ClientToServer => Direction::ServerToClient,
+                Direction::ServerToClient => Direction::ClientToServer,
+            },
+            disable_default_response: true,
+            sequence: self.sequence,
+            command,
+        }
+    }
+}
+
+impl TryRead<'_> for Header {
+    fn try_read(bytes: &[u8], _ctx: ()) -> byte::Result<(Self, usize)> {
+        let offset = &mut 0;
+        let control: u8 = bytes.read(offset)?;
+
+        let frame_type = match control & FRAME_TYPE_MASK {
+            0 => FrameType::General,
+            1 => FrameType::ClusterSpecific,
+            _ => {
+                return Err(byte::Error::BadInput {
+                    err: "Unknown frame type",
+                })
+            }
+        };
+        let manufacturer = if control & MANUFACTURER_SPECIFIC != 0 {
+            Some(bytes.read_with(offset, LE)?)
+        } else {
+            None
+        };
+
+        let header = Self {
+            frame_type,
+            manufacturer,
+            direction: if control & SERVER_TO_CLIENT != 0 {
+                Direction::ServerToClient
+            } else {
+                Direction::ClientToServer
+            },
+            disable_default_response: control & DISABLE_DEFAULT_RESPONSE != 0,
+            sequence: bytes.read(offset)?,
+            command: bytes.read(offset)?,
+        };
+
+        Ok((header, *offset))
+    }
+}
+
+impl TryWrite for Header {
+    fn try_write(self, bytes: &mut [u8], _ctx: ()) -> byte::Result<usize> {
+        let offset = &mut 0;
+
+        let mut control = match self.frame_type {
+            FrameType::General => 0,
+            FrameType::ClusterSpecific => 1,
+        };
+        for (set, flag) in [
+            (self.manufacturer.is_some(), MANUFACTURER_SPECIFIC),
+            (
+                self.direction == Direction::ServerToClient,
+                SERVER_TO_CLIENT,
+            ),
+            (self.disable_default_response, DISABLE_DEFAULT_RESPONSE),
+        ] {
+            if set {
+                control |= flag;
+            }
+        }
+
+        bytes.write(offset, control)?;
+        if let Some(manufacturer) = self.manufacturer {
+            bytes.write_with(offset, manufacturer, LE)?;
+        }
+        bytes.write(offset, self.sequence)?;
+        bytes.write(offset, self.command)?;
+
+        Ok(*offset)
+    }
+}
+
+/// ZCL frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame<'p> {
+    pub header: Header,
+    /// Payload of the command
+    pub payload: &'p [u8],
+}
+
+impl<'p> Frame<'p> {
+    /// Decode a ZCL frame, usually the payload of an APS data frame
+    pub fn decode(bytes: &'p [u8]) -> Result<Self, Error> {
+        let offset = &mut 0;
+        let header = bytes.read(offset)?;
+
+        Ok(Self {
+            header,
+            payload: &bytes[*offset..],
+        })
+    }
+
+    /// Encode the frame into `bytes`, returns the length of the frame
+    pub fn encode(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        let offset = &mut 0;
+        bytes.write(offset, self.header)?;
+        bytes.write(offset, self.payload)?;
+
+        Ok(*offset)
+    }
+
+    /// Decode the general command of the frame, `None` for cluster specific
+    /// commands
+    pub fn general_command(&self) -> Result<Option<general::Command<'p>>, Error> {
+        match self.header.frame_type {
+            FrameType::General => {
+                general::Command::decode(self.header.command, self.payload).map(Some)
+            }
+            FrameType::ClusterSpecific => Ok(None),
+        }
+    }
+}
+
+/// Encode a frame of `header` followed by the payload written by `payload`
+/// into `bytes`, returns the length of the frame
+///
+/// This avoids encoding the payload of commands into a separate buffer, as in
+/// `zcl::encode(&header, &mut buf, |bytes| command.encode(bytes))`.
+pub fn encode(
+    header: &Header,
+    bytes: &mut [u8],
+    payload: impl FnOnce(&mut [u8]) -> Result<usize, Error>,
+) -> Result<usize, Error> {
+    let offset = &mut 0;
+    bytes.write(offset, *header)?;
+
+    Ok(*offset + payload(&mut bytes[*offset..])?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_round_trips() {
+        let header = Header {
+            manufacturer: Some(0x1234),
+            ..Header::cluster_specific(on_off::commands::ON_WITH_TIMED_OFF, 9)
+        };
+        let frame = Frame {
+            header,
+            payload: &[7],
+        };
+
+        let mut bytes = [0; 8];
+        let len = frame.encode(&mut bytes).unwrap();
+        assert_eq!(bytes[..len], [0x05, 0x34, 0x12, 9, 0x42, 7]);
+
+        let decoded = Frame::decode(&bytes[..len]).unwrap();
+        assert_eq!(decoded, frame);
+        assert!(matches!(decoded.general_command(), Ok(None)));
+    }
+
+    #[test]
+    fn responses_reverse_the_direction() {
+        let request = Header {
+            manufacturer: Some(0x1234),
+            ..Header::general(general::READ_ATTRIBUTES, 5)
+        };
+        let response = request.response(general::READ_ATTRIBUTES_RESPONSE);
+
+        assert_eq!(
+            response,
+            Header {
+                frame_type: FrameType::General,
+                manufacturer: Some(0x1234),
+                direction: Direction::ServerToClient,
+                disable_default_response: true,
+                sequence: 5,
+                command: general::READ_ATTRIBUTES_RESPONSE,
+            }
+        );
+        assert_eq!(
+            response.response(general::DEFAULT_RESPONSE).direction,
+            Direction::ClientToServer
+        );
+    }
+
+    #[test]
+    fn payload_is_encoded_after_the_header() {
+        let header = Header::general(general::DEFAULT_RESPONSE, 3);
+        let command = general::Command::DefaultResponse {
+            command: on_off::commands::TOGGLE,
+            status: status::SUCCESS,
+        };
+
+        let mut bytes = [0; 8];
+        let len = encode(&header, &mut bytes, |bytes| command.encode(bytes)).unwrap();
+        assert_eq!(bytes[..len], [0x00, 3, 0x0b, 0x02, 0x00]);
+
+        let frame = Frame::decode(&bytes[..len]).unwrap();
+        assert_eq!(frame.header, header);
+        assert_eq!(frame.general_command().unwrap(), Some(command));
+
+        // no room for the payload
+        assert!(encode(&header, &mut bytes[..4], |bytes| command.encode(bytes)).is_err());
+    }
+
+    #[test]
+    fn malformed_headers_are_rejected() {
+        // reserved frame type
+        assert!(matches!(Frame::decode(&[0x03, 0, 0]), Err(Error::BadInput)));
+
+        let header = [0x05, 0x34, 0x12, 9, 0x42];
+        for len in 0..header.len() {
+            assert!(matches!(
+                Frame::decode(&header[..len]),
+                Err(Error::Incomplete)
+            ));
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//! On/off cluster
+
+use byte::{BytesExt, LE};
+
+use crate::Error;
+
+/// Cluster identifier
+pub const ID: u16 = 0x0006;
+
+/// Attribute identifiers
+pub mod attributes {
+    /// `bool`
+    pub const ON_OFF: u16 = 0x0000;
+    /// `bool`
+    pub const GLOBAL_SCENE_CONTROL: u16 = 0x4000;
+    /// `uint16`, in tenths of a second
+    pub const ON_TIME: u16 = 0x4001;
+    /// `uint16`, in tenths of a second
+    pub const OFF_WAIT_TIME: u16 = 0x4002;
+    /// `enum8`, 0 for off, 1 for on, 2 to toggle and 0xff for the previous
+    /// value
+    pub const START_UP_ON_OFF: u16 = 0x4003;
+}
+
+/// Command identifiers
+pub mod commands {
+    pub const OFF: u8 = 0x00;
+    pub const ON: u8 = 0x01;
+    pub const TOGGLE: u8 = 0x02;
+    pub const OFF_WITH_EFFECT: u8 = 0x40;
+    pub const ON_WITH_RECALL_GLOBAL_SCENE: u8 = 0x41;
+    pub const ON_WITH_TIMED_OFF: u8 = 0x42;
+}
+
+/// Command of the cluster, sent by clients
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Off,
+    On,
+    Toggle,
+    OffWithEffect {
+        effect: u8,
+        variant: u8,
+    },
+    OnWithRecallGlobalScene,
+    OnWithTimedOff {
+        /// Bit 0 is set to accept the command only when on
+        control: u8,
+        /// In tenths of a second
+        on_time: u16,
+        /// In tenths of a second
+        off_wait_time: u16,
+    },
+}
+
+impl Command {
+    /// The command identifier, of the ZCL header
+    pub fn id(&self) -> u8 {
+        match self {
+            Command::Off => commands::OFF,
+            Command::On => commands::ON,
+            Command::Toggle => commands::TOGGLE,
+            Command::OffWithEffect { .. } => commands::OFF_WITH_EFFECT,
+            Command::OnWithRecallGlobalScene => commands::ON_WITH_RECALL_GLOBAL_SCENE,
+            Command::OnWithTimedOff { .. } => commands::ON_WITH_TIMED_OFF,
+        }
+    }
+
+    /// Decode the payload of the command `id`
+    pub fn decode(id: u8, payload: &[u8]) -> Result<Self, Error> {
+        let offset = &mut 0;
+
+        let command = match id {
+            commands::OFF => Command::Off,
+            commands::ON => Command::On,
+            commands::TOGGLE => Command::Toggle,
+            commands::OFF_WITH_EFFECT => Command::OffWithEffect {
+                effect: payload.read(offset)?,
+                variant: payload.read(offset)?,
+            },
+            commands::ON_WITH_RECALL_GLOBAL_SCENE => Command::OnWithRecallGlobalScene,
+            commands::ON_WITH_TIMED_OFF => Command::OnWithTimedOff {
+                control: payload.read(offset)?,
+                on_time: payload.read_with(offset, LE)?,
+                off_wait_time: payload.read_with(offset, LE)?,
+            },
+            _ => return Err(Error::BadInput),
+        };
+
+        Ok(command)
+    }
+
+    /// Encode the payload of the command into `bytes`, returns its length
+    pub fn encode(&self, bytes: &mut [u8]) -> Result<usize, Error> {
+        let offset = &mut 0;
+
+        match *self {
+            Command::Off | Command::On | Command::Toggle | Command::OnWithRecallGlobalScene => {}
+            Command::OffWithEffect { effect, variant } => {
+                bytes.write(offset, effect)?;
+                bytes.write(offset, variant)?;
+            }
+            Command::OnWithTimedOff {
+                control,
+                on_time,
+                off_wait_time,
+            } => {
+                bytes.write(offset, control)?;
+                bytes.write_with(offset, on_time, LE)?;
+                bytes.write_with(offset, off_wait_time, LE)?;
+            }
+        }
+
+        Ok(*offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commands_round_trip() {
+        let mut bytes = [0; 8];
+        for command in [
+            Command::Off,
+            Command::On,
+            Command::Toggle,
+            Command::OffWithEffect {
+                effect: 1,
+                variant: 0,
+            },
+            Command::OnWithRecallGlobalScene,
+            Command::OnWithTimedOff {
+                control: 1,
+                on_time: 300,
+                off_wait_time: 10,
+            },
+        ] {
+            let len = command.encode(&mut bytes).unwrap();
+            assert_eq!(
+                Command::decode(command.id(), &bytes[..len]).unwrap(),
+                command
+            );
+        }
+    }
+
+    #[test]
+    fn malformed_commands_are_rejected() {
+        assert!(matches!(Command::decode(0x03, &[]), Err(Error::BadInput)));
+        assert!(matches!(
+            Command::decode(commands::OFF_WITH_EFFECT, &[1]),
+            Err(Error::Incomplete)
+        ));
+        assert!(matches!(
+            Command::decode(commands::ON_WITH_TIMED_OFF, &[1, 0x2c, 0x01, 0x0a]),
+            Err(Error::Incomplete)
+        ));
+    }
+}
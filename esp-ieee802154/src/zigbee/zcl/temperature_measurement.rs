@@ -0,0 +1,57 @@
+//! Temperature measurement cluster
+
+/// Cluster identifier
+pub const ID: u16 = 0x0402;
+
+/// Attribute identifiers
+pub mod attributes {
+    /// `int16`, temperature in hundredths of °C, or [`INVALID`](super::INVALID)
+    pub const MEASURED_VALUE: u16 = 0x0000;
+    /// `int16`, in hundredths of °C
+    pub const MIN_MEASURED_VALUE: u16 = 0x0001;
+    /// `int16`, in hundredths of °C
+    pub const MAX_MEASURED_VALUE: u16 = 0x0002;
+    /// `uint16`, in hundredths of °C
+    pub const TOLERANCE: u16 = 0x0003;
+}
+
+/// Measured value when the temperature can't be measured, or limit which is
+/// unknown
+pub const INVALID: i16 = i16::MIN;
+
+/// The measured value of a temperature in °C, rounded to the nearest
+/// hundredth
+pub fn measured_value(celsius: f32) -> i16 {
+    if celsius.is_nan() {
+        return INVALID;
+    }
+
+    let value = celsius * 100.0;
+    let rounded = if value < 0.0 {
+        value - 0.5
+    } else {
+        value + 0.5
+    };
+    // the conversion saturates, and i16::MIN is reserved for INVALID
+    (rounded as i16).max(i16::MIN + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn measured_values_are_rounded() {
+        assert_eq!(measured_value(21.005), 2101);
+        assert_eq!(measured_value(-3.456), -346);
+        assert_eq!(measured_value(0.0), 0);
+    }
+
+    #[test]
+    fn invalid_temperatures_are_reported() {
+        assert_eq!(measured_value(f32::NAN), INVALID);
+        // saturated, short of the invalid value
+        assert_eq!(measured_value(-1000.0), i16::MIN + 1);
+        assert_eq!(measured_value(1000.0), i16::MAX);
+    }
+}
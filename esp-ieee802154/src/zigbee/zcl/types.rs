@@ -0,0 +1,806 @@
+//! Attribute data types
+
+use byte::{check_len, BytesExt, TryRead, TryWrite, LE};
+
+use crate::Error;
+
+/// ZCL data type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DataType {
+    NoData = 0x00,
+    Data8 = 0x08,
+    Data16 = 0x09,
+    Data24 = 0x0a,
+    Data32 = 0x0b,
+    Data40 = 0x0c,
+    Data48 = 0x0d,
+    Data56 = 0x0e,
+    Data64 = 0x0f,
+    Bool = 0x10,
+    Bitmap8 = 0x18,
+    Bitmap16 = 0x19,
+    Bitmap24 = 0x1a,
+    Bitmap32 = 0x1b,
+    Bitmap40 = 0x1c,
+    Bitmap48 = 0x1d,
+    Bitmap56 = 0x1e,
+    Bitmap64 = 0x1f,
+    Uint8 = 0x20,
+    Uint16 = 0x21,
+    Uint24 = 0x22,
+    Uint32 = 0x23,
+    Uint40 = 0x24,
+    Uint48 = 0x25,
+    Uint56 = 0x26,
+    Uint64 = 0x27,
+    Int8 = 0x28,
+    Int16 = 0x29,
+    Int24 = 0x2a,
+    Int32 = 0x2b,
+    Int40 = 0x2c,
+    Int48 = 0x2d,
+    Int56 = 0x2e,
+    Int64 = 0x2f,
+    Enum8 = 0x30,
+    Enum16 = 0x31,
+    /// Half precision float
+    Semi = 0x38,
+    Single = 0x39,
+    Double = 0x3a,
+    OctetString = 0x41,
+    CharString = 0x42,
+    LongOctetString = 0x43,
+    LongCharString = 0x44,
+    Array = 0x48,
+    Struct = 0x4c,
+    Set = 0x50,
+    Bag = 0x51,
+    TimeOfDay = 0xe0,
+    Date = 0xe1,
+    UtcTime = 0xe2,
+    ClusterId = 0xe8,
+    AttributeId = 0xe9,
+    BacnetOid = 0xea,
+    IeeeAddress = 0xf0,
+    SecurityKey = 0xf1,
+}
+
+impl TryFrom<u8> for DataType {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        use DataType::*;
+
+        const TYPES: [DataType; 55] = [
+            NoData,
+            Data8,
+            Data16,
+            Data24,
+            Data32,
+            Data40,
+            Data48,
+            Data56,
+            Data64,
+            Bool,
+            Bitmap8,
+            Bitmap16,
+            Bitmap24,
+            Bitmap32,
+            Bitmap40,
+            Bitmap48,
+            Bitmap56,
+            Bitmap64,
+            Uint8,
+            Uint16,
+            Uint24,
+            Uint32,
+            Uint40,
+            Uint48,
+            Uint56,
+            Uint64,
+            Int8,
+            Int16,
+            Int24,
+            Int32,
+            Int40,
+            Int48,
+            Int56,
+            Int64,
+            Enum8,
+            Enum16,
+            Semi,
+            Single,
+            Double,
+            OctetString,
+            CharString,
+            LongOctetString,
+            LongCharString,
+            Array,
+            Struct,
+            Set,
+            Bag,
+            TimeOfDay,
+            Date,
+            UtcTime,
+            ClusterId,
+            AttributeId,
+            BacnetOid,
+            IeeeAddress,
+            SecurityKey,
+        ];
+
+        TYPES
+            .into_iter()
+            .find(|&data_type| data_type as u8 == value)
+            .ok_or(Error::BadInput)
+    }
+}
+
+impl DataType {
+    /// Whether the type is analog, for which a reportable change is
+    /// configured, rather than discrete
+    pub fn is_analog(self) -> bool {
+        matches!(
+            self as u8,
+            0x20..=0x2f | 0x38..=0x3a | 0xe0..=0xe2
+        )
+    }
+
+    /// Size in bytes of a value of the type, `None` for strings and
+    /// collections
+    pub fn size(self) -> Option<usize> {
+        let size = match self as u8 {
+            0x00 => 0,
+            // data, bitmap, unsigned and signed integers of 8 to 64 bits
+            id @ 0x08..=0x0f | id @ 0x18..=0x2f => (id as usize & 0x07) + 1,
+            0x10 | 0x30 => 1,
+            0x31 | 0x38 | 0xe8 | 0xe9 => 2,
+            0x39 | 0xe0..=0xe2 | 0xea => 4,
+            0x3a | 0xf0 => 8,
+            0xf1 => 16,
+            _ => return None,
+        };
+
+        Some(size)
+    }
+}
+
+/// Time of day
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeOfDay {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub hundredths: u8,
+}
+
+/// Date
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Date {
+    /// Years since 1900
+    pub year: u8,
+    pub month: u8,
+    pub day: u8,
+    /// Day of the week, 1 being Monday
+    pub weekday: u8,
+}
+
+/// Elements of an array, set, bag or structure
+///
+/// The elements are decoded while iterating over them.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Elements<'a> {
+    /// Type of the elements, `None` for structures where each element has its
+    /// own type
+    element_type: Option<DataType>,
+    count: u16,
+    data: &'a [u8],
+}
+
+impl<'a> Elements<'a> {
+    /// Elements encoded in `data`, each one prefixed by its type if
+    /// `element_type` is `None`
+    pub fn new(element_type: Option<DataType>, count: u16, data: &'a [u8]) -> Self {
+        Self {
+            element_type,
+            count,
+            data,
+        }
+    }
+
+    pub fn element_type(&self) -> Option<DataType> {
+        self.element_type
+    }
+
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Result<Value<'a>, Error>> + '_ {
+        let mut offset = 0;
+        let mut remaining = self.count;
+
+        core::iter::from_fn(move || {
+            if remaining == 0 {
+                return None;
+            }
+            remaining -= 1;
+
+            let value = match self.element_type {
+                Some(data_type) => self.data.read_with(&mut offset, data_type),
+                None => self.data.read_with(&mut offset, ()),
+            };
+
+            Some(value.map_err(Error::from))
+        })
+    }
+
+    fn read(
+        bytes: &'a [u8],
+        offset: &mut usize,
+        element_type: Option<DataType>,
+    ) -> byte::Result<Self> {
+        let count: u16 = bytes.read_with(offset, LE)?;
+        let start = *offset;
+
+        for _ in 0..count {
+            match element_type {
+                Some(data_type) => {
+                    bytes.read_with::<Value>(offset, data_type)?;
+                }
+                None => {
+                    bytes.read_with::<Value>(offset, ())?;
+                }
+            }
+        }
+
+        Ok(Self::new(element_type, count, &bytes[start..*offset]))
+    }
+}
+
+/// Attribute value
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value<'a> {
+    NoData,
+    /// `data8` to `data64`, with the size in bytes
+    Data(u8, u64),
+    Bool(bool),
+    /// `bitmap8` to `bitmap64`, with the size in bytes
+    Bitmap(u8, u64),
+    /// `uint8` to `uint64`, with the size in bytes
+    Uint(u8, u64),
+    /// `int8` to `int64`, with the size in bytes
+    Int(u8, i64),
+    Enum8(u8),
+    Enum16(u16),
+    /// Half precision float, as its bits
+    Semi(u16),
+    Single(f32),
+    Double(f64),
+    OctetString(&'a [u8]),
+    CharString(&'a str),
+    LongOctetString(&'a [u8]),
+    LongCharString(&'a str),
+    Array(Elements<'a>),
+    Struct(Elements<'a>),
+    Set(Elements<'a>),
+    Bag(Elements<'a>),
+    TimeOfDay(TimeOfDay),
+    Date(Date),
+    /// Seconds since 2000-01-01 00:00 UTC
+    UtcTime(u32),
+    ClusterId(u16),
+    AttributeId(u16),
+    BacnetOid(u32),
+    IeeeAddress(u64),
+    SecurityKey([u8; 16]),
+}
+
+impl Value<'_> {
+    pub fn u8(value: u8) -> Self {
+        Value::Uint(1, value as u64)
+    }
+
+    pub fn u16(value: u16) -> Self {
+        Value::Uint(2, value as u64)
+    }
+
+    pub fn u32(value: u32) -> Self {
+        Value::Uint(4, value as u64)
+    }
+
+    pub fn i8(value: i8) -> Self {
+        Value::Int(1, value as i64)
+    }
+
+    pub fn i16(value: i16) -> Self {
+        Value::Int(2, value as i64)
+    }
+
+    pub fn i32(value: i32) -> Self {
+        Value::Int(4, value as i64)
+    }
+
+    /// The data type of the value
+    ///
+    /// Sizes of integers, data and bitmaps out of 1 to 8 bytes are clamped.
+    pub fn data_type(&self) -> DataType {
+        let sized = |base: u8, size: u8| {
+            // the types of each size follow each other
+            DataType::try_from(base + size.clamp(1, 8) - 1).unwrap_or(DataType::NoData)
+        };
+
+        match *self {
+            Value::NoData => DataType::NoData,
+            Value::Data(size, _) => sized(DataType::Data8 as u8, size),
+            Value::Bool(_) => DataType::Bool,
+            Value::Bitmap(size, _) => sized(DataType::Bitmap8 as u8, size),
+            Value::Uint(size, _) => sized(DataType::Uint8 as u8, size),
+            Value::Int(size, _) => sized(DataType::Int8 as u8, size),
+            Value::Enum8(_) => DataType::Enum8,
+            Value::Enum16(_) => DataType::Enum16,
+            Value::Semi(_) => DataType::Semi,
+            Value::Single(_) => DataType::Single,
+            Value::Double(_) => DataType::Double,
+            Value::OctetString(_) => DataType::OctetString,
+            Value::CharString(_) => DataType::CharString,
+            Value::LongOctetString(_) => DataType::LongOctetString,
+            Value::LongCharString(_) => DataType::LongCharString,
+            Value::Array(_) => DataType::Array,
+            Value::Struct(_) => DataType::Struct,
+            Value::Set(_) => DataType::Set,
+            Value::Bag(_) => DataType::Bag,
+            Value::TimeOfDay(_) => DataType::TimeOfDay,
+            Value::Date(_) => DataType::Date,
+            Value::UtcTime(_) => DataType::UtcTime,
+            Value::ClusterId(_) => DataType::ClusterId,
+            Value::AttributeId(_) => DataType::AttributeId,
+            Value::BacnetOid(_) => DataType::BacnetOid,
+            Value::IeeeAddress(_) => DataType::IeeeAddress,
+            Value::SecurityKey(_) => DataType::SecurityKey,
+        }
+    }
+
+    /// The value of unsigned integers, data, bitmaps and enumerations
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::Data(_, value) | Value::Bitmap(_, value) | Value::Uint(_, value) => Some(value),
+            Value::Enum8(value) => Some(value as u64),
+            Value::Enum16(value) => Some(value as u64),
+            _ => None,
+        }
+    }
+
+    /// The value of signed integers
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Int(_, value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Write the value without its type
+    pub(super) fn write_untyped(&self, bytes: &mut [u8], offset: &mut usize) -> byte::Result<()> {
+        let write_sized = |bytes: &mut [u8], offset: &mut usize, size: u8, value: u64| {
+            let size = size.clamp(1, 8) as usize;
+            check_len(&bytes[*offset..], size)?;
+            bytes[*offset..][..size].copy_from_slice(&value.to_le_bytes()[..size]);
+            *offset += size;
+            Ok::<_, byte::Error>(())
+        };
+
+        match *self {
+            Value::NoData => {}
+            Value::Data(size, value) | Value::Bitmap(size, value) | Value::Uint(size, value) => {
+                write_sized(bytes, offset, size, value)?
+            }
+            Value::Int(size, value) => write_sized(bytes, offset, size, value as u64)?,
+            Value::Bool(value) => bytes.write(offset, value as u8)?,
+            Value::Enum8(value) => bytes.write(offset, value)?,
+            Value::Enum16(value) | Value::Semi(value) => bytes.write_with(offset, value, LE)?,
+            Value::Single(value) => bytes.write_with(offset, value, LE)?,
+            Value::Double(value) => bytes.write_with(offset, value, LE)?,
+            Value::OctetString(value) => {
+                let len = u8::try_from(value.len()).map_err(|_| too_long())?;
+                bytes.write(offset, len)?;
+                bytes.write(offset, value)?;
+            }
+            Value::CharString(value) => {
+                let len = u8::try_from(value.len()).map_err(|_| too_long())?;
+                bytes.write(offset, len)?;
+                bytes.write(offset, value.as_bytes())?;
+            }
+            Value::LongOctetString(value) => {
+                let len = u16::try_from(value.len()).map_err(|_| too_long())?;
+                bytes.write_with(offset, len, LE)?;
+                bytes.write(offset, value)?;
+            }
+            Value::LongCharString(value) => {
+                let len = u16::try_from(value.len()).map_err(|_| too_long())?;
+                bytes.write_with(offset, len, LE)?;
+                bytes.write(offset, value.as_bytes())?;
+            }
+            Value::Array(elements) | Value::Set(elements) | Value::Bag(elements) => {
+                let element_type = elements.element_type.ok_or(byte::Error::BadInput {
+                    err: "Missing element type",
+                })?;
+                bytes.write(offset, element_type as u8)?;
+                bytes.write_with(offset, elements.count, LE)?;
+                bytes.write(offset, elements.data)?;
+            }
+            Value::Struct(elements) => {
+                bytes.write_with(offset, elements.count, LE)?;
+                bytes.write(offset, elements.data)?;
+            }
+            Value::TimeOfDay(time) => {
+                bytes.write(offset, time.hours)?;
+                bytes.write(offset, time.minutes)?;
+                bytes.write(offset, time.seconds)?;
+                bytes.write(offset, time.hundredths)?;
+            }
+            Value::Date(date) => {
+                bytes.write(offset, date.year)?;
+                bytes.write(offset, date.month)?;
+                bytes.write(offset, date.day)?;
+                bytes.write(offset, date.weekday)?;
+            }
+            Value::UtcTime(value) | Value::BacnetOid(value) => {
+                bytes.write_with(offset, value, LE)?
+            }
+            Value::ClusterId(value) | Value::AttributeId(value) => {
+                bytes.write_with(offset, value, LE)?
+            }
+            Value::IeeeAddress(value) => bytes.write_with(offset, value, LE)?,
+            Value::SecurityKey(value) => bytes.write(offset, &value[..])?,
+        }
+
+        Ok(())
+    }
+}
+
+fn too_long() -> byte::Error {
+    byte::Error::BadInput {
+        err: "String too long",
+    }
+}
+
+fn read_str<'a>(bytes: &'a [u8], offset: &mut usize, len: usize) -> byte::Result<&'a str> {
+    let data = bytes.read_with::<&[u8]>(offset, byte::ctx::Bytes::Len(len))?;
+
+    core::str::from_utf8(data).map_err(|_| byte::Error::BadInput {
+        err: "Invalid character string",
+    })
+}
+
+/// Read a value of the given type
+impl<'a> TryRead<'a, DataType> for Value<'a> {
+    fn try_read(bytes: &'a [u8], data_type: DataType) -> byte::Result<(Self, usize)> {
+        let offset = &mut 0;
+
+        let read_sized = |offset: &mut usize| -> byte::Result<(u8, u64)> {
+            // the size of the integer, data and bitmap types follows from their ID
+            let size = data_type.size().unwrap_or(1);
+            check_len(&bytes[*offset..], size)?;
+
+            let mut value = [0u8; 8];
+            value[..size].copy_from_slice(&bytes[*offset..][..size]);
+            *offset += size;
+
+            Ok((size as u8, u64::from_le_bytes(value)))
+        };
+
+        let value = match data_type {
+            DataType::NoData => Value::NoData,
+            DataType::Data8
+            | DataType::Data16
+            | DataType::Data24
+            | DataType::Data32
+            | DataType::Data40
+            | DataType::Data48
+            | DataType::Data56
+            | DataType::Data64 => {
+                let (size, value) = read_sized(offset)?;
+                Value::Data(size, value)
+            }
+            DataType::Bool => Value::Bool(bytes.read::<u8>(offset)? != 0),
+            DataType::Bitmap8
+            | DataType::Bitmap16
+            | DataType::Bitmap24
+            | DataType::Bitmap32
+            | DataType::Bitmap40
+            | DataType::Bitmap48
+            | DataType::Bitmap56
+            | DataType::Bitmap64 => {
+                let (size, value) = read_sized(offset)?;
+                Value::Bitmap(size, value)
+            }
+            DataType::Uint8
+            | DataType::Uint16
+            | DataType::Uint24
+            | DataType::Uint32
+            | DataType::Uint40
+            | DataType::Uint48
+            | DataType::Uint56
+            | DataType::Uint64 => {
+                let (size, value) = read_sized(offset)?;
+                Value::Uint(size, value)
+            }
+            DataType::Int8
+            | DataType::Int16
+            | DataType::Int24
+            | DataType::Int32
+            | DataType::Int40
+            | DataType::Int48
+            | DataType::Int56
+            | DataType::Int64 => {
+                let (size, value) = read_sized(offset)?;
+                // sign extend
+                let shift = 64 - 8 * size as u32;
+                Value::Int(size, ((value << shift) as i64) >> shift)
+            }
+            DataType::Enum8 => Value::Enum8(bytes.read(offset)?),
+            DataType::Enum16 => Value::Enum16(bytes.read_with(offset, LE)?),
+            DataType::Semi => Value::Semi(bytes.read_with(offset, LE)?),
+            DataType::Single => Value::Single(bytes.read_with(offset, LE)?),
+            DataType::Double => Value::Double(bytes.read_with(offset, LE)?),
+            DataType::OctetString => {
+                // 0xff is an invalid string, without data
+                let len = match bytes.read::<u8>(offset)? {
+                    0xff => 0,
+                    len => len as usize,
+                };
+                Value::OctetString(bytes.read_with(offset, byte::ctx::Bytes::Len(len))?)
+            }
+            DataType::CharString => {
+                let len = match bytes.read::<u8>(offset)? {
+                    0xff => 0,
+                    len => len as usize,
+                };
+                Value::CharString(read_str(bytes, offset, len)?)
+            }
+            DataType::LongOctetString => {
+                let len = match bytes.read_with::<u16>(offset, LE)? {
+                    0xffff => 0,
+                    len => len as usize,
+                };
+                Value::LongOctetString(bytes.read_with(offset, byte::ctx::Bytes::Len(len))?)
+            }
+            DataType::LongCharString => {
+                let len = match bytes.read_with::<u16>(offset, LE)? {
+                    0xffff => 0,
+                    len => len as usize,
+                };
+                Value::LongCharString(read_str(bytes, offset, len)?)
+            }
+            DataType::Array | DataType::Set | DataType::Bag => {
+                let element_type = DataType::try_from(bytes.read::<u8>(offset)?).map_err(|_| {
+                    byte::Error::BadInput {
+                        err: "Unknown element type",
+                    }
+                })?;
+                let elements = Elements::read(bytes, offset, Some(element_type))?;
+
+                match data_type {
+                    DataType::Array => Value::Array(elements),
+                    DataType::Set => Value::Set(elements),
+                    _ => Value::Bag(elements),
+                }
+            }
+            DataType::Struct => Value::Struct(Elements::read(bytes, offset, None)?),
+            DataType::TimeOfDay => Value::TimeOfDay(TimeOfDay {
+                hours: bytes.read(offset)?,
+                minutes: bytes.read(offset)?,
+                seconds: bytes.read(offset)?,
+                hundredths: bytes.read(offset)?,
+            }),
+            DataType::Date => Value::Date(Date {
+                year: bytes.read(offset)?,
+                month: bytes.read(offset)?,
+                day: bytes.read(offset)?,
+                weekday: bytes.read(offset)?,
+            }),
+            DataType::UtcTime => Value::UtcTime(bytes.read_with(offset, LE)?),
+            DataType::ClusterId => Value::ClusterId(bytes.read_with(offset, LE)?),
+            DataType::AttributeId => Value::AttributeId(bytes.read_with(offset, LE)?),
+            DataType::BacnetOid => Value::BacnetOid(bytes.read_with(offset, LE)?),
+            DataType::IeeeAddress => Value::IeeeAddress(bytes.read_with(offset, LE)?),
+            DataType::SecurityKey => {
+                let key: &[u8] = bytes.read_with(offset, byte::ctx::Bytes::Len(16))?;
+                let mut value = [0u8; 16];
+                value.copy_from_slice(key);
+                Value::SecurityKey(value)
+            }
+        };
+
+        Ok((value, *offset))
+    }
+}
+
+/// Read a value preceded by its type
+impl<'a> TryRead<'a> for Value<'a> {
+    fn try_read(bytes: &'a [u8], _ctx: ()) -> byte::Result<(Self, usize)> {
+        let offset = &mut 0;
+        let data_type = read_data_type(bytes, offset)?;
+        let value = bytes.read_with(offset, data_type)?;
+
+        Ok((value, *offset))
+    }
+}
+
+/// Write the value preceded by its type
+impl TryWrite for Value<'_> {
+    fn try_write(self, bytes: &mut [u8], _ctx: ()) -> byte::Result<usize> {
+        let offset = &mut 0;
+        bytes.write(offset, self.data_type() as u8)?;
+        self.write_untyped(bytes, offset)?;
+
+        Ok(*offset)
+    }
+}
+
+pub(super) fn read_data_type(bytes: &[u8], offset: &mut usize) -> byte::Result<DataType> {
+    DataType::try_from(bytes.read::<u8>(offset)?).map_err(|_| byte::Error::BadInput {
+        err: "Unknown data type",
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decode the value with its type in `bytes`, checking that it encodes to
+    /// the same bytes
+    fn round_trip(bytes: &[u8]) -> Value<'_> {
+        let offset = &mut 0;
+        let value: Value = bytes.read_with(offset, ()).unwrap();
+        assert_eq!(*offset, bytes.len());
+
+        let mut encoded = [0; 64];
+        let len = value.try_write(&mut encoded, ()).unwrap();
+        assert_eq!(encoded[..len], *bytes);
+
+        value
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Value<'_>, Error> {
+        Ok(bytes.read_with(&mut 0, ())?)
+    }
+
+    const VALUES: [&[u8]; 16] = [
+        &[0x00],
+        &[0x10, 0x01],
+        &[0x18, 0xa5],
+        &[0x22, 0x01, 0x02, 0x03],
+        &[0x2a, 0xfe, 0xff, 0xff],
+        &[0x30, 0x03],
+        &[0x39, 0x00, 0x00, 0x80, 0x3f],
+        &[0x41, 0x02, 0x01, 0x02],
+        &[0x42, 0x02, b'h', b'i'],
+        &[0x44, 0x02, 0x00, b'h', b'i'],
+        &[0x48, 0x21, 0x02, 0x00, 0x01, 0x00, 0x02, 0x00],
+        &[0x4c, 0x02, 0x00, 0x20, 0x07, 0x42, 0x01, b'x'],
+        &[0xe0, 12, 30, 15, 50],
+        &[0xe1, 124, 10, 19, 1],
+        &[0xe8, 0x06, 0x00],
+        &[0xf0, 1, 2, 3, 4, 5, 6, 7, 8],
+    ];
+
+    #[test]
+    fn values_round_trip() {
+        let values: std::vec::Vec<_> = VALUES.iter().map(|bytes| round_trip(bytes)).collect();
+
+        assert_eq!(values[0], Value::NoData);
+        assert_eq!(values[1], Value::Bool(true));
+        assert_eq!(values[2], Value::Bitmap(1, 0xa5));
+        assert_eq!(values[3], Value::Uint(3, 0x03_0201));
+        // sign extended
+        assert_eq!(values[4], Value::Int(3, -2));
+        assert_eq!(values[5], Value::Enum8(3));
+        assert_eq!(values[6], Value::Single(1.0));
+        assert_eq!(values[7], Value::OctetString(&[1, 2]));
+        assert_eq!(values[8], Value::CharString("hi"));
+        assert_eq!(values[9], Value::LongCharString("hi"));
+        assert_eq!(
+            values[12],
+            Value::TimeOfDay(TimeOfDay {
+                hours: 12,
+                minutes: 30,
+                seconds: 15,
+                hundredths: 50,
+            })
+        );
+        assert_eq!(values[14], Value::ClusterId(0x0006));
+        assert_eq!(values[15], Value::IeeeAddress(0x0807_0605_0403_0201));
+    }
+
+    #[test]
+    fn elements_are_decoded_while_iterating() {
+        let Value::Array(elements) = round_trip(VALUES[10]) else {
+            panic!("not an array");
+        };
+        assert_eq!(elements.element_type(), Some(DataType::Uint16));
+        assert_eq!(elements.len(), 2);
+        assert_eq!(
+            elements
+                .iter()
+                .map(Result::unwrap)
+                .collect::<std::vec::Vec<_>>(),
+            [Value::u16(1), Value::u16(2)]
+        );
+
+        let Value::Struct(elements) = round_trip(VALUES[11]) else {
+            panic!("not a structure");
+        };
+        assert_eq!(elements.element_type(), None);
+        assert_eq!(
+            elements
+                .iter()
+                .map(Result::unwrap)
+                .collect::<std::vec::Vec<_>>(),
+            [Value::u8(7), Value::CharString("x")]
+        );
+
+        assert!(Elements::new(None, 0, &[]).is_empty());
+        assert_eq!(Elements::new(None, 0, &[]).iter().count(), 0);
+    }
+
+    #[test]
+    fn truncated_values_are_rejected() {
+        for bytes in VALUES {
+            for len in 0..bytes.len() {
+                assert!(matches!(decode(&bytes[..len]), Err(Error::Incomplete)));
+            }
+        }
+    }
+
+    #[test]
+    fn malformed_values_are_rejected() {
+        // unknown data type and element type
+        assert!(matches!(decode(&[0x07, 0x00]), Err(Error::BadInput)));
+        assert!(matches!(
+            decode(&[0x48, 0x07, 0x01, 0x00, 0x00]),
+            Err(Error::BadInput)
+        ));
+        // not UTF-8
+        assert!(matches!(
+            decode(&[0x42, 0x02, 0xc3, 0x28]),
+            Err(Error::BadInput)
+        ));
+
+        // invalid strings have no data
+        assert_eq!(decode(&[0x42, 0xff]).unwrap(), Value::CharString(""));
+        assert_eq!(
+            decode(&[0x43, 0xff, 0xff]).unwrap(),
+            Value::LongOctetString(&[])
+        );
+
+        // collections of elements with their own type need the type
+        let elements = Value::Set(Elements::new(None, 0, &[]));
+        assert!(elements.try_write(&mut [0; 8], ()).is_err());
+    }
+
+    #[test]
+    fn data_types_describe_values() {
+        assert_eq!(Value::Uint(9, 0).data_type(), DataType::Uint64);
+        assert_eq!(Value::Int(0, 0).data_type(), DataType::Int8);
+        assert_eq!(Value::u32(1).data_type(), DataType::Uint32);
+        assert_eq!(DataType::try_from(0x29).unwrap(), DataType::Int16);
+        assert!(DataType::try_from(0x07).is_err());
+
+        assert!(DataType::Int16.is_analog());
+        assert!(DataType::UtcTime.is_analog());
+        assert!(!DataType::Bitmap8.is_analog());
+        assert_eq!(DataType::Uint24.size(), Some(3));
+        assert_eq!(DataType::SecurityKey.size(), Some(16));
+        assert_eq!(DataType::CharString.size(), None);
+
+        assert_eq!(Value::Enum16(7).as_u64(), Some(7));
+        assert_eq!(Value::i8(-1).as_i64(), Some(-1));
+        assert_eq!(Value::i8(-1).as_u64(), None);
+    }
+}
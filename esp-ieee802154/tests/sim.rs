@@ -0,0 +1,349 @@
+//! Several nodes exchanging frames over a simulated medium
+
+#![cfg(feature = "sim")]
+
+use std::{
+    sync::{mpsc, Arc, Barrier},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+use esp_ieee802154::{
+    mock,
+    sim::{run_for, Link, Medium, MediumConfig, NodeId},
+    Channel, Config, Ieee802154, RadioStats,
+};
+
+const PAN_ID: u16 = 0x1234;
+// Long enough for a frame, its ACK and the ACK timeout
+const EXCHANGE: Duration = Duration::from_millis(15);
+const COORDINATOR: u64 = 0x00c0_ffee_0000_0001;
+const DEVICE: u64 = 0x00de_71ce_0000_0002;
+
+/// The frames received by a node, and its stats once done
+type Received = (Vec<Vec<u8>>, RadioStats);
+
+fn node(medium: &Medium, short_addr: Option<u16>, ext_addr: u64) -> (Ieee802154<'static>, NodeId) {
+    let mut radio = Ieee802154::new(mock::IEEE802154, &mut mock::RADIO_CLK);
+    let id = medium.attach();
+    radio.set_config(Config {
+        channel: Channel::new(15).unwrap(),
+        pan_id: Some(PAN_ID),
+        short_addr,
+        ext_addr: Some(ext_addr),
+        auto_ack_tx: true,
+        auto_ack_rx: true,
+        rx_when_idle: true,
+        ..Default::default()
+    });
+    radio.start_receive();
+    (radio, id)
+}
+
+/// A node receiving on `channel` until `duration` passed after the barrier,
+/// which sends its id before waiting
+fn receiver(
+    medium: &Medium,
+    barrier: &Arc<Barrier>,
+    short_addr: u16,
+    channel: u8,
+    duration: Duration,
+) -> (NodeId, JoinHandle<Received>) {
+    let (medium, barrier) = (medium.clone(), barrier.clone());
+    let (id_tx, id_rx) = mpsc::channel();
+
+    let handle = thread::spawn(move || {
+        let (mut radio, id) = node(&medium, Some(short_addr), short_addr as u64);
+        radio.reconfigure(Config {
+            channel: Channel::new(channel).unwrap(),
+            ..radio.config()
+        });
+        id_tx.send(id).unwrap();
+        barrier.wait();
+        let frames = receive_for(&mut radio, duration);
+        (frames, radio.stats())
+    });
+
+    (id_rx.recv().unwrap(), handle)
+}
+
+/// A data frame between short addresses, followed by room for the FCS
+fn data_frame(sequence: u8, ack_request: bool, destination: u16, source: u16) -> Vec<u8> {
+    let mut frame = vec![if ack_request { 0x61 } else { 0x41 }, 0x88, sequence];
+    frame.extend_from_slice(&PAN_ID.to_le_bytes());
+    frame.extend_from_slice(&destination.to_le_bytes());
+    frame.extend_from_slice(&source.to_le_bytes());
+    frame.extend_from_slice(b"payload");
+    frame.extend_from_slice(&[0, 0]);
+    frame
+}
+
+fn without_fcs(frame: &[u8]) -> Vec<u8> {
+    frame[..frame.len() - 2].to_vec()
+}
+
+/// Transmit the frame and wait for its outcome, returns whether it was ACKed
+fn send(radio: &mut Ieee802154<'static>, frame: &[u8]) -> bool {
+    let before = radio.stats();
+    radio.transmit_raw(frame).unwrap();
+    run_for(EXCHANGE);
+
+    let after = radio.stats();
+    assert_eq!(after.tx_done, before.tx_done + 1);
+    after.rx_ack_timeout == before.rx_ack_timeout
+}
+
+/// The PSDUs received until `duration` passed, without RSSI and LQI
+fn receive_for(radio: &mut Ieee802154<'static>, duration: Duration) -> Vec<Vec<u8>> {
+    let until = Instant::now() + duration;
+    let mut frames = Vec::new();
+
+    while Instant::now() < until {
+        frames.extend(receive(radio));
+    }
+
+    frames
+}
+
+/// Wait briefly for received frames
+fn receive(radio: &mut Ieee802154<'static>) -> Vec<Vec<u8>> {
+    run_for(Duration::from_micros(200));
+
+    let mut frames = Vec::new();
+    while let Some(received) = radio.get_raw_received() {
+        frames.push(without_fcs(
+            &received.data[1..][..received.data[0] as usize],
+        ));
+    }
+
+    frames
+}
+
+#[test]
+fn acked_frame_is_received_once() {
+    let medium = Medium::new(MediumConfig::default());
+    let barrier = Arc::new(Barrier::new(2));
+    let (_, receiver) = receiver(&medium, &barrier, 2, 15, Duration::from_millis(60));
+
+    let (mut radio, _) = node(&medium, Some(1), 1);
+    barrier.wait();
+    let frame = data_frame(7, true, 2, 1);
+    assert!(send(&mut radio, &frame));
+    assert_eq!(radio.stats().tx_abort, 0);
+
+    assert_eq!(receiver.join().unwrap().0, [without_fcs(&frame)]);
+}
+
+#[test]
+fn unreachable_receivers_time_out() {
+    let medium = Medium::new(MediumConfig::default());
+    let barrier = Arc::new(Barrier::new(3));
+    let duration = Duration::from_millis(60);
+    let (out_of_range, out_of_range_rx) = receiver(&medium, &barrier, 2, 15, duration);
+    let (_, other_channel_rx) = receiver(&medium, &barrier, 3, 20, duration);
+
+    let (mut radio, id) = node(&medium, Some(1), 1);
+    medium.set_links(id, out_of_range, Link::disconnected());
+    barrier.wait();
+
+    assert!(!send(&mut radio, &data_frame(1, true, 2, 1)));
+    assert!(!send(&mut radio, &data_frame(2, true, 3, 1)));
+    // broadcasts are not ACKed, and so don't time out
+    assert!(send(&mut radio, &data_frame(3, true, 0xffff, 1)));
+    assert_eq!(radio.stats().rx_ack_timeout, 2);
+    assert_eq!(radio.stats().tx_abort, 2);
+
+    assert!(out_of_range_rx.join().unwrap().0.is_empty());
+    assert!(other_channel_rx.join().unwrap().0.is_empty());
+}
+
+#[test]
+fn lost_acks_lead_to_duplicates() {
+    let medium = Medium::new(MediumConfig::default());
+    let barrier = Arc::new(Barrier::new(2));
+    let (receiver_id, receiver) = receiver(&medium, &barrier, 2, 15, Duration::from_millis(120));
+
+    let (mut radio, id) = node(&medium, Some(1), 1);
+    medium.set_link(receiver_id, id, Link::disconnected());
+    barrier.wait();
+
+    // the frame is retried with the same sequence number until it is ACKed
+    let frame = data_frame(42, true, 2, 1);
+    for _ in 0..3 {
+        assert!(!send(&mut radio, &frame));
+    }
+    medium.set_link(receiver_id, id, Link::default());
+    assert!(send(&mut radio, &frame));
+    assert_eq!(radio.stats().rx_ack_timeout, 3);
+
+    assert_eq!(receiver.join().unwrap().0, vec![without_fcs(&frame); 4]);
+}
+
+#[test]
+fn lossy_link_drops_some_frames() {
+    let medium = Medium::new(MediumConfig {
+        link: Link {
+            loss: 0.5,
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+    let barrier = Arc::new(Barrier::new(2));
+    let (_, receiver) = receiver(&medium, &barrier, 2, 15, Duration::from_millis(200));
+
+    let (mut radio, _) = node(&medium, Some(1), 1);
+    barrier.wait();
+    for sequence in 0..40 {
+        radio
+            .transmit_raw(&data_frame(sequence, false, 2, 1))
+            .unwrap();
+        run_for(Duration::from_millis(3));
+    }
+
+    let (received, _) = receiver.join().unwrap();
+    assert!(
+        (5..35).contains(&received.len()),
+        "{} received",
+        received.len()
+    );
+    // the frames which made it are received in order
+    assert!(received
+        .windows(2)
+        .all(|frames| frames[0][2] < frames[1][2]));
+}
+
+fn overlapping_frames(collisions: bool) -> (Vec<Vec<u8>>, RadioStats, [u16; 2]) {
+    let medium = Medium::new(MediumConfig {
+        collisions,
+        ..Default::default()
+    });
+    let barrier = Arc::new(Barrier::new(3));
+    let (_, receiver) = receiver(&medium, &barrier, 3, 15, Duration::from_millis(60));
+
+    let senders = [1, 2].map(|short_addr| {
+        let (medium, barrier) = (medium.clone(), barrier.clone());
+        thread::spawn(move || {
+            let (mut radio, _) = node(&medium, Some(short_addr), short_addr as u64);
+            barrier.wait();
+            // long enough to still be on the air when the other one starts
+            let mut frame = data_frame(short_addr as u8, true, 3, short_addr);
+            frame.splice(9..9, [0x55; 100]);
+            send(&mut radio, &frame);
+            radio.stats().rx_ack_timeout
+        })
+    });
+
+    let timeouts = senders.map(|sender| sender.join().unwrap());
+    let (received, stats) = receiver.join().unwrap();
+    (received, stats, timeouts)
+}
+
+#[test]
+fn overlapping_frames_collide() {
+    let (received, stats, timeouts) = overlapping_frames(true);
+    assert!(received.is_empty());
+    assert_eq!(stats.crc_error, 2);
+    assert_eq!(timeouts, [1, 1]);
+}
+
+#[test]
+fn overlapping_frames_without_collisions() {
+    let (received, stats, timeouts) = overlapping_frames(false);
+    assert_eq!(stats.crc_error, 0);
+    // a frame ending while the receiver sends the ACK of the other one is
+    // missed, and so not ACKed either
+    assert!(!received.is_empty());
+    assert_eq!(received.len() as u16 + timeouts[0] + timeouts[1], 2);
+    assert!(received
+        .iter()
+        .all(|frame| timeouts[frame[2] as usize - 1] == 0));
+}
+
+#[test]
+fn device_associates_and_sends_data() {
+    let medium = Medium::new(MediumConfig::default());
+    let barrier = Arc::new(Barrier::new(2));
+    let deadline = Duration::from_millis(300);
+
+    let coordinator = thread::spawn({
+        let (medium, barrier) = (medium.clone(), barrier.clone());
+        move || {
+            let (mut radio, _) = node(&medium, Some(0x0000), COORDINATOR);
+            radio.reconfigure(Config {
+                coordinator: true,
+                ..radio.config()
+            });
+            barrier.wait();
+
+            let until = Instant::now() + deadline;
+            let mut data = Vec::new();
+            while Instant::now() < until && data.is_empty() {
+                for frame in receive(&mut radio) {
+                    match frame.last() {
+                        // association request, the response is sent once it is polled for
+                        Some(0x80) if frame[17] == 0x01 => {
+                            radio.add_pending_ext_address(DEVICE).unwrap();
+                        }
+                        // data request
+                        Some(0x04) if frame.len() == 16 => {
+                            radio.remove_pending_ext_address(DEVICE);
+                            // let the ACK of the data request go out first
+                            run_for(Duration::from_millis(1));
+                            let mut response = vec![0x63, 0xcc, 0x10];
+                            response.extend_from_slice(&PAN_ID.to_le_bytes());
+                            response.extend_from_slice(&DEVICE.to_le_bytes());
+                            response.extend_from_slice(&COORDINATOR.to_le_bytes());
+                            response.extend_from_slice(&[0x02, 0x42, 0x00, 0x00, 0, 0]);
+                            assert!(send(&mut radio, &response));
+                        }
+                        _ => data.push(frame),
+                    }
+                }
+            }
+
+            data
+        }
+    });
+
+    let (mut radio, _) = node(&medium, None, DEVICE);
+    barrier.wait();
+
+    let mut request = vec![0x23, 0xc8, 1];
+    request.extend_from_slice(&PAN_ID.to_le_bytes());
+    request.extend_from_slice(&0x0000u16.to_le_bytes());
+    request.extend_from_slice(&0xffffu16.to_le_bytes());
+    request.extend_from_slice(&DEVICE.to_le_bytes());
+    request.extend_from_slice(&[0x01, 0x80, 0, 0]);
+    assert!(send(&mut radio, &request));
+
+    let mut poll = vec![0x63, 0xc8, 2];
+    poll.extend_from_slice(&PAN_ID.to_le_bytes());
+    poll.extend_from_slice(&0x0000u16.to_le_bytes());
+    poll.extend_from_slice(&DEVICE.to_le_bytes());
+    poll.extend_from_slice(&[0x04, 0, 0]);
+    assert!(send(&mut radio, &poll));
+
+    let until = Instant::now() + deadline;
+    let mut response = None;
+    while Instant::now() < until && response.is_none() {
+        response = receive(&mut radio)
+            .into_iter()
+            .find(|frame| frame.len() == 25 && frame[21] == 0x02);
+    }
+    let response = response.expect("association response");
+    assert_eq!(&response[5..13], DEVICE.to_le_bytes());
+    assert_eq!(response[24], 0x00, "association status");
+
+    let short_addr = u16::from_le_bytes([response[22], response[23]]);
+    assert_eq!(short_addr, 0x0042);
+    radio.reconfigure(Config {
+        short_addr: Some(short_addr),
+        ..radio.config()
+    });
+
+    let frame = data_frame(3, true, 0x0000, short_addr);
+    assert!(send(&mut radio, &frame));
+    assert_eq!(radio.stats().rx_ack_timeout, 0);
+
+    assert_eq!(coordinator.join().unwrap(), [without_fcs(&frame)]);
+}
@@ -151,7 +151,7 @@ fn main() {
             panic!("Unsupported operation");
         }
         ExtcapStep::Capture(capture_step) => {
-            let (data_link, prefix) = (DataLink::IEEE802_15_4, "@RAW [");
+            let (data_link, prefix, bad_prefix) = (DataLink::IEEE802_15_4, "@RAW [", "@BAD [");
 
             let mut controls = (
                 capture_step.spawn_channel_control_reader(),
@@ -220,6 +220,12 @@ fn main() {
                 line.clear();
                 if let Ok(len) = buf_read.read_line(&mut line) {
                     if len > 0 {
+                        // the radio replaces the FCS with the RSSI and LQI, so the
+                        // FCS of frames received with a CRC error, or truncated by
+                        // the address filter, is unknown
+                        let bad = line.contains(bad_prefix);
+                        let prefix = if bad { bad_prefix } else { prefix };
+
                         if line.contains(prefix) {
                             if !line.contains(']') {
                                 panic!("Unexpected {}", line);
@@ -234,17 +240,25 @@ fn main() {
                             }
                         }
 
-                        if !packet.is_empty() {
+                        if packet.len() > 1 && packet[0] >= 2 {
                             let len = packet[0] - 2;
-                            let crc = crc(&packet[1..][..len as usize]);
-                            packet.insert(1 + (len as usize), crc[0]);
-                            packet.insert(1 + (len as usize) + 1, crc[1]);
+                            // the FCS of a good frame is the one the radio checked,
+                            // a bad frame is recorded without it, the original length
+                            // telling Wireshark the FCS was not captured
+                            let captured = if bad {
+                                len
+                            } else {
+                                let crc = crc(&packet[1..][..len as usize]);
+                                packet.insert(1 + (len as usize), crc[0]);
+                                packet.insert(1 + (len as usize) + 1, crc[1]);
+                                len + 2
+                            };
 
                             pcap_writer
                                 .write_packet(&PcapPacket::new(
                                     SystemTime::now().duration_since(UNIX_EPOCH).unwrap(),
                                     (len as u32) + 2,
-                                    &packet[1..][..(len + 2) as usize],
+                                    &packet[1..][..captured as usize],
                                 ))
                                 .unwrap();
                         }